@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use local_sns_rs::config::Config;
+use local_sns_rs::state::{AppState, topic_arn};
+
+/// Subscription counts Publish is timed with.
+const SUBSCRIPTIONS: &[usize] = &[1, 10, 100, 1000];
+
+/// Times one Publish to a topic with N memory subscriptions. Delivery is
+/// synchronous, so each iteration includes the whole fan-out.
+fn publish_fanout(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("publish_fanout");
+    for &subscriptions in SUBSCRIPTIONS {
+        let config = Config {
+            sync_delivery: true,
+            ..Config::default()
+        };
+        let app = local_sns_rs::app(Arc::new(AppState::new(config)));
+        let arn = topic_arn("fanout");
+        runtime.block_on(async {
+            local_sns_rs::call(&app, &[("Action", "CreateTopic"), ("Name", "fanout")])
+                .await
+                .unwrap();
+            for index in 0..subscriptions {
+                let endpoint = format!("fanout-{}", index);
+                local_sns_rs::call(
+                    &app,
+                    &[
+                        ("Action", "Subscribe"),
+                        ("TopicArn", &arn),
+                        ("Protocol", "memory"),
+                        ("Endpoint", &endpoint),
+                    ],
+                )
+                .await
+                .unwrap();
+            }
+        });
+
+        group.throughput(Throughput::Elements(subscriptions as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(subscriptions),
+            &subscriptions,
+            |b, _| {
+                b.to_async(&runtime).iter(|| async {
+                    let status = local_sns_rs::call(
+                        &app,
+                        &[
+                            ("Action", "Publish"),
+                            ("TopicArn", &arn),
+                            ("Message", "fanout"),
+                        ],
+                    )
+                    .await
+                    .unwrap();
+                    assert!(status.is_success(), "Publish failed with {}", status);
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, publish_fanout);
+criterion_main!(benches);
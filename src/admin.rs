@@ -0,0 +1,965 @@
+use crate::bench::parse_duration;
+use crate::bulk::{self, BulkReport, BulkRequest};
+use crate::client_cache::ClientCacheStats;
+use crate::config::Config;
+use crate::delivery;
+use crate::latency::LatencyProfile;
+use crate::memory::{self, ReceivedMessage};
+use crate::platform::PushDelivery;
+use crate::replay::{self, ReplayReport};
+use crate::scheduler::{DeliveryConfig, DeliveryConfigPatch};
+use crate::seed::ReloadStatus;
+use crate::snapshot::{self, FixtureResult, ImportMode, StateDocument, TopicDocument};
+use crate::state::{
+    HistoryEvent, Message, OutboxEntry, SequencedMessage, SharedState, Subscription,
+    format_sequence_number,
+};
+use crate::stats::{
+    LastDeliveryError, RetentionStatsSnapshot, SuspiciousStatsSnapshot, TopicStatsSnapshot,
+};
+use crate::tracker::{DEFAULT_WAIT_TIMEOUT, FanOutStatus};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, patch, post, put};
+use axum::{Json, Router};
+use chrono::Utc;
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+/// Local-only management endpoints, mounted under `/admin`, behind
+/// `require_token`.
+pub fn router(state: SharedState) -> Router<SharedState> {
+    Router::new()
+        .route("/export", get(export_state))
+        .route("/import", post(import_state))
+        .route("/fixtures", post(create_fixtures))
+        .route("/config", get(active_config))
+        .route("/config/delivery", patch(update_delivery_config))
+        .route("/config/status", get(config_status))
+        .route("/reset", post(reset))
+        .route("/stats", get(stats))
+        .route("/topics", get(list_topics).delete(delete_topics))
+        .route("/topics/:name", get(topic_detail))
+        .route("/topics/:name/stats", get(topic_stats))
+        .route("/topics/:name/messages", get(topic_messages))
+        .route("/topics/:name/messages/export", get(export_messages))
+        .route("/topics/:name/fifo-state", get(fifo_state))
+        .route("/replay", post(replay_messages))
+        .route("/subscriptions", delete(delete_subscriptions))
+        .route("/subscriptions/bulk", post(bulk_subscriptions))
+        .route("/subscriptions/:arn", get(subscription))
+        .route("/subscriptions/:arn/test", post(test_subscription))
+        .route("/subscriptions/:arn/confirm", post(confirm_subscription))
+        .route(
+            "/subscriptions/:arn/resend-confirmation",
+            post(resend_confirmation),
+        )
+        .route("/deliveries/:message_id/redeliver", post(redeliver))
+        .route(MESSAGE_STREAM_PATH, get(message_stream))
+        .route("/messages/:message_id/status", get(message_status))
+        .route("/messages/:message_id/wait", get(wait_for_message))
+        .route("/outbox", get(outbox))
+        .route("/platform-endpoints/*path", get(platform_endpoint_messages))
+        .route(
+            "/queues/:name/messages",
+            get(queue_messages).delete(clear_queue_messages),
+        )
+        .route(
+            "/latency",
+            get(latency_profiles).delete(clear_latency_profiles),
+        )
+        .route(
+            "/latency/:action",
+            put(set_latency_profile).delete(delete_latency_profile),
+        )
+        .layer(middleware::from_fn_with_state(state, require_token))
+}
+
+/// Query parameter carrying the admin token where a client cannot set
+/// headers, as with the UI's `EventSource`.
+pub const ACCESS_TOKEN_PARAMETER: &str = "access_token";
+
+/// The message stream, relative to `/admin`; the only route taking the
+/// token as `ACCESS_TOKEN_PARAMETER`, so it does not end up in the logs and
+/// histories of every other URL.
+const MESSAGE_STREAM_PATH: &str = "/messages/stream";
+
+/// Answers 401 to admin requests that lack `--admin-token`, given as
+/// `Authorization: Bearer <token>`, or on the message stream as the
+/// `access_token` query parameter. Passes everything when no token is
+/// configured.
+async fn require_token(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    let Some(token) = &state.admin_token else {
+        return next.run(request).await;
+    };
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or_else(|| {
+            if request.uri().path() != MESSAGE_STREAM_PATH {
+                return None;
+            }
+            url::form_urlencoded::parse(request.uri().query()?.as_bytes())
+                .find(|(name, _)| name == ACCESS_TOKEN_PARAMETER)
+                .map(|(_, value)| value.into_owned())
+        });
+    let error = match presented {
+        Some(presented) if tokens_match(presented.trim(), token) => {
+            return next.run(request).await;
+        }
+        Some(_) => "Invalid admin token",
+        None => "Missing admin token",
+    };
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        Json(json!({ "error": error })),
+    )
+        .into_response()
+}
+
+/// Compares in time independent of where the tokens differ.
+fn tokens_match(presented: &str, token: &str) -> bool {
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub include_messages: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportParams {
+    #[serde(default)]
+    pub mode: ImportMode,
+}
+
+pub async fn export_state(
+    State(state): State<SharedState>,
+    Query(params): Query<ExportParams>,
+) -> Json<StateDocument> {
+    Json(snapshot::export(&state, params.include_messages))
+}
+
+pub async fn import_state(
+    State(state): State<SharedState>,
+    Query(params): Query<ImportParams>,
+    Json(document): Json<StateDocument>,
+) -> Json<snapshot::ImportReport> {
+    let report = snapshot::import(&state, document, params.mode);
+    tracing::info!(
+        "Imported state: {} topics created, {} updated, {} subscriptions, {} errors",
+        report.topics_created,
+        report.topics_updated,
+        report.subscriptions_created,
+        report.errors.len()
+    );
+    Json(report)
+}
+
+pub async fn create_fixtures(
+    State(state): State<SharedState>,
+    Json(topics): Json<Vec<TopicDocument>>,
+) -> Json<Vec<FixtureResult>> {
+    let results = snapshot::create_fixtures(&state, topics);
+    tracing::info!("Created fixtures for {} topics", results.len());
+    Json(results)
+}
+
+/// The configuration in effect: the startup configuration, redacted, with
+/// the delivery tuning as last changed at runtime.
+pub async fn active_config(State(state): State<SharedState>) -> Json<Config> {
+    let delivery = state.scheduler.config();
+    let mut config = state.startup_config.clone();
+    config.delivery_workers = delivery.delivery_workers;
+    config.topic_worker_share = delivery.topic_worker_share;
+    config.max_concurrent_deliveries = delivery.max_concurrent_deliveries;
+    config.retry_time_scale = delivery.retry_time_scale;
+    config.http_timeout_ms = delivery.http_timeout_ms;
+    Json(config)
+}
+
+/// Changes the delivery tuning without a restart. Queued fan-outs start as
+/// soon as workers are added; fan-outs read the concurrent delivery limit
+/// as they run.
+pub async fn update_delivery_config(
+    State(state): State<SharedState>,
+    Json(patch): Json<DeliveryConfigPatch>,
+) -> Response {
+    match state.scheduler.reconfigure(patch) {
+        Ok(config) => {
+            tracing::info!("Delivery configuration changed to {:?}", config);
+            Json(DeliveryConfig::clone(&config)).into_response()
+        }
+        Err(error) => bad_request(&error),
+    }
+}
+
+pub async fn config_status(State(state): State<SharedState>) -> Json<Option<ReloadStatus>> {
+    Json(state.seed_status.read().unwrap().clone())
+}
+
+/// Usage of the simulated quotas; limits are omitted when unlimited.
+#[derive(Debug, Default, Serialize)]
+pub struct QuotaUsage {
+    pub topics: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_topics: Option<usize>,
+    pub subscriptions: usize,
+    /// Subscriptions of the topic that has the most.
+    pub largest_topic_subscriptions: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_subscriptions_per_topic: Option<usize>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AdminStats {
+    pub topics: usize,
+    pub quotas: QuotaUsage,
+    pub disabled_subscriptions: usize,
+    pub suspicious: SuspiciousStatsSnapshot,
+    pub retention: RetentionStatsSnapshot,
+    /// Absent in builds without SQS delivery.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sqs_clients: Option<ClientCacheStats>,
+    pub totals: TopicStatsSnapshot,
+    pub per_topic: BTreeMap<String, TopicStatsSnapshot>,
+}
+
+pub async fn stats(State(state): State<SharedState>) -> Json<AdminStats> {
+    let mut stats = AdminStats {
+        topics: state.topics.len(),
+        disabled_subscriptions: state
+            .subscription_health
+            .iter()
+            .filter(|health| health.disabled)
+            .count(),
+        suspicious: state.suspicious.snapshot(),
+        retention: state.retention.snapshot(),
+        #[cfg(feature = "sqs-delivery")]
+        sqs_clients: Some(state.sqs_clients.snapshot()),
+        ..Default::default()
+    };
+    stats.quotas = QuotaUsage {
+        topics: stats.topics,
+        max_topics: state.max_topics,
+        max_subscriptions_per_topic: state.max_subscriptions_per_topic,
+        ..Default::default()
+    };
+    for topic in state.topics.iter() {
+        let subscriptions = topic.subscriptions.len();
+        stats.quotas.subscriptions += subscriptions;
+        stats.quotas.largest_topic_subscriptions =
+            stats.quotas.largest_topic_subscriptions.max(subscriptions);
+    }
+    for entry in state.stats.iter() {
+        let snapshot = entry.value().snapshot();
+        stats.totals.add(&snapshot);
+        stats.per_topic.insert(entry.key().clone(), snapshot);
+    }
+    Json(stats)
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopicDetail {
+    pub name: String,
+    pub topic_arn: String,
+    /// Explicitly set attributes, keyed by their AWS name.
+    pub attributes: BTreeMap<String, String>,
+    pub tags: BTreeMap<String, String>,
+    pub subscriptions: Vec<SubscriptionStatus>,
+}
+
+/// A topic with its attributes and the status of its subscriptions.
+pub async fn topic_detail(State(state): State<SharedState>, Path(name): Path<String>) -> Response {
+    let Some(topic) = state.topics.get(&name).map(|t| t.clone()) else {
+        return not_found("Topic does not exist");
+    };
+    let attributes = topic
+        .explicit_attributes()
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    Json(TopicDetail {
+        name: topic.name,
+        topic_arn: topic.arn,
+        attributes,
+        tags: topic.tags.into_iter().collect(),
+        subscriptions: topic
+            .subscriptions
+            .into_iter()
+            .map(|subscription| subscription_status(&state, subscription))
+            .collect(),
+    })
+    .into_response()
+}
+
+/// The topic's message history, newest first.
+pub async fn topic_messages(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+) -> Response {
+    if !state.topics.contains_key(&name) {
+        return not_found("Topic does not exist");
+    }
+    let messages: Vec<Message> = state
+        .messages
+        .get(&name)
+        .map(|history| history.iter().rev().cloned().collect())
+        .unwrap_or_default();
+    Json(messages).into_response()
+}
+
+/// Server-sent events of every message added to a topic history, as
+/// `message` events with a `HistoryEvent` payload. A client too slow to
+/// keep up skips the messages it missed rather than stalling publishes.
+pub async fn message_stream(
+    State(state): State<SharedState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.message_events.subscribe();
+    let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((Ok(history_event(&event)), receiver)),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::debug!("Message stream client skipped {} messages", skipped);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn history_event(event: &HistoryEvent) -> Event {
+    Event::default()
+        .event("message")
+        .id(event.message.id.clone())
+        .json_data(event)
+        .unwrap_or_else(|_| Event::default().comment("unserializable message"))
+}
+
+pub async fn topic_stats(State(state): State<SharedState>, Path(name): Path<String>) -> Response {
+    if !state.topics.contains_key(&name) {
+        return not_found("Topic does not exist");
+    }
+    Json(state.existing_topic_stats(&name).snapshot()).into_response()
+}
+
+#[derive(Debug, Serialize)]
+pub struct FifoState {
+    pub fifo: bool,
+    /// Last SequenceNumber of each message group.
+    pub sequence_numbers: BTreeMap<String, String>,
+    /// The latest allocations, oldest first.
+    pub recent: Vec<SequencedMessage>,
+}
+
+/// The SequenceNumbers a topic handed out, for debugging FIFO ordering.
+pub async fn fifo_state(State(state): State<SharedState>, Path(name): Path<String>) -> Response {
+    let Some(topic) = state.topics.get(&name) else {
+        return not_found("Topic does not exist");
+    };
+    Json(FifoState {
+        fifo: topic.is_fifo(),
+        sequence_numbers: topic
+            .sequence_numbers
+            .iter()
+            .map(|(group, counter)| (group.clone(), format_sequence_number(*counter)))
+            .collect(),
+        recent: topic.recent_sequence_numbers.iter().cloned().collect(),
+    })
+    .into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageExportFormat {
+    #[default]
+    Ndjson,
+    Script,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessageExportParams {
+    #[serde(default)]
+    pub format: MessageExportFormat,
+    /// Endpoint the script targets unless `LOCAL_SNS_ENDPOINT` is set;
+    /// this server's base URL by default.
+    pub endpoint: Option<String>,
+}
+
+/// The topic's message history as NDJSON publish records that
+/// `/admin/replay` accepts, or as a shell script of `aws sns publish`
+/// calls. Only messages still retained in the history are exported.
+pub async fn export_messages(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+    Query(params): Query<MessageExportParams>,
+) -> Response {
+    if !state.topics.contains_key(&name) {
+        return not_found("Topic does not exist");
+    }
+    let records = replay::records(&state, &name);
+    match params.format {
+        MessageExportFormat::Ndjson => (
+            [(header::CONTENT_TYPE, "application/x-ndjson")],
+            replay::to_ndjson(&records),
+        )
+            .into_response(),
+        MessageExportFormat::Script => {
+            let endpoint = params.endpoint.unwrap_or_else(|| state.base_url.clone());
+            (
+                [(header::CONTENT_TYPE, "text/x-shellscript")],
+                replay::to_script(&records, &endpoint),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplayParams {
+    #[serde(default)]
+    pub preserve_timing: bool,
+}
+
+/// Republishes an NDJSON body of publish records, one Publish per line,
+/// through the regular Query API so topics, filters and fan-out behave as
+/// for any publisher.
+pub async fn replay_messages(
+    State(state): State<SharedState>,
+    Query(params): Query<ReplayParams>,
+    body: String,
+) -> Json<ReplayReport> {
+    let app = crate::app(state.clone());
+    let report = replay::replay(&app, &body, params.preserve_timing).await;
+    tracing::info!(
+        "Replayed {} messages via admin API, {} failed",
+        report.published,
+        report.failed.len()
+    );
+    Json(report)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListTopicsParams {
+    /// `key:value`; only topics carrying this tag are listed.
+    pub tag: Option<String>,
+    /// Only topics carrying a tag with this key are listed.
+    #[serde(rename = "tag-key")]
+    pub tag_key: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopicSummary {
+    pub name: String,
+    pub topic_arn: String,
+    pub tags: BTreeMap<String, String>,
+    pub subscriptions: usize,
+}
+
+/// Lists topics, optionally filtered by tag. Only matching topics are copied
+/// out of the topic map.
+pub async fn list_topics(
+    State(state): State<SharedState>,
+    Query(params): Query<ListTopicsParams>,
+) -> Json<Vec<TopicSummary>> {
+    let tag = params
+        .tag
+        .as_deref()
+        .map(|tag| tag.split_once(':').unwrap_or((tag, "")));
+    let mut topics: Vec<TopicSummary> = state
+        .topics
+        .iter()
+        .filter(|topic| {
+            tag.is_none_or(|(key, value)| topic.tags.get(key).is_some_and(|v| v == value))
+                && params
+                    .tag_key
+                    .as_ref()
+                    .is_none_or(|key| topic.tags.contains_key(key))
+        })
+        .map(|topic| TopicSummary {
+            name: topic.name.clone(),
+            topic_arn: topic.arn.clone(),
+            tags: topic
+                .tags
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            subscriptions: topic.subscriptions.len(),
+        })
+        .collect();
+    topics.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(topics)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteTopicsParams {
+    pub prefix: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DeletedResources {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub topics: Vec<String>,
+    pub subscriptions: Vec<String>,
+}
+
+/// Deletes every topic whose name starts with `prefix`, as DeleteTopic
+/// would.
+pub async fn delete_topics(
+    State(state): State<SharedState>,
+    Query(params): Query<DeleteTopicsParams>,
+) -> Response {
+    if params.prefix.is_empty() {
+        return bad_request("prefix must not be empty");
+    }
+    let names: Vec<String> = state
+        .topics
+        .iter()
+        .filter(|topic| topic.name.starts_with(&params.prefix))
+        .map(|topic| topic.name.clone())
+        .collect();
+
+    let mut deleted = DeletedResources::default();
+    for name in names {
+        if let Some(topic) = state.remove_topic(&name) {
+            deleted.topics.push(topic.arn);
+            deleted.subscriptions.extend(
+                topic
+                    .subscriptions
+                    .into_iter()
+                    .map(|subscription| subscription.subscription_arn),
+            );
+        }
+    }
+    tracing::info!(
+        "Deleted {} topics with prefix {}",
+        deleted.topics.len(),
+        params.prefix
+    );
+    Json(deleted).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteSubscriptionsParams {
+    pub endpoint: String,
+}
+
+/// Unsubscribes every subscription pointing at `endpoint`, across topics.
+pub async fn delete_subscriptions(
+    State(state): State<SharedState>,
+    Query(params): Query<DeleteSubscriptionsParams>,
+) -> Json<DeletedResources> {
+    let arns: Vec<String> = state
+        .topics
+        .iter()
+        .flat_map(|topic| {
+            topic
+                .subscriptions
+                .iter()
+                .filter(|s| s.endpoint == params.endpoint)
+                .map(|s| s.subscription_arn.clone())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let mut deleted = DeletedResources::default();
+    for arn in arns {
+        if let Some(subscription) = state.remove_subscription(&arn) {
+            deleted.subscriptions.push(subscription.subscription_arn);
+        }
+    }
+    tracing::info!(
+        "Deleted {} subscriptions to {}",
+        deleted.subscriptions.len(),
+        params.endpoint
+    );
+    Json(deleted)
+}
+
+/// Subscribes, unsubscribes and re-points subscriptions in one call, or
+/// with `dry_run` reports what that would change.
+pub async fn bulk_subscriptions(
+    State(state): State<SharedState>,
+    Json(request): Json<BulkRequest>,
+) -> Json<BulkReport> {
+    let report = bulk::apply(&state, request);
+    if !report.dry_run {
+        tracing::info!(
+            "Bulk subscription update: {} changed, {} unchanged, {} failed",
+            report.changed,
+            report.unchanged,
+            report.failed
+        );
+    }
+    Json(report)
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionStatus {
+    pub subscription_arn: String,
+    pub topic_arn: String,
+    pub protocol: String,
+    pub endpoint: String,
+    pub pending_confirmation: bool,
+    pub effective_delivery_state: &'static str,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_delivery_error: Option<LastDeliveryError>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+fn subscription_status(state: &SharedState, subscription: Subscription) -> SubscriptionStatus {
+    let health = state.subscription_health(&subscription.subscription_arn);
+    SubscriptionStatus {
+        subscription_arn: subscription.subscription_arn,
+        topic_arn: subscription.arn,
+        protocol: subscription.protocol,
+        endpoint: subscription.endpoint,
+        pending_confirmation: subscription.pending_confirmation,
+        effective_delivery_state: health.effective_delivery_state(),
+        consecutive_failures: health.consecutive_failures,
+        last_delivery_error: health.last_error,
+        tags: subscription.tags.into_iter().collect(),
+    }
+}
+
+pub async fn subscription(State(state): State<SharedState>, Path(arn): Path<String>) -> Response {
+    match state.find_subscription(&arn) {
+        Some(subscription) => Json(subscription_status(&state, subscription)).into_response(),
+        None => not_found("Subscription does not exist"),
+    }
+}
+
+/// Confirms a subscription as visiting its SubscribeURL would, for tests
+/// that do not read the confirmation email.
+pub async fn confirm_subscription(
+    State(state): State<SharedState>,
+    Path(arn): Path<String>,
+) -> Response {
+    match state.confirm_subscription(&arn) {
+        Some((subscription, was_pending)) => {
+            if was_pending {
+                delivery::send_test_notification(&state, &subscription);
+            }
+            Json(subscription_status(&state, subscription)).into_response()
+        }
+        None => not_found("Subscription does not exist"),
+    }
+}
+
+/// Sends a pending subscription its confirmation message again, for
+/// endpoints that missed the first one. The token is kept unless it
+/// expired.
+pub async fn resend_confirmation(
+    State(state): State<SharedState>,
+    Path(arn): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let Some((subscription, token)) = state.renew_confirmation_token(&arn) else {
+        return match state.find_subscription(&arn) {
+            Some(_) => bad_request("Subscription is not pending confirmation"),
+            None => not_found("Subscription does not exist"),
+        };
+    };
+    let base_url = state.request_base_url(&headers);
+    delivery::send_subscription_confirmation(&state, &base_url, &subscription, &token);
+    Json(subscription_status(&state, subscription)).into_response()
+}
+
+/// Sends a test notification to the endpoint; success re-enables a disabled
+/// subscription.
+pub async fn test_subscription(
+    State(state): State<SharedState>,
+    Path(arn): Path<String>,
+) -> Response {
+    let Some(subscription) = state.find_subscription(&arn) else {
+        return not_found("Subscription does not exist");
+    };
+
+    let message = Message {
+        id: state.new_message_id(),
+        subject: Some("Test delivery".to_string()),
+        body: "This is a test delivery from local-sns-rs".to_string(),
+        timestamp: Utc::now(),
+        message_group_id: None,
+        message_deduplication_id: None,
+        sequence_number: None,
+        trace_header: None,
+        message_attributes: Default::default(),
+        base_url: None,
+        message_structure: None,
+        publisher: None,
+
+        synthetic: false,
+    };
+    let result = delivery::deliver(&state, &subscription, &message).await;
+    state.record_delivery_result(&arn, &result);
+    state.audit_delivery(&subscription, &message, &result);
+
+    let status = if result.is_ok() {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_GATEWAY
+    };
+    let mut body = json!(subscription_status(&state, subscription));
+    if let Err(error) = result {
+        body["error"] = json!(error.message);
+    }
+    (status, Json(body)).into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RedeliverRequest {
+    pub subscription_arn: Option<String>,
+    /// Also delivers to subscriptions disabled by endpoint health tracking.
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeliveryAttempt {
+    pub delivery_id: Option<String>,
+    pub subscription_arn: String,
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RedeliveryReport {
+    pub message_id: String,
+    pub topic_arn: String,
+    pub deliveries: Vec<DeliveryAttempt>,
+}
+
+/// Pushes a message from history through the normal delivery path again, to
+/// one subscription of its topic or to all of them.
+pub async fn redeliver(
+    State(state): State<SharedState>,
+    Path(message_id): Path<String>,
+    request: Option<Json<RedeliverRequest>>,
+) -> Response {
+    let Json(request) = request.unwrap_or_default();
+    let Some((topic_name, message)) = state.find_message(&message_id) else {
+        return not_found("Message is not in the message history");
+    };
+    let Some(topic) = state.topics.get(&topic_name).map(|t| t.clone()) else {
+        return not_found("Topic does not exist");
+    };
+
+    let subscriptions: Vec<Subscription> = match &request.subscription_arn {
+        Some(arn) => match topic
+            .subscriptions
+            .iter()
+            .find(|s| &s.subscription_arn == arn)
+        {
+            Some(subscription) => vec![subscription.clone()],
+            None => return not_found("Subscription does not exist"),
+        },
+        None => topic.subscriptions.clone(),
+    };
+
+    let stats = state.existing_topic_stats(&topic_name);
+    let mut deliveries = Vec::with_capacity(subscriptions.len());
+    for subscription in &subscriptions {
+        if subscription.pending_confirmation
+            || !request.force && state.is_subscription_disabled(&subscription.subscription_arn)
+        {
+            deliveries.push(DeliveryAttempt {
+                delivery_id: None,
+                subscription_arn: subscription.subscription_arn.clone(),
+                status: "skipped",
+                error: None,
+            });
+            continue;
+        }
+        let result = delivery::deliver(&state, subscription, &message).await;
+        stats.record_delivery(&result);
+        state.record_delivery_result(&subscription.subscription_arn, &result);
+        state.audit_delivery(subscription, &message, &result);
+        deliveries.push(DeliveryAttempt {
+            delivery_id: Some(Uuid::new_v4().to_string()),
+            subscription_arn: subscription.subscription_arn.clone(),
+            status: if result.is_ok() {
+                "delivered"
+            } else {
+                "failed"
+            },
+            error: result.err().map(|error| error.message),
+        });
+    }
+
+    tracing::info!(
+        "Redelivered message {} to {} subscriptions",
+        message_id,
+        deliveries.len()
+    );
+    Json(RedeliveryReport {
+        message_id,
+        topic_arn: topic.arn,
+        deliveries,
+    })
+    .into_response()
+}
+
+/// Longest a `/admin/messages/:id/wait` call may block.
+const MAX_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub async fn message_status(
+    State(state): State<SharedState>,
+    Path(message_id): Path<String>,
+) -> Result<Json<FanOutStatus>, Response> {
+    state
+        .fan_outs
+        .status(&message_id)
+        .map(Json)
+        .ok_or_else(|| not_found("Message is not tracked"))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WaitParams {
+    /// How long to wait, e.g. 5s or 500ms; 5s by default.
+    pub timeout: Option<String>,
+}
+
+/// Long-polls until every delivery of the message finished, or the
+/// timeout passed; `complete` in the returned status tells which.
+pub async fn wait_for_message(
+    State(state): State<SharedState>,
+    Path(message_id): Path<String>,
+    Query(params): Query<WaitParams>,
+) -> Result<Json<FanOutStatus>, Response> {
+    let timeout = match params.timeout.as_deref().map(parse_duration) {
+        Some(Ok(timeout)) => timeout.min(MAX_WAIT_TIMEOUT),
+        Some(Err(error)) => return Err(bad_request(&error)),
+        None => DEFAULT_WAIT_TIMEOUT,
+    };
+    state
+        .fan_outs
+        .wait(&message_id, timeout)
+        .await
+        .map(Json)
+        .ok_or_else(|| not_found("Message is not tracked"))
+}
+
+pub async fn outbox(State(state): State<SharedState>) -> Json<Vec<OutboxEntry>> {
+    Json(state.outbox.read().unwrap().iter().cloned().collect())
+}
+
+/// Push deliveries recorded for a platform endpoint, served at
+/// `/admin/platform-endpoints/<endpoint arn>/messages`. Endpoint ARNs contain
+/// slashes, hence the wildcard path.
+pub async fn platform_endpoint_messages(
+    State(state): State<SharedState>,
+    Path(path): Path<String>,
+) -> Result<Json<Vec<PushDelivery>>, Response> {
+    let Some(arn) = path.strip_suffix("/messages") else {
+        return Err(not_found("Not found"));
+    };
+    if !state.platform_endpoints.contains_key(arn) {
+        return Err(not_found("Platform endpoint does not exist"));
+    }
+    Ok(Json(
+        state
+            .push_outbox
+            .get(arn)
+            .map(|outbox| outbox.iter().cloned().collect())
+            .unwrap_or_default(),
+    ))
+}
+
+/// Deliveries to `memory` subscriptions with endpoint `name`. Unknown
+/// buffers are empty rather than missing, since they exist once something
+/// is delivered to them.
+pub async fn queue_messages(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+) -> Json<Vec<ReceivedMessage>> {
+    Json(memory::received(&state, &name))
+}
+
+pub async fn clear_queue_messages(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+) -> Json<serde_json::Value> {
+    let cleared = memory::clear(&state, &name);
+    Json(json!({ "cleared": cleared }))
+}
+
+pub async fn latency_profiles(
+    State(state): State<SharedState>,
+) -> Json<BTreeMap<String, LatencyProfile>> {
+    Json(
+        state
+            .latency_profiles
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect(),
+    )
+}
+
+/// Installs the profile for one action; `*` applies to all actions that
+/// have none.
+pub async fn set_latency_profile(
+    State(state): State<SharedState>,
+    Path(action): Path<String>,
+    Json(profile): Json<LatencyProfile>,
+) -> Response {
+    if let Err(error) = profile.validate() {
+        return bad_request(&error);
+    }
+    tracing::info!("Latency profile for {} set to {:?}", action, profile);
+    state.latency_profiles.insert(action, profile.clone());
+    Json(profile).into_response()
+}
+
+pub async fn delete_latency_profile(
+    State(state): State<SharedState>,
+    Path(action): Path<String>,
+) -> Response {
+    match state.latency_profiles.remove(&action) {
+        Some(_) => StatusCode::NO_CONTENT.into_response(),
+        None => not_found("No latency profile for this action"),
+    }
+}
+
+pub async fn clear_latency_profiles(State(state): State<SharedState>) -> StatusCode {
+    state.latency_profiles.clear();
+    StatusCode::NO_CONTENT
+}
+
+pub async fn reset(State(state): State<SharedState>) -> StatusCode {
+    state.reset();
+    tracing::info!("State reset via admin API");
+    StatusCode::NO_CONTENT
+}
+
+fn bad_request(message: &str) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": message }))).into_response()
+}
+
+fn not_found(message: &str) -> Response {
+    (StatusCode::NOT_FOUND, Json(json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,226 @@
+use crate::delivery::DeliveryResult;
+use crate::state::{Message, SnsRequest, Subscription};
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Files kept by default when the audit log rotates, the live one included.
+pub const DEFAULT_AUDIT_LOG_KEEP: usize = 5;
+
+/// One line of the audit log. `kind` tells the record types apart; fields
+/// are only ever added, so parsers should ignore unknown ones.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditRecord {
+    ApiCall(ApiCallRecord),
+    Delivery(DeliveryRecord),
+}
+
+/// A Query API call, written once its response is ready.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiCallRecord {
+    /// RFC 3339, UTC.
+    pub timestamp: String,
+    pub request_id: String,
+    /// Absent when the request could not be parsed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// The publisher label: SigV4 access key id, else User-Agent and IP.
+    pub caller: String,
+    pub params: ParamsSummary,
+    /// HTTP status of the response.
+    pub status: u16,
+    /// AWS error code of a failed call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<&'static str>,
+}
+
+/// The identifying parameters of a call. Message bodies, attribute values
+/// and tags are left out; only sizes and counts are kept.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ParamsSummary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic_arn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_arn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_arn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribute_name: Option<String>,
+    /// Bytes of the Publish message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_bytes: Option<usize>,
+    /// Entries of a PublishBatch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_entries: Option<usize>,
+}
+
+/// The action and parameters of a call, left in its response's extensions
+/// for the audit record.
+#[derive(Debug, Clone)]
+pub struct AuditedCall {
+    pub action: String,
+    pub params: ParamsSummary,
+}
+
+impl ParamsSummary {
+    pub fn of(params: &SnsRequest) -> Self {
+        ParamsSummary {
+            name: params.name.clone(),
+            topic_arn: params.topic_arn.clone(),
+            subscription_arn: params.subscription_arn.clone(),
+            resource_arn: params.resource_arn.clone(),
+            protocol: params.protocol.clone(),
+            endpoint: params.endpoint.clone(),
+            attribute_name: params.attribute_name.clone(),
+            message_bytes: params.message.as_ref().map(String::len),
+            batch_entries: params.publish_batch_request_entries.as_ref().map(Vec::len),
+        }
+    }
+}
+
+/// One attempt to deliver a message to a subscription.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryRecord {
+    /// RFC 3339, UTC.
+    pub timestamp: String,
+    pub message_id: String,
+    pub topic_arn: String,
+    pub subscription_arn: String,
+    pub protocol: String,
+    pub endpoint: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DeliveryRecord {
+    pub fn new(subscription: &Subscription, message: &Message, result: &DeliveryResult) -> Self {
+        DeliveryRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            message_id: message.id.clone(),
+            topic_arn: subscription.arn.clone(),
+            subscription_arn: subscription.subscription_arn.clone(),
+            protocol: subscription.protocol.clone(),
+            endpoint: subscription.endpoint.clone(),
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|error| error.message.clone()),
+        }
+    }
+}
+
+/// Where and how the audit log is written.
+#[derive(Debug, Clone)]
+pub struct AuditLogConfig {
+    pub path: PathBuf,
+    /// Size at which the file is rotated; never when unset.
+    pub max_bytes: Option<u64>,
+    /// Files kept across rotations, the live one included.
+    pub keep: usize,
+}
+
+/// Handle to the audit log writer. Recording only queues the record; a
+/// dedicated thread writes it, so callers never wait on the disk.
+#[derive(Debug)]
+pub struct AuditLog {
+    sender: UnboundedSender<AuditRecord>,
+}
+
+impl AuditLog {
+    /// Opens the log for appending and starts its writer thread.
+    pub fn start(config: AuditLogConfig) -> io::Result<Self> {
+        let file = open(&config.path)?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        std::thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || write_records(config, file, receiver))?;
+        Ok(AuditLog { sender })
+    }
+
+    pub fn record(&self, record: AuditRecord) {
+        // Only fails once the writer has stopped, which it has logged.
+        let _ = self.sender.send(record);
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// `path` with `.N` appended, the name of the Nth rotated file.
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Shifts `path.1`, `path.2`, ... up by one, dropping the oldest beyond
+/// `keep`, and moves the live file to `path.1`.
+fn rotate(path: &Path, keep: usize) -> io::Result<()> {
+    let rotated = keep.saturating_sub(1);
+    if rotated == 0 {
+        return fs::remove_file(path);
+    }
+    let _ = fs::remove_file(rotated_path(path, rotated));
+    for n in (1..rotated).rev() {
+        let from = rotated_path(path, n);
+        if from.exists() {
+            fs::rename(from, rotated_path(path, n + 1))?;
+        }
+    }
+    fs::rename(path, rotated_path(path, 1))
+}
+
+/// The writer thread: appends each record as a JSON line, flushing whenever
+/// the queue runs dry, and rotates once the file reaches `max_bytes`.
+fn write_records(config: AuditLogConfig, file: File, mut receiver: UnboundedReceiver<AuditRecord>) {
+    let mut size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    let mut writer = BufWriter::new(file);
+    while let Some(record) = receiver.blocking_recv() {
+        let mut next = Some(record);
+        while let Some(record) = next {
+            let mut line = serde_json::to_vec(&record).unwrap();
+            line.push(b'\n');
+            if let Some(max_bytes) = config.max_bytes
+                && size > 0
+                && size + line.len() as u64 > max_bytes
+            {
+                let rotated = writer
+                    .flush()
+                    .and_then(|()| rotate(&config.path, config.keep))
+                    .and_then(|()| open(&config.path));
+                match rotated {
+                    Ok(file) => {
+                        writer = BufWriter::new(file);
+                        size = 0;
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            "Failed to rotate audit log {}: {}",
+                            config.path.display(),
+                            e
+                        );
+                    }
+                }
+            }
+            if let Err(e) = writer.write_all(&line) {
+                tracing::error!("Failed to write audit log {}: {}", config.path.display(), e);
+                return;
+            }
+            size += line.len() as u64;
+            next = receiver.try_recv().ok();
+        }
+        if let Err(e) = writer.flush() {
+            tracing::error!("Failed to write audit log {}: {}", config.path.display(), e);
+            return;
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::config::Config;
+use crate::memory;
+use crate::state::{AppState, topic_arn};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+
+/// How long to wait for queued deliveries after the last publish.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options of the `bench` subcommand.
+#[derive(Debug, Clone, clap::Args)]
+pub struct BenchArgs {
+    /// Topic to publish to
+    #[arg(long, default_value = "bench")]
+    pub topic: String,
+    /// Target publishes per second
+    #[arg(long, default_value_t = 1000)]
+    pub rate: u32,
+    /// How long to publish, e.g. 30s, 500ms or 2m
+    #[arg(long, default_value = "10s", value_parser = parse_duration)]
+    pub duration: Duration,
+    /// Number of `memory` subscriptions on the topic
+    #[arg(long, default_value_t = 1)]
+    pub subscribers: usize,
+    /// Publishes allowed in flight at once; ticks beyond it are shed
+    #[arg(long, default_value_t = 256)]
+    pub max_in_flight: usize,
+    /// Print the report as JSON instead of a table
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration {}", value))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        _ => Err(format!("invalid duration unit in {}", value)),
+    }
+}
+
+/// Millisecond percentiles of a set of samples.
+#[derive(Debug, Default, Serialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Percentiles {
+    fn of(mut samples: Vec<Duration>) -> Self {
+        if samples.is_empty() {
+            return Percentiles::default();
+        }
+        samples.sort_unstable();
+        let at = |quantile: f64| {
+            let index = ((samples.len() - 1) as f64 * quantile).round() as usize;
+            samples[index].as_secs_f64() * 1000.0
+        };
+        Percentiles {
+            p50: at(0.5),
+            p99: at(0.99),
+            max: at(1.0),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    pub topic: String,
+    pub subscribers: usize,
+    pub target_rate: u32,
+    pub duration_secs: f64,
+    pub published: u64,
+    pub failed_publishes: u64,
+    /// Ticks skipped because `max_in_flight` publishes were outstanding.
+    pub shed: u64,
+    pub achieved_rate: f64,
+    pub publish_latency_ms: Percentiles,
+    /// Time from publish to arrival in a memory buffer. Buffers are bounded,
+    /// so only their most recent messages are sampled.
+    pub delivery_lag_ms: Percentiles,
+    pub deliveries_expected: u64,
+    pub deliveries_ok: u64,
+    pub deliveries_failed: u64,
+    /// Shed publishes plus deliveries that never arrived.
+    pub dropped: u64,
+}
+
+impl BenchReport {
+    pub fn print(&self, json: bool) {
+        if json {
+            println!("{}", serde_json::to_string_pretty(self).unwrap());
+            return;
+        }
+        let rows = [
+            ("topic", self.topic.clone()),
+            ("subscribers", self.subscribers.to_string()),
+            ("target rate", format!("{}/s", self.target_rate)),
+            ("achieved rate", format!("{:.1}/s", self.achieved_rate)),
+            ("duration", format!("{:.1}s", self.duration_secs)),
+            ("published", self.published.to_string()),
+            ("failed publishes", self.failed_publishes.to_string()),
+            ("shed", self.shed.to_string()),
+            (
+                "publish latency p50 / p99 / max",
+                format!(
+                    "{:.2} / {:.2} / {:.2} ms",
+                    self.publish_latency_ms.p50,
+                    self.publish_latency_ms.p99,
+                    self.publish_latency_ms.max
+                ),
+            ),
+            (
+                "delivery lag p50 / p99 / max",
+                format!(
+                    "{:.2} / {:.2} / {:.2} ms",
+                    self.delivery_lag_ms.p50, self.delivery_lag_ms.p99, self.delivery_lag_ms.max
+                ),
+            ),
+            ("deliveries expected", self.deliveries_expected.to_string()),
+            ("deliveries ok", self.deliveries_ok.to_string()),
+            ("deliveries failed", self.deliveries_failed.to_string()),
+            ("dropped", self.dropped.to_string()),
+        ];
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, value) in rows {
+            println!("{:<width$}  {}", name, value, width = width);
+        }
+    }
+}
+
+/// Runs a publish load test against an embedded server built from
+/// `config`: creates the topic with `subscribers` memory subscriptions,
+/// publishes at the target rate for the given duration, then waits for
+/// deliveries to drain.
+pub async fn run(config: Config, args: &BenchArgs) -> Result<BenchReport, String> {
+    if args.rate == 0 {
+        return Err("rate: must be positive".to_string());
+    }
+    let state = Arc::new(AppState::new(config));
+    let app = crate::app(state.clone());
+    let arn = topic_arn(&args.topic);
+
+    let status = crate::call(&app, &[("Action", "CreateTopic"), ("Name", &args.topic)]).await?;
+    if !status.is_success() {
+        return Err(format!("CreateTopic failed with {}", status));
+    }
+    for index in 0..args.subscribers {
+        let endpoint = format!("{}-{}", args.topic, index);
+        let status = crate::call(
+            &app,
+            &[
+                ("Action", "Subscribe"),
+                ("TopicArn", &arn),
+                ("Protocol", "memory"),
+                ("Endpoint", &endpoint),
+            ],
+        )
+        .await?;
+        if !status.is_success() {
+            return Err(format!("Subscribe failed with {}", status));
+        }
+    }
+
+    let in_flight = Arc::new(Semaphore::new(args.max_in_flight.max(1)));
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / f64::from(args.rate)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let mut publishes = JoinSet::new();
+    let mut shed = 0;
+    let started = Instant::now();
+    let mut sequence = 0u64;
+    while started.elapsed() < args.duration {
+        ticker.tick().await;
+        let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+            shed += 1;
+            continue;
+        };
+        sequence += 1;
+        let (app, arn) = (app.clone(), arn.clone());
+        publishes.spawn(async move {
+            let message = format!("bench message {}", sequence);
+            let sent = Instant::now();
+            let status = crate::call(
+                &app,
+                &[
+                    ("Action", "Publish"),
+                    ("TopicArn", &arn),
+                    ("Message", &message),
+                ],
+            )
+            .await;
+            drop(permit);
+            (
+                sent.elapsed(),
+                matches!(status, Ok(status) if status.is_success()),
+            )
+        });
+    }
+
+    let mut latencies = Vec::new();
+    let mut failed_publishes = 0;
+    while let Some(result) = publishes.join_next().await {
+        match result {
+            Ok((latency, true)) => latencies.push(latency),
+            _ => failed_publishes += 1,
+        }
+    }
+    let elapsed = started.elapsed();
+    let published = latencies.len() as u64;
+
+    let stats = state.existing_topic_stats(&args.topic);
+    let deliveries_expected = published * args.subscribers as u64;
+    let drain_started = Instant::now();
+    loop {
+        let snapshot = stats.snapshot();
+        if snapshot.delivered_ok + snapshot.delivered_failed >= deliveries_expected
+            || drain_started.elapsed() > DRAIN_TIMEOUT
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let snapshot = stats.snapshot();
+
+    let lags = (0..args.subscribers)
+        .flat_map(|index| memory::received(&state, &format!("{}-{}", args.topic, index)))
+        .filter_map(|received| (received.received_at - received.timestamp).to_std().ok())
+        .collect();
+
+    Ok(BenchReport {
+        topic: args.topic.clone(),
+        subscribers: args.subscribers,
+        target_rate: args.rate,
+        duration_secs: elapsed.as_secs_f64(),
+        published,
+        failed_publishes,
+        shed,
+        achieved_rate: published as f64 / elapsed.as_secs_f64(),
+        publish_latency_ms: Percentiles::of(latencies),
+        delivery_lag_ms: Percentiles::of(lags),
+        deliveries_expected,
+        deliveries_ok: snapshot.delivered_ok,
+        deliveries_failed: snapshot.delivered_failed,
+        dropped: shed + deliveries_expected.saturating_sub(snapshot.delivered_ok),
+    })
+}
@@ -0,0 +1,283 @@
+use crate::error::{
+    FIFO_TOPIC_PROTOCOL_NOT_SUPPORTED, SUBSCRIPTION_NOT_FOUND, TOPIC_NOT_FOUND, invalid_parameter,
+};
+use crate::state::{
+    AppState, EXTENSION_ATTRIBUTE_PREFIX, Subscription, Topic, is_xml_text, topic_name_from_arn,
+    validate_subscription,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+
+/// A batch of subscription changes for `POST /admin/subscriptions/bulk`.
+#[derive(Debug, Deserialize)]
+pub struct BulkRequest {
+    /// Report what would change without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    pub operations: Vec<BulkOperation>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum BulkOperation {
+    Subscribe {
+        topic_arn: String,
+        protocol: String,
+        endpoint: String,
+        #[serde(default)]
+        attributes: BTreeMap<String, String>,
+    },
+    Unsubscribe {
+        subscription_arn: String,
+    },
+    /// Points a subscription at another endpoint, keeping its ARN and
+    /// attributes.
+    UpdateEndpoint {
+        subscription_arn: String,
+        endpoint: String,
+    },
+}
+
+impl BulkOperation {
+    fn name(&self) -> &'static str {
+        match self {
+            BulkOperation::Subscribe { .. } => "subscribe",
+            BulkOperation::Unsubscribe { .. } => "unsubscribe",
+            BulkOperation::UpdateEndpoint { .. } => "update-endpoint",
+        }
+    }
+
+    fn topic_name(&self) -> &str {
+        match self {
+            BulkOperation::Subscribe { topic_arn, .. } => topic_name_from_arn(topic_arn),
+            BulkOperation::Unsubscribe { subscription_arn }
+            | BulkOperation::UpdateEndpoint {
+                subscription_arn, ..
+            } => subscription_arn
+                .rsplit_once(':')
+                .map(|(topic_arn, _)| topic_name_from_arn(topic_arn))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkStatus {
+    Changed,
+    Unchanged,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkResult {
+    pub op: &'static str,
+    pub status: BulkStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_arn: Option<String>,
+    /// The endpoint an `update-endpoint` replaced.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkReport {
+    pub dry_run: bool,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    /// One result per operation, in request order.
+    pub results: Vec<BulkResult>,
+}
+
+/// What an operation did to its topic.
+struct Change {
+    status: BulkStatus,
+    subscription_arn: String,
+    previous_endpoint: Option<String>,
+}
+
+/// Runs the operations in order. Each one is validated in full before it
+/// changes anything, so a failed operation leaves no trace and the rest
+/// still run. A dry run applies them to copies of the topics instead, so
+/// later operations see the effect of earlier ones just as they would.
+pub fn apply(state: &AppState, request: BulkRequest) -> BulkReport {
+    let mut drafts: HashMap<String, Topic> = HashMap::new();
+    let mut results = Vec::with_capacity(request.operations.len());
+    for operation in request.operations {
+        let op = operation.name();
+        let topic_name = operation.topic_name().to_string();
+        let change = if request.dry_run {
+            let draft = match drafts.entry(topic_name) {
+                Entry::Occupied(entry) => Some(entry.into_mut()),
+                Entry::Vacant(entry) => state
+                    .topics
+                    .get(entry.key())
+                    .map(|topic| topic.clone())
+                    .map(|topic| entry.insert(topic)),
+            };
+            draft
+                .ok_or_else(|| TOPIC_NOT_FOUND.to_string())
+                .and_then(|topic| apply_operation(state, topic, operation))
+        } else {
+            let change = match state.topics.get_mut(&topic_name) {
+                Some(mut topic) => apply_operation(state, &mut topic, operation),
+                None => Err(TOPIC_NOT_FOUND.to_string()),
+            };
+            // Health belongs to the endpoint: a removed subscription drops
+            // it, and a new endpoint starts over, re-enabling a
+            // subscription the old one had disabled.
+            if let Ok(change) = &change
+                && change.status == BulkStatus::Changed
+                && op != "subscribe"
+            {
+                state.subscription_health.remove(&change.subscription_arn);
+            }
+            change
+        };
+        results.push(match change {
+            Ok(change) => BulkResult {
+                op,
+                status: change.status,
+                subscription_arn: Some(change.subscription_arn),
+                previous_endpoint: change.previous_endpoint,
+                error: None,
+            },
+            Err(error) => BulkResult {
+                op,
+                status: BulkStatus::Failed,
+                subscription_arn: None,
+                previous_endpoint: None,
+                error: Some(error),
+            },
+        });
+    }
+
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    BulkReport {
+        dry_run: request.dry_run,
+        changed: count(BulkStatus::Changed),
+        unchanged: count(BulkStatus::Unchanged),
+        failed: count(BulkStatus::Failed),
+        results,
+    }
+}
+
+/// Applies one operation to `topic`, which is left as it was on error.
+/// Runs under the topic's lock, so it must not look up other topics.
+fn apply_operation(
+    state: &AppState,
+    topic: &mut Topic,
+    operation: BulkOperation,
+) -> Result<Change, String> {
+    match operation {
+        BulkOperation::Subscribe {
+            topic_arn: _,
+            protocol,
+            endpoint,
+            attributes,
+        } => {
+            validate_subscription(&protocol, &endpoint)?;
+            if state.strict_aws && matches!(protocol.as_str(), "memory" | "demux") {
+                return Err(invalid_parameter("Protocol"));
+            }
+            if !topic.accepts_protocol(&protocol) {
+                return Err(FIFO_TOPIC_PROTOCOL_NOT_SUPPORTED.to_string());
+            }
+            if let Some(existing) = topic
+                .subscriptions
+                .iter()
+                .find(|s| s.protocol == protocol && s.endpoint == endpoint)
+            {
+                return Ok(Change {
+                    status: BulkStatus::Unchanged,
+                    subscription_arn: existing.subscription_arn.clone(),
+                    previous_endpoint: None,
+                });
+            }
+            state
+                .check_subscription_quota(topic)
+                .map_err(|e| e.message().to_string())?;
+            let mut subscription = Subscription::new(&topic.arn, protocol, endpoint);
+            let invalid: Vec<&str> = attributes
+                .iter()
+                .filter(|(key, value)| {
+                    state.strict_aws && key.starts_with(EXTENSION_ATTRIBUTE_PREFIX)
+                        || !is_xml_text(value)
+                        || !subscription.set_attribute(key, value)
+                })
+                .map(|(key, _)| key.as_str())
+                .collect();
+            if !invalid.is_empty() {
+                return Err(invalid_parameter(&invalid.join(", ")));
+            }
+            state
+                .check_new_subscription(&subscription)
+                .map_err(|e| e.message().to_string())?;
+            if state.inherit_tags {
+                subscription.tags = topic.tags.clone();
+            }
+            let subscription_arn = subscription.subscription_arn.clone();
+            topic.subscriptions.push(subscription);
+            Ok(Change {
+                status: BulkStatus::Changed,
+                subscription_arn,
+                previous_endpoint: None,
+            })
+        }
+        BulkOperation::Unsubscribe { subscription_arn } => {
+            let index = topic
+                .subscriptions
+                .iter()
+                .position(|s| s.subscription_arn == subscription_arn)
+                .ok_or_else(|| SUBSCRIPTION_NOT_FOUND.to_string())?;
+            topic.subscriptions.remove(index);
+            topic.subscriptions_deleted += 1;
+            Ok(Change {
+                status: BulkStatus::Changed,
+                subscription_arn,
+                previous_endpoint: None,
+            })
+        }
+        BulkOperation::UpdateEndpoint {
+            subscription_arn,
+            endpoint,
+        } => {
+            let index = topic
+                .subscriptions
+                .iter()
+                .position(|s| s.subscription_arn == subscription_arn)
+                .ok_or_else(|| SUBSCRIPTION_NOT_FOUND.to_string())?;
+            let protocol = &topic.subscriptions[index].protocol;
+            if topic.subscriptions[index].endpoint == endpoint {
+                return Ok(Change {
+                    status: BulkStatus::Unchanged,
+                    subscription_arn,
+                    previous_endpoint: None,
+                });
+            }
+            if protocol == "application" && !state.platform_endpoints.contains_key(&endpoint) {
+                return Err(invalid_parameter("Endpoint"));
+            }
+            if topic
+                .subscriptions
+                .iter()
+                .any(|s| &s.protocol == protocol && s.endpoint == endpoint)
+            {
+                return Err(format!(
+                    "A {} subscription to {} already exists",
+                    protocol, endpoint
+                ));
+            }
+            let previous_endpoint = topic.subscriptions[index].set_endpoint(endpoint)?;
+            Ok(Change {
+                status: BulkStatus::Changed,
+                subscription_arn,
+                previous_endpoint: Some(previous_endpoint),
+            })
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// SQS clients kept by default, one per service endpoint.
+pub const DEFAULT_MAX_SQS_CLIENTS: usize = 128;
+
+/// Clients created within `GROWTH_WINDOW` that trigger a warning: a test
+/// minting endpoints (random ports, say) rather than reusing a few.
+const GROWTH_WARN_THRESHOLD: usize = 50;
+const GROWTH_WINDOW: Duration = Duration::from_secs(60);
+
+struct Entry<C> {
+    client: Arc<C>,
+    /// Tick of the last lookup, for picking the least recently used.
+    last_used: u64,
+}
+
+struct Inner<C> {
+    entries: HashMap<String, Entry<C>>,
+    tick: u64,
+    /// When the clients of the last `GROWTH_WINDOW` were created.
+    created: VecDeque<Instant>,
+    warned_at: Option<Instant>,
+}
+
+/// AWS SDK clients keyed by endpoint URL, bounded to `capacity` by evicting
+/// the least recently used. Callers hold an `Arc`, so a client evicted
+/// while a delivery still uses it lives until that delivery ends.
+pub struct ClientCache<C> {
+    /// Service the clients are for, in log lines.
+    service: &'static str,
+    capacity: usize,
+    inner: Mutex<Inner<C>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// Served in `/admin/stats`.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ClientCacheStats {
+    pub size: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Share of lookups that found a client, 0 before the first.
+    pub hit_rate: f64,
+}
+
+impl<C> ClientCache<C> {
+    /// A `capacity` of 0 is taken as 1.
+    pub fn new(service: &'static str, capacity: usize) -> Self {
+        ClientCache {
+            service,
+            capacity: capacity.max(1),
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                tick: 0,
+                created: VecDeque::new(),
+                warned_at: None,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, endpoint: &str) -> Option<Arc<C>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let tick = inner.tick;
+        let client = inner.entries.get_mut(endpoint).map(|entry| {
+            entry.last_used = tick;
+            entry.client.clone()
+        });
+        let counter = if client.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        client
+    }
+
+    /// Caches `client` for `endpoint`, evicting the least recently used
+    /// client when full. A client cached meanwhile by a racing lookup is
+    /// replaced, which only costs that one its reuse.
+    pub fn insert(&self, endpoint: String, client: Arc<C>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let last_used = inner.tick;
+        if !inner.entries.contains_key(&endpoint) && inner.entries.len() >= self.capacity {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(endpoint, _)| endpoint.clone());
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        inner.entries.insert(endpoint, Entry { client, last_used });
+
+        let now = Instant::now();
+        while inner
+            .created
+            .front()
+            .is_some_and(|created| now.duration_since(*created) > GROWTH_WINDOW)
+        {
+            inner.created.pop_front();
+        }
+        inner.created.push_back(now);
+        if inner.created.len() > GROWTH_WARN_THRESHOLD
+            && inner
+                .warned_at
+                .is_none_or(|warned_at| now.duration_since(warned_at) > GROWTH_WINDOW)
+        {
+            inner.warned_at = Some(now);
+            tracing::warn!(
+                "{} {} clients created in the last {}s ({} cached, at most {}); are endpoints being generated rather than reused?",
+                inner.created.len(),
+                self.service,
+                GROWTH_WINDOW.as_secs(),
+                inner.entries.len(),
+                self.capacity
+            );
+        }
+    }
+
+    pub fn snapshot(&self) -> ClientCacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
+        ClientCacheStats {
+            size: self.inner.lock().unwrap().entries.len(),
+            capacity: self.capacity,
+            hits,
+            misses,
+            evictions: self.evictions.load(Ordering::Relaxed),
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                hits as f64 / lookups as f64
+            },
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+
+/// Renders a timestamp as SNS does in notifications, e.g.
+/// `2012-04-25T21:49:25.719Z`: always milliseconds and a `Z` suffix, which
+/// some signature verifiers insist on.
+pub fn format_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Shifts emitted timestamps by a fixed amount, to exercise how consumers
+/// handle clock skew. Stored times are never shifted, only their rendering.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Clock {
+    offset: TimeDelta,
+}
+
+impl Clock {
+    /// A clock shifted by `secs` seconds, or `None` when that is out of
+    /// range.
+    pub fn with_offset_secs(secs: i64) -> Option<Self> {
+        TimeDelta::try_seconds(secs).map(|offset| Clock { offset })
+    }
+
+    /// `time` as it appears in deliveries.
+    pub fn format(&self, time: DateTime<Utc>) -> String {
+        format_timestamp(time.checked_add_signed(self.offset).unwrap_or(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().into()
+    }
+
+    #[test]
+    fn timestamps_match_the_aws_examples() {
+        // From the SNS notification and confirmation examples.
+        for example in [
+            "2012-04-25T21:49:25.719Z",
+            "2012-05-02T00:54:06.655Z",
+            "2012-04-26T20:45:04.751Z",
+        ] {
+            assert_eq!(format_timestamp(time(example)), example);
+        }
+    }
+
+    #[test]
+    fn timestamps_have_exactly_milliseconds_in_utc() {
+        assert_eq!(
+            format_timestamp(time("2026-01-01T00:00:00Z")),
+            "2026-01-01T00:00:00.000Z"
+        );
+        assert_eq!(
+            format_timestamp(time("2026-01-01T00:00:00.123456789Z")),
+            "2026-01-01T00:00:00.123Z"
+        );
+        assert_eq!(
+            format_timestamp(time("2026-01-01T02:00:00.5+02:00")),
+            "2026-01-01T00:00:00.500Z"
+        );
+    }
+
+    #[test]
+    fn offsets_shift_only_the_rendering() {
+        let now = time("2026-01-01T00:00:00Z");
+        assert_eq!(
+            Clock::with_offset_secs(-90).unwrap().format(now),
+            "2025-12-31T23:58:30.000Z"
+        );
+        assert_eq!(
+            Clock::with_offset_secs(3600).unwrap().format(now),
+            "2026-01-01T01:00:00.000Z"
+        );
+        assert_eq!(Clock::default().format(now), format_timestamp(now));
+        assert!(Clock::with_offset_secs(i64::MAX).is_none());
+    }
+}
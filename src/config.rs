@@ -0,0 +1,945 @@
+use crate::audit::DEFAULT_AUDIT_LOG_KEEP;
+use crate::bench::BenchArgs;
+use crate::client_cache::DEFAULT_MAX_SQS_CLIENTS;
+use crate::clock::Clock;
+use crate::delivery::{self, DEFAULT_SQS_ENDPOINT};
+use crate::latency::{self, LatencyProfile};
+use crate::scheduler::{
+    DEFAULT_HTTP_TIMEOUT_MS, DEFAULT_MAX_CONCURRENT_DELIVERIES, DEFAULT_TOPIC_WORKER_SHARE,
+    DeliveryConfig,
+};
+use crate::seed::SeedConfig;
+use crate::state::{MAX_MESSAGE_SIZE, topic_defaults};
+use crate::state_file::StateArgs;
+use clap::builder::BoolishValueParser;
+use clap::{Parser, Subcommand, ValueEnum};
+use figment::Figment;
+use figment::providers::{Format, Serialized, Toml};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use url::Url;
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:9911";
+pub const DEFAULT_ENDPOINT_FAILURE_THRESHOLD: u32 = 0;
+pub const DEFAULT_RAW_CONTENT_TYPE: &str = "application/json";
+pub const DEFAULT_PAYLOAD_OFFLOAD_BUCKET: &str = "local-sns-payloads";
+/// Three days, as on AWS.
+pub const DEFAULT_CONFIRMATION_TOKEN_TTL_SECS: u64 = 3 * 24 * 60 * 60;
+
+const AFTER_HELP: &str = "\
+Settings are taken from command-line flags, then LOCAL_SNS_* environment
+variables, then the --config file, then built-in defaults. The config file
+is TOML with the same keys as the flags (in snake_case), plus a [defaults]
+table of topic attributes and [latency_profiles.<Action>] tables.
+
+Subscription endpoints may reference environment variables as ${NAME},
+e.g. http://consumer:${CONSUMER_PORT}/events. They are expanded on every
+delivery; a delivery fails if a referenced variable is unset.";
+
+/// Server configuration. Build it with `Config::load`, or start from
+/// `Config::default()` when embedding the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Socket address the server listens on.
+    pub addr: String,
+    /// Base URL of generated links; `http://<addr>` when unset.
+    pub base_url: Option<String>,
+    /// JSON or YAML state document loaded at startup and on SIGHUP.
+    pub seed: Option<PathBuf>,
+    /// Also reload the seed file when it changes.
+    pub watch_config: bool,
+    /// Delete topics and subscriptions removed from the seed file on reload.
+    pub prune_on_reload: bool,
+    /// Consecutive failed deliveries that disable a subscription; 0 never
+    /// disables.
+    pub endpoint_failure_threshold: u32,
+    pub trust_proxy_headers: bool,
+    pub sync_delivery: bool,
+    pub strict_sync: bool,
+    /// Fail publishes that would reach no subscription.
+    pub fail_unrouted: bool,
+    pub inherit_tags: bool,
+    /// Hold email subscriptions pending until their confirmation link is
+    /// visited.
+    pub confirm_email_subscriptions: bool,
+    /// Seconds ConfirmSubscription accepts a confirmation token for.
+    pub confirmation_token_ttl_secs: u64,
+    /// Send every new subscription a test notification once it is
+    /// confirmed.
+    pub send_test_notification: bool,
+    pub delivery_status_dir: Option<PathBuf>,
+    /// JSON Lines file every API call and delivery attempt is appended to.
+    pub audit_log: Option<PathBuf>,
+    /// Size in bytes at which the audit log rotates; never when unset.
+    pub audit_log_max_bytes: Option<u64>,
+    /// Audit log files kept across rotations, the live one included.
+    pub audit_log_keep: usize,
+    /// S3-compatible endpoint oversized publish bodies are offloaded to, as
+    /// the extended client libraries do; off when unset.
+    pub payload_offload_endpoint: Option<String>,
+    /// Existing bucket offloaded bodies are stored in.
+    pub payload_offload_bucket: String,
+    /// Publish size in bytes, body and attributes, above which the body is
+    /// offloaded.
+    pub payload_offload_threshold: usize,
+    /// Seconds history messages and delivery records are kept; topics may
+    /// override it with an `x-localsns-retention` tag.
+    pub message_retention_secs: Option<u64>,
+    /// Seconds without a publish or subscribe after which a topic is
+    /// deleted; topics may override it with an `x-localsns-ttl` tag.
+    pub default_topic_ttl_secs: Option<u64>,
+    /// Milliseconds new topics and subscriptions stay invisible to reads
+    /// and publishes; 0 makes them visible at once.
+    pub consistency_delay_ms: u64,
+    /// Latency profiles keyed by action name.
+    pub latency_profiles: BTreeMap<String, LatencyProfile>,
+    /// Topic attributes new topics get unless CreateTopic sets them.
+    pub defaults: BTreeMap<String, String>,
+    /// Log deliveries at WARN and above only.
+    pub quiet: bool,
+    /// Short colored console lines for topics, subscriptions, publishes and
+    /// deliveries instead of the full log, when stdout is a terminal.
+    pub pretty: bool,
+    /// Log one in N successful deliveries; 0 and 1 log all of them.
+    pub log_sample: u32,
+    /// Seconds added to the timestamps of deliveries; may be negative.
+    pub clock_offset_secs: i64,
+    /// Content-Type of HTTP/S deliveries with RawMessageDelivery.
+    pub raw_content_type: String,
+    /// Show X-LocalSns-Headers values in GetSubscriptionAttributes.
+    pub expose_secrets: bool,
+    /// Report runtime subscription state in GetSubscriptionAttributes and
+    /// publish and delivery counters in GetTopicAttributes.
+    pub extended_attributes: bool,
+    /// Include runtime subscription state in exported snapshots.
+    pub persist_runtime_state: bool,
+    /// Name of this instance, reported by `/version`; the hostname when
+    /// unset.
+    pub instance_name: Option<String>,
+    /// Prepended to the IDs of published messages.
+    pub message_id_prefix: Option<String>,
+    /// Add the instance name to notifications as a message attribute.
+    pub tag_instance: bool,
+    /// Add the publisher to notifications as a message attribute.
+    pub tag_publisher: bool,
+    /// Wrap SQS deliveries without RawMessageDelivery in the notification
+    /// envelope, as AWS does, instead of sending the bare message.
+    pub sqs_envelope: bool,
+    /// Most topics CreateTopic may create; unlimited when unset.
+    pub max_topics: Option<usize>,
+    /// Most subscriptions Subscribe may add to one topic; unlimited when
+    /// unset.
+    pub max_subscriptions_per_topic: Option<usize>,
+    /// Background fan-outs run at once; unbounded when unset.
+    pub delivery_workers: Option<usize>,
+    /// Largest share of `delivery_workers` one topic may occupy.
+    pub topic_worker_share: f64,
+    /// Subscriptions one fan-out delivers to at once.
+    pub max_concurrent_deliveries: usize,
+    /// Factor the pauses between delivery retries are multiplied by.
+    pub retry_time_scale: f64,
+    /// How long one HTTP/S delivery attempt may take before it fails.
+    pub http_timeout_ms: u64,
+    /// Background fan-outs queued or running beyond which `/health`
+    /// answers 503; unchecked when unset.
+    pub max_delivery_backlog: Option<u64>,
+    /// SQS clients kept, one per service endpoint; the least recently used
+    /// is dropped beyond it.
+    pub max_sqs_clients: usize,
+    /// SQS service endpoint queue ARNs resolve onto.
+    pub sqs_endpoint: String,
+    /// How queue ARNs become queue URLs: a preset name or a template; see
+    /// `delivery::queue_url_template`.
+    pub sqs_queue_url_template: String,
+    /// Probe delivery endpoints at startup.
+    pub check_endpoints: Option<EndpointCheck>,
+    /// Probe HTTP/S endpoints when they are subscribed.
+    pub validate_endpoints_on_subscribe: Option<EndpointCheck>,
+    /// Serve only the AWS API surface: no admin API, no extension
+    /// attributes or parameters, and AWS limits enforced.
+    pub strict_aws: bool,
+    /// Accept topic ARNs of any region or account, resolving them by name.
+    pub ignore_arn_scope: bool,
+    /// `xmlns` of responses instead of AWS's; see `responses::xml_namespace`.
+    pub xml_namespace: Option<String>,
+    /// Do not serve the browser UI under `/ui`.
+    pub no_ui: bool,
+    /// Bearer token `/admin` requests must carry; the admin API is open
+    /// when unset.
+    pub admin_token: Option<String>,
+}
+
+/// What a failed probe of delivery endpoints does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum EndpointCheck {
+    /// Log a warning and carry on.
+    Warn,
+    /// Refuse to start, or reject the Subscribe.
+    Strict,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            addr: DEFAULT_ADDR.to_string(),
+            base_url: None,
+            seed: None,
+            watch_config: false,
+            prune_on_reload: false,
+            endpoint_failure_threshold: DEFAULT_ENDPOINT_FAILURE_THRESHOLD,
+            trust_proxy_headers: false,
+            sync_delivery: false,
+            strict_sync: false,
+            fail_unrouted: false,
+            inherit_tags: false,
+            confirm_email_subscriptions: false,
+            confirmation_token_ttl_secs: DEFAULT_CONFIRMATION_TOKEN_TTL_SECS,
+            send_test_notification: false,
+            delivery_status_dir: None,
+            audit_log: None,
+            audit_log_max_bytes: None,
+            audit_log_keep: DEFAULT_AUDIT_LOG_KEEP,
+            payload_offload_endpoint: None,
+            payload_offload_bucket: DEFAULT_PAYLOAD_OFFLOAD_BUCKET.to_string(),
+            payload_offload_threshold: MAX_MESSAGE_SIZE,
+            message_retention_secs: None,
+            default_topic_ttl_secs: None,
+            consistency_delay_ms: 0,
+            latency_profiles: BTreeMap::new(),
+            defaults: BTreeMap::new(),
+            quiet: false,
+            pretty: false,
+            log_sample: 0,
+            clock_offset_secs: 0,
+            raw_content_type: DEFAULT_RAW_CONTENT_TYPE.to_string(),
+            expose_secrets: false,
+            extended_attributes: false,
+            persist_runtime_state: false,
+            instance_name: None,
+            message_id_prefix: None,
+            tag_instance: false,
+            tag_publisher: false,
+            sqs_envelope: false,
+            max_topics: None,
+            max_subscriptions_per_topic: None,
+            delivery_workers: None,
+            topic_worker_share: DEFAULT_TOPIC_WORKER_SHARE,
+            max_concurrent_deliveries: DEFAULT_MAX_CONCURRENT_DELIVERIES,
+            retry_time_scale: 1.0,
+            http_timeout_ms: DEFAULT_HTTP_TIMEOUT_MS,
+            max_delivery_backlog: None,
+            max_sqs_clients: DEFAULT_MAX_SQS_CLIENTS,
+            sqs_endpoint: DEFAULT_SQS_ENDPOINT.to_string(),
+            sqs_queue_url_template: "elasticmq".to_string(),
+            check_endpoints: None,
+            validate_endpoints_on_subscribe: None,
+            strict_aws: false,
+            ignore_arn_scope: false,
+            xml_namespace: None,
+            no_ui: false,
+            admin_token: None,
+        }
+    }
+}
+
+/// Command-line flags and their environment variables. Only values that
+/// were given are serialized, so unset flags leave lower layers in place.
+/// Switches are optional too: `--quiet` turns one on and `--quiet=false`
+/// or `LOCAL_SNS_QUIET=false` off again over the config file.
+#[derive(Debug, Parser, Serialize)]
+#[command(name = "local-sns-rs", version, about = "A local SNS emulator", after_help = AFTER_HELP)]
+struct Cli {
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
+    /// TOML configuration file
+    #[arg(long, value_name = "PATH", env = "LOCAL_SNS_CONFIG")]
+    #[serde(skip)]
+    config: Option<PathBuf>,
+    /// Socket address to listen on [default: 127.0.0.1:9911]
+    #[arg(long, value_name = "ADDR", env = "LOCAL_SNS_ADDR")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    addr: Option<String>,
+    /// Base URL of generated links such as UnsubscribeURL
+    #[arg(long, value_name = "URL", env = "LOCAL_SNS_BASE_URL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base_url: Option<String>,
+    /// Load a JSON or YAML state document at startup; reloaded on SIGHUP
+    #[arg(long, value_name = "PATH", env = "LOCAL_SNS_SEED")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<PathBuf>,
+    /// Also reload the seed file when it changes
+    #[arg(
+        long,
+        env = "LOCAL_SNS_WATCH_CONFIG",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    watch_config: Option<bool>,
+    /// Delete topics and subscriptions removed from the seed file on reload
+    #[arg(
+        long,
+        env = "LOCAL_SNS_PRUNE_ON_RELOAD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prune_on_reload: Option<bool>,
+    /// Disable a subscription after N consecutive failed deliveries
+    /// (0 = never) [default: 0]
+    #[arg(long, value_name = "N", env = "LOCAL_SNS_ENDPOINT_FAILURE_THRESHOLD")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    endpoint_failure_threshold: Option<u32>,
+    /// Build generated URLs from X-Forwarded-Proto, X-Forwarded-Host and
+    /// X-Forwarded-Port
+    #[arg(
+        long,
+        env = "LOCAL_SNS_TRUST_PROXY_HEADERS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trust_proxy_headers: Option<bool>,
+    /// Deliver every publish before responding and report failures in
+    /// x-localsns-delivery-failures (per request: x-localsns-sync-delivery: true)
+    #[arg(
+        long,
+        env = "LOCAL_SNS_SYNC_DELIVERY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sync_delivery: Option<bool>,
+    /// Fail synchronous publishes whose deliveries fail with InternalFailure
+    #[arg(
+        long,
+        env = "LOCAL_SNS_STRICT_SYNC",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strict_sync: Option<bool>,
+    /// Answer publishes that would reach no subscription, because the topic
+    /// has none or all are pending confirmation or disabled, with a
+    /// non-standard UnroutedMessage error instead of success
+    #[arg(
+        long,
+        env = "LOCAL_SNS_FAIL_UNROUTED",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fail_unrouted: Option<bool>,
+    /// Copy topic tags onto new subscriptions
+    #[arg(
+        long,
+        env = "LOCAL_SNS_INHERIT_TAGS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inherit_tags: Option<bool>,
+    /// Start email and email-json subscriptions PendingConfirmation and
+    /// send a confirmation email to the outbox; publishes reach them once
+    /// its SubscribeURL is visited
+    #[arg(
+        long,
+        env = "LOCAL_SNS_CONFIRM_EMAIL_SUBSCRIPTIONS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confirm_email_subscriptions: Option<bool>,
+    /// Seconds a subscription confirmation token stays valid; after that
+    /// ConfirmSubscription refuses it [default: 259200, three days]
+    #[arg(long, value_name = "SECS", env = "LOCAL_SNS_CONFIRMATION_TOKEN_TTL")]
+    #[serde(
+        rename = "confirmation_token_ttl_secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    confirmation_token_ttl: Option<u64>,
+    /// Deliver a "local-sns subscription test" notification to every new
+    /// subscription once it is confirmed, for consumers that check their
+    /// wiring with the first message
+    #[arg(
+        long,
+        env = "LOCAL_SNS_SEND_TEST_NOTIFICATION",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    send_test_notification: Option<bool>,
+    /// Also append delivery status logs of topics with feedback role ARNs
+    /// to PATH/<topic>.log
+    #[arg(long, value_name = "PATH", env = "LOCAL_SNS_DELIVERY_STATUS_DIR")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delivery_status_dir: Option<PathBuf>,
+    /// Append one JSON line per API call and per delivery attempt to PATH
+    #[arg(long, value_name = "PATH", env = "LOCAL_SNS_AUDIT_LOG")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audit_log: Option<PathBuf>,
+    /// Rotate the audit log once it would grow past BYTES
+    #[arg(long, value_name = "BYTES", env = "LOCAL_SNS_AUDIT_LOG_MAX_BYTES")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audit_log_max_bytes: Option<u64>,
+    /// Audit log files to keep across rotations, the live one included
+    /// [default: 5]
+    #[arg(long, value_name = "N", env = "LOCAL_SNS_AUDIT_LOG_KEEP")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audit_log_keep: Option<usize>,
+    /// Store the bodies of publishes larger than the offload threshold in
+    /// this S3-compatible store (e.g. MinIO) and publish the extended client
+    /// pointer instead
+    #[arg(long, value_name = "URL", env = "LOCAL_SNS_PAYLOAD_OFFLOAD_ENDPOINT")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload_offload_endpoint: Option<String>,
+    /// Existing bucket offloaded bodies are stored in [default:
+    /// local-sns-payloads]
+    #[arg(long, value_name = "BUCKET", env = "LOCAL_SNS_PAYLOAD_OFFLOAD_BUCKET")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload_offload_bucket: Option<String>,
+    /// Offload publishes whose body and attributes exceed BYTES [default:
+    /// 262144]
+    #[arg(
+        long,
+        value_name = "BYTES",
+        env = "LOCAL_SNS_PAYLOAD_OFFLOAD_THRESHOLD"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload_offload_threshold: Option<usize>,
+    /// Expire history messages and delivery records after SECS seconds
+    /// (per topic: tag x-localsns-retention)
+    #[arg(long, value_name = "SECS", env = "LOCAL_SNS_MESSAGE_RETENTION")]
+    #[serde(
+        rename = "message_retention_secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    message_retention: Option<u64>,
+    /// Delete topics SECS seconds after their last publish or subscribe
+    /// (per topic: tag x-localsns-ttl)
+    #[arg(long, value_name = "SECS", env = "LOCAL_SNS_DEFAULT_TOPIC_TTL")]
+    #[serde(
+        rename = "default_topic_ttl_secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    default_topic_ttl: Option<u64>,
+    /// Simulate eventual consistency: new topics and subscriptions answer
+    /// NotFound to reads and publishes for their first MS milliseconds
+    /// [default: 0]
+    #[arg(long, value_name = "MS", env = "LOCAL_SNS_CONSISTENCY_DELAY")]
+    #[serde(
+        rename = "consistency_delay_ms",
+        skip_serializing_if = "Option::is_none"
+    )]
+    consistency_delay: Option<u64>,
+    /// JSON object of latency profiles by action, merged over the config
+    /// file's
+    #[arg(long, value_name = "JSON", env = "LOCAL_SNS_LATENCY_PROFILES")]
+    #[serde(skip)]
+    latency_profiles: Option<String>,
+    /// Only log deliveries at WARN and above
+    #[arg(
+        long,
+        env = "LOCAL_SNS_QUIET",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quiet: Option<bool>,
+    /// Print short colored lines for the main events instead of the full
+    /// log; ignored unless stdout is a terminal
+    #[arg(
+        long,
+        env = "LOCAL_SNS_PRETTY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pretty: Option<bool>,
+    /// Log one in N successful deliveries; failures are always logged
+    #[arg(long, value_name = "N", env = "LOCAL_SNS_LOG_SAMPLE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log_sample: Option<u32>,
+    /// Shift the timestamps of deliveries by SECS seconds (may be negative)
+    #[arg(
+        long,
+        value_name = "SECS",
+        env = "LOCAL_SNS_CLOCK_OFFSET",
+        allow_hyphen_values = true
+    )]
+    #[serde(rename = "clock_offset_secs", skip_serializing_if = "Option::is_none")]
+    clock_offset: Option<i64>,
+    /// Content-Type of raw HTTP/S deliveries [default: application/json]
+    #[arg(long, value_name = "TYPE", env = "LOCAL_SNS_RAW_CONTENT_TYPE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_content_type: Option<String>,
+    /// Show X-LocalSns-Headers values in GetSubscriptionAttributes instead
+    /// of masking them
+    #[arg(
+        long,
+        env = "LOCAL_SNS_EXPOSE_SECRETS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expose_secrets: Option<bool>,
+    /// Report runtime subscription state, such as
+    /// X-LocalSns-LastDeliveryError, in GetSubscriptionAttributes, and
+    /// counters, such as X-LocalSns-MessagesPublished, in GetTopicAttributes
+    #[arg(
+        long,
+        env = "LOCAL_SNS_EXTENDED_ATTRIBUTES",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extended_attributes: Option<bool>,
+    /// Keep runtime subscription state, such as the last delivery error, in
+    /// exported snapshots and restore it on import
+    #[arg(
+        long,
+        env = "LOCAL_SNS_PERSIST_RUNTIME_STATE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    persist_runtime_state: Option<bool>,
+    /// Name of this instance, shown by /version [default: the hostname]
+    #[arg(long, value_name = "NAME", env = "LOCAL_SNS_INSTANCE_NAME")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance_name: Option<String>,
+    /// Prefix the IDs of published messages with PREFIX, e.g. the test shard
+    #[arg(long, value_name = "PREFIX", env = "LOCAL_SNS_MESSAGE_ID_PREFIX")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_id_prefix: Option<String>,
+    /// Add the instance name to notifications as the local-sns-instance
+    /// message attribute
+    #[arg(
+        long,
+        env = "LOCAL_SNS_TAG_INSTANCE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag_instance: Option<bool>,
+    /// Add the publisher (SigV4 access key id, else User-Agent and source
+    /// IP) to notifications as the local-sns-publisher message attribute
+    #[arg(
+        long,
+        env = "LOCAL_SNS_TAG_PUBLISHER",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag_publisher: Option<bool>,
+    /// Deliver to SQS subscriptions without RawMessageDelivery the JSON
+    /// notification envelope, as AWS does, instead of the bare message
+    #[arg(
+        long,
+        env = "LOCAL_SNS_SQS_ENVELOPE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sqs_envelope: Option<bool>,
+    /// Fail CreateTopic with TopicLimitExceeded once N topics exist
+    #[arg(long, value_name = "N", env = "LOCAL_SNS_MAX_TOPICS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_topics: Option<usize>,
+    /// Fail Subscribe with SubscriptionLimitExceeded once a topic has N
+    /// subscriptions
+    #[arg(long, value_name = "N", env = "LOCAL_SNS_MAX_SUBSCRIPTIONS_PER_TOPIC")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_subscriptions_per_topic: Option<usize>,
+    /// Run at most N background fan-outs at once [default: unbounded]
+    #[arg(long, value_name = "N", env = "LOCAL_SNS_DELIVERY_WORKERS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delivery_workers: Option<usize>,
+    /// Largest share of the delivery workers one topic may occupy, so a
+    /// flooded topic cannot starve the others [default: 0.5]
+    #[arg(long, value_name = "FRACTION", env = "LOCAL_SNS_TOPIC_WORKER_SHARE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    topic_worker_share: Option<f64>,
+    /// Deliver each publish to at most N subscriptions at once [default: 16]
+    #[arg(long, value_name = "N", env = "LOCAL_SNS_MAX_CONCURRENT_DELIVERIES")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_concurrent_deliveries: Option<usize>,
+    /// Multiply the pauses between delivery retries, which follow the
+    /// DeliveryPolicy, by FACTOR; 0 retries at once [default: 1]
+    #[arg(long, value_name = "FACTOR", env = "LOCAL_SNS_RETRY_TIME_SCALE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_time_scale: Option<f64>,
+    /// Fail an HTTP/S delivery attempt after MS milliseconds without a
+    /// complete response [default: 15000]
+    #[arg(long, value_name = "MS", env = "LOCAL_SNS_HTTP_TIMEOUT_MS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_timeout_ms: Option<u64>,
+    /// Answer /health with 503 while more than N background fan-outs are
+    /// queued or running
+    #[arg(long, value_name = "N", env = "LOCAL_SNS_MAX_DELIVERY_BACKLOG")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_delivery_backlog: Option<u64>,
+    /// Keep SQS clients for at most N service endpoints, dropping the least
+    /// recently used [default: 128]
+    #[arg(long, value_name = "N", env = "LOCAL_SNS_MAX_SQS_CLIENTS")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_sqs_clients: Option<usize>,
+    /// Deliver to queues subscribed by ARN through the SQS service at URL
+    /// [default: http://localhost:4566]
+    #[arg(long, value_name = "URL", env = "LOCAL_SNS_SQS_ENDPOINT")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sqs_endpoint: Option<String>,
+    /// Build the queue URL of a queue subscribed by ARN from TEMPLATE:
+    /// elasticmq ({endpoint}/{account}/{queue}, also GoAWS), localstack
+    /// ({endpoint}/queue/{queue}), or a template of {endpoint}, {region},
+    /// {account} and {queue} [default: elasticmq]
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        env = "LOCAL_SNS_SQS_QUEUE_URL_TEMPLATE"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sqs_queue_url_template: Option<String>,
+    /// Check at startup that the SQS endpoints deliveries use accept
+    /// connections; warn about unreachable ones, or refuse to start with
+    /// =strict
+    #[arg(
+        long,
+        value_name = "MODE",
+        env = "LOCAL_SNS_CHECK_ENDPOINTS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "warn"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    check_endpoints: Option<EndpointCheck>,
+    /// Check that HTTP/S endpoints accept connections when subscribed;
+    /// warn about unreachable ones, or reject the Subscribe with =strict
+    #[arg(
+        long,
+        value_name = "MODE",
+        env = "LOCAL_SNS_VALIDATE_ENDPOINTS_ON_SUBSCRIBE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "warn"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    validate_endpoints_on_subscribe: Option<EndpointCheck>,
+    /// Match the AWS surface exactly: disable the admin API, reject
+    /// X-LocalSns-* attributes and the memory protocol, ignore non-standard
+    /// parameters and headers, and enforce AWS tag limits
+    #[arg(
+        long,
+        env = "LOCAL_SNS_STRICT_AWS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strict_aws: Option<bool>,
+    /// Accept topic ARNs of other regions and accounts than us-east-1 and
+    /// 000000000000, resolving them by topic name
+    #[arg(
+        long,
+        env = "LOCAL_SNS_IGNORE_ARN_SCOPE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ignore_arn_scope: Option<bool>,
+    /// Use URL as the xmlns of every response instead of AWS's
+    /// http://sns.amazonaws.com/doc/2010-03-31/
+    #[arg(long, value_name = "URL", env = "LOCAL_SNS_XML_NAMESPACE")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xml_namespace: Option<String>,
+    /// Do not serve the browser UI at /ui (also off with --strict-aws)
+    #[arg(
+        long,
+        env = "LOCAL_SNS_NO_UI",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    no_ui: Option<bool>,
+    /// Require "Authorization: Bearer TOKEN" on the admin API
+    /// [default: open]
+    #[arg(long, value_name = "TOKEN", env = "LOCAL_SNS_ADMIN_TOKEN")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    admin_token: Option<String>,
+}
+
+/// What to run instead of serving, when a subcommand is given.
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Publish at a fixed rate to an embedded server and report publish
+    /// latency, delivery lag and drops
+    Bench(BenchArgs),
+    /// Inspect or edit a state document while the server is stopped
+    State(StateArgs),
+}
+
+impl Config {
+    /// Loads the configuration from the command line, the environment and
+    /// the optional config file, in that order of precedence, and validates
+    /// it. Also returns the subcommand, if any.
+    pub fn load() -> Result<(Config, Option<Command>), String> {
+        let mut cli = Cli::parse();
+        let command = cli.command.take();
+        Ok((Self::from_cli(cli)?, command))
+    }
+
+    fn from_cli(cli: Cli) -> Result<Config, String> {
+        let mut figment = Figment::from(Serialized::defaults(Config::default()));
+        if let Some(path) = &cli.config {
+            if !path.is_file() {
+                return Err(format!("config: {} does not exist", path.display()));
+            }
+            figment = figment.merge(Toml::file(path));
+        }
+        figment = figment.merge(Serialized::defaults(&cli));
+        let mut config: Config = figment.extract().map_err(|e| e.to_string())?;
+
+        if let Some(json) = &cli.latency_profiles {
+            let profiles =
+                latency::parse_profiles(json).map_err(|e| format!("latency_profiles: {}", e))?;
+            config.latency_profiles.extend(profiles);
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks values serde cannot, naming the offending key.
+    pub fn validate(&self) -> Result<(), String> {
+        self.addr
+            .parse::<SocketAddr>()
+            .map_err(|e| format!("addr: {}: {}", self.addr, e))?;
+        if let Some(base_url) = &self.base_url {
+            let url = Url::parse(base_url).map_err(|e| format!("base_url: {}: {}", base_url, e))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(format!("base_url: {} is not an http(s) URL", base_url));
+            }
+        }
+        let sqs_endpoint = Url::parse(&self.sqs_endpoint)
+            .map_err(|e| format!("sqs_endpoint: {}: {}", self.sqs_endpoint, e))?;
+        if !matches!(sqs_endpoint.scheme(), "http" | "https") {
+            return Err(format!(
+                "sqs_endpoint: {} is not an http(s) URL",
+                self.sqs_endpoint
+            ));
+        }
+        delivery::queue_url_template(&self.sqs_queue_url_template)
+            .map_err(|e| format!("sqs_queue_url_template: {}", e))?;
+        if self.seed.is_none() {
+            if self.watch_config {
+                return Err("watch_config: requires seed".to_string());
+            }
+            if self.prune_on_reload {
+                return Err("prune_on_reload: requires seed".to_string());
+            }
+        }
+        for (action, profile) in &self.latency_profiles {
+            profile
+                .validate()
+                .map_err(|e| format!("latency_profiles.{}: {}", action, e))?;
+        }
+        topic_defaults(self.defaults.clone()).map_err(|e| format!("defaults: {}", e))?;
+        if axum::http::HeaderValue::from_str(&self.raw_content_type).is_err() {
+            return Err(format!(
+                "raw_content_type: {} is not a valid header value",
+                self.raw_content_type
+            ));
+        }
+        self.delivery_config().validate()?;
+        if let Some(instance_name) = &self.instance_name
+            && instance_name.trim().is_empty()
+        {
+            return Err("instance_name: must not be empty".to_string());
+        }
+        if let Some(token) = &self.admin_token
+            && (token.is_empty() || !token.bytes().all(|b| b.is_ascii_graphic()))
+        {
+            return Err("admin_token: must be non-empty visible ASCII".to_string());
+        }
+        if let Some(prefix) = &self.message_id_prefix
+            && !prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c))
+        {
+            return Err(format!(
+                "message_id_prefix: {} may only contain letters, digits and -_.:",
+                prefix
+            ));
+        }
+        if self.confirmation_token_ttl_secs == 0 {
+            return Err("confirmation_token_ttl_secs: must be positive".to_string());
+        }
+        if Clock::with_offset_secs(self.clock_offset_secs).is_none() {
+            return Err(format!(
+                "clock_offset_secs: {} is out of range",
+                self.clock_offset_secs
+            ));
+        }
+        Ok(())
+    }
+
+    /// The delivery tuning the server starts with.
+    pub fn delivery_config(&self) -> DeliveryConfig {
+        DeliveryConfig {
+            delivery_workers: self.delivery_workers,
+            topic_worker_share: self.topic_worker_share,
+            max_concurrent_deliveries: self.max_concurrent_deliveries,
+            retry_time_scale: self.retry_time_scale,
+            http_timeout_ms: self.http_timeout_ms,
+        }
+    }
+
+    /// A copy safe to log, with URL credentials and the admin token masked.
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        if config.admin_token.is_some() {
+            config.admin_token = Some("***".to_string());
+        }
+        if let Some(base_url) = &config.base_url
+            && let Ok(mut url) = Url::parse(base_url)
+            && url.password().is_some()
+        {
+            let _ = url.set_password(Some("***"));
+            config.base_url = Some(url.to_string());
+        }
+        config
+    }
+
+    /// Name of this instance: the configured one, else the hostname.
+    pub fn instance_name(&self) -> String {
+        if let Some(instance_name) = &self.instance_name {
+            return instance_name.clone();
+        }
+        std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|hostname| hostname.trim().to_string())
+            .filter(|hostname| !hostname.is_empty())
+            .unwrap_or_else(|| "local-sns".to_string())
+    }
+
+    /// Base URL of generated links.
+    pub fn base_url(&self) -> String {
+        match &self.base_url {
+            Some(base_url) => base_url.trim_end_matches('/').to_string(),
+            None => format!("http://{}", self.addr),
+        }
+    }
+
+    pub fn seed_config(&self) -> Option<SeedConfig> {
+        self.seed.clone().map(|path| SeedConfig {
+            path,
+            watch: self.watch_config,
+            prune_on_reload: self.prune_on_reload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn load(file: &str, args: &[&str]) -> Config {
+        let path = std::env::temp_dir().join(format!("local-sns-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, file).unwrap();
+        let config_arg = format!("--config={}", path.display());
+        let cli = Cli::try_parse_from(
+            ["local-sns-rs", config_arg.as_str()]
+                .into_iter()
+                .chain(args.iter().copied()),
+        )
+        .unwrap();
+        let config = Config::from_cli(cli);
+        std::fs::remove_file(&path).unwrap();
+        config.unwrap()
+    }
+
+    #[test]
+    fn flags_are_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn switches_from_the_file_apply_when_not_given() {
+        let config = load("quiet = true\nsync_delivery = true\n", &[]);
+        assert!(config.quiet);
+        assert!(config.sync_delivery);
+    }
+
+    #[test]
+    fn switches_on_the_command_line_override_the_file() {
+        let config = load("quiet = true\n", &["--quiet=false", "--sync-delivery"]);
+        assert!(!config.quiet);
+        assert!(config.sync_delivery);
+    }
+
+    #[test]
+    fn switches_take_boolish_values() {
+        let config = load("", &["--quiet=yes", "--pretty=0"]);
+        assert!(config.quiet);
+        assert!(!config.pretty);
+    }
+
+    #[test]
+    fn queue_url_templates_are_checked_at_startup() {
+        let config = load("", &["--sqs-queue-url-template=localstack"]);
+        assert_eq!(config.sqs_queue_url_template, "localstack");
+        let config = load("sqs_queue_url_template = \"{endpoint}/q/{queue}\"\n", &[]);
+        assert_eq!(config.sqs_queue_url_template, "{endpoint}/q/{queue}");
+
+        let cli =
+            Cli::try_parse_from(["local-sns-rs", "--sqs-queue-url-template={endpoint}"]).unwrap();
+        let error = Config::from_cli(cli).unwrap_err();
+        assert!(error.starts_with("sqs_queue_url_template:"), "{}", error);
+    }
+}
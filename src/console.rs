@@ -0,0 +1,225 @@
+use crate::delivery::DeliveryResult;
+use crate::state::{Subscription, topic_name_from_arn};
+use std::fmt::Debug;
+use std::io::Write;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+
+/// Target of the well-known events `--pretty` shows. They are logged at
+/// DEBUG, so the default output does not repeat what it already logs.
+pub const EVENT_TARGET: &str = "local_sns_rs::events";
+
+pub const LISTENING: &str = "listening";
+pub const TOPIC_CREATED: &str = "topic_created";
+pub const SUBSCRIPTION_ADDED: &str = "subscription_added";
+pub const MESSAGE_PUBLISHED: &str = "message_published";
+/// A delivery to one subscription; `outcome` is one of the `OUTCOME_*`
+/// values.
+pub const MESSAGE_DELIVERED: &str = "message_delivered";
+
+pub const OUTCOME_DELIVERED: &str = "delivered";
+pub const OUTCOME_FAILED: &str = "failed";
+/// Not attempted: the subscription is pending confirmation or disabled.
+pub const OUTCOME_SKIPPED: &str = "skipped";
+
+pub fn listening(addr: &str) {
+    tracing::debug!(target: EVENT_TARGET, event = LISTENING, addr);
+}
+
+pub fn topic_created(topic_name: &str) {
+    tracing::debug!(target: EVENT_TARGET, event = TOPIC_CREATED, topic = topic_name);
+}
+
+pub fn subscription_added(subscription: &Subscription) {
+    tracing::debug!(
+        target: EVENT_TARGET,
+        event = SUBSCRIPTION_ADDED,
+        topic = topic_name_from_arn(&subscription.arn),
+        protocol = subscription.protocol.as_str(),
+        endpoint = subscription.endpoint.as_str(),
+        subscription_arn = subscription.subscription_arn.as_str(),
+    );
+}
+
+pub fn message_published(topic_name: &str, message_id: &str) {
+    tracing::debug!(
+        target: EVENT_TARGET,
+        event = MESSAGE_PUBLISHED,
+        topic = topic_name,
+        message_id,
+    );
+}
+
+/// `result` is that of the delivery, or `None` when it was skipped.
+pub fn message_delivered(
+    subscription: &Subscription,
+    message_id: &str,
+    result: Option<&DeliveryResult>,
+) {
+    let (outcome, error) = match result {
+        Some(Ok(_)) => (OUTCOME_DELIVERED, None),
+        Some(Err(error)) => (OUTCOME_FAILED, Some(error.message.as_str())),
+        None => (OUTCOME_SKIPPED, None),
+    };
+    tracing::debug!(
+        target: EVENT_TARGET,
+        event = MESSAGE_DELIVERED,
+        topic = topic_name_from_arn(&subscription.arn),
+        message_id,
+        protocol = subscription.protocol.as_str(),
+        endpoint = subscription.endpoint.as_str(),
+        subscription_arn = subscription.subscription_arn.as_str(),
+        outcome,
+        error,
+    );
+}
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+
+/// Fields of a well-known event, or the message of any other one.
+#[derive(Default)]
+struct EventFields {
+    event: Option<String>,
+    addr: String,
+    topic: String,
+    protocol: String,
+    endpoint: String,
+    message_id: String,
+    outcome: String,
+    error: Option<String>,
+    message: String,
+}
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let value = value.to_string();
+        match field.name() {
+            "event" => self.event = Some(value),
+            "addr" => self.addr = value,
+            "topic" => self.topic = value,
+            "protocol" => self.protocol = value,
+            "endpoint" => self.endpoint = value,
+            "message_id" => self.message_id = value,
+            "outcome" => self.outcome = value,
+            "error" => self.error = Some(value),
+            "message" => self.message = value,
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            _ => self.record_str(field, &format!("{:?}", value)),
+        }
+    }
+}
+
+/// Console output for `--pretty`: one short colored line per well-known
+/// event, plus warnings and errors from anywhere. Everything else,
+/// per-request logging included, is left out.
+pub struct PrettyLayer {
+    color: bool,
+}
+
+impl PrettyLayer {
+    /// Colors are left out when `NO_COLOR` is set.
+    pub fn new() -> Self {
+        PrettyLayer {
+            color: std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+        }
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn format(&self, level: Level, is_event: bool, fields: &EventFields) -> Option<String> {
+        if !is_event {
+            return match level {
+                Level::ERROR => Some(format!("{} {}", self.paint(RED, "!"), fields.message)),
+                Level::WARN => Some(format!("{} {}", self.paint(YELLOW, "!"), fields.message)),
+                _ => None,
+            };
+        }
+        let short_id = &fields.message_id[..fields.message_id.len().min(8)];
+        let line = match fields.event.as_deref()? {
+            LISTENING => format!("{} listening on {}", self.paint(CYAN, "*"), fields.addr),
+            TOPIC_CREATED => format!("{} topic {}", self.paint(CYAN, "+"), fields.topic),
+            SUBSCRIPTION_ADDED => format!(
+                "{} subscription {} {} {}",
+                self.paint(CYAN, "+"),
+                fields.topic,
+                self.paint(DIM, "->"),
+                self.paint(DIM, &format!("{} {}", fields.protocol, fields.endpoint)),
+            ),
+            MESSAGE_PUBLISHED => format!(
+                "{} {} {}",
+                self.paint(CYAN, ">"),
+                fields.topic,
+                self.paint(DIM, short_id),
+            ),
+            MESSAGE_DELIVERED => {
+                let (glyph, color) = match fields.outcome.as_str() {
+                    OUTCOME_DELIVERED => ("\u{2713}", GREEN),
+                    OUTCOME_FAILED => ("\u{2717}", RED),
+                    _ => ("-", DIM),
+                };
+                let mut line = format!(
+                    "  {} {} {} {} {}",
+                    self.paint(color, glyph),
+                    fields.topic,
+                    self.paint(DIM, short_id),
+                    fields.protocol,
+                    fields.endpoint,
+                );
+                if let Some(error) = &fields.error {
+                    line.push_str(&format!(" {}", self.paint(RED, error)));
+                } else if fields.outcome == OUTCOME_SKIPPED {
+                    line.push_str(&format!(" {}", self.paint(DIM, OUTCOME_SKIPPED)));
+                }
+                line
+            }
+            _ => return None,
+        };
+        Some(line)
+    }
+}
+
+impl Default for PrettyLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for PrettyLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let is_event = metadata.target() == EVENT_TARGET;
+        if !is_event && *metadata.level() > Level::WARN {
+            return;
+        }
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+        if let Some(line) = self.format(*metadata.level(), is_event, &fields) {
+            let time = chrono::Local::now().format("%H:%M:%S").to_string();
+            let _ = writeln!(
+                std::io::stdout().lock(),
+                "{} {}",
+                self.paint(DIM, &time),
+                line
+            );
+        }
+    }
+}
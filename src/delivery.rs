@@ -0,0 +1,1255 @@
+use crate::console;
+#[cfg(feature = "http-delivery")]
+use crate::delivery_policy::RetryPolicy;
+use crate::delivery_status::DeliveryStatusLog;
+use crate::demux;
+#[cfg(feature = "http-delivery")]
+use crate::http_delivery;
+use crate::memory;
+use crate::platform;
+#[cfg(feature = "sqs-delivery")]
+use crate::sqs;
+use crate::state::{
+    AppState, INSTANCE_ATTRIBUTE, Message, OutboxEntry, PUBLISHER_ATTRIBUTE, SharedState,
+    Subscription, topic_name_from_arn,
+};
+use crate::tracker::JobOutcome;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+#[cfg(feature = "http-delivery")]
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::Instrument;
+use url::Url;
+use uuid::Uuid;
+
+/// The JSON document SNS delivers to subscribers without raw delivery.
+pub fn notification_envelope(
+    state: &AppState,
+    message: &Message,
+    subscription: &Subscription,
+) -> String {
+    let mut envelope = json!({
+        "Type": "Notification",
+        "MessageId": message.id,
+        "TopicArn": subscription.arn,
+        "Message": message.body_for(&subscription.protocol),
+        "Timestamp": state.clock.format(message.timestamp),
+        "UnsubscribeURL": format!(
+            "{}/?Action=Unsubscribe&SubscriptionArn={}",
+            message.base_url.as_deref().unwrap_or(&state.base_url),
+            subscription.subscription_arn
+        ),
+    });
+    if let Some(subject) = &message.subject {
+        envelope["Subject"] = json!(subject);
+    }
+    if let Some(sequence_number) = &message.sequence_number {
+        envelope["SequenceNumber"] = json!(sequence_number);
+    }
+    if let Some(message_group_id) = &message.message_group_id {
+        envelope["MessageGroupId"] = json!(message_group_id);
+    }
+    let mut attributes: serde_json::Map<String, serde_json::Value> = message
+        .message_attributes
+        .iter()
+        .map(|(name, attribute)| {
+            (
+                name.clone(),
+                json!({ "Type": attribute.data_type, "Value": attribute.value() }),
+            )
+        })
+        .collect();
+    if state.tag_instance {
+        attributes.insert(
+            INSTANCE_ATTRIBUTE.to_string(),
+            json!({ "Type": "String", "Value": state.instance_name }),
+        );
+    }
+    if state.tag_publisher
+        && let Some(publisher) = &message.publisher
+    {
+        attributes.insert(
+            PUBLISHER_ATTRIBUTE.to_string(),
+            json!({ "Type": "String", "Value": publisher.label() }),
+        );
+    }
+    if !attributes.is_empty() {
+        envelope["MessageAttributes"] = json!(attributes);
+    }
+    envelope.to_string()
+}
+
+/// Content type of enveloped HTTP/S deliveries, as AWS sends them.
+const ENVELOPE_CONTENT_TYPE: &str = "text/plain; charset=UTF-8";
+
+const HTTP_USER_AGENT: &str = "Amazon Simple Notification Service Agent";
+
+/// An HTTP/S POST as SNS makes it.
+#[derive(Debug, Clone)]
+pub struct HttpDelivery {
+    pub endpoint: String,
+    pub headers: Vec<(&'static str, String)>,
+    /// Headers from the subscription's `X-LocalSns-Headers`, expanded. They
+    /// replace SNS headers of the same name.
+    pub custom_headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpDelivery {
+    /// A POST of `body` with the headers every SNS message carries.
+    fn new(
+        endpoint: &str,
+        message_type: &str,
+        message_id: &str,
+        topic_arn: &str,
+        body: String,
+    ) -> Self {
+        HttpDelivery {
+            endpoint: endpoint.to_string(),
+            headers: vec![
+                ("x-amz-sns-message-type", message_type.to_string()),
+                ("x-amz-sns-message-id", message_id.to_string()),
+                ("x-amz-sns-topic-arn", topic_arn.to_string()),
+                ("Content-Type", ENVELOPE_CONTENT_TYPE.to_string()),
+                ("User-Agent", HTTP_USER_AGENT.to_string()),
+            ],
+            custom_headers: Vec::new(),
+            body,
+        }
+    }
+
+    /// Sets `name`, replacing an earlier value.
+    fn header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.retain(|(existing, _)| *existing != name);
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Adds the subscription's `X-LocalSns-Headers`, expanding `${NAME}`
+    /// references. Fails when a referenced variable is unset.
+    fn with_subscription_headers(mut self, subscription: &Subscription) -> Result<Self, String> {
+        for (name, value) in &subscription.headers {
+            let value = resolve_endpoint(value)?;
+            self.headers
+                .retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+            self.custom_headers.push((name.clone(), value));
+        }
+        Ok(self)
+    }
+
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        self.custom_headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+            .chain(self.headers.iter().map(|(name, value)| (*name, value)))
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The headers as they would be sent, for logging. Custom header values
+    /// may be secrets and are masked unless `expose_secrets`.
+    #[cfg(feature = "http-delivery")]
+    fn logged_headers(&self, expose_secrets: bool) -> Vec<(&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .chain(self.custom_headers.iter().map(|(name, value)| {
+                let value = if expose_secrets {
+                    value.as_str()
+                } else {
+                    "***"
+                };
+                (name.as_str(), value)
+            }))
+            .collect()
+    }
+}
+
+/// The POST delivering `message` to an HTTP/S subscription. Enveloped
+/// deliveries send the JSON envelope as text/plain. Raw deliveries send the
+/// message exactly as published with `x-amz-sns-rawdelivery: true` and the
+/// configured raw content type.
+pub fn notification_delivery(
+    state: &AppState,
+    subscription: &Subscription,
+    message: &Message,
+    endpoint: &str,
+) -> Result<HttpDelivery, String> {
+    let delivery = HttpDelivery::new(
+        endpoint,
+        "Notification",
+        &message.id,
+        &subscription.arn,
+        message_body(state, message, subscription),
+    )
+    .header(
+        "x-amz-sns-subscription-arn",
+        subscription.subscription_arn.clone(),
+    );
+    let delivery = if subscription.raw_message_delivery {
+        delivery
+            .header("x-amz-sns-rawdelivery", "true".to_string())
+            .header("Content-Type", state.raw_content_type.clone())
+    } else {
+        delivery
+    };
+    // Traced publishes carry their trace on to the endpoint, as they do to
+    // SQS in the `AWSTraceHeader` system attribute.
+    let delivery = match &message.trace_header {
+        Some(trace_header) => delivery.header("X-Amzn-Trace-Id", trace_header.clone()),
+        None => delivery,
+    };
+    delivery.with_subscription_headers(subscription)
+}
+
+/// A token for a SubscribeURL.
+pub fn new_confirmation_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// The SubscribeURL that confirms the subscription awaiting `token`, on
+/// `base_url`, the base URL of the request the link results from.
+fn subscribe_url(base_url: &str, subscription: &Subscription, token: &str) -> String {
+    format!(
+        "{}/?Action=ConfirmSubscription&TopicArn={}&Token={}",
+        base_url, subscription.arn, token
+    )
+}
+
+/// The `UnsubscribeConfirmation` SNS sends an HTTP/S endpoint once its
+/// subscription is gone, with a SubscribeURL that restores it. The
+/// subscription is remembered under the token of that URL.
+pub fn unsubscribe_confirmation(
+    state: &AppState,
+    base_url: &str,
+    subscription: &Subscription,
+    endpoint: &str,
+) -> Result<HttpDelivery, String> {
+    let token = new_confirmation_token();
+    let message_id = Uuid::new_v4().to_string();
+    let body = json!({
+        "Type": "UnsubscribeConfirmation",
+        "MessageId": message_id,
+        "Token": token,
+        "TopicArn": subscription.arn,
+        "Message": format!(
+            "You have chosen to deactivate subscription {}.\nTo cancel this operation and restore the subscription, visit the SubscribeURL included in this message.",
+            subscription.subscription_arn
+        ),
+        "SubscribeURL": subscribe_url(base_url, subscription, &token),
+        "Timestamp": state.clock.format(chrono::Utc::now()),
+    })
+    .to_string();
+    state.remember_unsubscribed(&token, subscription.clone());
+    HttpDelivery::new(
+        endpoint,
+        "UnsubscribeConfirmation",
+        &message_id,
+        &subscription.arn,
+        body,
+    )
+    .with_subscription_headers(subscription)
+}
+
+/// Sends an HTTP/S delivery, retrying failed attempts after the pauses
+/// `policy` sets, scaled by the active `retry_time_scale`.
+#[cfg(feature = "http-delivery")]
+async fn post_to_endpoint(
+    state: &AppState,
+    delivery: &HttpDelivery,
+    policy: &RetryPolicy,
+) -> DeliveryResult {
+    let message_type = delivery
+        .header_value("x-amz-sns-message-type")
+        .unwrap_or_default();
+    if state.sample_delivery_log() {
+        tracing::info!(
+            "Sending {} to endpoint {} (up to {} retries) with headers {:?}: {}",
+            message_type,
+            delivery.endpoint,
+            policy.num_retries,
+            delivery.logged_headers(state.expose_secrets),
+            delivery.body
+        );
+    }
+    let config = state.scheduler.config();
+    let delays = policy.delays(config.retry_time_scale);
+    let timeout = Duration::from_millis(config.http_timeout_ms);
+    let mut attempt = 1;
+    loop {
+        let error = match http_delivery::post(&state.http_client, delivery, timeout).await {
+            Ok(()) => return Ok(attempt),
+            Err(error) => error,
+        };
+        let Some(delay) = delays.get(attempt as usize - 1) else {
+            tracing::error!(
+                "Failed to deliver {} to endpoint {} after {} attempts: {}",
+                message_type,
+                delivery.endpoint,
+                attempt,
+                error
+            );
+            return Err(error.after(attempt));
+        };
+        tracing::warn!(
+            "Attempt {} to deliver {} to endpoint {} failed, retrying in {:?}: {}",
+            attempt,
+            message_type,
+            delivery.endpoint,
+            delay,
+            error
+        );
+        tokio::time::sleep(*delay).await;
+        attempt += 1;
+    }
+}
+
+/// Tells the HTTP/S endpoints among `subscriptions`, which were just
+/// removed, that they are unsubscribed, with links on `base_url`. Other
+/// protocols get nothing, as with AWS.
+#[cfg(feature = "http-delivery")]
+pub fn spawn_unsubscribe_confirmations(
+    state: SharedState,
+    base_url: String,
+    subscriptions: Vec<Subscription>,
+) {
+    let subscriptions: Vec<Subscription> = subscriptions
+        .into_iter()
+        .filter(|s| matches!(s.protocol.as_str(), "http" | "https"))
+        .collect();
+    if subscriptions.is_empty() {
+        return;
+    }
+    tokio::spawn(
+        async move {
+            for subscription in subscriptions {
+                let confirmation = resolved_endpoint(&subscription).and_then(|endpoint| {
+                    unsubscribe_confirmation(&state, &base_url, &subscription, &endpoint)
+                });
+                match confirmation {
+                    // Failures are logged by post_to_endpoint.
+                    Ok(confirmation) => {
+                        let policy = state.effective_delivery_policy(&subscription);
+                        let _ =
+                            post_to_endpoint(&state, &confirmation, &policy.healthy_retry_policy)
+                                .await;
+                    }
+                    Err(e) => tracing::error!(
+                        "Failed to send UnsubscribeConfirmation for {}: {}",
+                        subscription.subscription_arn,
+                        e
+                    ),
+                }
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+/// Without HTTP delivery there is no endpoint to tell.
+#[cfg(not(feature = "http-delivery"))]
+pub fn spawn_unsubscribe_confirmations(
+    _state: SharedState,
+    _base_url: String,
+    _subscriptions: Vec<Subscription>,
+) {
+}
+
+pub fn message_body(state: &AppState, message: &Message, subscription: &Subscription) -> String {
+    if is_enveloped(state, subscription) {
+        notification_envelope(state, message, subscription)
+    } else {
+        message.body_for(&subscription.protocol)
+    }
+}
+
+/// Whether deliveries to the subscription carry the notification envelope.
+/// SQS subscriptions without RawMessageDelivery only get it with
+/// `--sqs-envelope`, and the bare message otherwise, as they always have.
+pub fn is_enveloped(state: &AppState, subscription: &Subscription) -> bool {
+    !subscription.raw_message_delivery && (subscription.protocol != "sqs" || state.sqs_envelope)
+}
+
+/// SQS service endpoint queue ARNs resolve onto by default.
+pub const DEFAULT_SQS_ENDPOINT: &str = "http://localhost:4566";
+
+/// `--sqs-queue-url-template` preset for ElasticMQ and GoAWS, whose queue
+/// URLs carry the account: `http://localhost:9324/000000000000/orders`.
+pub const ELASTICMQ_QUEUE_URL_TEMPLATE: &str = "{endpoint}/{account}/{queue}";
+/// `--sqs-queue-url-template` preset for LocalStack's path-style queue
+/// URLs: `http://localhost:4566/queue/orders`.
+pub const LOCALSTACK_QUEUE_URL_TEMPLATE: &str = "{endpoint}/queue/{queue}";
+
+const QUEUE_URL_PLACEHOLDERS: [&str; 4] = ["endpoint", "region", "account", "queue"];
+
+/// The template a `--sqs-queue-url-template` value stands for: `elasticmq`
+/// and `localstack` name the presets, anything else is a template itself.
+/// Templates must place `{queue}` and may only use the placeholders
+/// `{endpoint}`, `{region}`, `{account}` and `{queue}`.
+pub fn queue_url_template(value: &str) -> Result<&str, String> {
+    let template = match value {
+        "elasticmq" => ELASTICMQ_QUEUE_URL_TEMPLATE,
+        "localstack" => LOCALSTACK_QUEUE_URL_TEMPLATE,
+        template => template,
+    };
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!("{} has an unclosed {{", template));
+        };
+        let name = &rest[start + 1..start + end];
+        if !QUEUE_URL_PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "{} has an unknown placeholder {{{}}}",
+                template, name
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    if !template.contains("{queue}") {
+        return Err(format!("{} does not place {{queue}}", template));
+    }
+    Ok(template)
+}
+
+/// Resolves SQS subscription endpoints, queue URLs or queue ARNs
+/// (`arn:aws:sqs:<region>:<account>:<queue>`), to queue URLs. ARNs name
+/// no host, so they are filled into the queue URL template on the
+/// configured service endpoint.
+#[derive(Debug, Clone)]
+pub struct SqsQueueUrls {
+    endpoint: String,
+    template: String,
+}
+
+impl SqsQueueUrls {
+    /// `template` is a `--sqs-queue-url-template` value; see
+    /// `queue_url_template`.
+    pub fn new(endpoint: &str, template: &str) -> Result<Self, String> {
+        Ok(SqsQueueUrls {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            template: queue_url_template(template)?.to_string(),
+        })
+    }
+
+    /// The SQS service endpoint queue ARNs resolve onto.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// The queue URL of an SQS endpoint; queue URLs are returned as they
+    /// are.
+    pub fn queue_url(&self, endpoint: &str) -> String {
+        match endpoint
+            .strip_prefix("arn:aws:sqs:")
+            .map(|rest| rest.split(':').collect::<Vec<_>>())
+        {
+            Some(parts) if parts.len() == 3 => self
+                .template
+                .replace("{endpoint}", &self.endpoint)
+                .replace("{region}", parts[0])
+                .replace("{account}", parts[1])
+                .replace("{queue}", parts[2]),
+            _ => endpoint.to_string(),
+        }
+    }
+
+    /// The SQS service endpoint deliveries to an SQS endpoint go through:
+    /// the configured one for queue ARNs, whatever their template makes of
+    /// the URL, else that derived from the queue URL.
+    pub fn service_endpoint(&self, endpoint: &str) -> String {
+        if endpoint.starts_with("arn:aws:sqs:") {
+            return self.endpoint.clone();
+        }
+        sqs_endpoint_url(endpoint).unwrap_or_else(|| self.endpoint.clone())
+    }
+
+    /// Identifies the physical queue behind an SQS endpoint, so the same
+    /// queue subscribed by URL and by ARN, or through differently spelled
+    /// URLs, is recognized: the queue URL as `Url` normalizes it (lowercase
+    /// scheme and host, no default port), without query, fragment or
+    /// trailing slash.
+    pub fn canonical(&self, endpoint: &str) -> String {
+        let queue_url = self.queue_url(endpoint);
+        let Ok(mut url) = Url::parse(&queue_url) else {
+            return queue_url;
+        };
+        url.set_query(None);
+        url.set_fragment(None);
+        url.to_string().trim_end_matches('/').to_string()
+    }
+}
+
+/// Drops SQS subscriptions whose queue an earlier subscription already
+/// reaches, so a publish is delivered once per physical queue. The first
+/// subscription wins; every collapse is counted and logged.
+fn collapse_duplicate_queues<'a>(
+    state: &AppState,
+    topic_name: &str,
+    subscriptions: &'a [Subscription],
+) -> Vec<&'a Subscription> {
+    let mut queues: HashMap<String, &str> = HashMap::new();
+    subscriptions
+        .iter()
+        .filter(|subscription| {
+            if subscription.protocol != "sqs" {
+                return true;
+            }
+            let queue = state.sqs_queue_urls.canonical(&subscription.endpoint);
+            match queues.get(queue.as_str()) {
+                Some(kept) => {
+                    state
+                        .suspicious
+                        .collapsed_deliveries
+                        .fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(
+                        "Topic {} reaches queue {} through {} and {}; delivering once",
+                        topic_name,
+                        queue,
+                        kept,
+                        subscription.subscription_arn
+                    );
+                    false
+                }
+                None => {
+                    queues.insert(queue, &subscription.subscription_arn);
+                    true
+                }
+            }
+        })
+        .collect()
+}
+
+/// Derives the SQS service endpoint from a queue URL. Queue URLs end in
+/// `<account>/<queue>` (ElasticMQ also uses `queue/<queue>`); whatever path
+/// precedes those two segments is the base path of a path-style gateway and
+/// is kept, as is an explicit port. Query strings and fragments are dropped.
+/// `None` when `queue_url` is no URL with a host.
+pub fn sqs_endpoint_url(queue_url: &str) -> Option<String> {
+    let mut url = Url::parse(queue_url).ok()?;
+    if url.cannot_be_a_base() || url.host_str().is_none() {
+        return None;
+    }
+    let segments: Vec<String> = url
+        .path_segments()
+        .map(|segments| {
+            segments
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    let base_path = segments[..segments.len().saturating_sub(2)].join("/");
+    url.set_path(&base_path);
+    url.set_query(None);
+    url.set_fragment(None);
+    Some(url.to_string().trim_end_matches('/').to_string())
+}
+
+/// Expands `${NAME}` references to environment variables in a subscription
+/// endpoint. Endpoints are stored as given and expanded on every delivery,
+/// so they follow the environment the server currently runs with.
+pub fn resolve_endpoint(endpoint: &str) -> Result<String, String> {
+    let mut resolved = String::with_capacity(endpoint.len());
+    let mut rest = endpoint;
+    while let Some(start) = rest.find("${") {
+        resolved.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!(
+                "Unterminated variable reference in endpoint {}",
+                endpoint
+            ));
+        };
+        let name = &rest[start + 2..start + end];
+        match std::env::var(name) {
+            Ok(value) => resolved.push_str(&value),
+            Err(_) => {
+                return Err(format!(
+                    "Environment variable {} used in endpoint {} is not set",
+                    name, endpoint
+                ));
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+fn resolved_endpoint(subscription: &Subscription) -> Result<String, String> {
+    resolve_endpoint(&subscription.endpoint).inspect_err(|e| {
+        tracing::error!(
+            "Cannot deliver to subscription {}: {}",
+            subscription.subscription_arn,
+            e
+        );
+    })
+}
+
+/// Subject line AWS uses for email notifications published without one.
+const DEFAULT_EMAIL_SUBJECT: &str = "AWS Notification Message";
+
+/// Records an email or SMS in the outbox, attributed to the topic's current
+/// DisplayName.
+fn deliver_to_outbox(
+    state: &AppState,
+    subscription: &Subscription,
+    endpoint: String,
+    message: &Message,
+) {
+    let topic_name = topic_name_from_arn(&subscription.arn);
+    let topic = state.topics.get(topic_name);
+    let (sender, subject, body) = match subscription.protocol.as_str() {
+        "sms" => (
+            topic
+                .as_ref()
+                .and_then(|topic| topic.sms_sender_id(&state.topic_defaults)),
+            None,
+            message.body_for("sms"),
+        ),
+        protocol => (
+            topic
+                .as_ref()
+                .map(|topic| topic.sender_name(&state.topic_defaults).to_string()),
+            Some(
+                message
+                    .subject
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_EMAIL_SUBJECT.to_string()),
+            ),
+            if protocol == "email-json" {
+                notification_envelope(state, message, subscription)
+            } else {
+                message.body_for(protocol)
+            },
+        ),
+    };
+    drop(topic);
+
+    if state.sample_delivery_log() {
+        tracing::info!(
+            "Sending {} message {} to {} from {:?}",
+            subscription.protocol,
+            message.id,
+            endpoint,
+            sender
+        );
+    }
+    state.record_outbox(OutboxEntry {
+        protocol: subscription.protocol.clone(),
+        endpoint,
+        subscription_arn: subscription.subscription_arn.clone(),
+        message_id: message.id.clone(),
+        sender,
+        subject,
+        body,
+        timestamp: message.timestamp,
+    });
+}
+
+/// Subject line of the email asking to confirm a subscription.
+const CONFIRMATION_EMAIL_SUBJECT: &str = "AWS Notification - Subscription Confirmation";
+
+/// Records in the outbox the email asking an email or email-json
+/// subscription, which awaits `token`, to be confirmed through a link on
+/// `base_url`.
+pub fn send_subscription_confirmation(
+    state: &AppState,
+    base_url: &str,
+    subscription: &Subscription,
+    token: &str,
+) {
+    let message_id = Uuid::new_v4().to_string();
+    let subscribe_url = subscribe_url(base_url, subscription, token);
+    let text = format!(
+        "You have chosen to subscribe to the topic:\n{}\n\nTo confirm this subscription, visit the SubscribeURL included in this message.",
+        subscription.arn
+    );
+    let body = if subscription.protocol == "email-json" {
+        json!({
+            "Type": "SubscriptionConfirmation",
+            "MessageId": message_id,
+            "Token": token,
+            "TopicArn": subscription.arn,
+            "Message": text,
+            "SubscribeURL": subscribe_url,
+            "Timestamp": state.clock.format(chrono::Utc::now()),
+        })
+        .to_string()
+    } else {
+        format!("{}\n\n{}", text, subscribe_url)
+    };
+    let sender = state
+        .topics
+        .get(topic_name_from_arn(&subscription.arn))
+        .map(|topic| topic.sender_name(&state.topic_defaults).to_string());
+    state.record_outbox(OutboxEntry {
+        protocol: subscription.protocol.clone(),
+        endpoint: subscription.endpoint.clone(),
+        subscription_arn: subscription.subscription_arn.clone(),
+        message_id,
+        sender,
+        subject: Some(CONFIRMATION_EMAIL_SUBJECT.to_string()),
+        body,
+        timestamp: chrono::Utc::now(),
+    });
+}
+
+/// Body of the test notification a new subscription is sent with
+/// `--send-test-notification` or `SEND_TEST_NOTIFICATION_ATTRIBUTE`.
+pub const TEST_NOTIFICATION_MESSAGE: &str = "local-sns subscription test";
+
+/// MessageGroupId of the test notification on FIFO topics.
+const TEST_NOTIFICATION_GROUP_ID: &str = "local-sns-subscription-test";
+
+/// Sends `subscription`, new or just confirmed, its test notification if
+/// one is asked for. It goes through the usual fan-out, so RawMessageDelivery
+/// and the other subscription settings apply, and is kept in the topic's
+/// history flagged as synthetic, but does not count as published.
+pub fn send_test_notification(state: &SharedState, subscription: &Subscription) {
+    if !(state.send_test_notification || subscription.send_test_notification)
+        || subscription.pending_confirmation
+    {
+        return;
+    }
+    let topic_name = topic_name_from_arn(&subscription.arn).to_string();
+    let Some(fifo) = state.topics.get(&topic_name).map(|topic| topic.is_fifo()) else {
+        return;
+    };
+    let id = state.new_message_id();
+    let message = Message {
+        id: id.clone(),
+        subject: None,
+        body: TEST_NOTIFICATION_MESSAGE.to_string(),
+        timestamp: chrono::Utc::now(),
+        message_group_id: fifo.then(|| TEST_NOTIFICATION_GROUP_ID.to_string()),
+        message_deduplication_id: fifo.then_some(id),
+        sequence_number: None,
+        trace_header: None,
+        message_attributes: Default::default(),
+        base_url: None,
+        message_structure: None,
+        publisher: None,
+        synthetic: true,
+    };
+    state.record_message(&topic_name, message.clone());
+    console::message_published(&topic_name, &message.id);
+    state.fan_outs.start(&message.id, &subscription.arn, 1);
+    spawn_fan_out(
+        state.clone(),
+        topic_name,
+        vec![subscription.clone()],
+        vec![message],
+    );
+}
+
+/// Protocols whose delivery is optional at build time, with whether this
+/// build has it. Subscriptions to the others are still accepted, but every
+/// delivery to them fails.
+pub const OPTIONAL_PROTOCOLS: &[(&str, &str, bool)] = &[
+    ("sqs", "sqs-delivery", cfg!(feature = "sqs-delivery")),
+    ("demux", "sqs-delivery", cfg!(feature = "sqs-delivery")),
+    ("http", "http-delivery", cfg!(feature = "http-delivery")),
+    ("https", "http-delivery", cfg!(feature = "http-delivery")),
+    (
+        "lambda",
+        "lambda-delivery",
+        cfg!(feature = "lambda-delivery"),
+    ),
+    (
+        "firehose",
+        "firehose-delivery",
+        cfg!(feature = "firehose-delivery"),
+    ),
+];
+
+/// Fails for a protocol whose delivery this build leaves out.
+pub fn check_compiled_in(protocol: &str) -> Result<(), String> {
+    match OPTIONAL_PROTOCOLS
+        .iter()
+        .find(|(name, _, _)| *name == protocol)
+    {
+        Some((_, feature, false)) => Err(format!(
+            "Protocol {} is not compiled in; build with the {} feature",
+            protocol, feature
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub async fn deliver(
+    state: &AppState,
+    subscription: &Subscription,
+    message: &Message,
+) -> DeliveryResult {
+    check_compiled_in(&subscription.protocol)?;
+    let endpoint = resolved_endpoint(subscription)?;
+    if matches!(
+        subscription.protocol.as_str(),
+        "email" | "email-json" | "sms"
+    ) {
+        deliver_to_outbox(state, subscription, endpoint, message);
+        return Ok(1);
+    }
+    if subscription.protocol == "memory" {
+        memory::deliver(state, subscription, message);
+        return Ok(1);
+    }
+    if subscription.protocol == "application" {
+        return platform::deliver(state, &subscription.subscription_arn, &endpoint, message)
+            .map(|()| 1)
+            .map_err(DeliveryError::from);
+    }
+    #[cfg(feature = "http-delivery")]
+    if matches!(subscription.protocol.as_str(), "http" | "https") {
+        let policy = state.effective_delivery_policy(subscription);
+        return post_to_endpoint(
+            state,
+            &notification_delivery(state, subscription, message, &endpoint)?,
+            &policy.healthy_retry_policy,
+        )
+        .await;
+    }
+    #[cfg(feature = "sqs-delivery")]
+    if subscription.protocol == "sqs" {
+        return sqs::send(state, subscription, message, &endpoint).await;
+    }
+    #[cfg(feature = "sqs-delivery")]
+    if subscription.protocol == "demux" {
+        return demux::deliver(state, subscription, message, &endpoint).await;
+    }
+    if state.sample_delivery_log() {
+        tracing::info!("Sending message {:?} to endpoint {}", message, endpoint);
+    }
+    Ok(1)
+}
+
+/// What a failed delivery ran into. SQS and HTTP/S errors are classified
+/// where they happen; every other failure is `Other`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DeliveryErrorKind {
+    QueueDoesNotExist,
+    AccessDenied,
+    /// The endpoint could not be reached, or did not answer in time.
+    Network,
+    Throttling,
+    #[default]
+    Other,
+}
+
+impl DeliveryErrorKind {
+    pub const ALL: [DeliveryErrorKind; 5] = [
+        DeliveryErrorKind::QueueDoesNotExist,
+        DeliveryErrorKind::AccessDenied,
+        DeliveryErrorKind::Network,
+        DeliveryErrorKind::Throttling,
+        DeliveryErrorKind::Other,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryErrorKind::QueueDoesNotExist => "QueueDoesNotExist",
+            DeliveryErrorKind::AccessDenied => "AccessDenied",
+            DeliveryErrorKind::Network => "Network",
+            DeliveryErrorKind::Throttling => "Throttling",
+            DeliveryErrorKind::Other => "Other",
+        }
+    }
+
+    /// Whether an SQS send failing this way is worth retrying. A missing
+    /// queue may be being created; denied access and unknown errors will
+    /// not go away by retrying.
+    pub fn is_retryable(self) -> bool {
+        match self {
+            DeliveryErrorKind::QueueDoesNotExist
+            | DeliveryErrorKind::Network
+            | DeliveryErrorKind::Throttling => true,
+            DeliveryErrorKind::AccessDenied | DeliveryErrorKind::Other => false,
+        }
+    }
+
+    /// Status code of failed deliveries in delivery status logs.
+    pub fn status_code(self) -> u16 {
+        match self {
+            DeliveryErrorKind::QueueDoesNotExist => 404,
+            DeliveryErrorKind::AccessDenied => 403,
+            DeliveryErrorKind::Network => 504,
+            DeliveryErrorKind::Throttling => 429,
+            DeliveryErrorKind::Other => 500,
+        }
+    }
+}
+
+/// A failed delivery: what it ran into, and what to report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryError {
+    pub kind: DeliveryErrorKind,
+    pub message: String,
+    /// Attempts made before giving up.
+    pub attempts: u32,
+}
+
+impl DeliveryError {
+    /// A failure of the first attempt.
+    pub fn new(kind: DeliveryErrorKind, message: impl Into<String>) -> Self {
+        DeliveryError {
+            kind,
+            message: message.into(),
+            attempts: 1,
+        }
+    }
+
+    /// The error, as the last of `attempts` attempts.
+    pub fn after(self, attempts: u32) -> Self {
+        DeliveryError { attempts, ..self }
+    }
+}
+
+/// Unclassified failures, such as an endpoint that cannot be resolved.
+impl From<String> for DeliveryError {
+    fn from(message: String) -> Self {
+        DeliveryError::new(DeliveryErrorKind::Other, message)
+    }
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// The outcome of one delivery; a successful one holds the attempts it
+/// took.
+pub type DeliveryResult = Result<u32, DeliveryError>;
+
+/// A delivery that failed during a synchronous publish.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryFailure {
+    pub message_id: String,
+    pub subscription_arn: String,
+    pub error: String,
+    pub kind: DeliveryErrorKind,
+}
+
+/// Delivers the messages of one Publish or PublishBatch call to every
+/// enabled subscription, updating delivery counters and subscription
+/// health. The same code runs inline for synchronous publishes and on a
+/// background task otherwise. Subscriptions are delivered to concurrently,
+/// up to `--max-concurrent-deliveries`; the messages of one subscription are
+/// still delivered in order by a single task.
+pub async fn fan_out(
+    state: &SharedState,
+    topic_name: &str,
+    subscriptions: &[Subscription],
+    messages: &[Message],
+) -> Vec<DeliveryFailure> {
+    // Deliveries of a topic deleted meanwhile still complete, but must not
+    // bring its counters back.
+    let stats = state.existing_topic_stats(topic_name);
+    let status_log = state
+        .topics
+        .get(topic_name)
+        .map(|topic| DeliveryStatusLog::for_topic(&topic, &state.topic_defaults))
+        .unwrap_or_default();
+    let mut failures = Vec::new();
+    let collapsed = collapse_duplicate_queues(state, topic_name, subscriptions);
+    let skip = |count: usize| {
+        for message in messages {
+            for _ in 0..count {
+                state.fan_outs.finish(&message.id, JobOutcome::Skipped);
+            }
+        }
+    };
+    skip(subscriptions.len() - collapsed.len());
+
+    let shared: Arc<[Message]> = Arc::from(messages);
+    // The messages a subscription takes; demux subscriptions filter out
+    // those matching none of their routes, as a filter policy would.
+    let accepted = |subscription: &Subscription| -> Arc<[Message]> {
+        if subscription.protocol != "demux" {
+            return shared.clone();
+        }
+        let (accepted, filtered): (Vec<&Message>, Vec<&Message>) = messages
+            .iter()
+            .partition(|message| demux::accepts(&subscription.endpoint, message));
+        for message in &filtered {
+            stats.filtered_out.fetch_add(1, Ordering::Relaxed);
+            state.fan_outs.finish(&message.id, JobOutcome::Skipped);
+        }
+        accepted.into_iter().cloned().collect()
+    };
+    let mut queued = collapsed.into_iter();
+    let mut deliveries = JoinSet::new();
+    let mut in_flight = HashMap::new();
+    loop {
+        let max_concurrent_deliveries = state.scheduler.config().max_concurrent_deliveries;
+        while deliveries.len() < max_concurrent_deliveries
+            && let Some(subscription) = queued.next()
+        {
+            if subscription.pending_confirmation
+                || state.is_subscription_disabled(&subscription.subscription_arn)
+            {
+                for message in messages {
+                    console::message_delivered(subscription, &message.id, None);
+                }
+                skip(1);
+                continue;
+            }
+            let task_messages = accepted(subscription);
+            if task_messages.is_empty() {
+                continue;
+            }
+            let delivered = task_messages.clone();
+            let task_state = state.clone();
+            let task_subscription = subscription.clone();
+            let task = deliveries.spawn(
+                async move {
+                    let subscription = &task_subscription;
+                    let _in_flight = task_state.delivery_gauges.deliver(&subscription.protocol);
+                    match &task_messages[..] {
+                        [message] => vec![deliver(&task_state, subscription, message).await],
+                        messages => deliver_batch(&task_state, subscription, messages).await,
+                    }
+                }
+                .in_current_span(),
+            );
+            in_flight.insert(task.id(), (subscription, delivered));
+        }
+
+        let (task_id, results) = match deliveries.join_next_with_id().await {
+            Some(Ok(joined)) => joined,
+            Some(Err(e)) => {
+                tracing::error!("Delivery task failed: {}", e);
+                (e.id(), vec![Err(e.to_string().into()); messages.len()])
+            }
+            None => break,
+        };
+        let Some((subscription, messages)) = in_flight.remove(&task_id) else {
+            continue;
+        };
+        for (message, result) in messages.iter().zip(results) {
+            stats.record_delivery(&result);
+            state.record_delivery_result(&subscription.subscription_arn, &result);
+            status_log.record(state, subscription, message, &result);
+            state.audit_delivery(subscription, message, &result);
+            console::message_delivered(subscription, &message.id, Some(&result));
+            state
+                .fan_outs
+                .finish(&message.id, JobOutcome::from(&result));
+            if let Err(error) = result {
+                failures.push(DeliveryFailure {
+                    message_id: message.id.clone(),
+                    subscription_arn: subscription.subscription_arn.clone(),
+                    error: error.message,
+                    kind: error.kind,
+                });
+            }
+        }
+    }
+    failures
+}
+
+/// Runs `fan_out` on a background task, so the publish returns before its
+/// deliveries complete, as with AWS. The task waits for a delivery worker
+/// first when `--delivery-workers` bounds them.
+pub fn spawn_fan_out(
+    state: SharedState,
+    topic_name: String,
+    subscriptions: Vec<Subscription>,
+    messages: Vec<Message>,
+) {
+    tokio::spawn(
+        async move {
+            let queued = state.delivery_gauges.queue();
+            let _permit = state.scheduler.acquire(&topic_name).await;
+            drop(queued);
+            let worker = state.delivery_gauges.start_worker();
+            fan_out(&state, &topic_name, &subscriptions, &messages).await;
+            drop(worker);
+            state.delivery_gauges.finish_fan_out();
+        }
+        .in_current_span(),
+    );
+}
+
+/// Delivers the messages of one PublishBatch call, returning one result per
+/// message. SQS subscriptions receive a single SendMessageBatch; PublishBatch
+/// caps entries at the same limit.
+pub async fn deliver_batch(
+    state: &AppState,
+    subscription: &Subscription,
+    messages: &[Message],
+) -> Vec<DeliveryResult> {
+    #[cfg(feature = "sqs-delivery")]
+    if subscription.protocol == "sqs" {
+        return match resolved_endpoint(subscription) {
+            Ok(endpoint) => sqs::send_batch(state, subscription, messages, &endpoint).await,
+            Err(e) => vec![Err(e.into()); messages.len()],
+        };
+    }
+    let mut results = Vec::with_capacity(messages.len());
+    for message in messages {
+        results.push(deliver(state, subscription, message).await);
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_endpoints_are_derived_from_queue_urls() {
+        let cases = [
+            // LocalStack, path style and the account form.
+            (
+                "http://localhost:4566/queue/orders",
+                "http://localhost:4566",
+            ),
+            (
+                "http://localhost:4566/000000000000/orders",
+                "http://localhost:4566",
+            ),
+            // ElasticMQ.
+            (
+                "http://elasticmq:9324/000000000000/orders",
+                "http://elasticmq:9324",
+            ),
+            // GoAWS, queried.
+            (
+                "http://goaws:4100/100010001000/orders?Action=SendMessage",
+                "http://goaws:4100",
+            ),
+            // A gateway behind a reverse proxy keeps its base path.
+            (
+                "https://gateway.internal/sqs/v1/000000000000/orders/",
+                "https://gateway.internal/sqs/v1",
+            ),
+            // Default ports are implied, others kept.
+            (
+                "https://sqs.us-east-1.amazonaws.com:443/000000000000/orders",
+                "https://sqs.us-east-1.amazonaws.com",
+            ),
+            (
+                "HTTP://LocalHost:8080/000000000000/orders",
+                "http://localhost:8080",
+            ),
+        ];
+        for (queue_url, endpoint) in cases {
+            assert_eq!(
+                sqs_endpoint_url(queue_url).as_deref(),
+                Some(endpoint),
+                "{}",
+                queue_url
+            );
+        }
+        assert_eq!(sqs_endpoint_url("orders"), None);
+        assert_eq!(sqs_endpoint_url("mailto:ops@example.com"), None);
+    }
+
+    #[test]
+    fn queue_arns_resolve_through_the_template() {
+        let arn = "arn:aws:sqs:us-east-1:000000000000:orders";
+        let elasticmq = SqsQueueUrls::new("http://localhost:9324/", "elasticmq").unwrap();
+        assert_eq!(
+            elasticmq.queue_url(arn),
+            "http://localhost:9324/000000000000/orders"
+        );
+        assert_eq!(elasticmq.service_endpoint(arn), "http://localhost:9324");
+        let localstack = SqsQueueUrls::new("http://localhost:4566", "localstack").unwrap();
+        assert_eq!(
+            localstack.queue_url(arn),
+            "http://localhost:4566/queue/orders"
+        );
+        let custom =
+            SqsQueueUrls::new("http://proxy/sqs", "{endpoint}/{region}/{account}/{queue}").unwrap();
+        assert_eq!(
+            custom.queue_url(arn),
+            "http://proxy/sqs/us-east-1/000000000000/orders"
+        );
+        assert_eq!(
+            custom.service_endpoint("http://other:9324/000000000000/orders"),
+            "http://other:9324"
+        );
+        assert!(SqsQueueUrls::new("http://proxy", "{endpoint}/{queues}").is_err());
+        assert!(SqsQueueUrls::new("http://proxy", "{endpoint}/fixed").is_err());
+    }
+
+    #[test]
+    fn aws_style_templates_place_the_region_in_the_host() {
+        let aws = SqsQueueUrls::new(
+            "https://sqs.us-east-1.amazonaws.com",
+            "https://sqs.{region}.amazonaws.com/{account}/{queue}",
+        )
+        .unwrap();
+        assert_eq!(
+            aws.queue_url("arn:aws:sqs:eu-west-1:123456789012:orders.fifo"),
+            "https://sqs.eu-west-1.amazonaws.com/123456789012/orders.fifo"
+        );
+    }
+
+    #[test]
+    fn queue_urls_and_foreign_arns_pass_through() {
+        let urls = SqsQueueUrls::new(DEFAULT_SQS_ENDPOINT, "localstack").unwrap();
+        for endpoint in [
+            "http://localhost:9324/000000000000/orders",
+            "arn:aws:sqs:us-east-1:orders",
+            "arn:aws:sqs:us-east-1:000000000000:orders:extra",
+            "arn:aws-cn:sqs:cn-north-1:000000000000:orders",
+        ] {
+            assert_eq!(urls.queue_url(endpoint), endpoint);
+        }
+    }
+
+    #[test]
+    fn templates_name_only_known_placeholders() {
+        assert_eq!(
+            queue_url_template("elasticmq").unwrap(),
+            ELASTICMQ_QUEUE_URL_TEMPLATE
+        );
+        assert_eq!(
+            queue_url_template("localstack").unwrap(),
+            LOCALSTACK_QUEUE_URL_TEMPLATE
+        );
+        assert_eq!(
+            queue_url_template("{endpoint}/{queue}").unwrap(),
+            "{endpoint}/{queue}"
+        );
+        for (template, error) in [
+            ("{endpoint}/{queue", "{endpoint}/{queue has an unclosed {"),
+            (
+                "{endpoint}/{Queue}",
+                "{endpoint}/{Queue} has an unknown placeholder {Queue}",
+            ),
+            (
+                "{endpoint}/{}/{queue}",
+                "{endpoint}/{}/{queue} has an unknown placeholder {}",
+            ),
+            (
+                "{endpoint}/{account}",
+                "{endpoint}/{account} does not place {queue}",
+            ),
+            ("ElasticMQ", "ElasticMQ does not place {queue}"),
+        ] {
+            assert_eq!(queue_url_template(template), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn spellings_of_one_queue_share_a_canonical_form() {
+        let urls = SqsQueueUrls::new(DEFAULT_SQS_ENDPOINT, "elasticmq").unwrap();
+        let canonical = urls.canonical("arn:aws:sqs:us-east-1:000000000000:orders");
+        for endpoint in [
+            "http://localhost:4566/000000000000/orders",
+            "HTTP://LOCALHOST:4566/000000000000/orders/",
+            "http://localhost:4566/000000000000/orders?x=1#y",
+        ] {
+            assert_eq!(urls.canonical(endpoint), canonical, "{}", endpoint);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Retry settings of an HTTP/S delivery policy, with the AWS defaults for
+/// anything a policy leaves out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RetryPolicy {
+    pub min_delay_target: u32,
+    pub max_delay_target: u32,
+    pub num_retries: u32,
+    pub num_max_delay_retries: u32,
+    pub num_no_delay_retries: u32,
+    pub num_min_delay_retries: u32,
+    pub backoff_function: String,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            min_delay_target: 20,
+            max_delay_target: 20,
+            num_retries: 3,
+            num_max_delay_retries: 0,
+            num_no_delay_retries: 0,
+            num_min_delay_retries: 0,
+            backoff_function: "linear".to_string(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The pause before each retry, in order, over SNS's four phases: the
+    /// no-delay retries, those at `min_delay_target`, a backoff from the
+    /// minimum to the maximum delay shaped by `backoff_function`, and those
+    /// at `max_delay_target`. The backoff takes the retries the other
+    /// phases leave of `num_retries`. Delays are in seconds, multiplied by
+    /// `scale`.
+    pub fn delays(&self, scale: f64) -> Vec<Duration> {
+        let min = f64::from(self.min_delay_target);
+        let max = f64::from(self.max_delay_target.max(self.min_delay_target));
+        let backoff_retries = self.num_retries.saturating_sub(
+            self.num_no_delay_retries + self.num_min_delay_retries + self.num_max_delay_retries,
+        );
+        let backoff = (1..=backoff_retries).map(|retry| {
+            let progress = f64::from(retry) / f64::from(backoff_retries);
+            min + (max - min) * backoff_curve(&self.backoff_function, progress)
+        });
+        std::iter::repeat_n(0.0, self.num_no_delay_retries as usize)
+            .chain(std::iter::repeat_n(
+                min,
+                self.num_min_delay_retries as usize,
+            ))
+            .chain(backoff)
+            .chain(std::iter::repeat_n(
+                max,
+                self.num_max_delay_retries as usize,
+            ))
+            .take(self.num_retries as usize)
+            .map(|secs| Duration::from_secs_f64(secs * scale))
+            .collect()
+    }
+}
+
+/// How far along the backoff phase, from 0 at the minimum delay to 1 at
+/// the maximum, a retry `progress` of the way through it waits. AWS does
+/// not publish its curves; these rise as its graphs do, slowest first.
+fn backoff_curve(function: &str, progress: f64) -> f64 {
+    match function {
+        "arithmetic" => progress.powi(2),
+        "geometric" => progress.powi(3),
+        "exponential" => (2f64.powf(10.0 * progress) - 1.0) / 1023.0,
+        _ => progress,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThrottlePolicy {
+    pub max_receives_per_second: u32,
+}
+
+/// The `DeliveryPolicy` attribute of an HTTP/S subscription.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionDeliveryPolicy {
+    pub healthy_retry_policy: Option<RetryPolicy>,
+    pub throttle_policy: Option<ThrottlePolicy>,
+}
+
+/// The `http` section of a topic's `DeliveryPolicy` attribute.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpDeliveryPolicy {
+    pub default_healthy_retry_policy: Option<RetryPolicy>,
+    pub default_throttle_policy: Option<ThrottlePolicy>,
+    #[serde(default)]
+    pub disable_subscription_overrides: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TopicDeliveryPolicy {
+    pub http: Option<HttpDeliveryPolicy>,
+}
+
+/// The policy HTTP/S deliveries of a subscription follow, reported as
+/// `EffectiveDeliveryPolicy` by GetSubscriptionAttributes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveDeliveryPolicy {
+    pub healthy_retry_policy: RetryPolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub throttle_policy: Option<ThrottlePolicy>,
+}
+
+pub fn parse_subscription_policy(policy: &str) -> Result<SubscriptionDeliveryPolicy, String> {
+    serde_json::from_str(policy).map_err(|e| e.to_string())
+}
+
+/// Resolves the effective policy as subscription, then topic, then the
+/// built-in default. A topic with `disableSubscriptionOverrides` ignores the
+/// subscription's policy. Unparseable policies are skipped.
+pub fn effective_policy(
+    topic_policy: Option<&str>,
+    subscription_policy: Option<&str>,
+) -> EffectiveDeliveryPolicy {
+    let topic = topic_policy
+        .and_then(|policy| serde_json::from_str::<TopicDeliveryPolicy>(policy).ok())
+        .and_then(|policy| policy.http)
+        .unwrap_or_default();
+    let subscription = subscription_policy
+        .filter(|_| !topic.disable_subscription_overrides)
+        .and_then(|policy| parse_subscription_policy(policy).ok())
+        .unwrap_or_default();
+
+    EffectiveDeliveryPolicy {
+        healthy_retry_policy: subscription
+            .healthy_retry_policy
+            .or(topic.default_healthy_retry_policy)
+            .unwrap_or_default(),
+        throttle_policy: subscription
+            .throttle_policy
+            .or(topic.default_throttle_policy),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(delays: Vec<Duration>) -> Vec<f64> {
+        delays.iter().map(Duration::as_secs_f64).collect()
+    }
+
+    #[test]
+    fn the_default_policy_retries_three_times_after_20_seconds() {
+        assert_eq!(secs(RetryPolicy::default().delays(1.0)), [20.0, 20.0, 20.0]);
+    }
+
+    #[test]
+    fn retries_pass_through_the_four_phases() {
+        let policy = RetryPolicy {
+            min_delay_target: 1,
+            max_delay_target: 9,
+            num_retries: 7,
+            num_no_delay_retries: 1,
+            num_min_delay_retries: 1,
+            num_max_delay_retries: 1,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(
+            secs(policy.delays(1.0)),
+            [0.0, 1.0, 3.0, 5.0, 7.0, 9.0, 9.0]
+        );
+    }
+
+    #[test]
+    fn delays_are_scaled() {
+        let policy = RetryPolicy {
+            num_retries: 2,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(secs(policy.delays(0.5)), [10.0, 10.0]);
+        assert_eq!(secs(policy.delays(0.0)), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn faster_backoff_functions_start_slower() {
+        let policy = |backoff_function: &str| RetryPolicy {
+            min_delay_target: 0,
+            max_delay_target: 100,
+            num_retries: 4,
+            backoff_function: backoff_function.to_string(),
+            ..RetryPolicy::default()
+        };
+        let first = |function| secs(policy(function).delays(1.0))[0];
+        assert!(first("linear") > first("arithmetic"));
+        assert!(first("arithmetic") > first("geometric"));
+        assert!(first("geometric") > first("exponential"));
+        assert_eq!(secs(policy("exponential").delays(1.0))[3], 100.0);
+    }
+
+    #[test]
+    fn the_subscription_policy_wins_unless_the_topic_disables_overrides() {
+        let topic = r#"{"http":{"defaultHealthyRetryPolicy":{"numRetries":5}}}"#;
+        let locked = r#"{"http":{"defaultHealthyRetryPolicy":{"numRetries":5},"disableSubscriptionOverrides":true}}"#;
+        let subscription = r#"{"healthyRetryPolicy":{"numRetries":1}}"#;
+        let retries = |topic, subscription| {
+            effective_policy(topic, subscription)
+                .healthy_retry_policy
+                .num_retries
+        };
+        assert_eq!(retries(Some(topic), Some(subscription)), 1);
+        assert_eq!(retries(Some(topic), None), 5);
+        assert_eq!(retries(Some(locked), Some(subscription)), 5);
+        assert_eq!(retries(None, None), 3);
+    }
+}
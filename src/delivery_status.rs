@@ -0,0 +1,195 @@
+use crate::delivery::DeliveryResult;
+use crate::state::{AppState, Message, Subscription, Topic};
+use rand::Rng;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use uuid::Uuid;
+
+/// Delivery status logging settings for one protocol family, taken from
+/// the topic's `<Family>SuccessFeedbackRoleArn`, `<Family>FailureFeedbackRoleArn`
+/// and `<Family>SuccessFeedbackSampleRate` attributes.
+#[derive(Debug, Clone)]
+struct Feedback {
+    /// Percentage of successful deliveries logged; failures are always logged.
+    success_sample_rate: u32,
+}
+
+/// The feedback attribute family a subscription protocol reports under.
+fn feedback_family(protocol: &str) -> Option<&'static str> {
+    match protocol {
+        "http" | "https" => Some("HTTP"),
+        "sqs" | "demux" => Some("SQS"),
+        "lambda" => Some("Lambda"),
+        "firehose" => Some("Firehose"),
+        "application" => Some("Application"),
+        _ => None,
+    }
+}
+
+/// CloudWatch-style delivery status logging for one topic, resolved once
+/// per fan-out.
+#[derive(Debug, Default)]
+pub struct DeliveryStatusLog {
+    topic_name: String,
+    topic_arn: String,
+    families: HashMap<&'static str, Feedback>,
+}
+
+impl DeliveryStatusLog {
+    /// Reads the feedback configuration of `topic`, falling back on the
+    /// topic defaults. A family is logged once either of its feedback role
+    /// ARNs is set.
+    pub fn for_topic(topic: &Topic, defaults: &BTreeMap<String, String>) -> Self {
+        let families = ["HTTP", "SQS", "Lambda", "Firehose", "Application"]
+            .into_iter()
+            .filter_map(|family| {
+                let attribute =
+                    |suffix: &str| topic.attribute(&format!("{}{}", family, suffix), defaults);
+                if attribute("SuccessFeedbackRoleArn").is_none()
+                    && attribute("FailureFeedbackRoleArn").is_none()
+                {
+                    return None;
+                }
+                let success_sample_rate = attribute("SuccessFeedbackSampleRate")
+                    .and_then(|rate| rate.parse::<u32>().ok())
+                    .unwrap_or(0)
+                    .min(100);
+                Some((
+                    family,
+                    Feedback {
+                        success_sample_rate,
+                    },
+                ))
+            })
+            .collect();
+
+        DeliveryStatusLog {
+            topic_name: topic.name.clone(),
+            topic_arn: topic.arn.clone(),
+            families,
+        }
+    }
+
+    /// Logs the outcome of delivering `message` to `subscription`, if its
+    /// protocol family has feedback configured and the record is sampled.
+    pub fn record(
+        &self,
+        state: &AppState,
+        subscription: &Subscription,
+        message: &Message,
+        result: &DeliveryResult,
+    ) {
+        let Some(feedback) =
+            feedback_family(&subscription.protocol).and_then(|family| self.families.get(family))
+        else {
+            return;
+        };
+        if result.is_ok() && rand::thread_rng().gen_range(0..100) >= feedback.success_sample_rate {
+            return;
+        }
+
+        let now = chrono::Utc::now();
+        let (status, status_code, provider_response, attempts) = match result {
+            Ok(attempts) => ("SUCCESS", 200, "Delivered".to_string(), *attempts),
+            Err(error) => (
+                "FAILURE",
+                error.kind.status_code(),
+                error.message.clone(),
+                error.attempts,
+            ),
+        };
+        let mut record = json!({
+            "notification": {
+                "messageId": message.id,
+                "topicArn": self.topic_arn,
+                "timestamp": state.clock.format(message.timestamp),
+            },
+            "delivery": {
+                "deliveryId": Uuid::new_v4().to_string(),
+                "destination": subscription.endpoint,
+                "providerResponse": provider_response,
+                "dwellTimeMs": (now - message.timestamp).num_milliseconds(),
+                "attempts": attempts,
+                "statusCode": status_code,
+            },
+            "status": status,
+        });
+        if let Err(error) = result {
+            record["delivery"]["errorKind"] = json!(error.kind);
+        }
+        let record = record.to_string();
+
+        tracing::info!(target: "delivery_status", "{}", record);
+        if let Some(writer) = &state.delivery_status {
+            writer.write(&self.topic_name, record);
+        }
+    }
+}
+
+/// Handle to the writer of the per-topic files in `--delivery-status-dir`.
+/// As with the audit log, writing only queues the record for a dedicated
+/// thread, so deliveries never wait on the disk.
+#[derive(Debug)]
+pub struct DeliveryStatusWriter {
+    sender: UnboundedSender<(String, String)>,
+}
+
+impl DeliveryStatusWriter {
+    /// Starts the writer thread for the files in `dir`.
+    pub fn start(dir: PathBuf) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        std::thread::Builder::new()
+            .name("delivery-status".to_string())
+            .spawn(move || write_records(dir, receiver))?;
+        Ok(DeliveryStatusWriter { sender })
+    }
+
+    /// Queues `record` to be appended to the file of the topic.
+    pub fn write(&self, topic_name: &str, record: String) {
+        // The writer thread only stops with the server.
+        let _ = self.sender.send((topic_name.to_string(), record));
+    }
+}
+
+/// The writer thread: appends each record as a line to `<topic>.log`. The
+/// files written are kept open until the queue runs dry, then flushed and
+/// closed, so a deleted topic does not hold on to its file.
+fn write_records(dir: PathBuf, mut receiver: UnboundedReceiver<(String, String)>) {
+    while let Some(record) = receiver.blocking_recv() {
+        let mut files: HashMap<String, Option<BufWriter<File>>> = HashMap::new();
+        let mut next = Some(record);
+        while let Some((topic_name, record)) = next {
+            let path = dir.join(format!("{}.log", topic_name));
+            let file = files.entry(topic_name).or_insert_with(|| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .inspect_err(|e| {
+                        tracing::warn!("Failed to open {}: {}", path.display(), e);
+                    })
+                    .ok()
+                    .map(BufWriter::new)
+            });
+            if let Some(writer) = file
+                && let Err(e) = writeln!(writer, "{}", record)
+            {
+                tracing::warn!(
+                    "Failed to write delivery status to {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+            next = receiver.try_recv().ok();
+        }
+        for (topic_name, writer) in files {
+            if let Some(Err(e)) = writer.map(|mut writer| writer.flush()) {
+                tracing::warn!("Failed to write delivery status of {}: {}", topic_name, e);
+            }
+        }
+    }
+}
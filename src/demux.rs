@@ -0,0 +1,175 @@
+#[cfg(feature = "sqs-delivery")]
+use crate::delivery::DeliveryResult;
+use crate::error::invalid_parameter;
+use crate::state::Message;
+#[cfg(feature = "sqs-delivery")]
+use crate::state::{AppState, Subscription};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Route name a message taking the `default` queue is counted under.
+pub const DEFAULT_ROUTE: &str = "default";
+
+/// Route name a message matching no route, without a default, is counted
+/// under; it is dropped as a filtered-out message would be.
+#[cfg(feature = "sqs-delivery")]
+const UNROUTED: &str = "(unrouted)";
+
+/// The endpoint of a `demux` subscription: SQS queues keyed by the value
+/// of one message attribute, so a single subscription stands in for a
+/// filtered subscription per queue. For example
+/// `{"attribute":"event_type","routes":{"order":"http://..."},"default":"http://..."}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DemuxRoutes {
+    pub attribute: String,
+    #[serde(default)]
+    pub routes: BTreeMap<String, String>,
+    /// Queue for messages whose attribute is missing or has no route; such
+    /// messages are dropped without one.
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+impl DemuxRoutes {
+    /// Parses and validates a `demux` endpoint.
+    pub fn parse(endpoint: &str) -> Result<Self, String> {
+        let routes: DemuxRoutes = serde_json::from_str(endpoint).map_err(|e| {
+            format!(
+                "{} Reason: demux endpoint is not valid JSON: {}",
+                invalid_parameter("Endpoint"),
+                e
+            )
+        })?;
+        let invalid = |reason: &str| {
+            Err(format!(
+                "{} Reason: {}",
+                invalid_parameter("Endpoint"),
+                reason
+            ))
+        };
+        if routes.attribute.is_empty() {
+            return invalid("demux endpoint has an empty attribute");
+        }
+        if routes.routes.is_empty() && routes.default.is_none() {
+            return invalid("demux endpoint has neither routes nor a default");
+        }
+        if routes.routes.values().any(String::is_empty)
+            || routes.default.as_deref().is_some_and(str::is_empty)
+        {
+            return invalid("demux endpoint has an empty queue");
+        }
+        Ok(routes)
+    }
+
+    /// The route name and queue `message` is delivered to, if any: the route
+    /// matching the String value of its attribute, else the default.
+    pub fn route(&self, message: &Message) -> Option<(&str, &str)> {
+        message
+            .message_attributes
+            .get(&self.attribute)
+            .filter(|attribute| attribute.data_type.starts_with("String"))
+            .and_then(|attribute| self.routes.get_key_value(attribute.value()))
+            .map(|(value, queue)| (value.as_str(), queue.as_str()))
+            .or_else(|| Some((DEFAULT_ROUTE, self.default.as_deref()?)))
+    }
+}
+
+/// Whether the `demux` subscription with `endpoint` takes `message`. One
+/// matching no route, without a default, is filtered out before delivery;
+/// an endpoint that does not parse takes everything, so the delivery
+/// reports the error.
+pub fn accepts(endpoint: &str, message: &Message) -> bool {
+    DemuxRoutes::parse(endpoint).map_or(true, |routes| routes.route(message).is_some())
+}
+
+/// Sends `message` to the queue its attribute selects through the SQS
+/// delivery, then logs the route taken with the subscription's running
+/// per-route delivered and failed counts.
+#[cfg(feature = "sqs-delivery")]
+pub async fn deliver(
+    state: &AppState,
+    subscription: &Subscription,
+    message: &Message,
+    endpoint: &str,
+) -> DeliveryResult {
+    let routes = DemuxRoutes::parse(endpoint)?;
+    let (route, result) = match routes.route(message) {
+        Some((route, queue)) => (
+            route,
+            crate::sqs::send(state, subscription, message, queue).await,
+        ),
+        None => (UNROUTED, Ok(1)),
+    };
+    let counts = {
+        let mut counts = state
+            .demux_routes
+            .entry(subscription.subscription_arn.clone())
+            .or_default();
+        let (delivered, failed) = counts.entry(route.to_string()).or_default();
+        if result.is_ok() {
+            *delivered += 1;
+        } else {
+            *failed += 1;
+        }
+        counts
+            .iter()
+            .map(|(route, (delivered, failed))| format!("{}={}/{}", route, delivered, failed))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if state.sample_delivery_log() {
+        tracing::info!(
+            "Demux subscription {} routed message {} ({} = {:?}) to {}; delivered/failed per route: {}",
+            subscription.subscription_arn,
+            message.id,
+            routes.attribute,
+            message
+                .message_attributes
+                .get(&routes.attribute)
+                .map(|attribute| attribute.value()),
+            route,
+            counts
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(event_type: Option<&str>) -> Message {
+        let mut message = serde_json::json!({
+            "id": "m1",
+            "subject": null,
+            "body": "hello",
+            "timestamp": "2026-01-01T00:00:00Z",
+        });
+        if let Some(event_type) = event_type {
+            message["message_attributes"] = serde_json::json!({
+                "event_type": {"data_type": "String", "string_value": event_type}
+            });
+        }
+        serde_json::from_value(message).unwrap()
+    }
+
+    #[test]
+    fn messages_matching_no_route_are_filtered_out() {
+        let endpoint = r#"{"attribute":"event_type","routes":{"order":"http://q/orders"}}"#;
+        assert!(accepts(endpoint, &message(Some("order"))));
+        assert!(!accepts(endpoint, &message(Some("refund"))));
+        assert!(!accepts(endpoint, &message(None)));
+    }
+
+    #[test]
+    fn the_default_route_takes_everything_else() {
+        let endpoint = r#"{"attribute":"event_type","routes":{"order":"http://q/orders"},"default":"http://q/rest"}"#;
+        let routes = DemuxRoutes::parse(endpoint).unwrap();
+        assert_eq!(
+            routes.route(&message(Some("refund"))),
+            Some((DEFAULT_ROUTE, "http://q/rest"))
+        );
+        assert!(accepts(endpoint, &message(None)));
+    }
+}
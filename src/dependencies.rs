@@ -0,0 +1,105 @@
+use crate::delivery::resolve_endpoint;
+use crate::state::AppState;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use url::Url;
+
+/// How long a dependency gets to accept a TCP connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long Subscribe waits for an endpoint with
+/// `--validate-endpoints-on-subscribe=strict`.
+const SUBSCRIBE_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Outcome of probing one downstream service deliveries depend on.
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyStatus {
+    pub name: &'static str,
+    pub endpoint: String,
+    pub reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The SQS service endpoints deliveries may reach: the configured one plus
+/// those of every SQS subscription's queue URL.
+fn sqs_endpoints(state: &AppState) -> BTreeSet<String> {
+    let mut endpoints = BTreeSet::from([state.sqs_queue_urls.endpoint().to_string()]);
+    for topic in state.topics.iter() {
+        for subscription in topic.subscriptions.iter().filter(|s| s.protocol == "sqs") {
+            endpoints.insert(
+                state
+                    .sqs_queue_urls
+                    .service_endpoint(&subscription.endpoint),
+            );
+        }
+    }
+    endpoints
+}
+
+async fn connect(endpoint: &str, timeout: Duration) -> Result<(), String> {
+    let url = Url::parse(endpoint).map_err(|e| e.to_string())?;
+    let host = url.host_str().ok_or("no host")?;
+    let port = url.port_or_known_default().ok_or("no port")?;
+    match tokio::time::timeout(timeout, TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("no connection within {:?}", timeout)),
+    }
+}
+
+/// Checks with a TCP connect that each dependency accepts connections.
+/// Only SQS is probed: Lambda and SMTP deliveries are not made by this
+/// server, so there are no such endpoints to check.
+pub async fn probe(state: &AppState) -> Vec<DependencyStatus> {
+    let mut statuses = Vec::new();
+    for endpoint in sqs_endpoints(state) {
+        let result = connect(&endpoint, CONNECT_TIMEOUT).await;
+        statuses.push(DependencyStatus {
+            name: "sqs",
+            endpoint,
+            reachable: result.is_ok(),
+            error: result.err(),
+        });
+    }
+    statuses
+}
+
+/// Probes the dependencies and logs a WARN naming the unreachable ones.
+/// Returns whether all of them were reachable.
+pub async fn check(state: &AppState) -> bool {
+    let unreachable: Vec<String> = probe(state)
+        .await
+        .into_iter()
+        .filter(|status| !status.reachable)
+        .map(|status| {
+            format!(
+                "{} at {} ({})",
+                status.name,
+                status.endpoint,
+                status.error.unwrap_or_default()
+            )
+        })
+        .collect();
+    if unreachable.is_empty() {
+        tracing::info!("All delivery endpoints are reachable");
+        return true;
+    }
+    tracing::warn!(
+        "Unreachable delivery endpoints, deliveries to them will fail: {}",
+        unreachable.join(", ")
+    );
+    false
+}
+
+/// Checks that a new HTTP/S subscription's endpoint accepts connections.
+/// Other protocols are not probed and always pass.
+pub async fn probe_subscription_endpoint(protocol: &str, endpoint: &str) -> Result<(), String> {
+    if !matches!(protocol, "http" | "https") {
+        return Ok(());
+    }
+    let endpoint = resolve_endpoint(endpoint)?;
+    connect(&endpoint, SUBSCRIBE_PROBE_TIMEOUT).await
+}
@@ -1,41 +1,461 @@
+use crate::request_id;
+use crate::responses;
 use axum::http::StatusCode;
-use axum::response::Response;
+use axum::response::{IntoResponse, Response};
 use quick_xml::Writer;
 use quick_xml::events::BytesText;
 use std::io::Cursor;
-use uuid::Uuid;
-
-pub async fn error_response(code: &str, message: &str, status_code: StatusCode) -> Response {
-    let mut writer = Writer::new(Cursor::new(Vec::new()));
-    writer
-        .create_element("ErrorResponse")
-        .with_attribute(("xmlns", "http://sns.amazonaws.com/doc/2010-03-31/"))
-        .write_inner_content(|writer| {
-            writer
-                .create_element("Error")
-                .write_inner_content(|writer| {
-                    writer
-                        .create_element("Type")
-                        .write_text_content(BytesText::new("Sender"))?;
-                    writer
-                        .create_element("Code")
-                        .write_text_content(BytesText::new(code))?;
-                    writer
-                        .create_element("Message")
-                        .write_text_content(BytesText::new(message))?;
-                    Ok(())
-                })?;
-            writer
-                .create_element("RequestId")
-                .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
-            Ok(())
-        })
-        .unwrap();
-
-    let xml_response = writer.into_inner().into_inner();
-    Response::builder()
-        .status(status_code)
-        .header("Content-Type", "application/xml")
-        .body(axum::body::Body::from(xml_response))
-        .unwrap()
+
+// Error codes and messages as returned by AWS SNS.
+pub const INVALID_PARAMETER: &str = "InvalidParameter";
+const INVALID_ACTION: &str = "InvalidAction";
+const INTERNAL_FAILURE: &str = "InternalFailure";
+const NOT_FOUND: &str = "NotFound";
+const RESOURCE_NOT_FOUND: &str = "ResourceNotFound";
+const NO_SUCH_VERSION: &str = "NoSuchVersion";
+const MALFORMED_INPUT: &str = "MalformedInput";
+const EMPTY_BATCH_REQUEST: &str = "EmptyBatchRequest";
+const TOO_MANY_ENTRIES_IN_BATCH_REQUEST: &str = "TooManyEntriesInBatchRequest";
+const BATCH_ENTRY_IDS_NOT_DISTINCT: &str = "BatchEntryIdsNotDistinct";
+const THROTTLING: &str = "Throttling";
+const TOPIC_LIMIT_EXCEEDED: &str = "TopicLimitExceeded";
+const SUBSCRIPTION_LIMIT_EXCEEDED: &str = "SubscriptionLimitExceeded";
+const TAG_LIMIT_EXCEEDED: &str = "TagLimitExceeded";
+/// Not an AWS code; only returned with `--fail-unrouted`.
+const UNROUTED_MESSAGE: &str = "UnroutedMessage";
+
+pub const MESSAGE_TOO_LONG: &str = "Invalid parameter: Message too long";
+pub const TOPIC_NOT_FOUND: &str = "Topic does not exist";
+pub const SUBSCRIPTION_EXISTS_WITH_DIFFERENT_ATTRIBUTES: &str =
+    "Invalid parameter: Attributes Reason: Subscription already exists with different attributes";
+pub const SUBSCRIPTION_ROLE_ARN_REQUIRED: &str =
+    "Invalid parameter: Attributes Reason: SubscriptionRoleArn is required for protocol firehose";
+pub const FIFO_TOPIC_PROTOCOL_NOT_SUPPORTED: &str =
+    "Invalid parameter: Only SQS subscriptions are supported for FIFO topics";
+pub const SUBSCRIPTION_NOT_FOUND: &str = "Subscription does not exist";
+pub const RESOURCE_DOES_NOT_EXIST: &str = "Resource does not exist";
+pub const EMPTY_BATCH_REQUEST_MESSAGE: &str = "The batch request doesn't contain any entries";
+pub const TOO_MANY_ENTRIES_MESSAGE: &str =
+    "The batch request contains more entries than permissible";
+pub const BATCH_ENTRY_IDS_NOT_DISTINCT_MESSAGE: &str =
+    "Two or more batch entries in the request have the same Id";
+pub const RATE_EXCEEDED: &str = "Rate exceeded";
+pub const TOPIC_LIMIT_EXCEEDED_MESSAGE: &str = "Topic limit exceeded";
+pub const SUBSCRIPTION_LIMIT_EXCEEDED_MESSAGE: &str = "Subscription limit exceeded";
+pub const TAG_LIMIT_EXCEEDED_MESSAGE: &str =
+    "Could not complete request: tag quota of per resource exceeded";
+
+pub fn missing_parameter(name: &str) -> String {
+    format!(
+        "Invalid parameter: {} Reason: no value for required parameter",
+        name
+    )
+}
+
+pub fn no_such_version(version: &str) -> String {
+    format!(
+        "The requested version ({}) of service AmazonSNS does not exist",
+        version
+    )
+}
+
+pub fn invalid_action(action: &str) -> String {
+    format!("The action {} is not valid for this endpoint", action)
+}
+
+pub fn unknown_path(path: &str) -> String {
+    format!("The path {} is not valid for this endpoint", path)
+}
+
+pub fn invalid_parameter(name: &str) -> String {
+    format!("Invalid parameter: {}", name)
+}
+
+pub fn unreachable_endpoint(reason: &str) -> String {
+    format!(
+        "Invalid parameter: Endpoint Reason: unreachable: {}",
+        reason
+    )
+}
+
+pub fn unrouted_message(topic_name: &str, reason: &str) -> String {
+    format!(
+        "A message published to {} would reach no subscription: {}",
+        topic_name, reason
+    )
+}
+
+pub fn delivery_failed(summary: &str) -> String {
+    format!("Delivery to one or more subscriptions failed: {}", summary)
+}
+
+pub fn invalid_message_attributes(reason: &str) -> String {
+    format!("Invalid parameter: MessageAttributes Reason: {}", reason)
+}
+
+/// The AWS error code of an error response, kept in its extensions for
+/// the audit log.
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorCode(pub &'static str);
+
+/// An SNS error response: HTTP status, AWS error code and message. It
+/// renders as the Query API's `ErrorResponse` XML; the error `Type` is
+/// `Receiver` for server-side failures and `Sender` for everything else.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnsError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl SnsError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        SnsError {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// InvalidParameter with a message already in AWS form.
+    pub fn invalid_parameter_message(message: impl Into<String>) -> Self {
+        SnsError::new(StatusCode::BAD_REQUEST, INVALID_PARAMETER, message)
+    }
+
+    pub fn invalid_parameter(name: &str) -> Self {
+        SnsError::invalid_parameter_message(invalid_parameter(name))
+    }
+
+    pub fn missing_parameter(name: &str) -> Self {
+        SnsError::invalid_parameter_message(missing_parameter(name))
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        SnsError::new(StatusCode::NOT_FOUND, NOT_FOUND, message)
+    }
+
+    pub fn not_found_topic() -> Self {
+        SnsError::not_found(TOPIC_NOT_FOUND)
+    }
+
+    pub fn not_found_subscription() -> Self {
+        SnsError::not_found(SUBSCRIPTION_NOT_FOUND)
+    }
+
+    pub fn resource_not_found() -> Self {
+        SnsError::new(
+            StatusCode::NOT_FOUND,
+            RESOURCE_NOT_FOUND,
+            RESOURCE_DOES_NOT_EXIST,
+        )
+    }
+
+    pub fn invalid_action(action: &str) -> Self {
+        SnsError::new(
+            StatusCode::BAD_REQUEST,
+            INVALID_ACTION,
+            invalid_action(action),
+        )
+    }
+
+    /// InvalidAction for a request to a path other than `/` that names no
+    /// action, with the 404 such a path gets.
+    pub fn unknown_path(path: &str) -> Self {
+        SnsError::new(StatusCode::NOT_FOUND, INVALID_ACTION, unknown_path(path))
+    }
+
+    pub fn no_such_version(version: &str) -> Self {
+        SnsError::new(
+            StatusCode::BAD_REQUEST,
+            NO_SUCH_VERSION,
+            no_such_version(version),
+        )
+    }
+
+    pub fn malformed_input(message: impl Into<String>) -> Self {
+        SnsError::new(StatusCode::BAD_REQUEST, MALFORMED_INPUT, message)
+    }
+
+    pub fn empty_batch_request() -> Self {
+        SnsError::new(
+            StatusCode::BAD_REQUEST,
+            EMPTY_BATCH_REQUEST,
+            EMPTY_BATCH_REQUEST_MESSAGE,
+        )
+    }
+
+    pub fn too_many_entries_in_batch_request() -> Self {
+        SnsError::new(
+            StatusCode::BAD_REQUEST,
+            TOO_MANY_ENTRIES_IN_BATCH_REQUEST,
+            TOO_MANY_ENTRIES_MESSAGE,
+        )
+    }
+
+    pub fn batch_entry_ids_not_distinct() -> Self {
+        SnsError::new(
+            StatusCode::BAD_REQUEST,
+            BATCH_ENTRY_IDS_NOT_DISTINCT,
+            BATCH_ENTRY_IDS_NOT_DISTINCT_MESSAGE,
+        )
+    }
+
+    pub fn throttling() -> Self {
+        SnsError::new(StatusCode::BAD_REQUEST, THROTTLING, RATE_EXCEEDED)
+    }
+
+    pub fn topic_limit_exceeded() -> Self {
+        SnsError::new(
+            StatusCode::FORBIDDEN,
+            TOPIC_LIMIT_EXCEEDED,
+            TOPIC_LIMIT_EXCEEDED_MESSAGE,
+        )
+    }
+
+    pub fn subscription_limit_exceeded() -> Self {
+        SnsError::new(
+            StatusCode::FORBIDDEN,
+            SUBSCRIPTION_LIMIT_EXCEEDED,
+            SUBSCRIPTION_LIMIT_EXCEEDED_MESSAGE,
+        )
+    }
+
+    pub fn unrouted_message(topic_name: &str, reason: &str) -> Self {
+        SnsError::new(
+            StatusCode::BAD_REQUEST,
+            UNROUTED_MESSAGE,
+            unrouted_message(topic_name, reason),
+        )
+    }
+
+    pub fn tag_limit_exceeded() -> Self {
+        SnsError::new(
+            StatusCode::BAD_REQUEST,
+            TAG_LIMIT_EXCEEDED,
+            TAG_LIMIT_EXCEEDED_MESSAGE,
+        )
+    }
+
+    pub fn internal_failure(message: impl Into<String>) -> Self {
+        SnsError::new(StatusCode::INTERNAL_SERVER_ERROR, INTERNAL_FAILURE, message)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn error_type(&self) -> &'static str {
+        if self.status.is_server_error() {
+            "Receiver"
+        } else {
+            "Sender"
+        }
+    }
+}
+
+impl IntoResponse for SnsError {
+    fn into_response(self) -> Response {
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        writer
+            .create_element("ErrorResponse")
+            .with_attribute(("xmlns", responses::xml_namespace()))
+            .write_inner_content(|writer| {
+                writer
+                    .create_element("Error")
+                    .write_inner_content(|writer| {
+                        writer
+                            .create_element("Type")
+                            .write_text_content(BytesText::new(self.error_type()))?;
+                        writer
+                            .create_element("Code")
+                            .write_text_content(BytesText::new(self.code))?;
+                        writer
+                            .create_element("Message")
+                            .write_text_content(BytesText::new(&self.message))?;
+                        Ok(())
+                    })?;
+                writer
+                    .create_element("RequestId")
+                    .write_text_content(BytesText::new(&request_id::current()))?;
+                Ok(())
+            })
+            .unwrap();
+
+        let xml_response = writer.into_inner().into_inner();
+        Response::builder()
+            .status(self.status)
+            .header("Content-Type", "application/xml")
+            .extension(ErrorCode(self.code))
+            .body(axum::body::Body::from(xml_response))
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The status and body `error` renders to, inside a request `id-1`.
+    async fn render(error: SnsError) -> (StatusCode, String) {
+        let response = request_id::scope("id-1".to_string(), async { error.into_response() }).await;
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn xml(error_type: &str, code: &str, message: &str) -> String {
+        format!(
+            "<ErrorResponse xmlns=\"http://sns.amazonaws.com/doc/2010-03-31/\"><Error><Type>{}</Type><Code>{}</Code><Message>{}</Message></Error><RequestId>id-1</RequestId></ErrorResponse>",
+            error_type, code, message
+        )
+    }
+
+    #[tokio::test]
+    async fn constructors_render_their_status_code_and_message() {
+        let cases = [
+            (
+                SnsError::invalid_parameter("TopicArn"),
+                StatusCode::BAD_REQUEST,
+                "InvalidParameter",
+                "Invalid parameter: TopicArn",
+            ),
+            (
+                SnsError::missing_parameter("Message"),
+                StatusCode::BAD_REQUEST,
+                "InvalidParameter",
+                "Invalid parameter: Message Reason: no value for required parameter",
+            ),
+            (
+                SnsError::not_found_topic(),
+                StatusCode::NOT_FOUND,
+                "NotFound",
+                "Topic does not exist",
+            ),
+            (
+                SnsError::not_found_subscription(),
+                StatusCode::NOT_FOUND,
+                "NotFound",
+                "Subscription does not exist",
+            ),
+            (
+                SnsError::resource_not_found(),
+                StatusCode::NOT_FOUND,
+                "ResourceNotFound",
+                "Resource does not exist",
+            ),
+            (
+                SnsError::invalid_action("Frobnicate"),
+                StatusCode::BAD_REQUEST,
+                "InvalidAction",
+                "The action Frobnicate is not valid for this endpoint",
+            ),
+            (
+                SnsError::unknown_path("/nowhere"),
+                StatusCode::NOT_FOUND,
+                "InvalidAction",
+                "The path /nowhere is not valid for this endpoint",
+            ),
+            (
+                SnsError::no_such_version("2009-01-01"),
+                StatusCode::BAD_REQUEST,
+                "NoSuchVersion",
+                "The requested version (2009-01-01) of service AmazonSNS does not exist",
+            ),
+            (
+                SnsError::malformed_input("bad form"),
+                StatusCode::BAD_REQUEST,
+                "MalformedInput",
+                "bad form",
+            ),
+            (
+                SnsError::too_many_entries_in_batch_request(),
+                StatusCode::BAD_REQUEST,
+                "TooManyEntriesInBatchRequest",
+                "The batch request contains more entries than permissible",
+            ),
+            (
+                SnsError::batch_entry_ids_not_distinct(),
+                StatusCode::BAD_REQUEST,
+                "BatchEntryIdsNotDistinct",
+                "Two or more batch entries in the request have the same Id",
+            ),
+            (
+                SnsError::throttling(),
+                StatusCode::BAD_REQUEST,
+                "Throttling",
+                "Rate exceeded",
+            ),
+            (
+                SnsError::topic_limit_exceeded(),
+                StatusCode::FORBIDDEN,
+                "TopicLimitExceeded",
+                "Topic limit exceeded",
+            ),
+            (
+                SnsError::subscription_limit_exceeded(),
+                StatusCode::FORBIDDEN,
+                "SubscriptionLimitExceeded",
+                "Subscription limit exceeded",
+            ),
+            (
+                SnsError::tag_limit_exceeded(),
+                StatusCode::BAD_REQUEST,
+                "TagLimitExceeded",
+                "Could not complete request: tag quota of per resource exceeded",
+            ),
+        ];
+        for (error, status, code, message) in cases {
+            assert_eq!(error.code(), code);
+            assert_eq!(render(error).await, (status, xml("Sender", code, message)));
+        }
+    }
+
+    #[tokio::test]
+    async fn server_failures_are_the_receivers_fault() {
+        assert_eq!(
+            render(SnsError::internal_failure("disk full")).await,
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                xml("Receiver", "InternalFailure", "disk full")
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn messages_are_escaped() {
+        let (_, body) = render(SnsError::empty_batch_request()).await;
+        assert_eq!(
+            body,
+            xml(
+                "Sender",
+                "EmptyBatchRequest",
+                "The batch request doesn&apos;t contain any entries"
+            )
+        );
+        let (_, body) = render(SnsError::malformed_input("<a> & <b>")).await;
+        assert!(
+            body.contains("<Message>&lt;a&gt; &amp; &lt;b&gt;</Message>"),
+            "{}",
+            body
+        );
+    }
+
+    #[test]
+    fn the_code_is_kept_for_the_audit_log() {
+        let response = SnsError::throttling().into_response();
+        assert_eq!(
+            response.extensions().get::<ErrorCode>().unwrap().0,
+            "Throttling"
+        );
+    }
 }
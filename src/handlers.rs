@@ -1,45 +1,383 @@
-use crate::error::error_response;
-use crate::responses::Member;
-use crate::state::{Message, SharedState, SnsRequest, Subscription, Topic};
-use aws_config::BehaviorVersion;
-use axum::extract::{Form, State};
-use axum::http::StatusCode;
-use axum::response::Response;
+use crate::audit::{ApiCallRecord, AuditRecord, AuditedCall, ParamsSummary};
+use crate::config::EndpointCheck;
+use crate::console;
+use crate::delivery;
+use crate::dependencies;
+use crate::error::{
+    ErrorCode, FIFO_TOPIC_PROTOCOL_NOT_SUPPORTED, INVALID_PARAMETER, MESSAGE_TOO_LONG,
+    SUBSCRIPTION_EXISTS_WITH_DIFFERENT_ATTRIBUTES, SnsError, delivery_failed, invalid_parameter,
+    missing_parameter, unreachable_endpoint,
+};
+use crate::latency;
+use crate::multipart;
+#[cfg(feature = "payload-offload")]
+use crate::offload;
+use crate::pagination;
+use crate::platform;
+use crate::request_id;
+use crate::responses::{self, Member};
+use crate::retention;
+use crate::state::{
+    API_VERSION, AppState, AttributeEntry, DeduplicatedPublish, EXTENSION_ATTRIBUTE_PREFIX,
+    HEADERS_ATTRIBUTE, MAX_BATCH_ENTRIES, MAX_MESSAGE_ATTRIBUTES, MAX_MESSAGE_SIZE,
+    MAX_TAGS_PER_RESOURCE, MESSAGE_TEMPLATE_ATTRIBUTE, Message, MessageAttributeEntry,
+    PENDING_CONFIRMATION_ARN, PENDING_CONFIRMATION_LISTED, Publisher,
+    SEND_TEST_NOTIFICATION_ATTRIBUTE, SharedState, SnsRequest, Subscription, SubscriptionArn,
+    TRACE_HEADER_MESSAGE_ATTRIBUTE, Topic, is_local_arn, is_xml_text, topic_arn,
+    topic_name_from_arn, validate_message_attributes, validate_subscription, validate_tag,
+    validate_topic_attribute, validate_topic_name, validate_xml_text,
+};
+use crate::xml;
+use axum::Json;
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, Form, FromRequest, RawQuery, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, HeaderValue, Method, Uri};
+use axum::response::{IntoResponse, Response};
+use dashmap::mapref::entry::Entry;
 use quick_xml::Writer;
 use quick_xml::events::BytesText;
-use std::collections::HashMap;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
 use std::io::Cursor;
-use std::sync::Arc;
-use url::Url;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::LazyLock;
+use std::sync::atomic::Ordering;
+use tracing::Instrument;
 use uuid::Uuid;
 
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+
+/// Handles one Query API call. A RequestId is generated once per request:
+/// it is rendered into the response body, echoed in `x-amzn-RequestId` and
+/// recorded, with the SDK's `amz-sdk-invocation-id`, on the request span.
+/// The parameters may come as a form or, as some HTTP clients send them by
+/// default, as `multipart/form-data`.
 pub async fn handle_aws_request(
     State(state): State<SharedState>,
-    Form(params): Form<SnsRequest>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let params = match multipart::boundary(&headers) {
+        Some(boundary) => match multipart::to_form_urlencoded(&body, &boundary) {
+            Ok(form) => form_params(Some(FORM_CONTENT_TYPE), form).await,
+            Err(e) => Err(e),
+        },
+        None => {
+            let content_type = headers
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok());
+            form_params(content_type, body).await
+        }
+    };
+    let publisher = Publisher::from_request(&headers, connect_info.map(|info| info.0.ip()));
+    handle_query(state, headers, publisher, params).await
+}
+
+/// Parses a POST body of type `content_type` as the `SnsRequest` it
+/// encodes. Only forms are accepted; multipart bodies are re-encoded as
+/// one by the caller.
+async fn form_params(
+    content_type: Option<&str>,
+    body: impl Into<axum::body::Body>,
+) -> Result<SnsRequest, String> {
+    let mut request = axum::http::Request::post("/");
+    if let Some(content_type) = content_type {
+        request = request.header(CONTENT_TYPE, content_type);
+    }
+    Form::<SnsRequest>::from_request(request.body(body.into()).unwrap(), &())
+        .await
+        .map(|Form(params)| params)
+        .map_err(|rejection| rejection.body_text())
+}
+
+/// `GET /`: a Query API call with its parameters in the query string, as
+/// lightweight clients and curl smoke tests send them, or the service
+/// descriptor when there is no query. The query is parsed as a POST body
+/// would be, so calls dispatch, and fail, exactly as their POST
+/// equivalents.
+pub async fn handle_get_request(
+    State(state): State<SharedState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    RawQuery(query): RawQuery,
+) -> Response {
+    let Some(query) = query.filter(|query| !query.is_empty()) else {
+        return service_descriptor().await.into_response();
+    };
+    let params = form_params(Some(FORM_CONTENT_TYPE), query).await;
+    let publisher = Publisher::from_request(&headers, connect_info.map(|info| info.0.ip()));
+    handle_query(state, headers, publisher, params).await
+}
+
+/// Any other path. Some SDK endpoint settings prefix Query calls with a
+/// path such as `/doc/2010-03-31/`, or put the action in the path
+/// (`POST /Publish`); those are handled as on `/`. Anything else gets an
+/// SNS-style InvalidAction error instead of axum's bare 404, which SDKs
+/// cannot decode.
+pub async fn handle_other_path(
+    State(state): State<SharedState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> Response {
-    match params.action.as_str() {
-        "CreateTopic" => create_topic(State(state), params).await,
-        "DeleteTopic" => delete_topic(State(state), params).await,
-        "ListTopics" => list_topics(State(state)).await,
-        "Subscribe" => subscribe(State(state), params).await,
-        "Unsubscribe" => unsubscribe(State(state), params).await,
-        "Publish" => publish(State(state), params).await,
-        "GetTopicAttributes" => get_topic_attributes(State(state), params).await,
-        "SetTopicAttributes" => set_topic_attributes(State(state), params).await,
-        "ListTagsForResource" => list_tags_for_resource(State(state), params).await,
-        "TagResource" => tag_resource(State(state), params).await,
-        "UntagResource" => untag_resource(State(state), params).await,
-        "GetSubscriptionAttributes" => get_subscription_attributes(State(state), params).await,
-        "ListSubscriptionsByTopic" => list_subscriptions_by_topic(State(state), params).await,
-        _ => {
-            error_response(
-                "InvalidAction",
-                "Action not supported",
-                StatusCode::BAD_REQUEST,
-            )
-            .await
+    let mut form = if method == Method::GET || method == Method::HEAD {
+        uri.query().unwrap_or_default().to_string()
+    } else if let Some(boundary) = multipart::boundary(&headers) {
+        match multipart::to_form_urlencoded(&body, &boundary) {
+            Ok(form) => form,
+            Err(e) => return SnsError::malformed_input(e).into_response(),
         }
+    } else {
+        String::from_utf8_lossy(&body).into_owned()
+    };
+    let has_action = url::form_urlencoded::parse(form.as_bytes()).any(|(key, _)| key == "Action");
+    let path_action = uri
+        .path()
+        .rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .filter(|segment| ACTIONS.contains_key(segment));
+    if !has_action {
+        let Some(action) = path_action else {
+            return SnsError::unknown_path(uri.path()).into_response();
+        };
+        form = if form.is_empty() {
+            format!("Action={}", action)
+        } else {
+            format!("Action={}&{}", action, form)
+        };
+    }
+
+    let params = form_params(Some(FORM_CONTENT_TYPE), form).await;
+    let publisher = Publisher::from_request(&headers, connect_info.map(|info| info.0.ip()));
+    handle_query(state, headers, publisher, params).await
+}
+
+async fn handle_query(
+    state: SharedState,
+    headers: HeaderMap,
+    publisher: Publisher,
+    params: Result<SnsRequest, String>,
+) -> Response {
+    let request_id = Uuid::new_v4().to_string();
+    let invocation_id = headers
+        .get("amz-sdk-invocation-id")
+        .and_then(|value| value.to_str().ok());
+    let caller = state.audit.as_ref().map(|_| publisher.label());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        invocation_id,
+        action = tracing::field::Empty,
+    );
+
+    let mut response = request_id::scope(
+        request_id.clone(),
+        process_request(
+            state.clone(),
+            &headers,
+            request_id.clone(),
+            publisher,
+            params,
+        ),
+    )
+    .instrument(span.clone())
+    .await;
+    span.in_scope(|| tracing::info!(status = response.status().as_u16(), "Handled request"));
+    if let Some(audit) = &state.audit
+        && let Some(caller) = caller
+    {
+        let call = response.extensions_mut().remove::<AuditedCall>();
+        audit.record(AuditRecord::ApiCall(ApiCallRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            request_id: request_id.clone(),
+            action: call.as_ref().map(|call| call.action.clone()),
+            caller,
+            params: call.map(|call| call.params).unwrap_or_default(),
+            status: response.status().as_u16(),
+            error_code: response.extensions().get::<ErrorCode>().map(|code| code.0),
+        }));
+    }
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert("x-amzn-RequestId", value);
+    }
+    response
+}
+
+async fn process_request(
+    state: SharedState,
+    headers: &HeaderMap,
+    request_id: String,
+    publisher: Publisher,
+    params: Result<SnsRequest, String>,
+) -> Response {
+    let mut params = match params {
+        Ok(params) => params,
+        Err(e) => return SnsError::malformed_input(e).into_response(),
+    };
+
+    tracing::Span::current().record("action", params.action.as_str());
+    params.normalize();
+    params.context.request_id = request_id;
+    params.context.base_url = state.request_base_url(headers);
+    params.context.sync_delivery = state.sync_delivery
+        || !state.strict_aws
+            && headers
+                .get("x-localsns-sync-delivery")
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+    params.context.trace_header = headers
+        .get("x-amzn-trace-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    params.context.publisher = publisher;
+
+    let audited = state.audit.is_some().then(|| AuditedCall {
+        action: params.action.clone(),
+        params: ParamsSummary::of(&params),
+    });
+    let mut response = if let Some(version) = params.version.as_deref()
+        && version != API_VERSION
+    {
+        SnsError::no_such_version(version).into_response()
+    } else {
+        latency::apply(&state, &params.action).await;
+        dispatch(state, params).await
+    };
+    if let Some(audited) = audited {
+        response.extensions_mut().insert(audited);
     }
+    response
+}
+
+type HandlerFuture = Pin<Box<dyn Future<Output = Response> + Send>>;
+type Handler = fn(SharedState, SnsRequest) -> HandlerFuture;
+
+/// Every supported Query API action, keyed by its `Action` name.
+static ACTIONS: LazyLock<HashMap<&'static str, Handler>> = LazyLock::new(|| {
+    let actions: [(&'static str, Handler); 18] = [
+        ("CreateTopic", |state, params| {
+            Box::pin(create_topic(State(state), params))
+        }),
+        ("DeleteTopic", |state, params| {
+            Box::pin(delete_topic(State(state), params))
+        }),
+        ("ListTopics", |state, params| {
+            Box::pin(list_topics(State(state), params))
+        }),
+        ("Subscribe", |state, params| {
+            Box::pin(subscribe(State(state), params))
+        }),
+        ("ConfirmSubscription", |state, params| {
+            Box::pin(confirm_subscription(State(state), params))
+        }),
+        ("Unsubscribe", |state, params| {
+            Box::pin(unsubscribe(State(state), params))
+        }),
+        ("Publish", |state, params| {
+            Box::pin(publish(State(state), params))
+        }),
+        ("PublishBatch", |state, params| {
+            Box::pin(publish_batch(State(state), params))
+        }),
+        ("GetTopicAttributes", |state, params| {
+            Box::pin(get_topic_attributes(State(state), params))
+        }),
+        ("SetTopicAttributes", |state, params| {
+            Box::pin(set_topic_attributes(State(state), params))
+        }),
+        ("ListTagsForResource", |state, params| {
+            Box::pin(list_tags_for_resource(State(state), params))
+        }),
+        ("TagResource", |state, params| {
+            Box::pin(tag_resource(State(state), params))
+        }),
+        ("UntagResource", |state, params| {
+            Box::pin(untag_resource(State(state), params))
+        }),
+        ("GetSubscriptionAttributes", |state, params| {
+            Box::pin(get_subscription_attributes(State(state), params))
+        }),
+        ("SetSubscriptionAttributes", |state, params| {
+            Box::pin(set_subscription_attributes(State(state), params))
+        }),
+        ("ListSubscriptionsByTopic", |state, params| {
+            Box::pin(list_subscriptions_by_topic(State(state), params))
+        }),
+        ("CreatePlatformApplication", |state, params| {
+            Box::pin(platform::create_platform_application(State(state), params))
+        }),
+        ("CreatePlatformEndpoint", |state, params| {
+            Box::pin(platform::create_platform_endpoint(State(state), params))
+        }),
+    ];
+    actions.into_iter().collect()
+});
+
+/// Names of all actions `handle_aws_request` dispatches, sorted.
+pub fn supported_actions() -> Vec<&'static str> {
+    let mut actions: Vec<&'static str> = ACTIONS.keys().copied().collect();
+    actions.sort_unstable();
+    actions
+}
+
+/// Runs the handler registered for `params.action`. Every transport (form
+/// POST and future encodings) funnels through here.
+pub async fn dispatch(state: SharedState, params: SnsRequest) -> Response {
+    match ACTIONS.get(params.action.as_str()) {
+        Some(handler) => handler(state, params).await,
+        None => SnsError::invalid_action(&params.action).into_response(),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceDescriptor {
+    pub service: &'static str,
+    pub api_version: &'static str,
+    pub actions: Vec<&'static str>,
+}
+
+pub async fn service_descriptor() -> Json<ServiceDescriptor> {
+    Json(ServiceDescriptor {
+        service: "sns",
+        api_version: API_VERSION,
+        actions: supported_actions(),
+    })
+}
+
+/// The page size of a list call; `MaxItems` is an extension, ignored under
+/// `--strict-aws`.
+fn page_size(state: &AppState, max_items: Option<&str>) -> Result<usize, SnsError> {
+    if state.strict_aws {
+        return Ok(pagination::DEFAULT_PAGE_SIZE);
+    }
+    pagination::page_size(max_items)
+}
+
+/// Rejects an ARN of another region or account than this server's, which
+/// would otherwise resolve by name alone, unless `--ignore-arn-scope`.
+fn check_arn_scope(state: &AppState, arn: &str, parameter: &str) -> Result<(), SnsError> {
+    if state.ignore_arn_scope || is_local_arn(arn) {
+        return Ok(());
+    }
+    Err(SnsError::invalid_parameter(parameter))
+}
+
+/// Whether `--strict-aws` forbids the subscription attribute `name`.
+fn is_forbidden_attribute(state: &AppState, name: &str) -> bool {
+    state.strict_aws && name.starts_with(EXTENSION_ATTRIBUTE_PREFIX)
+}
+
+/// Whether tags exceed the AWS per-resource limit, which only
+/// `--strict-aws` enforces.
+fn exceeds_tag_limit(state: &AppState, tags: &HashMap<String, String>) -> bool {
+    state.strict_aws && tags.len() > MAX_TAGS_PER_RESOURCE
 }
 
 pub async fn list_subscriptions_by_topic(
@@ -49,26 +387,36 @@ pub async fn list_subscriptions_by_topic(
     let topic_arn = if let Some(topic_arn) = params.topic_arn {
         topic_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Topic ARN",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("TopicArn").into_response();
     };
 
-    let topic_name = topic_arn.split(':').last().unwrap_or_default();
+    let topic_name = topic_name_from_arn(&topic_arn);
 
-    let subscriptions = if let Some(topic) = state.topics.get(topic_name) {
-        topic.subscriptions.clone()
+    let subscriptions = if let Some(topic) = state
+        .topics
+        .get(topic_name)
+        .filter(|topic| topic.is_visible())
+    {
+        topic
+            .subscriptions
+            .iter()
+            .filter(|subscription| subscription.is_visible())
+            .cloned()
+            .collect()
     } else {
-        return error_response("NotFound", "Topic not found", StatusCode::NOT_FOUND).await;
+        return SnsError::not_found_topic().into_response();
+    };
+    let page = match page_size(&state, params.max_items.as_deref()).and_then(|page_size| {
+        pagination::paginate(subscriptions, params.next_token.as_deref(), page_size)
+    }) {
+        Ok(page) => page,
+        Err(e) => return e.into_response(),
     };
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("ListSubscriptionsByTopicResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("ListSubscriptionsByTopicResult")
@@ -76,34 +424,36 @@ pub async fn list_subscriptions_by_topic(
                     writer
                         .create_element("Subscriptions")
                         .write_inner_content(|writer| {
-                            for sub in subscriptions {
+                            xml::write_members(writer, page.items, |writer, sub| {
                                 writer
-                                    .create_element("member")
-                                    .write_inner_content(|writer| {
-                                        writer
-                                            .create_element("TopicArn")
-                                            .write_text_content(BytesText::new(&sub.arn))?;
-                                        writer
-                                            .create_element("Protocol")
-                                            .write_text_content(BytesText::new(&sub.protocol))?;
-                                        writer
-                                            .create_element("SubscriptionArn")
-                                            .write_text_content(BytesText::new(
-                                                &sub.subscription_arn,
-                                            ))?;
-                                        writer
-                                            .create_element("Owner")
-                                            .write_text_content(BytesText::new("000000000000"))?;
-                                        writer
-                                            .create_element("Endpoint")
-                                            .write_text_content(BytesText::new(&sub.endpoint))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            Ok(())
+                                    .create_element("TopicArn")
+                                    .write_text_content(BytesText::new(&sub.arn))?;
+                                writer
+                                    .create_element("Protocol")
+                                    .write_text_content(BytesText::new(&sub.protocol))?;
+                                writer
+                                    .create_element("SubscriptionArn")
+                                    .write_text_content(BytesText::new(
+                                        if sub.pending_confirmation {
+                                            PENDING_CONFIRMATION_LISTED
+                                        } else {
+                                            &sub.subscription_arn
+                                        },
+                                    ))?;
+                                writer
+                                    .create_element("Owner")
+                                    .write_text_content(BytesText::new("000000000000"))?;
+                                writer
+                                    .create_element("Endpoint")
+                                    .write_text_content(BytesText::new(&sub.endpoint))?;
+                                Ok(())
+                            })
                         })?;
-                    // Pagination is not implemented, so NextToken is empty or omitted
-                    // writer.create_element("NextToken").write_text_content(BytesText::new(""))?;
+                    if let Some(next_token) = &page.next_token {
+                        writer
+                            .create_element("NextToken")
+                            .write_text_content(BytesText::new(next_token))?;
+                    }
                     Ok(())
                 })?;
             writer
@@ -111,7 +461,7 @@ pub async fn list_subscriptions_by_topic(
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -125,43 +475,111 @@ pub async fn list_subscriptions_by_topic(
         .unwrap()
 }
 
-pub async fn get_subscription_attributes(
+pub async fn set_subscription_attributes(
     State(state): State<SharedState>,
     params: SnsRequest,
 ) -> Response {
     let subscription_arn = if let Some(subscription_arn) = params.subscription_arn {
         subscription_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Subscription ARN",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("SubscriptionArn").into_response();
+    };
+
+    let attribute_name = if let Some(attribute_name) = params.attribute_name {
+        attribute_name
+    } else {
+        return SnsError::missing_parameter("AttributeName").into_response();
     };
 
-    let mut found_subscription = None;
-    for topic in state.topics.iter() {
-        if let Some(sub) = topic
+    let attribute_value = params.attribute_value.unwrap_or_default();
+    if is_forbidden_attribute(&state, &attribute_name) {
+        return SnsError::invalid_parameter("AttributeName").into_response();
+    }
+    if let Err(message) = validate_xml_text("AttributeValue", &attribute_value) {
+        return SnsError::invalid_parameter_message(message).into_response();
+    }
+
+    let topic_name = match SubscriptionArn::parse(&subscription_arn) {
+        Ok(parsed) => parsed.topic_name,
+        Err(message) => return SnsError::invalid_parameter_message(message).into_response(),
+    };
+    let updated = state.topics.get_mut(topic_name).and_then(|mut topic| {
+        topic
             .subscriptions
-            .iter()
-            .find(|s| s.subscription_arn == subscription_arn)
-        {
-            found_subscription = Some(sub.clone());
-            break;
+            .iter_mut()
+            .find(|sub| sub.subscription_arn == subscription_arn)
+            .map(|sub| sub.set_attribute(&attribute_name, &attribute_value))
+    });
+    match updated {
+        Some(true) => {}
+        Some(false) => {
+            return SnsError::invalid_parameter("AttributeName").into_response();
+        }
+        None => {
+            return SnsError::not_found_subscription().into_response();
         }
     }
 
-    let subscription = if let Some(sub) = found_subscription {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer
+        .create_element("SetSubscriptionAttributesResponse")
+        .with_attribute(("xmlns", responses::xml_namespace()))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("ResponseMetadata")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("RequestId")
+                        .write_text_content(BytesText::new(&request_id::current()))?;
+                    Ok(())
+                })?;
+            Ok(())
+        })
+        .unwrap();
+
+    let xml_response = writer.into_inner().into_inner();
+    Response::builder()
+        .header("Content-Type", "application/xml")
+        .body(axum::body::Body::from(xml_response))
+        .unwrap()
+}
+
+pub async fn get_subscription_attributes(
+    State(state): State<SharedState>,
+    params: SnsRequest,
+) -> Response {
+    let subscription_arn = if let Some(subscription_arn) = params.subscription_arn {
+        subscription_arn
+    } else {
+        return SnsError::missing_parameter("SubscriptionArn").into_response();
+    };
+    if let Err(message) = SubscriptionArn::parse(&subscription_arn) {
+        return SnsError::invalid_parameter_message(message).into_response();
+    }
+
+    let subscription = if let Some(sub) = state
+        .find_subscription(&subscription_arn)
+        .filter(Subscription::is_visible)
+    {
         sub
     } else {
-        return error_response("NotFound", "Subscription not found", StatusCode::NOT_FOUND).await;
+        return SnsError::not_found_subscription().into_response();
     };
 
+    let health = state.subscription_health(&subscription.subscription_arn);
+    let effective_delivery_policy = matches!(subscription.protocol.as_str(), "http" | "https")
+        .then(|| serde_json::to_string(&state.effective_delivery_policy(&subscription)).unwrap());
+    let headers = subscription.reported_headers(state.expose_secrets);
+    let last_delivery_error = health
+        .last_error
+        .as_ref()
+        .filter(|_| state.extended_attributes)
+        .map(|error| serde_json::to_string(error).unwrap());
+
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("GetSubscriptionAttributesResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("GetSubscriptionAttributesResult")
@@ -169,30 +587,65 @@ pub async fn get_subscription_attributes(
                     writer
                         .create_element("Attributes")
                         .write_inner_content(|writer| {
-                            let attributes = vec![
+                            let mut attributes = vec![
                                 ("SubscriptionArn", subscription.subscription_arn.as_str()),
                                 ("TopicArn", subscription.arn.as_str()),
                                 ("Owner", "000000000000"),
                                 ("ConfirmationWasAuthenticated", "true"),
-                                ("PendingConfirmation", "false"),
+                                (
+                                    "PendingConfirmation",
+                                    if subscription.pending_confirmation {
+                                        "true"
+                                    } else {
+                                        "false"
+                                    },
+                                ),
                                 ("Protocol", subscription.protocol.as_str()),
                                 ("Endpoint", subscription.endpoint.as_str()),
+                                (
+                                    "RawMessageDelivery",
+                                    if subscription.raw_message_delivery {
+                                        "true"
+                                    } else {
+                                        "false"
+                                    },
+                                ),
                             ];
+                            if state.extended_attributes {
+                                attributes.push((
+                                    "X-LocalSns-EffectiveDeliveryState",
+                                    health.effective_delivery_state(),
+                                ));
+                            }
+                            if let Some(delivery_policy) = &subscription.delivery_policy {
+                                attributes.push(("DeliveryPolicy", delivery_policy));
+                            }
+                            if let Some(effective_delivery_policy) = &effective_delivery_policy {
+                                attributes
+                                    .push(("EffectiveDeliveryPolicy", effective_delivery_policy));
+                            }
+                            if let Some(subscription_role_arn) = &subscription.subscription_role_arn
+                            {
+                                attributes.push(("SubscriptionRoleArn", subscription_role_arn));
+                            }
+                            if let Some(headers) = &headers {
+                                attributes.push((HEADERS_ATTRIBUTE, headers));
+                            }
+                            if let Some(message_template) = &subscription.message_template {
+                                attributes
+                                    .push((MESSAGE_TEMPLATE_ATTRIBUTE, message_template.source()));
+                            }
+                            if let Some(last_delivery_error) = &last_delivery_error {
+                                attributes
+                                    .push(("X-LocalSns-LastDeliveryError", last_delivery_error));
+                            }
 
-                            for (key, value) in attributes {
-                                writer
-                                    .create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer
-                                            .create_element("key")
-                                            .write_text_content(BytesText::new(key))?;
-                                        writer
-                                            .create_element("value")
-                                            .write_text_content(BytesText::new(value))?;
-                                        Ok(())
-                                    })?;
+                            if state.strict_aws {
+                                attributes.retain(|(key, _)| {
+                                    !key.starts_with(EXTENSION_ATTRIBUTE_PREFIX)
+                                });
                             }
-                            Ok(())
+                            xml::write_attribute_entries(writer, &attributes)
                         })?;
                     Ok(())
                 })?;
@@ -201,7 +654,7 @@ pub async fn get_subscription_attributes(
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -222,26 +675,28 @@ pub async fn list_tags_for_resource(
     let resource_arn = if let Some(resource_arn) = params.resource_arn {
         resource_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Resource Arn",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("ResourceArn").into_response();
     };
 
-    let topic_name = resource_arn.split(':').last().unwrap_or_default();
+    let topic_name = topic_name_from_arn(&resource_arn);
+    if let Err(e) = check_arn_scope(&state, &resource_arn, "ResourceArn") {
+        return e.into_response();
+    }
 
-    let topic = if let Some(topic) = state.topics.get(topic_name) {
+    let topic = if let Some(topic) = state
+        .topics
+        .get(topic_name)
+        .filter(|topic| topic.is_visible())
+    {
         topic
     } else {
-        return error_response("NotFound", "Resource not found", StatusCode::NOT_FOUND).await;
+        return SnsError::resource_not_found().into_response();
     };
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("ListTagsForResourceResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("ListTagsForResourceResult")
@@ -249,20 +704,15 @@ pub async fn list_tags_for_resource(
                     writer
                         .create_element("Tags")
                         .write_inner_content(|writer| {
-                            for (key, value) in &topic.tags {
+                            xml::write_members(writer, &topic.tags, |writer, (key, value)| {
                                 writer
-                                    .create_element("member")
-                                    .write_inner_content(|writer| {
-                                        writer
-                                            .create_element("Key")
-                                            .write_text_content(BytesText::new(key))?;
-                                        writer
-                                            .create_element("Value")
-                                            .write_text_content(BytesText::new(value))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            Ok(())
+                                    .create_element("Key")
+                                    .write_text_content(BytesText::new(key))?;
+                                writer
+                                    .create_element("Value")
+                                    .write_text_content(BytesText::new(value))?;
+                                Ok(())
+                            })
                         })?;
                     Ok(())
                 })?;
@@ -271,7 +721,7 @@ pub async fn list_tags_for_resource(
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -289,34 +739,44 @@ pub async fn tag_resource(State(state): State<SharedState>, params: SnsRequest)
     let resource_arn = if let Some(resource_arn) = params.resource_arn {
         resource_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Resource Arn",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("ResourceArn").into_response();
     };
 
     let tags_entry = if let Some(tags_entry) = params.tags_entry {
         tags_entry
     } else {
-        return error_response("InvalidParameter", "Missing Tags", StatusCode::BAD_REQUEST).await;
+        return SnsError::missing_parameter("Tags").into_response();
     };
 
-    let topic_name = resource_arn.split(':').last().unwrap_or_default();
+    let topic_name = topic_name_from_arn(&resource_arn);
+    if let Err(e) = check_arn_scope(&state, &resource_arn, "ResourceArn") {
+        return e.into_response();
+    }
+    if let Some(message) = tags_entry
+        .iter()
+        .find_map(|tag| validate_tag(&tag.key, &tag.value).err())
+    {
+        return SnsError::invalid_parameter_message(message).into_response();
+    }
 
     if let Some(mut topic) = state.topics.get_mut(topic_name) {
+        let mut tags = topic.tags.clone();
         for tag in tags_entry {
-            topic.tags.insert(tag.key, tag.value);
+            tags.insert(tag.key, tag.value);
         }
+        if exceeds_tag_limit(&state, &tags) {
+            drop(topic);
+            return SnsError::tag_limit_exceeded().into_response();
+        }
+        topic.tags = tags;
     } else {
-        return error_response("NotFound", "Resource not found", StatusCode::NOT_FOUND).await;
+        return SnsError::resource_not_found().into_response();
     };
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("TagResourceResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("TagResourceResult")
@@ -326,7 +786,7 @@ pub async fn tag_resource(State(state): State<SharedState>, params: SnsRequest)
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -344,39 +804,32 @@ pub async fn untag_resource(State(state): State<SharedState>, params: SnsRequest
     let resource_arn = if let Some(resource_arn) = params.resource_arn {
         resource_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Resource Arn",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("ResourceArn").into_response();
     };
 
     let tag_keys = if let Some(tag_keys) = params.tag_keys_entry {
         tag_keys
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Tag Keys",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("TagKeys").into_response();
     };
 
-    let topic_name = resource_arn.split(':').last().unwrap_or_default();
+    let topic_name = topic_name_from_arn(&resource_arn);
+    if let Err(e) = check_arn_scope(&state, &resource_arn, "ResourceArn") {
+        return e.into_response();
+    }
 
     if let Some(mut topic) = state.topics.get_mut(topic_name) {
         for key in tag_keys {
             topic.tags.remove(&key);
         }
     } else {
-        return error_response("NotFound", "Resource not found", StatusCode::NOT_FOUND).await;
+        return SnsError::resource_not_found().into_response();
     };
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("UntagResourceResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("UntagResourceResult")
@@ -386,7 +839,7 @@ pub async fn untag_resource(State(state): State<SharedState>, params: SnsRequest
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -404,60 +857,65 @@ pub async fn create_topic(State(state): State<SharedState>, params: SnsRequest)
     let name = if let Some(name) = params.name {
         name
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Topic Name",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("Name").into_response();
     };
 
-    let arn = format!("arn:aws:sns:us-east-1:000000000000:{}", name);
+    if let Err(message) = validate_topic_name(&name) {
+        return SnsError::invalid_parameter_message(message).into_response();
+    }
+
+    let arn = topic_arn(&name);
 
     let mut tags = HashMap::new();
     if let Some(tags_entry) = params.tags_entry {
         for tag in tags_entry {
+            if let Err(message) = validate_tag(&tag.key, &tag.value) {
+                return SnsError::invalid_parameter_message(message).into_response();
+            }
             tags.insert(tag.key, tag.value);
         }
     }
+    if exceeds_tag_limit(&state, &tags) {
+        return SnsError::tag_limit_exceeded().into_response();
+    }
 
-    let topic = Topic {
-        name: name.clone(),
-        arn: arn.clone(),
-        tags,
-        subscriptions: vec![],
-        display_name: None,
-        policy: None,
-        delivery_policy: None,
-        tracing_config: None,
-        firehose_failure_feedback_role_arn: None,
-        firehose_success_feedback_role_arn: None,
-        firehose_success_feedback_sample_rate: None,
-        http_failure_feedback_role_arn: None,
-        sqs_failure_feedback_role_arn: None,
-        sqs_success_feedback_role_arn: None,
-        sqs_success_feedback_sample_rate: None,
-        http_success_feedback_role_arn: None,
-        http_success_feedback_sample_rate: None,
-        application_failure_feedback_role_arn: None,
-        application_success_feedback_role_arn: None,
-        application_success_feedback_sample_rate: None,
-        lambda_failure_feedback_role_arn: None,
-        lambda_success_feedback_role_arn: None,
-        lambda_success_feedback_sample_rate: None,
-        kms_master_key_id: None,
-        signature_version: None,
-        content_based_deduplication: None,
-        fifo_topic: None,
-        archive_policy: None,
-        fifo_throughput_scope: None,
-    };
-    state.topics.insert(name, topic);
+    let mut topic = Topic::new(name.clone(), arn.clone(), tags);
+    topic.visible_at = state.visible_at();
+    if let Some(attributes_entry) = params.attributes_entry {
+        for attribute in attributes_entry {
+            if let Err(message) = validate_topic_attribute(&attribute.key, &attribute.value) {
+                return SnsError::invalid_parameter_message(message).into_response();
+            }
+            if !topic.set_attribute(&attribute.key, attribute.value) {
+                return SnsError::invalid_parameter("AttributeName").into_response();
+            }
+        }
+    }
+    // Checked before taking the entry, which the count would deadlock on.
+    if let Err(e) = state.check_topic_quota(&name) {
+        return e.into_response();
+    }
+    // Insert-or-get under the shard lock, so concurrent CreateTopic calls
+    // for one name never replace a topic (and its subscriptions) another
+    // call created in between.
+    match state.topics.entry(name.clone()) {
+        Entry::Occupied(_) => {
+            state
+                .suspicious
+                .duplicate_topics
+                .fetch_add(1, Ordering::Relaxed);
+            tracing::warn!("CreateTopic called for existing topic {}", name);
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(topic);
+            console::topic_created(&name);
+        }
+    }
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("CreateTopicResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("CreateTopicResult")
@@ -472,7 +930,7 @@ pub async fn create_topic(State(state): State<SharedState>, params: SnsRequest)
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -490,28 +948,29 @@ pub async fn delete_topic(State(state): State<SharedState>, params: SnsRequest)
     let topic_arn = if let Some(topic_arn) = params.topic_arn {
         topic_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Topic ARN",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("TopicArn").into_response();
     };
 
-    let topic_name = topic_arn.split(':').last().unwrap_or_default();
-    state.topics.remove(topic_name);
+    let topic_name = topic_name_from_arn(&topic_arn);
+    if let Some(topic) = state.remove_topic(topic_name) {
+        delivery::spawn_unsubscribe_confirmations(
+            state.clone(),
+            params.context.base_url,
+            topic.subscriptions,
+        );
+    }
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("DeleteTopicResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("ResponseMetadata")
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -525,19 +984,29 @@ pub async fn delete_topic(State(state): State<SharedState>, params: SnsRequest)
         .unwrap()
 }
 
-pub async fn list_topics(State(state): State<SharedState>) -> Response {
-    let topics = state
+pub async fn list_topics(State(state): State<SharedState>, params: SnsRequest) -> Response {
+    let mut topics = state
         .topics
         .iter()
+        .filter(|topic_ref| topic_ref.is_visible())
         .map(|topic_ref| Member {
             topic_arn: topic_ref.value().arn.clone(),
         })
         .collect::<Vec<_>>();
+    // DashMap iteration order depends on hashing; sort by ARN so listings
+    // are stable and names differing only by case appear side by side.
+    topics.sort_by(|a, b| a.topic_arn.cmp(&b.topic_arn));
+    let page = match page_size(&state, params.max_items.as_deref())
+        .and_then(|page_size| pagination::paginate(topics, params.next_token.as_deref(), page_size))
+    {
+        Ok(page) => page,
+        Err(e) => return e.into_response(),
+    };
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("ListTopicsResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("ListTopicsResult")
@@ -545,21 +1014,18 @@ pub async fn list_topics(State(state): State<SharedState>) -> Response {
                     writer
                         .create_element("Topics")
                         .write_inner_content(|writer| {
-                            for topic in topics {
+                            xml::write_members(writer, page.items, |writer, topic| {
                                 writer
-                                    .create_element("member")
-                                    .write_inner_content(|writer| {
-                                        writer
-                                            .create_element("TopicArn")
-                                            .write_text_content(BytesText::new(&topic.topic_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            Ok(())
+                                    .create_element("TopicArn")
+                                    .write_text_content(BytesText::new(&topic.topic_arn))?;
+                                Ok(())
+                            })
                         })?;
-                    writer
-                        .create_element("NextToken")
-                        .write_text_content(BytesText::new(""))?;
+                    if let Some(next_token) = &page.next_token {
+                        writer
+                            .create_element("NextToken")
+                            .write_text_content(BytesText::new(next_token))?;
+                    }
                     Ok(())
                 })?;
             writer
@@ -567,7 +1033,7 @@ pub async fn list_topics(State(state): State<SharedState>) -> Response {
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -588,121 +1054,49 @@ pub async fn set_topic_attributes(
     let topic_arn = if let Some(topic_arn) = params.topic_arn {
         topic_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Topic ARN",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("TopicArn").into_response();
     };
 
     let attribute_name = if let Some(attribute_name) = params.attribute_name {
         attribute_name
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Attribute Name",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("AttributeName").into_response();
     };
 
     let attribute_value = if let Some(attribute_value) = params.attribute_value {
         attribute_value
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Attribute Value",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("AttributeValue").into_response();
     };
 
-    let topic_name = topic_arn.split(':').last().unwrap_or_default();
+    if let Err(message) = validate_topic_attribute(&attribute_name, &attribute_value) {
+        return SnsError::invalid_parameter_message(message).into_response();
+    }
+
+    let topic_name = topic_name_from_arn(&topic_arn);
+    if let Err(e) = check_arn_scope(&state, &topic_arn, "TopicArn") {
+        return e.into_response();
+    }
 
     if let Some(mut topic) = state.topics.get_mut(topic_name) {
-        match attribute_name.as_str() {
-            "DisplayName" => topic.display_name = Some(attribute_value),
-            "Policy" => topic.policy = Some(attribute_value),
-            "DeliveryPolicy" => topic.delivery_policy = Some(attribute_value),
-            "TracingConfig" => topic.tracing_config = Some(attribute_value),
-            "FirehoseSuccessFeedbackSampleRate" => {
-                topic.firehose_success_feedback_sample_rate = Some(attribute_value)
-            }
-            "FirehoseFailureFeedbackRoleArn" => {
-                topic.firehose_failure_feedback_role_arn = Some(attribute_value)
-            }
-            "FirehoseSuccessFeedbackRoleArn" => {
-                topic.firehose_success_feedback_role_arn = Some(attribute_value)
-            }
-            "HTTPFailureFeedbackRoleArn" => {
-                topic.http_failure_feedback_role_arn = Some(attribute_value)
-            }
-            "SQSSuccessFeedbackSampleRate" => {
-                topic.sqs_success_feedback_sample_rate = Some(attribute_value)
-            }
-            "SQSFailureFeedbackRoleArn" => {
-                topic.sqs_failure_feedback_role_arn = Some(attribute_value)
-            }
-            "SQSSuccessFeedbackRoleArn" => {
-                topic.sqs_success_feedback_role_arn = Some(attribute_value)
-            }
-            "HTTPSuccessFeedbackSampleRate" => {
-                topic.http_success_feedback_sample_rate = Some(attribute_value)
-            }
-            "HTTPSuccessFeedbackRoleArn" => {
-                topic.http_success_feedback_role_arn = Some(attribute_value)
-            }
-            "ApplicationSuccessFeedbackSampleRate" => {
-                topic.application_success_feedback_sample_rate = Some(attribute_value)
-            }
-            "ApplicationFailureFeedbackRoleArn" => {
-                topic.application_failure_feedback_role_arn = Some(attribute_value)
-            }
-            "ApplicationSuccessFeedbackRoleArn" => {
-                topic.application_success_feedback_role_arn = Some(attribute_value)
-            }
-            "LambdaSuccessFeedbackSampleRate" => {
-                topic.lambda_success_feedback_sample_rate = Some(attribute_value)
-            }
-            "LambdaFailureFeedbackRoleArn" => {
-                topic.lambda_failure_feedback_role_arn = Some(attribute_value)
-            }
-            "LambdaSuccessFeedbackRoleArn" => {
-                topic.lambda_success_feedback_role_arn = Some(attribute_value)
-            }
-            "KmsMasterKeyId" => topic.kms_master_key_id = Some(attribute_value),
-            "SignatureVersion" => topic.signature_version = Some(attribute_value),
-            "ContentBasedDeduplication" => {
-                topic.content_based_deduplication = Some(attribute_value)
-            }
-            "FifoTopic" => topic.fifo_topic = Some(attribute_value),
-            "ArchivePolicy" => topic.archive_policy = Some(attribute_value),
-            "FifoThroughputScope" => topic.fifo_throughput_scope = Some(attribute_value),
-            _ => {
-                return error_response(
-                    "InvalidParameter",
-                    "Attribute not supported",
-                    StatusCode::BAD_REQUEST,
-                )
-                .await;
-            }
+        if !topic.set_attribute(&attribute_name, attribute_value) {
+            return SnsError::invalid_parameter("AttributeName").into_response();
         }
     } else {
-        return error_response("NotFound", "Topic not found", StatusCode::NOT_FOUND).await;
+        return SnsError::not_found_topic().into_response();
     };
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("SetTopicAttributesResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("ResponseMetadata")
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -723,259 +1117,177 @@ pub async fn get_topic_attributes(
     let topic_arn = if let Some(topic_arn) = params.topic_arn {
         topic_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Topic ARN",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("TopicArn").into_response();
     };
 
-    let topic_name = topic_arn.split(':').last().unwrap_or_default();
+    let topic_name = topic_name_from_arn(&topic_arn);
+    if let Err(e) = check_arn_scope(&state, &topic_arn, "TopicArn") {
+        return e.into_response();
+    }
 
-    let topic = if let Some(topic) = state.topics.get(topic_name) {
+    // Unset attributes are reported with their defaults. An expired topic
+    // awaiting the sweeper is already gone, and one created within
+    // `--consistency-delay` not there yet.
+    let topic = if let Some(topic) = state.topics.get(topic_name).filter(|topic| {
+        topic.is_visible() && !retention::topic_expired(&state, topic, chrono::Utc::now())
+    }) {
+        let mut topic = topic.clone();
+        topic.apply_defaults(&state.topic_defaults);
         topic
     } else {
-        return error_response("NotFound", "Topic not found", StatusCode::NOT_FOUND).await;
+        return SnsError::not_found_topic().into_response();
     };
 
+    // Attributes still unset after the defaults are left out.
+    let mut attributes = vec![("TopicArn".to_string(), topic.arn.clone())];
+    let optional = [
+        ("DisplayName", &topic.display_name),
+        ("Policy", &topic.policy),
+        ("DeliveryPolicy", &topic.delivery_policy),
+        ("TracingConfig", &topic.tracing_config),
+        (
+            "FirehoseFailureFeedbackRoleArn",
+            &topic.firehose_failure_feedback_role_arn,
+        ),
+        (
+            "FirehoseSuccessFeedbackRoleArn",
+            &topic.firehose_success_feedback_role_arn,
+        ),
+        (
+            "FirehoseSuccessFeedbackSampleRate",
+            &topic.firehose_success_feedback_sample_rate,
+        ),
+        (
+            "HTTPFailureFeedbackRoleArn",
+            &topic.http_failure_feedback_role_arn,
+        ),
+        (
+            "SQSFailureFeedbackRoleArn",
+            &topic.sqs_failure_feedback_role_arn,
+        ),
+        (
+            "SQSSuccessFeedbackRoleArn",
+            &topic.sqs_success_feedback_role_arn,
+        ),
+        (
+            "SQSSuccessFeedbackSampleRate",
+            &topic.sqs_success_feedback_sample_rate,
+        ),
+        (
+            "HTTPSuccessFeedbackRoleArn",
+            &topic.http_success_feedback_role_arn,
+        ),
+        (
+            "HTTPSuccessFeedbackSampleRate",
+            &topic.http_success_feedback_sample_rate,
+        ),
+        (
+            "ApplicationFailureFeedbackRoleArn",
+            &topic.application_failure_feedback_role_arn,
+        ),
+        (
+            "ApplicationSuccessFeedbackRoleArn",
+            &topic.application_success_feedback_role_arn,
+        ),
+        (
+            "ApplicationSuccessFeedbackSampleRate",
+            &topic.application_success_feedback_sample_rate,
+        ),
+        (
+            "LambdaFailureFeedbackRoleArn",
+            &topic.lambda_failure_feedback_role_arn,
+        ),
+        (
+            "LambdaSuccessFeedbackRoleArn",
+            &topic.lambda_success_feedback_role_arn,
+        ),
+        (
+            "LambdaSuccessFeedbackSampleRate",
+            &topic.lambda_success_feedback_sample_rate,
+        ),
+        ("KmsMasterKeyId", &topic.kms_master_key_id),
+        ("SignatureVersion", &topic.signature_version),
+        (
+            "ContentBasedDeduplication",
+            &topic.content_based_deduplication,
+        ),
+        ("FifoTopic", &topic.fifo_topic),
+        ("ArchivePolicy", &topic.archive_policy),
+        ("FifoThroughputScope", &topic.fifo_throughput_scope),
+    ];
+    attributes.extend(
+        optional.into_iter().filter_map(|(key, value)| {
+            value.as_ref().map(|value| (key.to_string(), value.clone()))
+        }),
+    );
+    attributes.extend([
+        (
+            "SubscriptionsConfirmed".to_string(),
+            topic.subscriptions_confirmed().to_string(),
+        ),
+        (
+            "SubscriptionsPending".to_string(),
+            topic.subscriptions_pending().to_string(),
+        ),
+        (
+            "SubscriptionsDeleted".to_string(),
+            topic.subscriptions_deleted.to_string(),
+        ),
+    ]);
+    if state.extended_attributes && !state.strict_aws {
+        let stats = state.existing_topic_stats(topic_name).snapshot();
+        attributes.extend([
+            (
+                "X-LocalSns-MessagesPublished".to_string(),
+                stats.published.to_string(),
+            ),
+            (
+                "X-LocalSns-MessagesDelivered".to_string(),
+                stats.delivered_ok.to_string(),
+            ),
+            (
+                "X-LocalSns-DeliveryFailures".to_string(),
+                stats.delivered_failed.to_string(),
+            ),
+            (
+                "X-LocalSns-MessagesFilteredOut".to_string(),
+                stats.filtered_out.to_string(),
+            ),
+        ]);
+        if let Some(last_published) = stats.last_published {
+            attributes.push((
+                "X-LocalSns-LastPublishTimestamp".to_string(),
+                state.clock.format(last_published),
+            ));
+        }
+    }
+
     let mut writer = Writer::new(Cursor::new(Vec::new()));
-    writer.create_element("GetTopicAttributesResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+    writer
+        .create_element("GetTopicAttributesResponse")
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
-            writer.create_element("GetTopicAttributesResult")
+            writer
+                .create_element("GetTopicAttributesResult")
                 .write_inner_content(|writer| {
-                    writer.create_element("Attributes")
+                    writer
+                        .create_element("Attributes")
                         .write_inner_content(|writer| {
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("TopicArn"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new(&topic.arn))?;
-                                    Ok(())
-                                })?;
-                            if let Some(display_name) = &topic.display_name {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("DisplayName"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(display_name))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            let policy = topic.policy.as_deref().unwrap_or_else(|| r#"{"Version":"2012-10-17","Id":"__default_policy_ID","Statement":[]}"#);
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("Policy"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new(policy))?;
-                                    Ok(())
-                                })?;
-                            if let Some(delivery_policy) = &topic.delivery_policy {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("DeliveryPolicy"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(delivery_policy))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(tracing_config) = &topic.tracing_config {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("TracingConfig"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(tracing_config))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(firehose_failure_feedback_role_arn) = &topic.firehose_failure_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("FirehoseFailureFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(firehose_failure_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(firehose_success_feedback_role_arn) = &topic.firehose_success_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("FirehoseSuccessFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(firehose_success_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            let firehose_success_feedback_sample_rate = topic.firehose_success_feedback_sample_rate.as_deref().unwrap_or("0");
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("FirehoseSuccessFeedbackSampleRate"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new(firehose_success_feedback_sample_rate))?;
-                                    Ok(())
-                                })?;
-                            if let Some(http_failure_feedback_role_arn) = &topic.http_failure_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("HTTPFailureFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(http_failure_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(sqs_failure_feedback_role_arn) = &topic.sqs_failure_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("SQSFailureFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(sqs_failure_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(sqs_success_feedback_role_arn) = &topic.sqs_success_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("SQSSuccessFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(sqs_success_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            let sqs_success_feedback_sample_rate = topic.sqs_success_feedback_sample_rate.as_deref().unwrap_or("0");
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("SQSSuccessFeedbackSampleRate"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new(sqs_success_feedback_sample_rate))?;
-                                    Ok(())
-                                })?;
-                            if let Some(http_success_feedback_role_arn) = &topic.http_success_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("HTTPSuccessFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(http_success_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            let http_success_feedback_sample_rate = topic.http_success_feedback_sample_rate.as_deref().unwrap_or("0");
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("HTTPSuccessFeedbackSampleRate"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new(http_success_feedback_sample_rate))?;
-                                    Ok(())
-                                })?;
-                            if let Some(application_failure_feedback_role_arn) = &topic.application_failure_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("ApplicationFailureFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(application_failure_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(application_success_feedback_role_arn) = &topic.application_success_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("ApplicationSuccessFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(application_success_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            let application_success_feedback_sample_rate = topic.application_success_feedback_sample_rate.as_deref().unwrap_or("0");
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("ApplicationSuccessFeedbackSampleRate"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new(application_success_feedback_sample_rate))?;
-                                    Ok(())
-                                })?;
-                            if let Some(lambda_failure_feedback_role_arn) = &topic.lambda_failure_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("LambdaFailureFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(lambda_failure_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(lambda_success_feedback_role_arn) = &topic.lambda_success_feedback_role_arn {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("LambdaSuccessFeedbackRoleArn"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(lambda_success_feedback_role_arn))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            let lambda_success_feedback_sample_rate = topic.lambda_success_feedback_sample_rate.as_deref().unwrap_or("0");
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("LambdaSuccessFeedbackSampleRate"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new(lambda_success_feedback_sample_rate))?;
-                                    Ok(())
-                                })?;
-                            if let Some(kms_master_key_id) = &topic.kms_master_key_id {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("KmsMasterKeyId"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(kms_master_key_id))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(signature_version) = &topic.signature_version {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("SignatureVersion"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(signature_version))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(content_based_deduplication) = &topic.content_based_deduplication {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("ContentBasedDeduplication"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(content_based_deduplication))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(fifo_topic) = &topic.fifo_topic {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("FifoTopic"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(fifo_topic))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(archive_policy) = &topic.archive_policy {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("ArchivePolicy"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(archive_policy))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            if let Some(fifo_throughput_scope) = &topic.fifo_throughput_scope {
-                                writer.create_element("entry")
-                                    .write_inner_content(|writer| {
-                                        writer.create_element("key").write_text_content(BytesText::new("FifoThroughputScope"))?;
-                                        writer.create_element("value").write_text_content(BytesText::new(fifo_throughput_scope))?;
-                                        Ok(())
-                                    })?;
-                            }
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("SubscriptionsConfirmed"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new(topic.subscriptions.len().to_string().as_str()))?;
-                                    Ok(())
-                                })?;
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("SubscriptionsPending"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new("0"))?;
-                                    Ok(())
-                                })?;
-                            writer.create_element("entry")
-                                .write_inner_content(|writer| {
-                                    writer.create_element("key").write_text_content(BytesText::new("SubscriptionsDeleted"))?;
-                                    writer.create_element("value").write_text_content(BytesText::new("0"))?;
-                                    Ok(())
-                                })?;
-                            Ok(())
+                            xml::write_attribute_entries(writer, &attributes)
                         })?;
                     Ok(())
                 })?;
-            writer.create_element("ResponseMetadata")
+            writer
+                .create_element("ResponseMetadata")
                 .write_inner_content(|writer| {
-                    writer.create_element("RequestId").write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                    writer
+                        .create_element("RequestId")
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
-        }).unwrap();
+        })
+        .unwrap();
 
     let xml_response = writer.into_inner().into_inner();
     Response::builder()
@@ -984,61 +1296,212 @@ pub async fn get_topic_attributes(
         .unwrap()
 }
 
+/// Applies the `Attributes` of a Subscribe call to the new subscription.
+fn set_subscribe_attributes(
+    state: &AppState,
+    subscription: &mut Subscription,
+    attributes: Vec<AttributeEntry>,
+) -> Result<(), SnsError> {
+    for attribute in attributes {
+        if is_forbidden_attribute(state, &attribute.key) || !is_xml_text(&attribute.value) {
+            return Err(SnsError::invalid_parameter("Attributes"));
+        }
+        if attribute.key == SEND_TEST_NOTIFICATION_ATTRIBUTE {
+            subscription.send_test_notification = match attribute.value.as_str() {
+                "true" => true,
+                "false" => false,
+                _ => return Err(SnsError::invalid_parameter("Attributes")),
+            };
+        } else if !subscription.set_attribute(&attribute.key, &attribute.value) {
+            return Err(SnsError::invalid_parameter("Attributes"));
+        }
+    }
+    Ok(())
+}
+
 pub async fn subscribe(State(state): State<SharedState>, params: SnsRequest) -> Response {
     let topic_arn = if let Some(topic_arn) = params.topic_arn {
         topic_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Topic ARN",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("TopicArn").into_response();
     };
 
-    let topic_name = topic_arn.split(':').last().unwrap_or_default();
+    let topic_name = topic_name_from_arn(&topic_arn);
+    if let Err(e) = check_arn_scope(&state, &topic_arn, "TopicArn") {
+        return e.into_response();
+    }
 
     let endpoint = if let Some(endpoint) = params.endpoint {
         endpoint
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing endpoint",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("Endpoint").into_response();
     };
 
     let protocol = if let Some(protocol) = params.protocol {
         protocol
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing protocol",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("Protocol").into_response();
     };
 
-    let subscription_arn = format!("{}:{}", topic_arn, Uuid::new_v4());
+    if let Err(message) = validate_subscription(&protocol, &endpoint) {
+        return SnsError::invalid_parameter_message(message).into_response();
+    }
+    if state.strict_aws && matches!(protocol.as_str(), "memory" | "demux") {
+        return SnsError::invalid_parameter("Protocol").into_response();
+    }
 
-    let subscription = Subscription {
-        endpoint,
-        protocol,
-        arn: topic_arn.clone(),
-        subscription_arn: subscription_arn.clone(),
-    };
+    match state.validate_endpoints_on_subscribe {
+        Some(EndpointCheck::Strict) => {
+            if let Err(e) = dependencies::probe_subscription_endpoint(&protocol, &endpoint).await {
+                return SnsError::invalid_parameter_message(unreachable_endpoint(&e))
+                    .into_response();
+            }
+        }
+        // Probed in the background so the Subscribe is not held up.
+        Some(EndpointCheck::Warn) => {
+            let (protocol, endpoint) = (protocol.clone(), endpoint.clone());
+            tokio::spawn(
+                async move {
+                    if let Err(e) =
+                        dependencies::probe_subscription_endpoint(&protocol, &endpoint).await
+                    {
+                        tracing::warn!("Subscribed endpoint {} is not reachable: {}", endpoint, e);
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+        None => {}
+    }
 
-    if let Some(mut topic) = state.topics.get_mut(topic_name) {
-        topic.subscriptions.push(subscription);
+    let mut subscription = Subscription::new(&topic_arn, protocol, endpoint);
+    subscription.visible_at = state.visible_at();
+    if let Some(attributes_entry) = params.attributes_entry
+        && let Err(e) = set_subscribe_attributes(&state, &mut subscription, attributes_entry)
+    {
+        return e.into_response();
+    }
+    if state.confirm_email_subscriptions
+        && matches!(subscription.protocol.as_str(), "email" | "email-json")
+    {
+        subscription.pending_confirmation = true;
+        subscription.confirmation_token = Some(delivery::new_confirmation_token());
+        subscription.confirmation_issued_at = Some(chrono::Utc::now());
+    }
+    if let Err(e) = state.check_new_subscription(&subscription) {
+        return e.into_response();
+    }
+    let mut added = None;
+    let subscription_arn = if let Some(mut topic) = state
+        .topics
+        .get_mut(topic_name)
+        .filter(|topic| topic.is_visible())
+    {
+        topic.touch();
+        if !topic.accepts_protocol(&subscription.protocol) {
+            drop(topic);
+            return SnsError::invalid_parameter_message(FIFO_TOPIC_PROTOCOL_NOT_SUPPORTED)
+                .into_response();
+        }
+        // Subscribing an existing protocol and endpoint again returns the
+        // existing subscription, as AWS does when the attributes agree.
+        // SQS endpoints match by queue, so a queue subscribed by URL is
+        // found again by ARN; with different attributes that is taken
+        // as deliberate and subscribes again, since the endpoints differ.
+        let same_queue = |s: &Subscription| {
+            s.protocol == "sqs"
+                && subscription.protocol == "sqs"
+                && state.sqs_queue_urls.canonical(&s.endpoint)
+                    == state.sqs_queue_urls.canonical(&subscription.endpoint)
+        };
+        match topic.subscriptions.iter().find(|s| {
+            s.protocol == subscription.protocol && s.endpoint == subscription.endpoint
+                || same_queue(s)
+        }) {
+            Some(existing)
+                if existing.explicit_attributes() == subscription.explicit_attributes() =>
+            {
+                state
+                    .suspicious
+                    .duplicate_subscriptions
+                    .fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    "Subscribe matched existing subscription {} ({}:{})",
+                    existing.subscription_arn,
+                    existing.protocol,
+                    existing.endpoint
+                );
+                existing.subscription_arn.clone()
+            }
+            Some(existing) if existing.endpoint != subscription.endpoint => {
+                if let Err(e) = state.check_subscription_quota(&topic) {
+                    drop(topic);
+                    return e.into_response();
+                }
+                if state.inherit_tags {
+                    subscription.tags = topic.tags.clone();
+                }
+                let subscription_arn = subscription.subscription_arn.clone();
+                console::subscription_added(&subscription);
+                added = Some(subscription.clone());
+                topic.subscriptions.push(subscription);
+                subscription_arn
+            }
+            Some(_) => {
+                drop(topic);
+                return SnsError::invalid_parameter_message(
+                    SUBSCRIPTION_EXISTS_WITH_DIFFERENT_ATTRIBUTES,
+                )
+                .into_response();
+            }
+            None => {
+                if let Err(e) = state.check_subscription_quota(&topic) {
+                    drop(topic);
+                    return e.into_response();
+                }
+                if state.inherit_tags {
+                    subscription.tags = topic.tags.clone();
+                }
+                let subscription_arn = subscription.subscription_arn.clone();
+                console::subscription_added(&subscription);
+                added = Some(subscription.clone());
+                topic.subscriptions.push(subscription);
+                subscription_arn
+            }
+        }
     } else {
-        return error_response("NotFound", "Topic not found", StatusCode::NOT_FOUND).await;
+        return SnsError::not_found_topic().into_response();
+    };
+
+    // Pending ones get theirs once confirmed.
+    if let Some(added) = &added {
+        delivery::send_test_notification(&state, added);
+    }
+
+    // A pending subscription, new or subscribed again, gets a confirmation
+    // email, with a new token if its last one expired, and its ARN is only
+    // returned when asked for, as on AWS.
+    let subscription_arn = match state.renew_confirmation_token(&subscription_arn) {
+        Some((pending, token)) => {
+            delivery::send_subscription_confirmation(
+                &state,
+                &params.context.base_url,
+                &pending,
+                &token,
+            );
+            if params.return_subscription_arn.as_deref() == Some("true") {
+                subscription_arn
+            } else {
+                PENDING_CONFIRMATION_ARN.to_string()
+            }
+        }
+        None => subscription_arn,
     };
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("SubscribeResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("SubscribeResult")
@@ -1053,7 +1516,80 @@ pub async fn subscribe(State(state): State<SharedState>, params: SnsRequest) ->
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
+                    Ok(())
+                })?;
+            Ok(())
+        })
+        .unwrap();
+
+    let xml_response = writer.into_inner().into_inner();
+    Response::builder()
+        .header("Content-Type", "application/xml")
+        .body(axum::body::Body::from(xml_response))
+        .unwrap()
+}
+
+/// Confirms the subscription of `TopicArn` awaiting `Token`, the action a
+/// SubscribeURL invokes.
+pub async fn confirm_subscription(
+    State(state): State<SharedState>,
+    params: SnsRequest,
+) -> Response {
+    let Some(topic_arn) = params.topic_arn else {
+        return SnsError::missing_parameter("TopicArn").into_response();
+    };
+    let Some(token) = params.token else {
+        return SnsError::missing_parameter("Token").into_response();
+    };
+
+    let subscription_arn = match state.topics.get(topic_name_from_arn(&topic_arn)) {
+        Some(topic) => match topic
+            .subscriptions
+            .iter()
+            .find(|sub| sub.confirmation_token.as_deref() == Some(token.as_str()))
+        {
+            Some(sub) if !state.confirmation_token_valid(sub) => {
+                return SnsError::invalid_parameter("Token").into_response();
+            }
+            sub => sub.map(|sub| sub.subscription_arn.clone()),
+        },
+        None => return SnsError::not_found_topic().into_response(),
+    };
+    // A token no pending subscription awaits may be that of an
+    // UnsubscribeConfirmation, restoring the subscription it removed.
+    let subscription = match subscription_arn.and_then(|arn| state.confirm_subscription(&arn)) {
+        Some((subscription, was_pending)) => {
+            if was_pending {
+                delivery::send_test_notification(&state, &subscription);
+            }
+            subscription
+        }
+        None => match state.restore_unsubscribed(topic_name_from_arn(&topic_arn), &token) {
+            Ok(subscription) => subscription,
+            Err(e) => return e.into_response(),
+        },
+    };
+
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer
+        .create_element("ConfirmSubscriptionResponse")
+        .with_attribute(("xmlns", responses::xml_namespace()))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("ConfirmSubscriptionResult")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("SubscriptionArn")
+                        .write_text_content(BytesText::new(&subscription.subscription_arn))?;
+                    Ok(())
+                })?;
+            writer
+                .create_element("ResponseMetadata")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("RequestId")
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -1071,36 +1607,36 @@ pub async fn unsubscribe(State(state): State<SharedState>, params: SnsRequest) -
     let subscription_arn = if let Some(subscription_arn) = params.subscription_arn {
         subscription_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Subscription ARN",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("SubscriptionArn").into_response();
     };
 
-    let topic_arn = subscription_arn.rsplitn(2, ':').nth(1).unwrap_or_default();
-    let topic_name = topic_arn.split(':').last().unwrap_or_default();
+    let topic_name = match SubscriptionArn::parse(&subscription_arn) {
+        Ok(parsed) => parsed.topic_name,
+        Err(message) => return SnsError::invalid_parameter_message(message).into_response(),
+    };
 
-    if let Some(mut topic) = state.topics.get_mut(topic_name) {
-        topic
-            .subscriptions
-            .retain(|s| s.subscription_arn != subscription_arn);
-    } else {
-        return error_response("NotFound", "Topic not found", StatusCode::NOT_FOUND).await;
+    if !state.topics.contains_key(topic_name) {
+        return SnsError::not_found_subscription().into_response();
+    }
+    if let Some(subscription) = state.remove_subscription(&subscription_arn) {
+        delivery::spawn_unsubscribe_confirmations(
+            state.clone(),
+            params.context.base_url,
+            vec![subscription],
+        );
     }
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("UnsubscribeResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("ResponseMetadata")
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -1114,98 +1650,540 @@ pub async fn unsubscribe(State(state): State<SharedState>, params: SnsRequest) -
         .unwrap()
 }
 
+/// The fields of a Publish call, or of one PublishBatch entry.
+struct PublishInput {
+    message_id: String,
+    message: String,
+    subject: Option<String>,
+    message_group_id: Option<String>,
+    message_deduplication_id: Option<String>,
+    trace_header: Option<String>,
+    message_attributes: Vec<MessageAttributeEntry>,
+    message_structure: Option<String>,
+    base_url: String,
+    publisher: Publisher,
+}
+
+/// Response header summarizing failed synchronous deliveries.
+const DELIVERY_FAILURES_HEADER: &str = "x-localsns-delivery-failures";
+/// Response headers of Publish and PublishBatch telling load generators how
+/// backed up background delivery is: the fan-outs accepted but not
+/// finished, and how long to wait before publishing again.
+const BACKLOG_HEADER: &str = "x-localsns-backlog";
+const SUGGESTED_DELAY_HEADER: &str = "x-localsns-suggested-delay-ms";
+
+fn backpressure_headers(
+    state: &AppState,
+    response: axum::http::response::Builder,
+) -> axum::http::response::Builder {
+    let backlog = state.delivery_gauges.backlog();
+    response.header(BACKLOG_HEADER, backlog).header(
+        SUGGESTED_DELAY_HEADER,
+        state.delivery_gauges.suggested_delay_ms(backlog),
+    )
+}
+
+/// Delivers a publish inline when the caller asked for synchronous delivery,
+/// returning its failures; otherwise hands it to a background task.
+async fn deliver_publish(
+    state: &SharedState,
+    sync: bool,
+    topic_name: &str,
+    subscriptions: Vec<Subscription>,
+    messages: Vec<Message>,
+) -> Vec<delivery::DeliveryFailure> {
+    for message in &messages {
+        state
+            .fan_outs
+            .start(&message.id, &topic_arn(topic_name), subscriptions.len());
+    }
+    if sync {
+        delivery::fan_out(state, topic_name, &subscriptions, &messages).await
+    } else {
+        delivery::spawn_fan_out(
+            state.clone(),
+            topic_name.to_string(),
+            subscriptions,
+            messages,
+        );
+        Vec::new()
+    }
+}
+
+/// The JSON summary of failed synchronous deliveries for the response
+/// header, or with `--strict-sync` the InternalFailure error to return
+/// instead.
+fn delivery_failures_header(
+    state: &AppState,
+    failures: &[delivery::DeliveryFailure],
+) -> Result<Option<HeaderValue>, SnsError> {
+    if failures.is_empty() {
+        return Ok(None);
+    }
+    let summary: String = serde_json::to_string(failures)
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii() && !c.is_ascii_control())
+        .collect();
+    if state.strict_sync {
+        return Err(SnsError::internal_failure(delivery_failed(&summary)));
+    }
+    Ok(HeaderValue::from_str(&summary).ok())
+}
+
+/// Moves the body of a publish larger than the offload threshold to the
+/// offload store, leaving the extended client pointer in its place. Without
+/// a store the body stays, to be checked against `MAX_MESSAGE_SIZE`; a
+/// failed upload rejects the publish as too long.
+#[cfg_attr(
+    not(feature = "payload-offload"),
+    allow(unused_variables, clippy::ptr_arg)
+)]
+async fn offload_payload(
+    state: &AppState,
+    message: &mut String,
+    attributes: &mut Vec<MessageAttributeEntry>,
+) -> Result<(), PublishError> {
+    #[cfg(feature = "payload-offload")]
+    if let Some(endpoint) = &state.payload_offload_endpoint
+        && offload::payload_size(message, attributes) > state.payload_offload_threshold
+    {
+        let (pointer, attribute) =
+            offload::offload(state, endpoint, message)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to offload payload to {}: {}", endpoint, e);
+                    PublishError::TooLong
+                })?;
+        *message = pointer;
+        attributes.push(attribute);
+    }
+    Ok(())
+}
+
+/// Why a publish to `topic` would reach no subscription, if it would not.
+fn unrouted_reason(state: &AppState, topic: &Topic) -> Option<&'static str> {
+    if topic.subscriptions.is_empty() {
+        return Some("the topic has no subscriptions");
+    }
+    let routed = topic
+        .subscriptions
+        .iter()
+        .any(|s| !s.pending_confirmation && !state.is_subscription_disabled(&s.subscription_arn));
+    (!routed).then_some("every subscription is pending confirmation or disabled")
+}
+
+/// Flags `count` published messages that reach no subscription, which
+/// usually means a fixture forgot to subscribe.
+fn warn_unrouted(state: &AppState, topic_name: &str, reason: &str, count: usize) {
+    state
+        .suspicious
+        .publishes_unrouted
+        .fetch_add(count as u64, Ordering::Relaxed);
+    tracing::warn!(
+        "Publish to topic {} reaches no subscription: {}",
+        topic_name,
+        reason
+    );
+}
+
+/// Flags a publish that will reach the same endpoint through more than one
+/// subscription, which normally means a fixture subscribed twice.
+fn warn_on_duplicate_fanout(state: &AppState, topic_name: &str, subscriptions: &[Subscription]) {
+    let mut seen = HashSet::new();
+    let duplicates: Vec<&Subscription> = subscriptions
+        .iter()
+        .filter(|s| !seen.insert((s.protocol.as_str(), s.endpoint.as_str())))
+        .collect();
+    if duplicates.is_empty() {
+        return;
+    }
+    state
+        .suspicious
+        .duplicate_fanouts
+        .fetch_add(1, Ordering::Relaxed);
+    for duplicate in duplicates {
+        tracing::warn!(
+            "Topic {} delivers to {}:{} through more than one subscription",
+            topic_name,
+            duplicate.protocol,
+            duplicate.endpoint
+        );
+    }
+}
+
+/// Why a publish, or one PublishBatch entry, was rejected. Publish turns it
+/// into an ErrorResponse and PublishBatch into a `Failed` entry, both with
+/// code InvalidParameter.
+#[derive(Debug, Clone, PartialEq)]
+enum PublishError {
+    /// A required parameter was not given.
+    Missing(&'static str),
+    /// `field` holds a value SNS rejects; `reason` refines the message.
+    Invalid {
+        field: &'static str,
+        reason: Option<&'static str>,
+    },
+    /// Message attribute validation failed with this message.
+    MessageAttributes(String),
+    /// Message and attributes exceed `MAX_MESSAGE_SIZE`.
+    TooLong,
+}
+
+impl PublishError {
+    /// The parameter at fault.
+    fn field(&self) -> &'static str {
+        match self {
+            PublishError::Missing(field) | PublishError::Invalid { field, .. } => field,
+            PublishError::MessageAttributes(_) => "MessageAttributes",
+            PublishError::TooLong => "Message",
+        }
+    }
+
+    /// The message AWS returns for this error.
+    fn message(&self) -> String {
+        match self {
+            PublishError::Missing(field) => missing_parameter(field),
+            PublishError::Invalid {
+                field,
+                reason: None,
+            } => invalid_parameter(field),
+            PublishError::Invalid {
+                field,
+                reason: Some(reason),
+            } => invalid_parameter(&format!("{}{}", reason_prefix(field), reason)),
+            PublishError::MessageAttributes(message) => message.clone(),
+            PublishError::TooLong => MESSAGE_TOO_LONG.to_string(),
+        }
+    }
+}
+
+/// What precedes the reason in AWS messages about `field`.
+fn reason_prefix(field: &str) -> &'static str {
+    match field {
+        "MessageStructure" => "Message Structure - ",
+        _ => "",
+    }
+}
+
+/// Checks a MessageStructure=json body: a JSON object holding a string
+/// message per protocol, with at least a `default` one.
+fn validate_message_structure(message_structure: &str, body: &str) -> Result<(), PublishError> {
+    let invalid = |reason| PublishError::Invalid {
+        field: "MessageStructure",
+        reason,
+    };
+    if message_structure != "json" {
+        return Err(invalid(None));
+    }
+    let Ok(serde_json::Value::Object(bodies)) = serde_json::from_str(body) else {
+        return Err(invalid(Some("JSON message body failed to parse")));
+    };
+    if !bodies.contains_key("default") {
+        return Err(invalid(Some("No default entry in JSON message body")));
+    }
+    if bodies.values().any(|body| !body.is_string()) {
+        return Err(invalid(Some("JSON message body values must be strings")));
+    }
+    Ok(())
+}
+
+/// A valid publish: a new message, or a FIFO publish repeating the
+/// MessageDeduplicationId of one inside the deduplication window, which
+/// answers with the original MessageId and SequenceNumber and is not
+/// delivered again.
+enum Prepared {
+    Message(Box<Message>),
+    Duplicate {
+        message_id: String,
+        sequence_number: String,
+    },
+}
+
+impl Prepared {
+    fn message_id(&self) -> &str {
+        match self {
+            Prepared::Message(message) => &message.id,
+            Prepared::Duplicate { message_id, .. } => message_id,
+        }
+    }
+
+    fn sequence_number(&self) -> Option<&str> {
+        match self {
+            Prepared::Message(message) => message.sequence_number.as_deref(),
+            Prepared::Duplicate {
+                sequence_number, ..
+            } => Some(sequence_number),
+        }
+    }
+}
+
+/// Longest MessageGroupId or MessageDeduplicationId AWS accepts.
+const MAX_FIFO_ID_LENGTH: usize = 128;
+
+/// Whether `id` is a valid MessageGroupId or MessageDeduplicationId: 1 to
+/// 128 ASCII letters, digits and punctuation, so no whitespace.
+fn is_valid_fifo_id(id: &str) -> bool {
+    (1..=MAX_FIFO_ID_LENGTH).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b.is_ascii_punctuation())
+}
+
+/// Validates a publish against its topic without changing it, and builds
+/// the message to store. Checks run in a fixed order (Subject,
+/// MessageStructure, message attributes, size, FIFO parameters) so the
+/// reported error does not depend on the topic's state.
+///
+/// Like AWS, a Subject may not be empty or contain line breaks or other
+/// control characters, and MessageGroupId and MessageDeduplicationId are
+/// rejected on standard topics.
+fn validate_publish(
+    topic: &Topic,
+    defaults: &BTreeMap<String, String>,
+    input: PublishInput,
+) -> Result<Message, PublishError> {
+    let mut message = Message {
+        id: input.message_id,
+        subject: input.subject,
+        body: input.message,
+        timestamp: chrono::Utc::now(),
+        message_group_id: None,
+        message_deduplication_id: None,
+        sequence_number: None,
+        trace_header: input.trace_header,
+        message_attributes: BTreeMap::new(),
+        base_url: Some(input.base_url),
+        message_structure: None,
+        publisher: Some(input.publisher),
+
+        synthetic: false,
+    };
+
+    if message.subject.as_deref().is_some_and(|subject| {
+        subject.is_empty() || subject.chars().any(char::is_control) || !is_xml_text(subject)
+    }) {
+        return Err(PublishError::Invalid {
+            field: "Subject",
+            reason: None,
+        });
+    }
+
+    if let Some(message_structure) = input.message_structure {
+        validate_message_structure(&message_structure, &message.body)?;
+        message.message_structure = Some(message_structure);
+    }
+
+    // SQS drops deliveries carrying more attributes than it accepts, so the
+    // cap applies wherever a message can reach a queue.
+    let attribute_limit = (topic.is_fifo()
+        || topic.subscriptions.iter().any(|s| s.protocol == "sqs"))
+    .then_some(MAX_MESSAGE_ATTRIBUTES);
+    let (message_attributes, attributes_size) =
+        validate_message_attributes(input.message_attributes, attribute_limit)
+            .map_err(PublishError::MessageAttributes)?;
+    if message.body.len() + attributes_size > MAX_MESSAGE_SIZE {
+        return Err(PublishError::TooLong);
+    }
+    message.message_attributes = message_attributes;
+    // The X-Amzn-Trace-Id header wins over the reserved attribute.
+    if let Some(trace_header) = message
+        .message_attributes
+        .remove(TRACE_HEADER_MESSAGE_ATTRIBUTE)
+        && message.trace_header.is_none()
+    {
+        message.trace_header = trace_header.string_value;
+    }
+
+    if !topic.is_fifo() {
+        if input.message_group_id.is_some() {
+            return Err(PublishError::Invalid {
+                field: "MessageGroupId",
+                reason: Some(
+                    "The request includes MessageGroupId parameter that is not valid for this topic type",
+                ),
+            });
+        }
+        if input.message_deduplication_id.is_some() {
+            return Err(PublishError::Invalid {
+                field: "MessageDeduplicationId",
+                reason: Some(
+                    "The request includes MessageDeduplicationId parameter that is not valid for this topic type",
+                ),
+            });
+        }
+    } else {
+        let Some(message_group_id) = input.message_group_id else {
+            return Err(PublishError::Invalid {
+                field: "MessageGroupId",
+                reason: Some("The MessageGroupId parameter is required for FIFO topics"),
+            });
+        };
+        if !is_valid_fifo_id(&message_group_id) {
+            return Err(PublishError::Invalid {
+                field: "MessageGroupId",
+                reason: Some(
+                    "MessageGroupId can only include alphanumeric and punctuation characters. 1 to 128 in length.",
+                ),
+            });
+        }
+        let message_deduplication_id = match input.message_deduplication_id {
+            Some(id) if !is_valid_fifo_id(&id) => {
+                return Err(PublishError::Invalid {
+                    field: "MessageDeduplicationId",
+                    reason: Some(
+                        "MessageDeduplicationId can only include alphanumeric and punctuation characters. 1 to 128 in length.",
+                    ),
+                });
+            }
+            Some(id) => id,
+            None if topic.attribute("ContentBasedDeduplication", defaults) == Some("true") => {
+                hex::encode(Sha256::digest(message.body.as_bytes()))
+            }
+            None => {
+                return Err(PublishError::Invalid {
+                    field: "MessageDeduplicationId",
+                    reason: Some(
+                        "The topic should either have ContentBasedDeduplication enabled or MessageDeduplicationId provided explicitly",
+                    ),
+                });
+            }
+        };
+        message.message_group_id = Some(message_group_id);
+        message.message_deduplication_id = Some(message_deduplication_id);
+    }
+
+    Ok(message)
+}
+
+/// Validates a publish against its topic and builds the stored message,
+/// allocating a SequenceNumber on FIFO topics.
+fn prepare_message(
+    topic: &mut Topic,
+    defaults: &BTreeMap<String, String>,
+    input: PublishInput,
+) -> Result<Prepared, PublishError> {
+    let mut message = validate_publish(topic, defaults, input)?;
+    if let Some(message_deduplication_id) = &message.message_deduplication_id {
+        let message_group_id = message.message_group_id.as_deref().unwrap_or_default();
+        let key = topic.deduplication_key(defaults, message_group_id, message_deduplication_id);
+        if let Some(original) = topic.find_duplicate(&key) {
+            return Ok(Prepared::Duplicate {
+                message_id: original.message_id.clone(),
+                sequence_number: original.sequence_number.clone(),
+            });
+        }
+        let sequence_number = topic.next_sequence_number(message_group_id, &message.id);
+        topic.deduplication.insert(
+            key,
+            DeduplicatedPublish {
+                message_id: message.id.clone(),
+                sequence_number: sequence_number.clone(),
+                published_at: message.timestamp,
+            },
+        );
+        message.sequence_number = Some(sequence_number);
+    }
+
+    Ok(Prepared::Message(Box::new(message)))
+}
+
 pub async fn publish(State(state): State<SharedState>, params: SnsRequest) -> Response {
     let topic_arn = if let Some(topic_arn) = params.topic_arn {
         topic_arn
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing Topic ARN",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("TopicArn").into_response();
     };
 
-    let topic_name = topic_arn.split(':').last().unwrap_or_default();
+    let topic_name = topic_name_from_arn(&topic_arn);
+    if let Err(e) = check_arn_scope(&state, &topic_arn, "TopicArn") {
+        return e.into_response();
+    }
 
     let message_body = if let Some(message) = params.message {
         message
     } else {
-        return error_response(
-            "InvalidParameter",
-            "Missing message",
-            StatusCode::BAD_REQUEST,
-        )
-        .await;
+        return SnsError::missing_parameter("Message").into_response();
     };
 
-    let message_id = Uuid::new_v4().to_string();
-    let message = Message {
-        id: message_id.clone(),
+    let mut input = PublishInput {
+        message_id: state.new_message_id(),
+        message: message_body,
         subject: params.subject,
-        body: message_body.clone(),
-        timestamp: chrono::Utc::now(),
+        message_group_id: params.message_group_id,
+        message_deduplication_id: params.message_deduplication_id,
+        trace_header: params.context.trace_header,
+        message_attributes: params.message_attributes.unwrap_or_default(),
+        message_structure: params.message_structure,
+        base_url: params.context.base_url,
+        publisher: params.context.publisher,
     };
+    if let Err(error) =
+        offload_payload(&state, &mut input.message, &mut input.message_attributes).await
+    {
+        return SnsError::invalid_parameter_message(error.message()).into_response();
+    }
 
-    if let Some(topic) = state.topics.get(topic_name) {
-        for subscription in &topic.subscriptions {
-            if subscription.protocol == "sqs" {
-                let queue_url = subscription.endpoint.clone();
-                let endpoint_url = if let Ok(url) = Url::parse(&queue_url) {
-                    format!(
-                        "{}://{}:{}",
-                        url.scheme(),
-                        url.host_str().unwrap_or_default(),
-                        url.port().unwrap_or(4566)
-                    )
-                } else {
-                    "http://localhost:4566".to_string()
-                };
-
-                let sqs_client = if let Some(client) = state.sqs_clients.get(&endpoint_url) {
-                    client.clone()
-                } else {
-                    let config = aws_config::defaults(BehaviorVersion::latest())
-                        .endpoint_url(endpoint_url.clone())
-                        .load()
-                        .await;
-                    let client = Arc::new(aws_sdk_sqs::Client::new(&config));
-                    state
-                        .sqs_clients
-                        .insert(endpoint_url.clone(), client.clone());
-                    client
-                };
-
-                match sqs_client
-                    .send_message()
-                    .queue_url(queue_url.clone())
-                    .message_body(&message_body)
-                    .send()
-                    .await
-                {
-                    Ok(_) => tracing::info!("Message sent to SQS queue: {}", queue_url),
-                    Err(e) => tracing::error!(
-                        "Failed to send message to SQS queue: {}, error: {}",
-                        queue_url,
-                        e
-                    ),
-                }
-            } else {
-                tracing::info!(
-                    "Sending message {:?} to endpoint {}",
-                    message,
-                    subscription.endpoint
-                );
+    // The message is recorded under the topic guard, so a racing DeleteTopic
+    // either removes it along with the topic's history or has already
+    // removed the topic and this publish fails with NotFound.
+    let prepared = state
+        .topics
+        .get_mut(topic_name)
+        .filter(|topic| topic.is_visible())
+        .map(|mut topic| {
+            topic.touch();
+            let unrouted = unrouted_reason(&state, &topic);
+            if let Some(reason) = unrouted
+                && state.fail_unrouted
+            {
+                warn_unrouted(&state, topic_name, reason, 1);
+                return Err(SnsError::unrouted_message(topic_name, reason));
+            }
+            let prepared = prepare_message(&mut topic, &state.topic_defaults, input)
+                .map_err(|error| SnsError::invalid_parameter_message(error.message()))?;
+            if let Prepared::Message(message) = &prepared {
+                state
+                    .topic_stats(topic_name)
+                    .record_publish(message.timestamp);
+                state.record_message(topic_name, Message::clone(message));
+                console::message_published(topic_name, &message.id);
             }
+            Ok((prepared, topic.subscriptions.clone(), unrouted))
+        });
+    let (prepared, subscriptions, unrouted) = match prepared {
+        Some(Ok(prepared)) => prepared,
+        Some(Err(error)) => return error.into_response(),
+        None => return SnsError::not_found_topic().into_response(),
+    };
+    let message_id = prepared.message_id().to_string();
+    let sequence_number = prepared.sequence_number().map(str::to_string);
+
+    let mut failures_header = None;
+    if let Prepared::Message(message) = prepared {
+        warn_on_duplicate_fanout(&state, topic_name, &subscriptions);
+        if let Some(reason) = unrouted {
+            warn_unrouted(&state, topic_name, reason, 1);
         }
-    } else {
-        return error_response("NotFound", "Topic does not exist", StatusCode::NOT_FOUND).await;
+
+        let failures = deliver_publish(
+            &state,
+            params.context.sync_delivery,
+            topic_name,
+            subscriptions,
+            vec![*message],
+        )
+        .await;
+        failures_header = match delivery_failures_header(&state, &failures) {
+            Ok(header) => header,
+            Err(error) => return error.into_response(),
+        };
     }
 
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     writer
         .create_element("PublishResponse")
-        .with_attribute(("xmlns", "https://sns.amazonaws.com/doc/2010-03-31/"))
+        .with_attribute(("xmlns", responses::xml_namespace()))
         .write_inner_content(|writer| {
             writer
                 .create_element("PublishResult")
@@ -1213,6 +2191,11 @@ pub async fn publish(State(state): State<SharedState>, params: SnsRequest) -> Re
                     writer
                         .create_element("MessageId")
                         .write_text_content(BytesText::new(&message_id))?;
+                    if let Some(sequence_number) = &sequence_number {
+                        writer
+                            .create_element("SequenceNumber")
+                            .write_text_content(BytesText::new(sequence_number))?;
+                    }
                     Ok(())
                 })?;
             writer
@@ -1220,7 +2203,7 @@ pub async fn publish(State(state): State<SharedState>, params: SnsRequest) -> Re
                 .write_inner_content(|writer| {
                     writer
                         .create_element("RequestId")
-                        .write_text_content(BytesText::new(&Uuid::new_v4().to_string()))?;
+                        .write_text_content(BytesText::new(&request_id::current()))?;
                     Ok(())
                 })?;
             Ok(())
@@ -1228,8 +2211,242 @@ pub async fn publish(State(state): State<SharedState>, params: SnsRequest) -> Re
         .unwrap();
 
     let xml_response = writer.into_inner().into_inner();
-    Response::builder()
-        .header("Content-Type", "application/xml")
-        .body(axum::body::Body::from(xml_response))
-        .unwrap()
+    let mut response = backpressure_headers(
+        &state,
+        Response::builder().header("Content-Type", "application/xml"),
+    );
+    if let Some(failures) = failures_header {
+        response = response.header(DELIVERY_FAILURES_HEADER, failures);
+    }
+    response.body(axum::body::Body::from(xml_response)).unwrap()
+}
+
+pub async fn publish_batch(State(state): State<SharedState>, params: SnsRequest) -> Response {
+    let topic_arn = if let Some(topic_arn) = params.topic_arn {
+        topic_arn
+    } else {
+        return SnsError::missing_parameter("TopicArn").into_response();
+    };
+
+    let topic_name = topic_name_from_arn(&topic_arn);
+    if let Err(e) = check_arn_scope(&state, &topic_arn, "TopicArn") {
+        return e.into_response();
+    }
+
+    let entries = match params.publish_batch_request_entries {
+        Some(entries) => entries,
+        None => {
+            return SnsError::empty_batch_request().into_response();
+        }
+    };
+    if entries.len() > MAX_BATCH_ENTRIES {
+        return SnsError::too_many_entries_in_batch_request().into_response();
+    }
+    let mut ids = HashSet::new();
+    if !entries.iter().all(|entry| ids.insert(entry.id.as_str())) {
+        return SnsError::batch_entry_ids_not_distinct().into_response();
+    }
+
+    let mut successful: Vec<(String, Prepared)> = vec![];
+    let mut failed: Vec<(String, PublishError)> = vec![];
+    // Offloading awaits the store, so it happens before the topic is locked.
+    let mut offloaded = Vec::with_capacity(entries.len());
+    for mut entry in entries {
+        if let Some(message) = &mut entry.message
+            && let Err(error) =
+                offload_payload(&state, message, &mut entry.message_attributes).await
+        {
+            failed.push((entry.id, error));
+            continue;
+        }
+        offloaded.push(entry);
+    }
+    let entries = offloaded;
+    let (subscriptions, unrouted) = if let Some(mut topic) = state
+        .topics
+        .get_mut(topic_name)
+        .filter(|topic| topic.is_visible())
+    {
+        topic.touch();
+        let unrouted = unrouted_reason(&state, &topic);
+        if let Some(reason) = unrouted
+            && state.fail_unrouted
+        {
+            drop(topic);
+            warn_unrouted(&state, topic_name, reason, entries.len());
+            return SnsError::unrouted_message(topic_name, reason).into_response();
+        }
+        for entry in entries {
+            let Some(message) = entry.message else {
+                failed.push((entry.id, PublishError::Missing("Message")));
+                continue;
+            };
+            let input = PublishInput {
+                message_id: state.new_message_id(),
+                message,
+                subject: entry.subject,
+                message_group_id: entry.message_group_id,
+                message_deduplication_id: entry.message_deduplication_id,
+                trace_header: params.context.trace_header.clone(),
+                message_attributes: entry.message_attributes,
+                message_structure: entry.message_structure,
+                base_url: params.context.base_url.clone(),
+                publisher: params.context.publisher.clone(),
+            };
+            match prepare_message(&mut topic, &state.topic_defaults, input) {
+                Ok(prepared) => {
+                    // Recorded under the topic guard, as in Publish.
+                    if let Prepared::Message(message) = &prepared {
+                        state
+                            .topic_stats(topic_name)
+                            .record_publish(message.timestamp);
+                        state.record_message(topic_name, Message::clone(message));
+                        console::message_published(topic_name, &message.id);
+                    }
+                    successful.push((entry.id, prepared));
+                }
+                Err(error) => {
+                    tracing::debug!(
+                        "PublishBatch entry {} rejected: {}",
+                        entry.id,
+                        error.field()
+                    );
+                    failed.push((entry.id, error));
+                }
+            }
+        }
+        (topic.subscriptions.clone(), unrouted)
+    } else {
+        return SnsError::not_found_topic().into_response();
+    };
+
+    let messages: Vec<Message> = successful
+        .iter()
+        .filter_map(|(_, prepared)| match prepared {
+            Prepared::Message(message) => Some(Message::clone(message)),
+            Prepared::Duplicate { .. } => None,
+        })
+        .collect();
+    let mut failures_header = None;
+    if !messages.is_empty() {
+        warn_on_duplicate_fanout(&state, topic_name, &subscriptions);
+        if let Some(reason) = unrouted {
+            warn_unrouted(&state, topic_name, reason, messages.len());
+        }
+        let failures = deliver_publish(
+            &state,
+            params.context.sync_delivery,
+            topic_name,
+            subscriptions,
+            messages,
+        )
+        .await;
+        failures_header = match delivery_failures_header(&state, &failures) {
+            Ok(header) => header,
+            Err(error) => return error.into_response(),
+        };
+    }
+
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer
+        .create_element("PublishBatchResponse")
+        .with_attribute(("xmlns", responses::xml_namespace()))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("PublishBatchResult")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("Successful")
+                        .write_inner_content(|writer| {
+                            xml::write_members(writer, &successful, |writer, (id, prepared)| {
+                                writer
+                                    .create_element("Id")
+                                    .write_text_content(BytesText::new(id))?;
+                                writer
+                                    .create_element("MessageId")
+                                    .write_text_content(BytesText::new(prepared.message_id()))?;
+                                if let Some(sequence_number) = prepared.sequence_number() {
+                                    writer
+                                        .create_element("SequenceNumber")
+                                        .write_text_content(BytesText::new(sequence_number))?;
+                                }
+                                Ok(())
+                            })
+                        })?;
+                    writer
+                        .create_element("Failed")
+                        .write_inner_content(|writer| {
+                            xml::write_members(writer, &failed, |writer, (id, error)| {
+                                writer
+                                    .create_element("Id")
+                                    .write_text_content(BytesText::new(id))?;
+                                writer
+                                    .create_element("Code")
+                                    .write_text_content(BytesText::new(INVALID_PARAMETER))?;
+                                writer
+                                    .create_element("Message")
+                                    .write_text_content(BytesText::new(&error.message()))?;
+                                writer
+                                    .create_element("SenderFault")
+                                    .write_text_content(BytesText::new("true"))?;
+                                Ok(())
+                            })
+                        })?;
+                    Ok(())
+                })?;
+            writer
+                .create_element("ResponseMetadata")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("RequestId")
+                        .write_text_content(BytesText::new(&request_id::current()))?;
+                    Ok(())
+                })?;
+            Ok(())
+        })
+        .unwrap();
+
+    let xml_response = writer.into_inner().into_inner();
+    let mut response = backpressure_headers(
+        &state,
+        Response::builder().header("Content-Type", "application/xml"),
+    );
+    if let Some(failures) = failures_header {
+        response = response.header(DELIVERY_FAILURES_HEADER, failures);
+    }
+    response.body(axum::body::Body::from(xml_response)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fifo_ids_are_1_to_128_ascii_letters_digits_and_punctuation() {
+        let longest = "a".repeat(MAX_FIFO_ID_LENGTH);
+        let too_long = "a".repeat(MAX_FIFO_ID_LENGTH + 1);
+        let punctuation: String = (b'!'..=b'~')
+            .filter(u8::is_ascii_punctuation)
+            .map(char::from)
+            .collect();
+        for (id, valid) in [
+            ("a", true),
+            ("Z9", true),
+            (longest.as_str(), true),
+            (punctuation.as_str(), true),
+            ("order-1_eu.v2:{x}", true),
+            ("", false),
+            (too_long.as_str(), false),
+            ("order 1", false),
+            ("order\t1", false),
+            ("order\n", false),
+            ("\0", false),
+            ("\u{7F}", false),
+            ("ordér", false),
+            ("注文", false),
+            ("order\u{A0}1", false),
+        ] {
+            assert_eq!(is_valid_fifo_id(id), valid, "{:?}", id);
+        }
+    }
 }
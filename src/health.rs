@@ -0,0 +1,78 @@
+use crate::dependencies::{self, DependencyStatus};
+use crate::state::SharedState;
+use crate::static_content::StaticContent;
+use crate::stats::DeliveryGaugesSnapshot;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct HealthQuery {
+    #[serde(default)]
+    verbose: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Health {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dependencies: Option<Vec<DependencyStatus>>,
+    delivery: DeliveryGaugesSnapshot,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    topics: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subscriptions: Option<usize>,
+}
+
+/// Liveness probe for load balancers and compose healthchecks. Answers GET
+/// and HEAD, and honors `If-None-Match` so pollers can revalidate cheaply.
+/// With `?verbose=true` the delivery endpoints are probed too, and any
+/// unreachable one turns the answer into 503 so a healthcheck can gate on
+/// downstream readiness; verbose answers also count topics and
+/// subscriptions. The delivery gauges are always reported, and a backlog
+/// above `--max-delivery-backlog` answers 503 as well.
+pub async fn health(
+    State(state): State<SharedState>,
+    Query(query): Query<HealthQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let dependencies = if query.verbose {
+        Some(dependencies::probe(&state).await)
+    } else {
+        None
+    };
+    let delivery = state.delivery_gauges.snapshot();
+    let backlogged = state
+        .max_delivery_backlog
+        .is_some_and(|max| delivery.backlog() > max);
+    let healthy = !backlogged
+        && dependencies
+            .iter()
+            .flatten()
+            .all(|dependency| dependency.reachable);
+    let (topics, subscriptions) = if query.verbose {
+        let subscriptions = state
+            .topics
+            .iter()
+            .map(|topic| topic.subscriptions.len())
+            .sum();
+        (Some(state.topics.len()), Some(subscriptions))
+    } else {
+        (None, None)
+    };
+    let health = Health {
+        status: if healthy { "ok" } else { "degraded" },
+        dependencies,
+        delivery,
+        topics,
+        subscriptions,
+    };
+    let body = serde_json::to_vec(&health).unwrap();
+    let response = StaticContent::new("application/json", body).respond(&headers);
+    if healthy {
+        response
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, response).into_response()
+    }
+}
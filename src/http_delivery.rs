@@ -0,0 +1,81 @@
+use crate::delivery::{DeliveryError, DeliveryErrorKind, HttpDelivery};
+use http_body_util::{BodyExt, Full};
+use hyper::Request;
+use hyper::body::Bytes;
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use std::time::Duration;
+
+/// The client HTTP/S deliveries are sent with. Clones share one connection
+/// pool.
+pub type HttpClient = Client<HttpsConnector<HttpConnector>, Full<Bytes>>;
+
+/// Builds the delivery client, trusting the system's root certificates.
+/// Without any, https endpoints fail to verify but http ones still work.
+pub fn client() -> HttpClient {
+    let builder = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .unwrap_or_else(|e| {
+            tracing::warn!(
+                "No root certificates for https deliveries, they will fail: {}",
+                e
+            );
+            HttpsConnectorBuilder::new().with_tls_config(
+                rustls::ClientConfig::builder()
+                    .with_root_certificates(rustls::RootCertStore::empty())
+                    .with_no_client_auth(),
+            )
+        });
+    let connector = builder.https_or_http().enable_http1().build();
+    Client::builder(TokioExecutor::new()).build(connector)
+}
+
+/// POSTs `delivery` once. Any answer but 2xx fails it; an endpoint that
+/// cannot be reached or does not answer within `timeout` fails it as
+/// `Network`, and one answering 429 as `Throttling`.
+pub async fn post(
+    client: &HttpClient,
+    delivery: &HttpDelivery,
+    timeout: Duration,
+) -> Result<(), DeliveryError> {
+    let mut request = Request::post(&delivery.endpoint);
+    for (name, value) in &delivery.headers {
+        request = request.header(*name, value);
+    }
+    for (name, value) in &delivery.custom_headers {
+        request = request.header(name, value);
+    }
+    let request = request
+        .body(Full::new(Bytes::from(delivery.body.clone())))
+        .map_err(|e| format!("Invalid request to {}: {}", delivery.endpoint, e))?;
+
+    // The body is read to the end so the connection goes back to the pool.
+    let exchange = async {
+        let response = client.request(request).await?;
+        let status = response.status();
+        response.into_body().collect().await?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(status)
+    };
+    let (kind, message) = match tokio::time::timeout(timeout, exchange).await {
+        Ok(Ok(status)) if status.is_success() => return Ok(()),
+        Ok(Ok(status)) if status.as_u16() == 429 => (
+            DeliveryErrorKind::Throttling,
+            format!("{} answered {}", delivery.endpoint, status),
+        ),
+        Ok(Ok(status)) => (
+            DeliveryErrorKind::Other,
+            format!("{} answered {}", delivery.endpoint, status),
+        ),
+        Ok(Err(e)) => (
+            DeliveryErrorKind::Network,
+            format!("POST to {} failed: {}", delivery.endpoint, e),
+        ),
+        Err(_) => (
+            DeliveryErrorKind::Network,
+            format!("{} did not answer within {:?}", delivery.endpoint, timeout),
+        ),
+    };
+    Err(DeliveryError::new(kind, message))
+}
@@ -0,0 +1,100 @@
+use crate::state::AppState;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Profile key that applies to every action without a profile of its own.
+pub const ANY_ACTION: &str = "*";
+
+/// Artificial latency injected before an action is dispatched, for testing
+/// SDK timeout and retry settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LatencyProfile {
+    /// Fixed delay added to every request.
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// Random extra delay, drawn uniformly from the range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter_ms: Option<JitterRange>,
+    /// Requests that never get a response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hang: Option<HangPattern>,
+    /// Requests this profile has been applied to.
+    #[serde(default, skip_deserializing)]
+    pub requests: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JitterRange {
+    pub min: u64,
+    pub max: u64,
+}
+
+/// Hangs the first `requests` of every `out_of` consecutive requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HangPattern {
+    pub requests: u64,
+    pub out_of: u64,
+}
+
+impl LatencyProfile {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(jitter) = &self.jitter_ms
+            && jitter.min > jitter.max
+        {
+            return Err("jitter_ms.min must not exceed jitter_ms.max".to_string());
+        }
+        if let Some(hang) = &self.hang
+            && (hang.out_of == 0 || hang.requests > hang.out_of)
+        {
+            return Err("hang.requests must be between 0 and hang.out_of".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Parses a JSON object of profiles keyed by action name, as accepted by
+/// `LOCAL_SNS_LATENCY_PROFILES`.
+pub fn parse_profiles(json: &str) -> Result<HashMap<String, LatencyProfile>, String> {
+    let profiles: HashMap<String, LatencyProfile> =
+        serde_json::from_str(json).map_err(|e| e.to_string())?;
+    for (action, profile) in &profiles {
+        profile
+            .validate()
+            .map_err(|e| format!("{}: {}", action, e))?;
+    }
+    Ok(profiles)
+}
+
+/// Delays, or never completes, according to the profile for `action`.
+pub async fn apply(state: &AppState, action: &str) {
+    let (delay, hang) = {
+        let profile = match state.latency_profiles.get_mut(action) {
+            Some(profile) => Some(profile),
+            None => state.latency_profiles.get_mut(ANY_ACTION),
+        };
+        let Some(mut profile) = profile else {
+            return;
+        };
+        let position = profile.requests;
+        profile.requests += 1;
+
+        let jitter = profile.jitter_ms.as_ref().map_or(0, |jitter| {
+            rand::thread_rng().gen_range(jitter.min..=jitter.max)
+        });
+        let hang = profile
+            .hang
+            .as_ref()
+            .is_some_and(|hang| position % hang.out_of < hang.requests);
+        (Duration::from_millis(profile.delay_ms + jitter), hang)
+    };
+
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+    if hang {
+        tracing::info!("Latency profile hangs this {} request", action);
+        std::future::pending::<()>().await;
+    }
+}
@@ -0,0 +1,98 @@
+#[cfg(feature = "admin-api")]
+pub mod admin;
+pub mod audit;
+pub mod bench;
+#[cfg(feature = "admin-api")]
+pub mod bulk;
+pub mod client_cache;
+pub mod clock;
+pub mod config;
+pub mod console;
+pub mod delivery;
+pub mod delivery_policy;
+pub mod delivery_status;
+pub mod demux;
+pub mod dependencies;
+pub mod error;
+pub mod handlers;
+pub mod health;
+#[cfg(feature = "http-delivery")]
+pub mod http_delivery;
+pub mod latency;
+pub mod memory;
+pub mod multipart;
+#[cfg(feature = "payload-offload")]
+pub mod offload;
+pub mod pagination;
+pub mod platform;
+pub mod replay;
+pub mod request_id;
+pub mod responses;
+pub mod retention;
+pub mod scheduler;
+pub mod seed;
+pub mod snapshot;
+#[cfg(feature = "sqs-delivery")]
+pub mod sqs;
+pub mod state;
+pub mod state_file;
+pub mod static_content;
+pub mod stats;
+pub mod template;
+pub mod testing;
+pub mod tracker;
+#[cfg(feature = "ui")]
+pub mod ui;
+pub mod version;
+pub mod xml;
+
+use crate::handlers::{handle_aws_request, handle_get_request, handle_other_path};
+use crate::state::SharedState;
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::routing::get;
+use tower::ServiceExt;
+
+pub use crate::handlers::supported_actions;
+
+/// Builds the full HTTP surface: the SNS Query API on `/` (POST, or GET with
+/// a query string), a liveness probe on `/health`, build info on `/version`
+/// and, unless `--strict-aws`, the admin API under `/admin` and the browser
+/// UI under `/ui` (`--no-ui` drops the latter). The admin API and UI are
+/// only there when built with the `admin-api` and `ui` features.
+pub fn app(state: SharedState) -> Router {
+    #[cfg_attr(not(feature = "admin-api"), allow(unused_mut))]
+    let mut router = Router::new()
+        .route("/", get(handle_get_request).post(handle_aws_request))
+        .route("/health", get(health::health))
+        .route("/version", get(version::version));
+    // Under --strict-aws the admin API does not exist, so tests cannot
+    // come to depend on it.
+    #[cfg(feature = "admin-api")]
+    if !state.strict_aws {
+        router = router.nest("/admin", admin::router(state.clone()));
+        #[cfg(feature = "ui")]
+        if state.ui {
+            router = router.merge(ui::router());
+        }
+    }
+    router.fallback(handle_other_path).with_state(state)
+}
+
+/// Sends one form-encoded Query API call to the app, returning its status.
+pub async fn call(app: &Router, params: &[(&str, &str)]) -> Result<StatusCode, String> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    let request = Request::post("/")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(Body::from(body))
+        .map_err(|e| e.to_string())?;
+    let response = app
+        .clone()
+        .oneshot(request)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(response.status())
+}
@@ -1,34 +1,137 @@
-mod error;
-mod handlers;
-mod responses;
-mod state;
-
-use crate::handlers::handle_aws_request;
-use crate::state::AppState;
-use axum::Router;
-use axum::routing::post;
-use dashmap::DashMap;
+use local_sns_rs::app;
+use local_sns_rs::bench;
+use local_sns_rs::config::{Command, Config, EndpointCheck};
+use local_sns_rs::console::{self, PrettyLayer};
+use local_sns_rs::dependencies;
+use local_sns_rs::retention;
+use local_sns_rs::seed;
+use local_sns_rs::state::AppState;
+use local_sns_rs::state_file;
+use local_sns_rs::version;
+use std::io::IsTerminal;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing_subscriber;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Modules whose per-delivery log lines `--quiet` silences below WARN.
+const DELIVERY_LOG_TARGETS: [&str; 2] = ["local_sns_rs::delivery", "local_sns_rs::platform"];
+
+fn init_tracing(config: &Config, level: LevelFilter) {
+    let mut targets = Targets::new().with_default(level);
+    if config.quiet {
+        for target in DELIVERY_LOG_TARGETS {
+            targets = targets.with_target(target, LevelFilter::WARN);
+        }
+    }
+    // Piped or redirected output keeps the full log, which tools parse, as
+    // do the quieter levels the benchmark asks for.
+    if config.pretty && level >= LevelFilter::INFO && std::io::stdout().is_terminal() {
+        tracing_subscriber::registry()
+            .with(PrettyLayer::new())
+            .with(targets.with_target(console::EVENT_TARGET, LevelFilter::DEBUG))
+            .init();
+        return;
+    }
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer())
+        .with(targets)
+        .init();
+}
 
 #[tokio::main]
 async fn main() {
-    let shared_state = Arc::new(AppState {
-        topics: DashMap::new(),
-        sqs_clients: DashMap::new(),
-    });
+    let (config, command) = match Config::load() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            tracing_subscriber::fmt::init();
+            tracing::error!("Invalid configuration: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(Command::State(args)) = command {
+        init_tracing(&config, LevelFilter::WARN);
+        if let Err(e) = state_file::run(&config, args) {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Bench(args)) = command {
+        // Per-request logging would dominate the measurement.
+        init_tracing(&config, LevelFilter::WARN);
+        match bench::run(config, &args).await {
+            Ok(report) => report.print(args.json),
+            Err(e) => {
+                tracing::error!("Benchmark failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    init_tracing(&config, LevelFilter::INFO);
+    tracing::info!("Configuration: {:?}", config.redacted());
+
+    if let Some(dir) = &config.delivery_status_dir
+        && let Err(e) = std::fs::create_dir_all(dir)
+    {
+        tracing::error!("Failed to create {}: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+
+    let build = version::build_info(&config.instance_name(), config.strict_aws);
+    tracing::info!(
+        "local-sns-rs {} ({}) features [{}], {} actions",
+        build.version,
+        build.git_sha,
+        build.features.join(", "),
+        build.actions.len()
+    );
+
+    #[cfg(feature = "admin-api")]
+    if !config.strict_aws && config.admin_token.is_none() {
+        tracing::info!(
+            "Admin API is open to anyone who can reach /admin; set --admin-token to require a token"
+        );
+    }
+
+    let addr: SocketAddr = config.addr.parse().unwrap();
+    let seed = config.seed_config();
+    let check_endpoints = config.check_endpoints;
+    let audit_log = config.audit_log.is_some();
+    let shared_state = Arc::new(AppState::new(config));
+    if audit_log && shared_state.audit.is_none() {
+        std::process::exit(1);
+    }
+
+    if let Some(seed) = seed {
+        seed::apply(&shared_state, &seed, false);
+        seed::spawn_reloaders(shared_state.clone(), seed);
+    }
+
+    // After seeding, so the queues of seeded subscriptions are checked too.
+    if let Some(mode) = check_endpoints
+        && !dependencies::check(&shared_state).await
+        && mode == EndpointCheck::Strict
+    {
+        tracing::error!("Refusing to start with unreachable delivery endpoints");
+        std::process::exit(1);
+    }
 
-    tracing_subscriber::fmt::init();
+    retention::spawn_sweeper(shared_state.clone());
 
-    let app = Router::new()
-        .route("/", post(handle_aws_request))
-        .with_state(shared_state);
+    let app = app(shared_state);
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 9911));
     tracing::info!("listening on {}", addr);
+    console::listening(&addr.to_string());
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -0,0 +1,92 @@
+use crate::delivery;
+use crate::state::{AppState, MESSAGE_HISTORY_LIMIT, Message, Subscription};
+use crate::template::TemplateContext;
+use serde::Serialize;
+
+/// A message delivered to a `memory` subscription, whose endpoint names an
+/// in-process buffer. Lets tests receive SNS deliveries without running SQS.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedMessage {
+    pub message_id: String,
+    pub subscription_arn: String,
+    /// The notification envelope, or the bare message with
+    /// `RawMessageDelivery`; rendered instead when the subscription has a
+    /// message template.
+    pub body: String,
+    pub raw: bool,
+    /// When the message was published.
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// When the message reached the buffer.
+    pub received_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The delivered body: the subscription's message template rendered, or
+/// the usual body when it has none or rendering fails.
+fn body(state: &AppState, subscription: &Subscription, message: &Message) -> String {
+    let Some(template) = &subscription.message_template else {
+        return delivery::message_body(state, message, subscription);
+    };
+    let timestamp = state.clock.format(message.timestamp);
+    let context = TemplateContext {
+        subject: message.subject.as_deref(),
+        message: &message.body_for(&subscription.protocol),
+        message_id: &message.id,
+        topic_arn: &subscription.arn,
+        timestamp: &timestamp,
+    };
+    template.render(&context).unwrap_or_else(|e| {
+        tracing::warn!(
+            "Message template of {} failed for message {}, delivering it unrendered: {}",
+            subscription.subscription_arn,
+            message.id,
+            e
+        );
+        delivery::message_body(state, message, subscription)
+    })
+}
+
+/// Appends `message` to the buffer named by the subscription's endpoint,
+/// dropping the oldest entry once the buffer holds `MESSAGE_HISTORY_LIMIT`.
+pub fn deliver(state: &AppState, subscription: &Subscription, message: &Message) {
+    let received = ReceivedMessage {
+        message_id: message.id.clone(),
+        subscription_arn: subscription.subscription_arn.clone(),
+        body: body(state, subscription, message),
+        raw: subscription.raw_message_delivery,
+        timestamp: message.timestamp,
+        received_at: chrono::Utc::now(),
+    };
+    if state.sample_delivery_log() {
+        tracing::info!(
+            "Sending message {} to memory queue {}",
+            message.id,
+            subscription.endpoint
+        );
+    }
+    let mut queue = state
+        .memory_queues
+        .entry(subscription.endpoint.clone())
+        .or_default();
+    if queue.len() == MESSAGE_HISTORY_LIMIT {
+        queue.pop_front();
+    }
+    queue.push_back(received);
+}
+
+/// Messages in the named buffer, oldest first.
+pub fn received(state: &AppState, name: &str) -> Vec<ReceivedMessage> {
+    state
+        .memory_queues
+        .get(name)
+        .map(|queue| queue.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Empties the named buffer, returning how many messages it held.
+pub fn clear(state: &AppState, name: &str) -> usize {
+    state
+        .memory_queues
+        .remove(name)
+        .map(|(_, queue)| queue.len())
+        .unwrap_or(0)
+}
@@ -0,0 +1,191 @@
+use axum::http::HeaderMap;
+use axum::http::header::CONTENT_TYPE;
+
+/// The boundary of a `multipart/form-data` body, when `headers` declare
+/// one.
+pub fn boundary(headers: &HeaderMap) -> Option<String> {
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let mut parts = content_type.split(';');
+    if !parts
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+    parts.find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Re-encodes the fields of a `multipart/form-data` body as an
+/// `application/x-www-form-urlencoded` form, in their order, so it reaches
+/// the same parameter parsing as a form. File parts are read as text like
+/// any other field.
+pub fn to_form_urlencoded(body: &[u8], boundary: &str) -> Result<String, String> {
+    if boundary.is_empty() {
+        return Err("multipart body has an empty boundary".to_string());
+    }
+    let delimiter = format!("--{}", boundary);
+    let part_delimiter = format!("\r\n{}", delimiter);
+    let Some(start) = find(body, delimiter.as_bytes()) else {
+        return Err("multipart body has no boundary delimiter".to_string());
+    };
+    let mut rest = &body[start + delimiter.len()..];
+    let mut form = url::form_urlencoded::Serializer::new(String::new());
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(form.finish());
+        }
+        let Some(part) = rest.strip_prefix(b"\r\n") else {
+            return Err("multipart boundary delimiter is not followed by CRLF".to_string());
+        };
+        let Some(headers_end) = find(part, b"\r\n\r\n") else {
+            return Err("multipart part has no end of headers".to_string());
+        };
+        let headers = String::from_utf8_lossy(&part[..headers_end]);
+        let name = field_name(&headers)
+            .ok_or("multipart part has no Content-Disposition name".to_string())?;
+        let value = &part[headers_end + 4..];
+        let Some(value_end) = find(value, part_delimiter.as_bytes()) else {
+            return Err("multipart body has no closing boundary delimiter".to_string());
+        };
+        form.append_pair(&name, &String::from_utf8_lossy(&value[..value_end]));
+        rest = &value[value_end + part_delimiter.len()..];
+    }
+}
+
+/// The `name` of a part's `Content-Disposition: form-data` header.
+fn field_name(headers: &str) -> Option<String> {
+    headers.split("\r\n").find_map(|line| {
+        let (header, value) = line.split_once(':')?;
+        if !header.trim().eq_ignore_ascii_case("content-disposition") {
+            return None;
+        }
+        value.split(';').skip(1).find_map(|parameter| {
+            let (name, value) = parameter.split_once('=')?;
+            (name.trim() == "name").then(|| value.trim().trim_matches('"').to_string())
+        })
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(content_type: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+        headers
+    }
+
+    fn multipart(boundary: &str, parts: &[(&str, &str)]) -> Vec<u8> {
+        let mut body = String::new();
+        for (name, value) in parts {
+            body.push_str(&format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                boundary, name, value
+            ));
+        }
+        body.push_str(&format!("--{}--\r\n", boundary));
+        body.into_bytes()
+    }
+
+    #[test]
+    fn boundaries_come_from_multipart_content_types_only() {
+        let boundary = |content_type| super::boundary(&headers(content_type));
+        assert_eq!(
+            boundary("multipart/form-data; boundary=abc").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; BOUNDARY=\"a b\"").as_deref(),
+            Some("a b")
+        );
+        assert_eq!(boundary("multipart/form-data"), None);
+        assert_eq!(boundary("multipart/mixed; boundary=abc"), None);
+        assert_eq!(boundary("application/x-www-form-urlencoded"), None);
+        assert_eq!(super::boundary(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn fields_become_a_form_in_order() {
+        let body = multipart(
+            "xyz",
+            &[
+                ("Action", "Publish"),
+                ("Message", "a & b = c\r\nline two"),
+                ("Subject", "é"),
+                ("Empty", ""),
+            ],
+        );
+        assert_eq!(
+            to_form_urlencoded(&body, "xyz").unwrap(),
+            "Action=Publish&Message=a+%26+b+%3D+c%0D%0Aline+two&Subject=%C3%A9&Empty="
+        );
+        assert_eq!(
+            to_form_urlencoded(&multipart("xyz", &[]), "xyz").unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn file_parts_are_read_as_fields() {
+        let body = "--b\r\n\
+            Content-Disposition: form-data; name=\"Message\"; filename=\"m.json\"\r\n\
+            Content-Type: application/json\r\n\r\n\
+            {\"id\":1}\r\n--b--";
+        assert_eq!(
+            to_form_urlencoded(body.as_bytes(), "b").unwrap(),
+            "Message=%7B%22id%22%3A1%7D"
+        );
+    }
+
+    #[test]
+    fn malformed_bodies_are_refused() {
+        for (body, boundary, error) in [
+            ("--b--", "", "multipart body has an empty boundary"),
+            (
+                "no delimiter",
+                "b",
+                "multipart body has no boundary delimiter",
+            ),
+            (
+                "--bX",
+                "b",
+                "multipart boundary delimiter is not followed by CRLF",
+            ),
+            (
+                "--b\r\nContent-Disposition: form-data; name=\"a\"",
+                "b",
+                "multipart part has no end of headers",
+            ),
+            (
+                "--b\r\nContent-Type: text/plain\r\n\r\nx\r\n--b--",
+                "b",
+                "multipart part has no Content-Disposition name",
+            ),
+            (
+                "--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nx",
+                "b",
+                "multipart body has no closing boundary delimiter",
+            ),
+        ] {
+            assert_eq!(
+                to_form_urlencoded(body.as_bytes(), boundary),
+                Err(error.to_string()),
+                "{:?}",
+                body
+            );
+        }
+    }
+}
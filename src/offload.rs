@@ -0,0 +1,81 @@
+use crate::state::{AppState, MessageAttributeEntry};
+use aws_config::BehaviorVersion;
+use aws_sdk_s3::Client;
+use aws_sdk_s3::primitives::ByteStream;
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Message attribute the extended client libraries recognise an offloaded
+/// payload by, holding its size in bytes.
+pub const EXTENDED_PAYLOAD_SIZE_ATTRIBUTE: &str = "ExtendedPayloadSize";
+
+/// Class name the extended client libraries tag a payload pointer with.
+const S3_POINTER_CLASS: &str = "software.amazon.payloadoffloading.PayloadS3Pointer";
+
+/// Bytes a publish counts toward the offload threshold: its body and the
+/// name, data type and value of each message attribute, as the extended
+/// clients measure it.
+pub fn payload_size(body: &str, attributes: &[MessageAttributeEntry]) -> usize {
+    body.len()
+        + attributes
+            .iter()
+            .map(|attribute| {
+                attribute.name.len()
+                    + attribute.data_type.len()
+                    + attribute.string_value.as_ref().map_or(0, String::len)
+                    + attribute.binary_value.as_ref().map_or(0, String::len)
+            })
+            .sum::<usize>()
+}
+
+/// The S3 client for `endpoint`, created on first use and cached.
+async fn client(state: &AppState, endpoint: &str) -> Arc<Client> {
+    if let Some(client) = state.s3_clients.get(endpoint) {
+        return client.clone();
+    }
+    let config = aws_config::defaults(BehaviorVersion::latest())
+        .endpoint_url(endpoint)
+        .load()
+        .await;
+    // S3-compatible stores such as MinIO address buckets by path.
+    let config = aws_sdk_s3::config::Builder::from(&config)
+        .force_path_style(true)
+        .build();
+    let client = Arc::new(Client::from_conf(config));
+    state
+        .s3_clients
+        .insert(endpoint.to_string(), client.clone());
+    client
+}
+
+/// Stores `body` in the offload bucket and returns the pointer the message
+/// carries instead, together with the attribute that marks it.
+pub async fn offload(
+    state: &AppState,
+    endpoint: &str,
+    body: &str,
+) -> Result<(String, MessageAttributeEntry), String> {
+    let key = Uuid::new_v4().to_string();
+    client(state, endpoint)
+        .await
+        .put_object()
+        .bucket(&state.payload_offload_bucket)
+        .key(&key)
+        .body(ByteStream::from(body.as_bytes().to_vec()))
+        .send()
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    let pointer = json!([
+        S3_POINTER_CLASS,
+        { "s3BucketName": state.payload_offload_bucket, "s3Key": key },
+    ])
+    .to_string();
+    let attribute = MessageAttributeEntry {
+        name: EXTENDED_PAYLOAD_SIZE_ATTRIBUTE.to_string(),
+        data_type: "Number".to_string(),
+        string_value: Some(body.len().to_string()),
+        binary_value: None,
+    };
+    Ok((pointer, attribute))
+}
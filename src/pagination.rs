@@ -0,0 +1,106 @@
+use crate::error::SnsError;
+
+/// Page size of the AWS list actions, and the most `MaxItems` may ask for.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// The page size for a list call: the non-standard `MaxItems` parameter
+/// when given (1 to 100), so UIs can fetch small pages, else the AWS size.
+pub fn page_size(max_items: Option<&str>) -> Result<usize, SnsError> {
+    let Some(max_items) = max_items else {
+        return Ok(DEFAULT_PAGE_SIZE);
+    };
+    match max_items.parse::<usize>() {
+        Ok(size) if (1..=DEFAULT_PAGE_SIZE).contains(&size) => Ok(size),
+        _ => Err(SnsError::invalid_parameter("MaxItems")),
+    }
+}
+
+/// One page of a listing and the token of the next, if there is one.
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_token: Option<String>,
+}
+
+/// Cuts the page starting at `next_token` out of `items`, which must be in
+/// the same order on every call. Tokens carry the offset of the page, so
+/// they stay valid whatever page size the following calls use.
+pub fn paginate<T>(
+    items: Vec<T>,
+    next_token: Option<&str>,
+    page_size: usize,
+) -> Result<Page<T>, SnsError> {
+    let start = match next_token.filter(|token| !token.is_empty()) {
+        Some(token) => token
+            .parse::<usize>()
+            .ok()
+            .filter(|start| *start <= items.len())
+            .ok_or_else(|| SnsError::invalid_parameter("NextToken"))?,
+        None => 0,
+    };
+    let end = start.saturating_add(page_size).min(items.len());
+    let next_token = (end < items.len()).then(|| end.to_string());
+    Ok(Page {
+        items: items.into_iter().skip(start).take(end - start).collect(),
+        next_token,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_items_must_be_between_1_and_100() {
+        assert_eq!(page_size(None).unwrap(), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some("1")).unwrap(), 1);
+        assert_eq!(page_size(Some("100")).unwrap(), 100);
+        for max_items in ["0", "101", "-1", "", "ten"] {
+            assert!(page_size(Some(max_items)).is_err(), "{}", max_items);
+        }
+    }
+
+    #[test]
+    fn pages_of_one_walk_every_item() {
+        let items: Vec<_> = (0..5).collect();
+        let mut walked = vec![];
+        let mut next_token = None;
+        loop {
+            let page = paginate(items.clone(), next_token.as_deref(), 1).unwrap();
+            assert_eq!(page.items.len(), 1);
+            walked.extend(page.items);
+            next_token = page.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+        assert_eq!(walked, items);
+    }
+
+    #[test]
+    fn tokens_stay_valid_across_page_sizes() {
+        let items: Vec<_> = (0..5).collect();
+        let page = paginate(items.clone(), None, 2).unwrap();
+        assert_eq!(page.items, [0, 1]);
+        let page = paginate(items.clone(), page.next_token.as_deref(), 100).unwrap();
+        assert_eq!(page.items, [2, 3, 4]);
+        assert_eq!(page.next_token, None);
+
+        let page = paginate(items.clone(), None, 5).unwrap();
+        assert_eq!(page.next_token, None);
+        assert_eq!(paginate(items.clone(), Some(""), 2).unwrap().items, [0, 1]);
+        assert!(
+            paginate(items.clone(), Some("5"), 2)
+                .unwrap()
+                .items
+                .is_empty()
+        );
+        for token in ["6", "-1", "abc"] {
+            assert!(
+                paginate(items.clone(), Some(token), 2).is_err(),
+                "{}",
+                token
+            );
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::error::SnsError;
+use crate::request_id;
+use crate::responses;
+use crate::state::{AppState, MESSAGE_HISTORY_LIMIT, Message, SharedState, SnsRequest};
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use quick_xml::Writer;
+use quick_xml::events::BytesText;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Cursor;
+use uuid::Uuid;
+
+/// Push platforms SNS accepts for CreatePlatformApplication.
+pub const SUPPORTED_PLATFORMS: &[&str] =
+    &["ADM", "APNS", "APNS_SANDBOX", "BAIDU", "GCM", "MPNS", "WNS"];
+
+const PLATFORM_APPLICATION_NOT_FOUND: &str = "PlatformApplication does not exist";
+
+#[derive(Debug, Clone)]
+pub struct PlatformApplication {
+    pub arn: String,
+    pub name: String,
+    pub platform: String,
+    pub attributes: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlatformEndpoint {
+    pub arn: String,
+    pub application_arn: String,
+    pub platform: String,
+    pub token: String,
+    pub custom_user_data: Option<String>,
+    pub enabled: bool,
+}
+
+/// A push notification that would have been sent to a device.
+#[derive(Debug, Clone, Serialize)]
+pub struct PushDelivery {
+    pub platform: String,
+    pub message_id: String,
+    pub subscription_arn: String,
+    pub payload: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+pub fn platform_application_arn(platform: &str, name: &str) -> String {
+    format!(
+        "arn:aws:sns:us-east-1:000000000000:app/{}/{}",
+        platform, name
+    )
+}
+
+/// Delivers to an `application` subscription by recording the payload for
+/// the endpoint's platform in its push outbox.
+pub fn deliver(
+    state: &AppState,
+    subscription_arn: &str,
+    endpoint_arn: &str,
+    message: &Message,
+) -> Result<(), String> {
+    let Some(endpoint) = state.platform_endpoints.get(endpoint_arn) else {
+        return Err(format!("Platform endpoint {} does not exist", endpoint_arn));
+    };
+    if !endpoint.enabled {
+        return Err(format!("Platform endpoint {} is disabled", endpoint_arn));
+    }
+    let platform = endpoint.platform.clone();
+    drop(endpoint);
+
+    if state.sample_delivery_log() {
+        tracing::info!(
+            "Sending {} push message {} to {}",
+            platform,
+            message.id,
+            endpoint_arn
+        );
+    }
+    let mut outbox = state
+        .push_outbox
+        .entry(endpoint_arn.to_string())
+        .or_default();
+    if outbox.len() == MESSAGE_HISTORY_LIMIT {
+        outbox.pop_front();
+    }
+    outbox.push_back(PushDelivery {
+        payload: message.body_for(&platform),
+        platform,
+        message_id: message.id.clone(),
+        subscription_arn: subscription_arn.to_string(),
+        timestamp: message.timestamp,
+    });
+    Ok(())
+}
+
+pub async fn create_platform_application(
+    State(state): State<SharedState>,
+    params: SnsRequest,
+) -> Response {
+    let name = if let Some(name) = params.name {
+        name
+    } else {
+        return SnsError::missing_parameter("Name").into_response();
+    };
+
+    let platform = if let Some(platform) = params.platform {
+        platform
+    } else {
+        return SnsError::missing_parameter("Platform").into_response();
+    };
+
+    if !SUPPORTED_PLATFORMS.contains(&platform.as_str()) {
+        return SnsError::invalid_parameter("Platform").into_response();
+    }
+
+    let arn = platform_application_arn(&platform, &name);
+    let attributes = params
+        .attributes_entry
+        .unwrap_or_default()
+        .into_iter()
+        .map(|attribute| (attribute.key, attribute.value))
+        .collect();
+    state.platform_applications.insert(
+        arn.clone(),
+        PlatformApplication {
+            arn: arn.clone(),
+            name,
+            platform,
+            attributes,
+        },
+    );
+
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer
+        .create_element("CreatePlatformApplicationResponse")
+        .with_attribute(("xmlns", responses::xml_namespace()))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("CreatePlatformApplicationResult")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("PlatformApplicationArn")
+                        .write_text_content(BytesText::new(&arn))?;
+                    Ok(())
+                })?;
+            writer
+                .create_element("ResponseMetadata")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("RequestId")
+                        .write_text_content(BytesText::new(&request_id::current()))?;
+                    Ok(())
+                })?;
+            Ok(())
+        })
+        .unwrap();
+
+    let xml_response = writer.into_inner().into_inner();
+    Response::builder()
+        .header("Content-Type", "application/xml")
+        .body(axum::body::Body::from(xml_response))
+        .unwrap()
+}
+
+pub async fn create_platform_endpoint(
+    State(state): State<SharedState>,
+    params: SnsRequest,
+) -> Response {
+    let application_arn = if let Some(application_arn) = params.platform_application_arn {
+        application_arn
+    } else {
+        return SnsError::missing_parameter("PlatformApplicationArn").into_response();
+    };
+
+    let token = if let Some(token) = params.token {
+        token
+    } else {
+        return SnsError::missing_parameter("Token").into_response();
+    };
+
+    let (application_name, platform) =
+        if let Some(application) = state.platform_applications.get(&application_arn) {
+            (application.name.clone(), application.platform.clone())
+        } else {
+            return SnsError::not_found(PLATFORM_APPLICATION_NOT_FOUND).into_response();
+        };
+
+    // CreatePlatformEndpoint is idempotent per token.
+    let existing = state
+        .platform_endpoints
+        .iter()
+        .find(|endpoint| endpoint.application_arn == application_arn && endpoint.token == token)
+        .map(|endpoint| endpoint.arn.clone());
+    let arn = match existing {
+        Some(arn) => arn,
+        None => {
+            let arn = format!(
+                "arn:aws:sns:us-east-1:000000000000:endpoint/{}/{}/{}",
+                platform,
+                application_name,
+                Uuid::new_v4()
+            );
+            state.platform_endpoints.insert(
+                arn.clone(),
+                PlatformEndpoint {
+                    arn: arn.clone(),
+                    application_arn,
+                    platform,
+                    token,
+                    custom_user_data: params.custom_user_data,
+                    enabled: true,
+                },
+            );
+            arn
+        }
+    };
+
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer
+        .create_element("CreatePlatformEndpointResponse")
+        .with_attribute(("xmlns", responses::xml_namespace()))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("CreatePlatformEndpointResult")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("EndpointArn")
+                        .write_text_content(BytesText::new(&arn))?;
+                    Ok(())
+                })?;
+            writer
+                .create_element("ResponseMetadata")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("RequestId")
+                        .write_text_content(BytesText::new(&request_id::current()))?;
+                    Ok(())
+                })?;
+            Ok(())
+        })
+        .unwrap();
+
+    let xml_response = writer.into_inner().into_inner();
+    Response::builder()
+        .header("Content-Type", "application/xml")
+        .body(axum::body::Body::from(xml_response))
+        .unwrap()
+}
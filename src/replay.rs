@@ -0,0 +1,210 @@
+use crate::state::{AppState, MessageAttributeValue, topic_arn};
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// One publish as recorded in a topic's message history, in the form
+/// `POST /admin/replay` republishes it. Serialized one per line as NDJSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishRecord {
+    pub topic: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub message_attributes: BTreeMap<String, MessageAttributeValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_group_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_deduplication_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_structure: Option<String>,
+    /// When the message was originally published; replay with
+    /// `preserve_timing` keeps the gaps between records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// The publishes still in the topic's history, oldest first.
+pub fn records(state: &AppState, topic_name: &str) -> Vec<PublishRecord> {
+    state
+        .messages
+        .get(topic_name)
+        .map(|history| {
+            history
+                .iter()
+                .map(|message| PublishRecord {
+                    topic: topic_name.to_string(),
+                    message: message.body.clone(),
+                    subject: message.subject.clone(),
+                    message_attributes: message.message_attributes.clone(),
+                    message_group_id: message.message_group_id.clone(),
+                    message_deduplication_id: message.message_deduplication_id.clone(),
+                    message_structure: message.message_structure.clone(),
+                    timestamp: Some(message.timestamp),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn to_ndjson(records: &[PublishRecord]) -> String {
+    records
+        .iter()
+        .map(|record| serde_json::to_string(record).unwrap() + "\n")
+        .collect()
+}
+
+/// Quotes a value for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// A shell script of `aws sns publish` calls reproducing the records.
+/// `LOCAL_SNS_ENDPOINT` overrides `endpoint` when the script runs.
+pub fn to_script(records: &[PublishRecord], endpoint: &str) -> String {
+    let mut script = format!(
+        "#!/bin/sh\nset -e\nENDPOINT=\"${{LOCAL_SNS_ENDPOINT:-{}}}\"\n",
+        endpoint
+    );
+    for record in records {
+        let mut command = format!(
+            "aws --endpoint-url \"$ENDPOINT\" sns publish --topic-arn {} --message {}",
+            shell_quote(&topic_arn(&record.topic)),
+            shell_quote(&record.message)
+        );
+        let options = [
+            ("--subject", &record.subject),
+            ("--message-group-id", &record.message_group_id),
+            (
+                "--message-deduplication-id",
+                &record.message_deduplication_id,
+            ),
+            ("--message-structure", &record.message_structure),
+        ];
+        for (option, value) in options {
+            if let Some(value) = value {
+                command.push_str(&format!(" {} {}", option, shell_quote(value)));
+            }
+        }
+        if !record.message_attributes.is_empty() {
+            let attributes: BTreeMap<&str, serde_json::Value> = record
+                .message_attributes
+                .iter()
+                .map(|(name, attribute)| {
+                    let value_key = if attribute.binary_value.is_some() {
+                        "BinaryValue"
+                    } else {
+                        "StringValue"
+                    };
+                    (
+                        name.as_str(),
+                        serde_json::json!({
+                            "DataType": attribute.data_type,
+                            value_key: attribute.value(),
+                        }),
+                    )
+                })
+                .collect();
+            command.push_str(&format!(
+                " --message-attributes {}",
+                shell_quote(&serde_json::to_string(&attributes).unwrap())
+            ));
+        }
+        script.push_str(&command);
+        script.push('\n');
+    }
+    script
+}
+
+/// Query API parameters of the Publish call for a record.
+fn publish_params(record: &PublishRecord) -> Vec<(String, String)> {
+    let mut params = vec![
+        ("Action".to_string(), "Publish".to_string()),
+        ("TopicArn".to_string(), topic_arn(&record.topic)),
+        ("Message".to_string(), record.message.clone()),
+    ];
+    let optional = [
+        ("Subject", &record.subject),
+        ("MessageGroupId", &record.message_group_id),
+        ("MessageDeduplicationId", &record.message_deduplication_id),
+        ("MessageStructure", &record.message_structure),
+    ];
+    for (name, value) in optional {
+        if let Some(value) = value {
+            params.push((name.to_string(), value.clone()));
+        }
+    }
+    for (index, (name, attribute)) in record.message_attributes.iter().enumerate() {
+        let prefix = format!("MessageAttributes.entry.{}", index + 1);
+        params.push((format!("{}.Name", prefix), name.clone()));
+        params.push((
+            format!("{}.Value.DataType", prefix),
+            attribute.data_type.clone(),
+        ));
+        if let Some(value) = &attribute.string_value {
+            params.push((format!("{}.Value.StringValue", prefix), value.clone()));
+        }
+        if let Some(value) = &attribute.binary_value {
+            params.push((format!("{}.Value.BinaryValue", prefix), value.clone()));
+        }
+    }
+    params
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayFailure {
+    /// 1-based line of the NDJSON input.
+    pub line: usize,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReplayReport {
+    pub published: usize,
+    pub failed: Vec<ReplayFailure>,
+}
+
+/// Republishes NDJSON publish records through the Query API of `app`, so
+/// they take the normal publish path. With `preserve_timing` the gaps
+/// between the records' timestamps are slept.
+pub async fn replay(app: &Router, ndjson: &str, preserve_timing: bool) -> ReplayReport {
+    let mut report = ReplayReport::default();
+    let mut previous: Option<chrono::DateTime<chrono::Utc>> = None;
+    for (index, line) in ndjson.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fail = |error: String| ReplayFailure {
+            line: index + 1,
+            error,
+        };
+        let record: PublishRecord = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(e) => {
+                report.failed.push(fail(e.to_string()));
+                continue;
+            }
+        };
+        if preserve_timing && let Some(timestamp) = record.timestamp {
+            if let Some(gap) = previous.and_then(|previous| (timestamp - previous).to_std().ok()) {
+                tokio::time::sleep(gap.min(Duration::from_secs(3600))).await;
+            }
+            previous = Some(timestamp);
+        }
+        let params = publish_params(&record);
+        let params: Vec<(&str, &str)> = params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        match crate::call(app, &params).await {
+            Ok(status) if status.is_success() => report.published += 1,
+            Ok(status) => report
+                .failed
+                .push(fail(format!("Publish failed with {}", status))),
+            Err(e) => report.failed.push(fail(e)),
+        }
+    }
+    report
+}
@@ -0,0 +1,20 @@
+use std::future::Future;
+use uuid::Uuid;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Runs one API request with `request_id` as the RequestId of every
+/// response rendered while it is handled.
+pub async fn scope<F: Future>(request_id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, future).await
+}
+
+/// The RequestId of the request being handled; outside a request scope a
+/// fresh id is generated.
+pub fn current() -> String {
+    REQUEST_ID
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| Uuid::new_v4().to_string())
+}
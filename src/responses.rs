@@ -1,94 +1,36 @@
-// CreateTopic
-#[derive(Debug)]
-pub struct CreateTopicResponse {
-    pub create_topic_result: CreateTopicResult,
-    pub response_metadata: ResponseMetadata,
-}
+use std::sync::OnceLock;
 
-#[derive(Debug)]
-pub struct CreateTopicResult {
-    pub topic_arn: String,
-}
+/// XML namespace of every Query API response, errors included. AWS uses
+/// the `http` form throughout.
+pub const SNS_XML_NAMESPACE: &str = "http://sns.amazonaws.com/doc/2010-03-31/";
 
-#[derive(Debug)]
-pub struct ResponseMetadata {
-    pub request_id: String,
-}
+static XML_NAMESPACE: OnceLock<String> = OnceLock::new();
 
-// DeleteTopic
-#[derive(Debug)]
-pub struct DeleteTopicResponse {
-    pub response_metadata: ResponseMetadata,
+/// Overrides `SNS_XML_NAMESPACE` for the rest of the process. Responses are
+/// rendered without access to the state, so this is process-wide: the
+/// first override wins, and a later different one is ignored with a
+/// warning.
+pub fn set_xml_namespace(namespace: String) {
+    if let Err(namespace) = XML_NAMESPACE.set(namespace)
+        && Some(namespace.as_str()) != XML_NAMESPACE.get().map(String::as_str)
+    {
+        tracing::warn!(
+            "Ignoring XML namespace {}; responses already use {}",
+            namespace,
+            xml_namespace()
+        );
+    }
 }
 
-// ListTopics
-#[derive(Debug)]
-pub struct ListTopicsResponse {
-    pub list_topics_result: ListTopicsResult,
-    pub response_metadata: ResponseMetadata,
-}
-
-#[derive(Debug, Default)]
-pub struct ListTopicsResult {
-    pub topics: Topics,
-    pub next_token: Option<String>,
-}
-
-#[derive(Debug, Default)]
-pub struct Topics {
-    pub member: Vec<Member>,
+/// The `xmlns` of responses: `SNS_XML_NAMESPACE` unless overridden.
+pub fn xml_namespace() -> &'static str {
+    XML_NAMESPACE
+        .get()
+        .map_or(SNS_XML_NAMESPACE, String::as_str)
 }
 
+/// One topic in a ListTopics page.
 #[derive(Debug)]
 pub struct Member {
     pub topic_arn: String,
 }
-
-#[derive(Debug)]
-pub struct SubscribeResponse {
-    pub subscribe_result: SubscribeResult,
-    pub response_metadata: ResponseMetadata,
-}
-
-#[derive(Debug)]
-pub struct SubscribeResult {
-    pub subscription_arn: String,
-}
-
-#[derive(Debug)]
-pub struct UnsubscribeResponse {
-    pub response_metadata: ResponseMetadata,
-}
-
-#[derive(Debug)]
-pub struct PublishResponse {
-    pub publish_result: PublishResult,
-    pub response_metadata: ResponseMetadata,
-}
-
-#[derive(Debug)]
-pub struct PublishResult {
-    pub message_id: String,
-}
-
-#[derive(Debug)]
-pub struct GetTopicAttributesResponse {
-    pub get_topic_attributes_result: GetTopicAttributesResult,
-    pub response_metadata: ResponseMetadata,
-}
-
-#[derive(Debug, Default)]
-pub struct GetTopicAttributesResult {
-    pub attributes: Attributes,
-}
-
-#[derive(Debug, Default)]
-pub struct Attributes {
-    pub entry: Vec<Entry>,
-}
-
-#[derive(Debug)]
-pub struct Entry {
-    pub key: String,
-    pub value: String,
-}
@@ -0,0 +1,156 @@
+use crate::delivery;
+use crate::state::{AppState, SharedState, Topic};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// Topic tag overriding the global retention, in seconds.
+pub const RETENTION_TAG: &str = "x-localsns-retention";
+
+/// Topic tag overriding `--default-topic-ttl`, in seconds.
+pub const TTL_TAG: &str = "x-localsns-ttl";
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Parses a retention in seconds, as given by `RETENTION_TAG`.
+pub fn parse_retention(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// The cutoff before which stored records of each topic expire. Topics
+/// without a retention keep their records.
+fn cutoffs(state: &AppState, now: DateTime<Utc>) -> HashMap<String, DateTime<Utc>> {
+    state
+        .topics
+        .iter()
+        .filter_map(|topic| {
+            let retention = match topic.tags.get(RETENTION_TAG) {
+                Some(value) => parse_retention(value),
+                None => state.message_retention,
+            }?;
+            let retention = chrono::Duration::from_std(retention).ok()?;
+            Some((topic.name.clone(), now - retention))
+        })
+        .collect()
+}
+
+/// Whether `topic` has gone unused for longer than its TTL. It is deleted
+/// by the next sweep, and answers NotFound to GetTopicAttributes meanwhile.
+pub fn topic_expired(state: &AppState, topic: &Topic, now: DateTime<Utc>) -> bool {
+    let ttl = match topic.tags.get(TTL_TAG) {
+        Some(value) => parse_retention(value),
+        None => state.default_topic_ttl,
+    };
+    ttl.and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+        .is_some_and(|ttl| now - topic.last_used_at > ttl)
+}
+
+/// Deletes the topics that have expired, as DeleteTopic would, and returns
+/// how many.
+fn expire_topics(state: &SharedState, now: DateTime<Utc>) -> usize {
+    let expired: Vec<String> = state
+        .topics
+        .iter()
+        .filter(|topic| topic_expired(state, topic, now))
+        .map(|topic| topic.name.clone())
+        .collect();
+    let mut deleted = 0;
+    for topic_name in expired {
+        // Checked again, as a publish may have come in since.
+        if let Some(topic) =
+            state.remove_topic_if(&topic_name, |topic| topic_expired(state, topic, now))
+        {
+            tracing::info!(
+                "Deleted topic {} after {}s without publishes or subscribes",
+                topic_name,
+                (now - topic.last_used_at).num_seconds()
+            );
+            delivery::spawn_unsubscribe_confirmations(
+                state.clone(),
+                state.base_url.clone(),
+                topic.subscriptions,
+            );
+            deleted += 1;
+        }
+    }
+    deleted
+}
+
+fn topic_of_subscription(subscription_arn: &str) -> &str {
+    subscription_arn.rsplit(':').nth(1).unwrap_or_default()
+}
+
+/// Deletes expired topics, then drops history messages and delivery
+/// records older than their topic's retention. Each store is visited one
+/// shard guard at a time, never while holding a guard on another map.
+pub fn sweep(state: &SharedState) {
+    let now = Utc::now();
+    let topics_expired = expire_topics(state, now);
+    let cutoffs = cutoffs(state, now);
+
+    let mut messages_evicted = 0;
+    for (topic_name, cutoff) in &cutoffs {
+        if let Some(mut history) = state.messages.get_mut(topic_name) {
+            while history.front().is_some_and(|m| m.timestamp < *cutoff) {
+                history.pop_front();
+                messages_evicted += 1;
+            }
+        }
+    }
+
+    let expired = |subscription_arn: &str, timestamp: DateTime<Utc>| {
+        cutoffs
+            .get(topic_of_subscription(subscription_arn))
+            .is_some_and(|cutoff| timestamp < *cutoff)
+    };
+    let mut deliveries_evicted = 0;
+    {
+        let mut outbox = state.outbox.write().unwrap();
+        let before = outbox.len();
+        outbox.retain(|entry| !expired(&entry.subscription_arn, entry.timestamp));
+        deliveries_evicted += before - outbox.len();
+    }
+    let endpoints: Vec<String> = state
+        .push_outbox
+        .iter()
+        .map(|entry| entry.key().clone())
+        .collect();
+    for endpoint in endpoints {
+        if let Some(mut pushes) = state.push_outbox.get_mut(&endpoint) {
+            let before = pushes.len();
+            pushes.retain(|push| !expired(&push.subscription_arn, push.timestamp));
+            deliveries_evicted += before - pushes.len();
+        }
+    }
+
+    let stats = &state.retention;
+    *stats.last_run.write().unwrap() = Some(now);
+    stats
+        .messages_evicted
+        .fetch_add(messages_evicted as u64, Ordering::Relaxed);
+    stats
+        .deliveries_evicted
+        .fetch_add(deliveries_evicted as u64, Ordering::Relaxed);
+    stats
+        .topics_expired
+        .fetch_add(topics_expired as u64, Ordering::Relaxed);
+    if messages_evicted + deliveries_evicted > 0 {
+        tracing::debug!(
+            "Retention sweep evicted {} messages and {} deliveries",
+            messages_evicted,
+            deliveries_evicted
+        );
+    }
+}
+
+/// Spawns the background task that runs `sweep` periodically.
+pub fn spawn_sweeper(state: SharedState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            sweep(&state);
+        }
+    });
+}
@@ -0,0 +1,380 @@
+use arc_swap::ArcSwap;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Default share of the delivery workers one topic may occupy.
+pub const DEFAULT_TOPIC_WORKER_SHARE: f64 = 0.5;
+
+/// Subscriptions one fan-out delivers to at once by default, so a slow
+/// endpoint does not hold up the others while a large topic still cannot
+/// open a connection per subscription.
+pub const DEFAULT_MAX_CONCURRENT_DELIVERIES: usize = 16;
+
+/// Most delivery workers or concurrent deliveries that can be configured.
+pub const MAX_DELIVERY_CONCURRENCY: usize = 10_000;
+
+/// Largest retry time scale; beyond it a default policy waits for hours.
+pub const MAX_RETRY_TIME_SCALE: f64 = 100.0;
+
+/// How long one HTTP/S delivery attempt may take by default, response
+/// included; SNS gives endpoints 15 seconds.
+pub const DEFAULT_HTTP_TIMEOUT_MS: u64 = 15_000;
+
+/// Longest HTTP/S delivery attempt that can be configured.
+pub const MAX_HTTP_TIMEOUT_MS: u64 = 300_000;
+
+/// Delivery tuning, set from the configuration at startup and changed at
+/// runtime through `PATCH /admin/config/delivery`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeliveryConfig {
+    /// Background fan-outs run at once; unbounded when unset.
+    pub delivery_workers: Option<usize>,
+    /// Largest share of `delivery_workers` one topic may occupy.
+    pub topic_worker_share: f64,
+    /// Subscriptions one fan-out delivers to at once.
+    pub max_concurrent_deliveries: usize,
+    /// Factor the pauses between delivery retries are multiplied by.
+    pub retry_time_scale: f64,
+    /// How long one HTTP/S delivery attempt may take before it fails.
+    pub http_timeout_ms: u64,
+}
+
+/// A change to the `DeliveryConfig`; unset fields are kept. Workers cannot
+/// be made unbounded again once bounded.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeliveryConfigPatch {
+    pub delivery_workers: Option<usize>,
+    pub topic_worker_share: Option<f64>,
+    pub max_concurrent_deliveries: Option<usize>,
+    pub retry_time_scale: Option<f64>,
+    pub http_timeout_ms: Option<u64>,
+}
+
+impl DeliveryConfig {
+    /// Checks the bounds, naming the offending key.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(workers) = self.delivery_workers
+            && !(1..=MAX_DELIVERY_CONCURRENCY).contains(&workers)
+        {
+            return Err(format!(
+                "delivery_workers: {} is not in [1, {}]",
+                workers, MAX_DELIVERY_CONCURRENCY
+            ));
+        }
+        if !(self.topic_worker_share > 0.0 && self.topic_worker_share <= 1.0) {
+            return Err(format!(
+                "topic_worker_share: {} is not in (0, 1]",
+                self.topic_worker_share
+            ));
+        }
+        if !(1..=MAX_DELIVERY_CONCURRENCY).contains(&self.max_concurrent_deliveries) {
+            return Err(format!(
+                "max_concurrent_deliveries: {} is not in [1, {}]",
+                self.max_concurrent_deliveries, MAX_DELIVERY_CONCURRENCY
+            ));
+        }
+        if !(0.0..=MAX_RETRY_TIME_SCALE).contains(&self.retry_time_scale) {
+            return Err(format!(
+                "retry_time_scale: {} is not in [0, {}]",
+                self.retry_time_scale, MAX_RETRY_TIME_SCALE
+            ));
+        }
+        if !(1..=MAX_HTTP_TIMEOUT_MS).contains(&self.http_timeout_ms) {
+            return Err(format!(
+                "http_timeout_ms: {} is not in [1, {}]",
+                self.http_timeout_ms, MAX_HTTP_TIMEOUT_MS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Workers one topic may hold; 0 when unbounded.
+    fn per_topic(&self) -> usize {
+        self.delivery_workers
+            .map(|workers| {
+                ((workers as f64 * self.topic_worker_share).ceil() as usize).clamp(1, workers)
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// A semaphore that can be resized while its permits are held. Growing
+/// adds permits at once; shrinking forgets the free ones and the rest as
+/// they are returned.
+#[derive(Debug)]
+struct Pool {
+    semaphore: Arc<Semaphore>,
+    /// The size, and the permits still to forget when returned.
+    size: Mutex<(usize, usize)>,
+}
+
+/// A permit of a `Pool`, returned on drop unless the pool shrank.
+#[derive(Debug)]
+struct PoolPermit {
+    pool: Arc<Pool>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Pool {
+    fn new(size: usize) -> Arc<Self> {
+        Arc::new(Pool {
+            semaphore: Arc::new(Semaphore::new(size)),
+            size: Mutex::new((size, 0)),
+        })
+    }
+
+    fn resize(&self, new_size: usize) {
+        let mut size = self.size.lock().unwrap();
+        let (current, owed) = &mut *size;
+        if new_size >= *current {
+            let grown = new_size - *current;
+            let repaid = grown.min(*owed);
+            *owed -= repaid;
+            self.semaphore.add_permits(grown - repaid);
+        } else {
+            let shrunk = *current - new_size;
+            *owed += shrunk - self.semaphore.forget_permits(shrunk);
+        }
+        *current = new_size;
+    }
+
+    /// Tokio semaphores queue waiters in order, so permits are handed out
+    /// first come, first served.
+    async fn acquire(self: Arc<Self>) -> Option<PoolPermit> {
+        // The semaphores are never closed, so acquiring cannot fail.
+        let permit = self.semaphore.clone().acquire_owned().await.ok()?;
+        Some(PoolPermit {
+            pool: self,
+            permit: Some(permit),
+        })
+    }
+}
+
+impl Drop for PoolPermit {
+    fn drop(&mut self) {
+        let mut size = self.pool.size.lock().unwrap();
+        if size.1 > 0 {
+            size.1 -= 1;
+            if let Some(permit) = self.permit.take() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+/// Bounds background fan-outs to `--delivery-workers` at once, of which a
+/// single topic may hold at most its share. A flooded topic then queues on
+/// its own semaphore rather than on the shared one, so other topics always
+/// find a free worker. Without `--delivery-workers` nothing is bounded.
+///
+/// The `DeliveryConfig` can change while fan-outs run: the worker pools are
+/// resized in place, so queued fan-outs start as soon as workers are added.
+/// Fan-outs already running when an unbounded scheduler becomes bounded
+/// are not counted against the new bound.
+#[derive(Debug)]
+pub struct DeliveryScheduler {
+    config: ArcSwap<DeliveryConfig>,
+    /// `None` while unbounded. Its lock also serializes reconfiguration
+    /// with the creation of topic pools.
+    workers: Mutex<Option<Arc<Pool>>>,
+    topics: DashMap<String, Arc<Pool>>,
+}
+
+/// Held while a fan-out runs; dropping it frees the workers.
+#[derive(Debug)]
+pub struct WorkerPermit {
+    _topic: PoolPermit,
+    _worker: PoolPermit,
+}
+
+impl DeliveryScheduler {
+    pub fn new(config: DeliveryConfig) -> Self {
+        DeliveryScheduler {
+            workers: Mutex::new(config.delivery_workers.map(Pool::new)),
+            config: ArcSwap::from_pointee(config),
+            topics: DashMap::new(),
+        }
+    }
+
+    /// The active delivery configuration.
+    pub fn config(&self) -> Arc<DeliveryConfig> {
+        self.config.load_full()
+    }
+
+    /// Applies `patch` to the active configuration and resizes the worker
+    /// pools to match, returning the new configuration. Nothing changes
+    /// when the result is out of bounds.
+    pub fn reconfigure(&self, patch: DeliveryConfigPatch) -> Result<Arc<DeliveryConfig>, String> {
+        let mut workers = self.workers.lock().unwrap();
+        let mut config = (*self.config()).clone();
+        if let Some(delivery_workers) = patch.delivery_workers {
+            config.delivery_workers = Some(delivery_workers);
+        }
+        if let Some(topic_worker_share) = patch.topic_worker_share {
+            config.topic_worker_share = topic_worker_share;
+        }
+        if let Some(max_concurrent_deliveries) = patch.max_concurrent_deliveries {
+            config.max_concurrent_deliveries = max_concurrent_deliveries;
+        }
+        if let Some(retry_time_scale) = patch.retry_time_scale {
+            config.retry_time_scale = retry_time_scale;
+        }
+        if let Some(http_timeout_ms) = patch.http_timeout_ms {
+            config.http_timeout_ms = http_timeout_ms;
+        }
+        config.validate()?;
+
+        if let Some(delivery_workers) = config.delivery_workers {
+            match &*workers {
+                Some(pool) => pool.resize(delivery_workers),
+                None => *workers = Some(Pool::new(delivery_workers)),
+            }
+            let per_topic = config.per_topic();
+            for topic in self.topics.iter() {
+                topic.resize(per_topic);
+            }
+        }
+        let config = Arc::new(config);
+        self.config.store(config.clone());
+        Ok(config)
+    }
+
+    /// Waits for a worker for one fan-out of the topic, within the topic's
+    /// share. Returns `None` when unbounded.
+    pub async fn acquire(&self, topic_name: &str) -> Option<WorkerPermit> {
+        let (workers, topic) = {
+            let guard = self.workers.lock().unwrap();
+            let workers = guard.clone()?;
+            let per_topic = self.config().per_topic();
+            let topic = self
+                .topics
+                .entry(topic_name.to_string())
+                .or_insert_with(|| Pool::new(per_topic))
+                .clone();
+            (workers, topic)
+        };
+        let topic = topic.acquire().await?;
+        let worker = workers.acquire().await?;
+        Some(WorkerPermit {
+            _topic: topic,
+            _worker: worker,
+        })
+    }
+
+    /// Forgets a deleted topic; fan-outs still queued keep their semaphore.
+    pub fn remove_topic(&self, topic_name: &str) {
+        self.topics.remove(topic_name);
+    }
+
+    pub fn clear(&self) {
+        self.topics.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn scheduler(delivery_workers: Option<usize>, topic_worker_share: f64) -> DeliveryScheduler {
+        DeliveryScheduler::new(DeliveryConfig {
+            delivery_workers,
+            topic_worker_share,
+            max_concurrent_deliveries: DEFAULT_MAX_CONCURRENT_DELIVERIES,
+            retry_time_scale: 1.0,
+            http_timeout_ms: DEFAULT_HTTP_TIMEOUT_MS,
+        })
+    }
+
+    async fn acquired(scheduler: &DeliveryScheduler, topic_name: &str) -> bool {
+        tokio::time::timeout(Duration::from_millis(50), scheduler.acquire(topic_name))
+            .await
+            .is_ok()
+    }
+
+    #[test]
+    fn topic_shares_round_up_within_the_workers() {
+        let share = |workers, share| scheduler(Some(workers), share).config().per_topic();
+        assert_eq!(share(10, 0.5), 5);
+        assert_eq!(share(5, 0.5), 3);
+        assert_eq!(share(10, 0.01), 1);
+        assert_eq!(share(3, 1.0), 3);
+        assert_eq!(scheduler(None, 0.5).config().per_topic(), 0);
+    }
+
+    #[tokio::test]
+    async fn a_flooded_topic_leaves_workers_for_the_others() {
+        let scheduler = scheduler(Some(4), 0.5);
+        let mut held = vec![
+            scheduler.acquire("flood").await.unwrap(),
+            scheduler.acquire("flood").await.unwrap(),
+        ];
+        assert!(!acquired(&scheduler, "flood").await);
+
+        held.push(scheduler.acquire("quiet").await.unwrap());
+        held.push(scheduler.acquire("other").await.unwrap());
+        // Every worker is busy now, whatever the topic.
+        assert!(!acquired(&scheduler, "idle").await);
+
+        held.truncate(1);
+        assert!(acquired(&scheduler, "flood").await);
+        assert!(acquired(&scheduler, "idle").await);
+    }
+
+    #[tokio::test]
+    async fn unbounded_schedulers_hand_out_no_permits() {
+        let scheduler = scheduler(None, 0.5);
+        assert!(scheduler.acquire("orders").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn resizing_wakes_queued_fan_outs() {
+        let scheduler = Arc::new(scheduler(Some(1), 1.0));
+        let held = scheduler.acquire("orders").await.unwrap();
+        let queued = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.acquire("orders").await.is_some() }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!queued.is_finished());
+
+        let patch = DeliveryConfigPatch {
+            delivery_workers: Some(2),
+            ..DeliveryConfigPatch::default()
+        };
+        scheduler.reconfigure(patch).unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), queued)
+                .await
+                .unwrap()
+                .unwrap()
+        );
+
+        // Shrinking takes effect as permits come back.
+        let patch = DeliveryConfigPatch {
+            delivery_workers: Some(1),
+            ..DeliveryConfigPatch::default()
+        };
+        scheduler.reconfigure(patch).unwrap();
+        drop(held);
+        assert!(acquired(&scheduler, "orders").await);
+        let _held = scheduler.acquire("orders").await.unwrap();
+        assert!(!acquired(&scheduler, "orders").await);
+    }
+
+    #[test]
+    fn invalid_patches_change_nothing() {
+        let scheduler = scheduler(Some(4), 0.5);
+        let patch = DeliveryConfigPatch {
+            delivery_workers: Some(8),
+            topic_worker_share: Some(1.5),
+            ..DeliveryConfigPatch::default()
+        };
+        let error = scheduler.reconfigure(patch).unwrap_err();
+        assert!(error.starts_with("topic_worker_share"), "{}", error);
+        assert_eq!(scheduler.config().delivery_workers, Some(4));
+    }
+}
@@ -0,0 +1,279 @@
+use crate::snapshot::{self, ImportError, ImportMode, StateDocument};
+use crate::state::{AppState, SharedState};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct SeedConfig {
+    pub path: PathBuf,
+    pub watch: bool,
+    pub prune_on_reload: bool,
+}
+
+/// Outcome of the most recent seed load, served at `/admin/config/status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadStatus {
+    pub path: String,
+    pub loaded_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub reloads: u64,
+    pub topics_created: usize,
+    pub topics_updated: usize,
+    pub topics_deleted: usize,
+    pub subscriptions_created: usize,
+    pub subscriptions_deleted: usize,
+    pub errors: Vec<ImportError>,
+    /// Topics the seed declared, by name. Only these, and the subscriptions
+    /// below, are ever pruned; whatever clients created stays.
+    #[serde(skip)]
+    pub seeded_topics: HashSet<String>,
+    /// Subscriptions the seed declared, by ARN.
+    #[serde(skip)]
+    pub seeded_subscriptions: HashSet<String>,
+}
+
+/// Whether a state document is YAML rather than JSON, by its extension.
+pub fn is_yaml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yaml" | "yml")
+    )
+}
+
+pub fn read_document(path: &Path) -> Result<StateDocument, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    if is_yaml(path) {
+        serde_yaml::from_str(&contents).map_err(|e| format!("invalid seed YAML: {}", e))
+    } else {
+        serde_json::from_str(&contents).map_err(|e| format!("invalid seed JSON: {}", e))
+    }
+}
+
+/// Reconciles the server state with the seed file. New topics and
+/// subscriptions are created and changed attributes updated; with `prune`,
+/// topics and subscriptions an earlier load declared but the file no longer
+/// does are deleted. Topics and subscriptions created at runtime are never
+/// pruned.
+pub fn apply(state: &AppState, config: &SeedConfig, prune: bool) {
+    let previous = state.seed_status.read().unwrap().clone();
+    let mut status = ReloadStatus {
+        path: config.path.display().to_string(),
+        loaded_at: Some(chrono::Utc::now()),
+        reloads: previous.as_ref().map_or(0, |previous| previous.reloads + 1),
+        ..Default::default()
+    };
+    let previous = previous.unwrap_or_default();
+
+    match read_document(&config.path) {
+        Ok(document) => {
+            let report = snapshot::import(state, document, ImportMode::Merge);
+            status.topics_created = report.topics_created;
+            status.topics_updated = report.topics_updated;
+            status.subscriptions_created = report.subscriptions_created;
+            status.errors = report.errors;
+            status.seeded_topics = report.topics.into_iter().collect();
+            status.seeded_subscriptions = report.subscription_arns.into_iter().collect();
+            if prune {
+                let (topics, subscriptions) = prune_missing(state, &previous, &status);
+                status.topics_deleted = topics;
+                status.subscriptions_deleted = subscriptions;
+            }
+        }
+        Err(error) => {
+            status.errors.push(ImportError {
+                item: status.path.clone(),
+                error,
+            });
+            // A file that failed to load declares nothing new, so a later
+            // reload still knows what to prune.
+            status.seeded_topics = previous.seeded_topics;
+            status.seeded_subscriptions = previous.seeded_subscriptions;
+        }
+    }
+
+    tracing::info!(
+        "Seed {} loaded: created {} topics/{} subscriptions, updated {}, deleted {} topics/{} subscriptions, {} errors",
+        status.path,
+        status.topics_created,
+        status.subscriptions_created,
+        status.topics_updated,
+        status.topics_deleted,
+        status.subscriptions_deleted,
+        status.errors.len()
+    );
+    for error in &status.errors {
+        tracing::warn!("Seed error in {}: {}", error.item, error.error);
+    }
+    *state.seed_status.write().unwrap() = Some(status);
+}
+
+/// Deletes what `previous` declared and `current` no longer does, through
+/// the same removal paths as DeleteTopic and Unsubscribe.
+fn prune_missing(
+    state: &AppState,
+    previous: &ReloadStatus,
+    current: &ReloadStatus,
+) -> (usize, usize) {
+    let topics_deleted = previous
+        .seeded_topics
+        .difference(&current.seeded_topics)
+        .filter(|name| state.remove_topic(name).is_some())
+        .count();
+    let subscriptions_deleted = previous
+        .seeded_subscriptions
+        .difference(&current.seeded_subscriptions)
+        .filter(|arn| state.remove_subscription(arn).is_some())
+        .count();
+    (topics_deleted, subscriptions_deleted)
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Spawns the SIGHUP listener and, with `watch`, a file poller that reload
+/// the seed file.
+pub fn spawn_reloaders(state: SharedState, config: SeedConfig) {
+    #[cfg(unix)]
+    {
+        let state = state.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            use tokio::signal::unix::{SignalKind, signal};
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => hangup,
+                Err(e) => {
+                    tracing::error!("Failed to install SIGHUP handler: {}", e);
+                    return;
+                }
+            };
+            while hangup.recv().await.is_some() {
+                tracing::info!("SIGHUP received, reloading seed {}", config.path.display());
+                apply(&state, &config, config.prune_on_reload);
+            }
+        });
+    }
+
+    if config.watch {
+        tokio::spawn(async move {
+            let mut last_modified = modified_at(&config.path);
+            let mut interval = tokio::time::interval(WATCH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let modified = modified_at(&config.path);
+                if modified != last_modified {
+                    last_modified = modified;
+                    tracing::info!("Seed {} changed, reloading", config.path.display());
+                    apply(&state, &config, config.prune_on_reload);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::snapshot::TopicDocument;
+
+    fn topic_document(value: serde_json::Value) -> TopicDocument {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn prune_only_deletes_what_the_seed_declared() {
+        let path =
+            std::env::temp_dir().join(format!("local-sns-seed-{}.json", uuid::Uuid::new_v4()));
+        let config = SeedConfig {
+            path: path.clone(),
+            watch: false,
+            prune_on_reload: true,
+        };
+        std::fs::write(
+            &path,
+            r#"{"topics":[
+                {"name":"seeded","subscriptions":[{"protocol":"memory","endpoint":"from-seed"}]},
+                {"name":"dropped"}
+            ]}"#,
+        )
+        .unwrap();
+        let state = AppState::new(Config::default());
+        apply(&state, &config, false);
+
+        // Created at runtime, as CreateTopic and Subscribe would.
+        snapshot::create_fixtures(
+            &state,
+            vec![
+                topic_document(serde_json::json!({"name": "runtime"})),
+                topic_document(serde_json::json!({
+                    "name": "seeded",
+                    "subscriptions": [{"protocol": "memory", "endpoint": "from-client"}]
+                })),
+            ],
+        );
+
+        std::fs::write(&path, r#"{"topics":[{"name":"seeded"}]}"#).unwrap();
+        apply(&state, &config, true);
+        std::fs::remove_file(&path).unwrap();
+
+        let status = state.seed_status.read().unwrap().clone().unwrap();
+        assert_eq!(status.errors.len(), 0);
+        assert_eq!(status.topics_deleted, 1);
+        assert_eq!(status.subscriptions_deleted, 1);
+        assert!(!state.topics.contains_key("dropped"));
+        assert!(state.topics.contains_key("runtime"));
+        let seeded = state.topics.get("seeded").unwrap();
+        let endpoints: Vec<&str> = seeded
+            .subscriptions
+            .iter()
+            .map(|s| s.endpoint.as_str())
+            .collect();
+        assert_eq!(endpoints, ["from-client"]);
+        assert_eq!(seeded.subscriptions_deleted, 1);
+    }
+
+    #[test]
+    fn seeded_fifo_topics_only_take_queue_subscriptions() {
+        let path =
+            std::env::temp_dir().join(format!("local-sns-seed-{}.json", uuid::Uuid::new_v4()));
+        let config = SeedConfig {
+            path: path.clone(),
+            watch: false,
+            prune_on_reload: false,
+        };
+        std::fs::write(
+            &path,
+            r#"{"topics":[{
+                "name":"orders.fifo",
+                "attributes":{"FifoTopic":"true"},
+                "subscriptions":[
+                    {"protocol":"http","endpoint":"http://example.com/events"},
+                    {"protocol":"email","endpoint":"orders@example.com"},
+                    {"protocol":"sqs","endpoint":"arn:aws:sqs:us-east-1:000000000000:orders.fifo"}
+                ]
+            }]}"#,
+        )
+        .unwrap();
+        let state = AppState::new(Config::default());
+        apply(&state, &config, false);
+        std::fs::remove_file(&path).unwrap();
+
+        let status = state.seed_status.read().unwrap().clone().unwrap();
+        assert_eq!(status.errors.len(), 2, "{:?}", status.errors);
+        for error in &status.errors {
+            assert_eq!(error.error, crate::error::FIFO_TOPIC_PROTOCOL_NOT_SUPPORTED);
+        }
+        let topic = state.topics.get("orders.fifo").unwrap();
+        let protocols: Vec<&str> = topic
+            .subscriptions
+            .iter()
+            .map(|s| s.protocol.as_str())
+            .collect();
+        assert_eq!(protocols, ["sqs"]);
+    }
+}
@@ -0,0 +1,409 @@
+use crate::error::{FIFO_TOPIC_PROTOCOL_NOT_SUPPORTED, invalid_parameter};
+use crate::state::{
+    AppState, MESSAGE_HISTORY_LIMIT, Message, Subscription, Topic, is_xml_text, topic_arn,
+    validate_subscription, validate_tag, validate_topic_attribute, validate_topic_name,
+};
+use crate::stats::{LastDeliveryError, SubscriptionHealth};
+use dashmap::mapref::entry::Entry;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Portable description of the server state, shared by the admin
+/// export/import endpoints.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StateDocument {
+    #[serde(default)]
+    pub topics: Vec<TopicDocument>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TopicDocument {
+    pub name: String,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub subscriptions: Vec<SubscriptionDocument>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<Message>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscriptionDocument {
+    pub protocol: String,
+    pub endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription_arn: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+    /// Runtime state, exported and restored with `--persist-runtime-state`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_delivery_error: Option<LastDeliveryError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    #[default]
+    Merge,
+    Replace,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub topics_created: usize,
+    pub topics_updated: usize,
+    pub subscriptions_created: usize,
+    pub errors: Vec<ImportError>,
+    /// Names of the topics the document declared and that were applied.
+    #[serde(skip)]
+    pub topics: Vec<String>,
+    /// ARNs of the subscriptions the document declared, created or found.
+    #[serde(skip)]
+    pub subscription_arns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportError {
+    pub item: String,
+    pub error: String,
+}
+
+fn attribute_map(topic: &Topic) -> BTreeMap<String, String> {
+    topic
+        .explicit_attributes()
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+pub fn export(state: &AppState, include_messages: bool) -> StateDocument {
+    let mut topics: Vec<TopicDocument> = state
+        .topics
+        .iter()
+        .map(|topic| TopicDocument {
+            name: topic.name.clone(),
+            attributes: attribute_map(&topic),
+            tags: topic
+                .tags
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            subscriptions: topic
+                .subscriptions
+                .iter()
+                .map(|sub| SubscriptionDocument {
+                    protocol: sub.protocol.clone(),
+                    endpoint: sub.endpoint.clone(),
+                    subscription_arn: Some(sub.subscription_arn.clone()),
+                    attributes: sub
+                        .explicit_attributes()
+                        .into_iter()
+                        .map(|(key, value)| (key.to_string(), value))
+                        .collect(),
+                    last_delivery_error: state
+                        .persist_runtime_state
+                        .then(|| state.subscription_health(&sub.subscription_arn).last_error)
+                        .flatten(),
+                })
+                .collect(),
+            messages: None,
+        })
+        .collect();
+    topics.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if include_messages {
+        for topic in &mut topics {
+            let messages = state
+                .messages
+                .get(&topic.name)
+                .map(|history| history.iter().cloned().collect())
+                .unwrap_or_default();
+            topic.messages = Some(messages);
+        }
+    }
+
+    StateDocument { topics }
+}
+
+/// What applying one topic document did, or would have done.
+struct TopicOutcome {
+    arn: String,
+    created: bool,
+    updated: bool,
+    applied: bool,
+    subscription_arns: Vec<String>,
+    subscriptions_created: usize,
+    /// Last delivery errors of created subscriptions, by subscription ARN.
+    last_delivery_errors: Vec<(String, LastDeliveryError)>,
+    errors: Vec<ImportError>,
+}
+
+/// Applies one topic document to a working copy of the topic. Invalid
+/// attributes and subscriptions are reported; with `all_or_nothing` any
+/// error leaves the topic untouched, otherwise the valid parts are kept.
+fn apply_topic(state: &AppState, topic_doc: TopicDocument, all_or_nothing: bool) -> TopicOutcome {
+    let arn = topic_arn(&topic_doc.name);
+    let mut outcome = TopicOutcome {
+        arn: arn.clone(),
+        created: false,
+        updated: false,
+        applied: false,
+        subscription_arns: Vec::new(),
+        subscriptions_created: 0,
+        last_delivery_errors: Vec::new(),
+        errors: Vec::new(),
+    };
+    if let Err(error) = validate_topic_name(&topic_doc.name) {
+        outcome.errors.push(ImportError {
+            item: format!("topic {}", topic_doc.name),
+            error,
+        });
+        return outcome;
+    }
+    // Checked before taking the entry, which the count would deadlock on.
+    if let Err(e) = state.check_topic_quota(&topic_doc.name) {
+        outcome.errors.push(ImportError {
+            item: format!("topic {}", topic_doc.name),
+            error: e.message().to_string(),
+        });
+        return outcome;
+    }
+
+    let entry = state.topics.entry(topic_doc.name.clone());
+    let mut topic = match &entry {
+        Entry::Occupied(entry) => entry.get().clone(),
+        Entry::Vacant(_) => Topic::new(topic_doc.name.clone(), arn.clone(), Default::default()),
+    };
+    let existing = matches!(entry, Entry::Occupied(_));
+
+    let before = (attribute_map(&topic), topic.tags.clone());
+    for (key, value) in topic_doc.attributes {
+        if let Err(error) = validate_topic_attribute(&key, &value) {
+            outcome.errors.push(ImportError {
+                item: format!("topic {} attribute {}", topic_doc.name, key),
+                error,
+            });
+            continue;
+        }
+        if !topic.set_attribute(&key, value) {
+            outcome.errors.push(ImportError {
+                item: format!("topic {} attribute {}", topic_doc.name, key),
+                error: invalid_parameter("AttributeName"),
+            });
+        }
+    }
+    for (key, value) in topic_doc.tags {
+        if let Err(error) = validate_tag(&key, &value) {
+            outcome.errors.push(ImportError {
+                item: format!("topic {} tag {}", topic_doc.name, key),
+                error,
+            });
+            continue;
+        }
+        topic.tags.insert(key, value);
+    }
+    outcome.updated = existing && before != (attribute_map(&topic), topic.tags.clone());
+
+    for sub_doc in topic_doc.subscriptions {
+        let item = format!(
+            "topic {} subscription {}:{}",
+            topic_doc.name, sub_doc.protocol, sub_doc.endpoint
+        );
+        if let Err(error) = validate_subscription(&sub_doc.protocol, &sub_doc.endpoint) {
+            outcome.errors.push(ImportError { item, error });
+            continue;
+        }
+        if !topic.accepts_protocol(&sub_doc.protocol) {
+            outcome.errors.push(ImportError {
+                item,
+                error: FIFO_TOPIC_PROTOCOL_NOT_SUPPORTED.to_string(),
+            });
+            continue;
+        }
+        if let Some(existing) = topic
+            .subscriptions
+            .iter()
+            .find(|s| s.protocol == sub_doc.protocol && s.endpoint == sub_doc.endpoint)
+        {
+            outcome
+                .subscription_arns
+                .push(existing.subscription_arn.clone());
+            continue;
+        }
+        if let Err(e) = state.check_subscription_quota(&topic) {
+            outcome.errors.push(ImportError {
+                item,
+                error: e.message().to_string(),
+            });
+            continue;
+        }
+        let mut subscription = Subscription::new(&arn, sub_doc.protocol, sub_doc.endpoint);
+        match sub_doc.subscription_arn {
+            Some(subscription_arn) if subscription_arn.starts_with(&format!("{}:", arn)) => {
+                subscription.subscription_arn = subscription_arn;
+            }
+            Some(subscription_arn) => {
+                outcome.errors.push(ImportError {
+                    item,
+                    error: format!(
+                        "SubscriptionArn {} does not belong to topic",
+                        subscription_arn
+                    ),
+                });
+                continue;
+            }
+            None => {}
+        }
+        let invalid: Vec<String> = sub_doc
+            .attributes
+            .iter()
+            .filter(|(key, value)| !is_xml_text(value) || !subscription.set_attribute(key, value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        if !invalid.is_empty() {
+            outcome.errors.push(ImportError {
+                item,
+                error: invalid_parameter(&invalid.join(", ")),
+            });
+            continue;
+        }
+        if let Err(e) = state.check_new_subscription(&subscription) {
+            outcome.errors.push(ImportError {
+                item,
+                error: e.message().to_string(),
+            });
+            continue;
+        }
+        if state.inherit_tags {
+            subscription.tags = topic.tags.clone();
+        }
+        outcome
+            .subscription_arns
+            .push(subscription.subscription_arn.clone());
+        if let Some(last_delivery_error) = sub_doc.last_delivery_error
+            && state.persist_runtime_state
+        {
+            outcome
+                .last_delivery_errors
+                .push((subscription.subscription_arn.clone(), last_delivery_error));
+        }
+        topic.subscriptions.push(subscription);
+        outcome.subscriptions_created += 1;
+    }
+
+    if all_or_nothing && !outcome.errors.is_empty() {
+        return outcome;
+    }
+    match entry {
+        Entry::Occupied(mut entry) => {
+            entry.insert(topic);
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(topic);
+            outcome.created = true;
+        }
+    }
+    outcome.applied = true;
+    for (subscription_arn, last_delivery_error) in outcome.last_delivery_errors.drain(..) {
+        state.subscription_health.insert(
+            subscription_arn,
+            SubscriptionHealth {
+                consecutive_failures: last_delivery_error.attempts,
+                disabled: false,
+                last_error: Some(last_delivery_error),
+            },
+        );
+    }
+
+    if let Some(messages) = topic_doc.messages {
+        let skip = messages.len().saturating_sub(MESSAGE_HISTORY_LIMIT);
+        let history: VecDeque<Message> = messages.into_iter().skip(skip).collect();
+        state.messages.insert(topic_doc.name, history);
+    }
+    outcome
+}
+
+/// Applies a state document. Invalid topics, attributes and subscriptions are
+/// reported and skipped; everything else is still imported.
+pub fn import(state: &AppState, document: StateDocument, mode: ImportMode) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    if mode == ImportMode::Replace {
+        state.reset();
+    }
+
+    for topic_doc in document.topics {
+        let name = topic_doc.name.clone();
+        let outcome = apply_topic(state, topic_doc, false);
+        report.topics_created += usize::from(outcome.created);
+        report.topics_updated += usize::from(outcome.updated);
+        report.subscriptions_created += outcome.subscriptions_created;
+        report.errors.extend(outcome.errors);
+        if outcome.applied {
+            report.topics.push(name);
+            report.subscription_arns.extend(outcome.subscription_arns);
+        }
+    }
+
+    report
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureStatus {
+    Created,
+    Updated,
+    Unchanged,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FixtureResult {
+    pub name: String,
+    pub status: FixtureStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic_arn: Option<String>,
+    /// ARNs of the requested subscriptions, in request order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subscription_arns: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ImportError>,
+}
+
+/// Creates test fixture topics through the import path. Each topic is
+/// applied as a unit: if any of its attributes or subscriptions is invalid,
+/// nothing of that topic is created or changed.
+pub fn create_fixtures(state: &AppState, topics: Vec<TopicDocument>) -> Vec<FixtureResult> {
+    topics
+        .into_iter()
+        .map(|topic_doc| {
+            let name = topic_doc.name.clone();
+            let outcome = apply_topic(state, topic_doc, true);
+            if !outcome.applied {
+                return FixtureResult {
+                    name,
+                    status: FixtureStatus::Failed,
+                    topic_arn: None,
+                    subscription_arns: Vec::new(),
+                    errors: outcome.errors,
+                };
+            }
+            let status = if outcome.created {
+                FixtureStatus::Created
+            } else if outcome.updated || outcome.subscriptions_created > 0 {
+                FixtureStatus::Updated
+            } else {
+                FixtureStatus::Unchanged
+            };
+            FixtureResult {
+                name,
+                status,
+                topic_arn: Some(outcome.arn),
+                subscription_arns: outcome.subscription_arns,
+                errors: Vec::new(),
+            }
+        })
+        .collect()
+}
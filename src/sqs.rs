@@ -0,0 +1,594 @@
+use crate::delivery::{
+    DeliveryError, DeliveryErrorKind, DeliveryResult, is_enveloped, message_body,
+};
+use crate::state::{AppState, MAX_MESSAGE_SIZE, Message, MessageAttributeValue, Subscription};
+use aws_config::BehaviorVersion;
+use aws_config::retry::RetryConfig;
+use aws_sdk_sqs::Client;
+use aws_sdk_sqs::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_sqs::primitives::Blob;
+use aws_sdk_sqs::types::{
+    MessageAttributeValue as SqsMessageAttributeValue, MessageSystemAttributeNameForSends,
+    MessageSystemAttributeValue, SendMessageBatchRequestEntry,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Maps an SNS message attribute onto the SQS attribute a raw delivery
+/// carries: `String.Array` has no SQS counterpart and travels as a String
+/// holding the JSON array, Binary values are sent as bytes, and everything
+/// else (Number included) keeps its data type with a string value.
+pub fn sqs_message_attribute(
+    attribute: &MessageAttributeValue,
+) -> Option<SqsMessageAttributeValue> {
+    let builder = SqsMessageAttributeValue::builder();
+    let builder = if attribute.data_type == "String.Array" {
+        builder
+            .data_type("String")
+            .set_string_value(attribute.string_value.clone())
+    } else if attribute.data_type.split('.').next() == Some("Binary") {
+        builder
+            .data_type(&attribute.data_type)
+            .binary_value(Blob::new(attribute.binary_bytes()?))
+    } else {
+        builder
+            .data_type(&attribute.data_type)
+            .set_string_value(attribute.string_value.clone())
+    };
+    builder.build().ok()
+}
+
+/// The SQS message attributes for a delivery. SNS only forwards them as
+/// attributes when the body is not an envelope; otherwise they are part of
+/// it.
+fn sqs_message_attributes(
+    state: &AppState,
+    message: &Message,
+    subscription: &Subscription,
+) -> Option<HashMap<String, SqsMessageAttributeValue>> {
+    if is_enveloped(state, subscription) || message.message_attributes.is_empty() {
+        return None;
+    }
+    Some(
+        message
+            .message_attributes
+            .iter()
+            .filter_map(|(name, attribute)| {
+                let value = sqs_message_attribute(attribute);
+                if value.is_none() {
+                    tracing::warn!("Dropping message attribute {} that SQS cannot carry", name);
+                }
+                Some((name.clone(), value?))
+            })
+            .collect(),
+    )
+}
+
+const AWS_TRACE_HEADER: MessageSystemAttributeNameForSends =
+    MessageSystemAttributeNameForSends::AwsTraceHeader;
+
+/// The `AWSTraceHeader` system attribute SNS forwards to SQS for traced
+/// publishes.
+fn trace_header_attribute(message: &Message) -> Option<MessageSystemAttributeValue> {
+    let trace_header = message.trace_header.as_ref()?;
+    MessageSystemAttributeValue::builder()
+        .data_type("String")
+        .string_value(trace_header)
+        .build()
+        .ok()
+}
+
+/// The kind of an SQS error code, as SQS reports it in either protocol.
+fn error_code_kind(code: &str) -> Option<DeliveryErrorKind> {
+    match code {
+        "AWS.SimpleQueueService.NonExistentQueue" | "QueueDoesNotExist" => {
+            Some(DeliveryErrorKind::QueueDoesNotExist)
+        }
+        "AccessDenied"
+        | "AccessDeniedException"
+        | "KmsAccessDenied"
+        | "InvalidClientTokenId"
+        | "InvalidSecurity" => Some(DeliveryErrorKind::AccessDenied),
+        "Throttling" | "ThrottlingException" | "RequestThrottled" | "KmsThrottled" => {
+            Some(DeliveryErrorKind::Throttling)
+        }
+        _ => None,
+    }
+}
+
+/// Classifies a failed SQS call: by error code, else by HTTP status for
+/// service errors, and as a network error when no response arrived.
+fn error_kind<E: ProvideErrorMetadata>(error: &SdkError<E>) -> DeliveryErrorKind {
+    match error {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) => DeliveryErrorKind::Network,
+        SdkError::ServiceError(service) => {
+            service_error_kind(service.err().code(), service.raw().status().as_u16())
+        }
+        _ => DeliveryErrorKind::Other,
+    }
+}
+
+/// The kind of an error SQS answered with: by its code, else by status.
+fn service_error_kind(code: Option<&str>, status: u16) -> DeliveryErrorKind {
+    code.and_then(error_code_kind).unwrap_or(match status {
+        403 => DeliveryErrorKind::AccessDenied,
+        429 => DeliveryErrorKind::Throttling,
+        _ => DeliveryErrorKind::Other,
+    })
+}
+
+/// A one-line description of a failed SQS call: the error code and message
+/// SQS answered with, else the chain of causes.
+fn describe<E: ProvideErrorMetadata + std::error::Error + 'static>(error: &SdkError<E>) -> String {
+    if let SdkError::ServiceError(service) = error
+        && let Some(code) = service.err().code()
+    {
+        return format!("{}: {}", code, service.err().message().unwrap_or_default());
+    }
+    let mut description = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        description.push_str(": ");
+        description.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    description
+}
+
+/// Pauses between the attempts at a send to the subscription: those of its
+/// effective healthy retry policy, scaled by the active `retry_time_scale`.
+fn retry_delays(state: &AppState, subscription: &Subscription) -> Vec<Duration> {
+    state
+        .effective_delivery_policy(subscription)
+        .healthy_retry_policy
+        .delays(state.scheduler.config().retry_time_scale)
+}
+
+/// Makes an SQS call, retrying errors of a retryable `DeliveryErrorKind`
+/// after `delays`; the SDK's own retries are turned off. Returns the output
+/// with the attempts it took.
+async fn send_with_retries<T, E, F>(
+    queue_url: &str,
+    delays: &[Duration],
+    mut send: impl FnMut() -> F,
+) -> Result<(T, u32), DeliveryError>
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+    F: Future<Output = Result<T, SdkError<E>>>,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match send().await {
+            Ok(output) => return Ok((output, attempt)),
+            Err(error) => error,
+        };
+        let kind = error_kind(&error);
+        let delay = match delays.get(attempt as usize - 1) {
+            Some(delay) if kind.is_retryable() => delay,
+            _ => return Err(DeliveryError::new(kind, describe(&error)).after(attempt)),
+        };
+        tracing::warn!(
+            "Retrying send to SQS queue {} in {:?} after a {} error (attempt {} of {}): {}",
+            queue_url,
+            delay,
+            kind.as_str(),
+            attempt,
+            delays.len() + 1,
+            error
+        );
+        tokio::time::sleep(*delay).await;
+        attempt += 1;
+    }
+}
+
+fn is_fifo_queue(queue_url: &str) -> bool {
+    queue_url.ends_with(".fifo")
+}
+
+/// The SQS client for the service endpoint of the SQS endpoint `endpoint`,
+/// created on first use and cached, up to `--max-sqs-clients` endpoints.
+async fn client(state: &AppState, endpoint: &str) -> Arc<Client> {
+    let endpoint_url = state.sqs_queue_urls.service_endpoint(endpoint);
+
+    if let Some(client) = state.sqs_clients.get(&endpoint_url) {
+        return client.clone();
+    }
+    let config = aws_config::defaults(BehaviorVersion::latest())
+        .endpoint_url(endpoint_url.clone())
+        .retry_config(RetryConfig::disabled())
+        .load()
+        .await;
+    let client = Arc::new(Client::new(&config));
+    state.sqs_clients.insert(endpoint_url, client.clone());
+    client
+}
+
+/// Sends one message to the queue `endpoint` names.
+pub async fn send(
+    state: &AppState,
+    subscription: &Subscription,
+    message: &Message,
+    endpoint: &str,
+) -> DeliveryResult {
+    let queue_url = &state.sqs_queue_urls.queue_url(endpoint);
+    let client = client(state, endpoint).await;
+    let mut request = client
+        .send_message()
+        .queue_url(queue_url)
+        .message_body(message_body(state, message, subscription))
+        .set_message_attributes(sqs_message_attributes(state, message, subscription));
+    if let Some(trace_header) = trace_header_attribute(message) {
+        request = request.message_system_attributes(AWS_TRACE_HEADER, trace_header);
+    }
+    if is_fifo_queue(queue_url) {
+        request = request
+            .set_message_group_id(message.message_group_id.clone())
+            .set_message_deduplication_id(message.message_deduplication_id.clone());
+    }
+
+    let delays = retry_delays(state, subscription);
+    match send_with_retries(queue_url, &delays, || request.clone().send()).await {
+        Ok((_, attempts)) => {
+            if state.sample_delivery_log() {
+                tracing::info!("Message sent to SQS queue: {}", queue_url);
+            }
+            Ok(attempts)
+        }
+        Err(e) => {
+            tracing::error!(
+                "Failed to send message to SQS queue: {}, error: {}",
+                queue_url,
+                e
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Largest total payload of one SendMessageBatch: the bodies and message
+/// attributes of all its entries.
+const MAX_BATCH_PAYLOAD: usize = MAX_MESSAGE_SIZE;
+
+/// The bytes an entry counts toward `MAX_BATCH_PAYLOAD`: its body, and the
+/// name, data type and value of each message attribute.
+fn entry_size(entry: &SendMessageBatchRequestEntry) -> usize {
+    let attributes: usize = entry
+        .message_attributes()
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| {
+            name.len()
+                + value.data_type().len()
+                + value.string_value().map_or(0, str::len)
+                + value.binary_value().map_or(0, |blob| blob.as_ref().len())
+        })
+        .sum();
+    entry.message_body().len() + attributes
+}
+
+/// Groups entries, in order, into batches whose payload stays within
+/// `MAX_BATCH_PAYLOAD`. An entry is never split, so one that is too large
+/// on its own goes alone and SQS rejects it.
+fn split_by_payload(
+    entries: Vec<SendMessageBatchRequestEntry>,
+) -> Vec<Vec<SendMessageBatchRequestEntry>> {
+    let mut batches: Vec<Vec<SendMessageBatchRequestEntry>> = vec![];
+    let mut batch_size = 0;
+    for entry in entries {
+        let size = entry_size(&entry);
+        match batches.last_mut() {
+            Some(batch) if batch_size + size <= MAX_BATCH_PAYLOAD => {
+                batch_size += size;
+                batch.push(entry);
+            }
+            _ => {
+                batch_size = size;
+                batches.push(vec![entry]);
+            }
+        }
+    }
+    batches
+}
+
+/// Sends the messages of one PublishBatch call to the queue `endpoint`
+/// names, as few SendMessageBatch calls as the batch payload limit allows,
+/// returning one result per message.
+pub async fn send_batch(
+    state: &AppState,
+    subscription: &Subscription,
+    messages: &[Message],
+    endpoint: &str,
+) -> Vec<DeliveryResult> {
+    let queue_url = &state.sqs_queue_urls.queue_url(endpoint);
+    let fifo = is_fifo_queue(queue_url);
+    let mut results: Vec<DeliveryResult> = vec![Ok(1); messages.len()];
+    let mut entries = Vec::with_capacity(messages.len());
+    for (index, message) in messages.iter().enumerate() {
+        let mut entry = SendMessageBatchRequestEntry::builder()
+            .id(index.to_string())
+            .message_body(message_body(state, message, subscription))
+            .set_message_attributes(sqs_message_attributes(state, message, subscription));
+        if let Some(trace_header) = trace_header_attribute(message) {
+            entry = entry.message_system_attributes(AWS_TRACE_HEADER, trace_header);
+        }
+        if fifo {
+            entry = entry
+                .set_message_group_id(message.message_group_id.clone())
+                .set_message_deduplication_id(message.message_deduplication_id.clone());
+        }
+        match entry.build() {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                tracing::error!("Failed to build SQS batch entry: {}", e);
+                results[index] = Err(e.to_string().into());
+            }
+        }
+    }
+
+    let client = client(state, endpoint).await;
+    let delays = retry_delays(state, subscription);
+    for batch in split_by_payload(entries) {
+        let indices: Vec<usize> = batch
+            .iter()
+            .filter_map(|entry| entry.id().parse().ok())
+            .collect();
+        let request = client
+            .send_message_batch()
+            .queue_url(queue_url)
+            .set_entries(Some(batch));
+        match send_with_retries(queue_url, &delays, || request.clone().send()).await {
+            Ok((output, attempts)) => {
+                for &index in &indices {
+                    results[index] = Ok(attempts);
+                }
+                for failed in output.failed() {
+                    if let Ok(index) = failed.id().parse::<usize>()
+                        && index < results.len()
+                    {
+                        let kind = error_code_kind(failed.code()).unwrap_or_default();
+                        results[index] = Err(DeliveryError::new(
+                            kind,
+                            failed.message().unwrap_or(failed.code()),
+                        )
+                        .after(attempts));
+                    }
+                    tracing::error!(
+                        "Failed to send batch entry {} to SQS queue: {}, error: {}",
+                        failed.id(),
+                        queue_url,
+                        failed.message().unwrap_or(failed.code())
+                    );
+                }
+                if state.sample_delivery_log() {
+                    tracing::info!(
+                        "{} messages sent to SQS queue: {}",
+                        output.successful().len(),
+                        queue_url
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to send message batch to SQS queue: {}, error: {}",
+                    queue_url,
+                    e
+                );
+                for index in indices {
+                    results[index] = Err(e.clone());
+                }
+            }
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_sqs::operation::send_message::SendMessageError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn entry(id: usize, body_len: usize) -> SendMessageBatchRequestEntry {
+        SendMessageBatchRequestEntry::builder()
+            .id(id.to_string())
+            .message_body("x".repeat(body_len))
+            .build()
+            .unwrap()
+    }
+
+    fn ids(batches: &[Vec<SendMessageBatchRequestEntry>]) -> Vec<Vec<&str>> {
+        batches
+            .iter()
+            .map(|batch| batch.iter().map(|entry| entry.id()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn batches_stay_within_the_payload_limit() {
+        let entries = vec![
+            entry(0, 100_000),
+            entry(1, 100_000),
+            entry(2, 100_000),
+            entry(3, 10),
+        ];
+        let batches = split_by_payload(entries);
+        assert_eq!(ids(&batches), vec![vec!["0", "1"], vec!["2", "3"]]);
+    }
+
+    #[test]
+    fn small_batches_are_sent_whole() {
+        let batches = split_by_payload((0..10).map(|id| entry(id, 1000)).collect());
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].len(), 10);
+    }
+
+    #[test]
+    fn attributes_count_toward_the_payload() {
+        let attribute = SqsMessageAttributeValue::builder()
+            .data_type("String")
+            .string_value("v".repeat(200_000))
+            .build()
+            .unwrap();
+        let with_attribute = SendMessageBatchRequestEntry::builder()
+            .id("0")
+            .message_body("body")
+            .message_attributes("name", attribute)
+            .build()
+            .unwrap();
+        assert_eq!(entry_size(&with_attribute), 4 + 4 + 6 + 200_000);
+        let batches = split_by_payload(vec![with_attribute, entry(1, 100_000)]);
+        assert_eq!(ids(&batches), vec![vec!["0"], vec!["1"]]);
+    }
+
+    #[test]
+    fn error_codes_are_classified_in_either_protocol() {
+        for code in [
+            "AWS.SimpleQueueService.NonExistentQueue",
+            "QueueDoesNotExist",
+        ] {
+            assert_eq!(
+                error_code_kind(code),
+                Some(DeliveryErrorKind::QueueDoesNotExist)
+            );
+        }
+        for code in ["AccessDenied", "AccessDeniedException", "KmsAccessDenied"] {
+            assert_eq!(error_code_kind(code), Some(DeliveryErrorKind::AccessDenied));
+        }
+        for code in ["Throttling", "ThrottlingException", "RequestThrottled"] {
+            assert_eq!(error_code_kind(code), Some(DeliveryErrorKind::Throttling));
+        }
+        assert_eq!(error_code_kind("InvalidMessageContents"), None);
+    }
+
+    #[test]
+    fn unknown_codes_are_classified_by_status() {
+        assert_eq!(
+            service_error_kind(Some("QueueDoesNotExist"), 400),
+            DeliveryErrorKind::QueueDoesNotExist
+        );
+        assert_eq!(
+            service_error_kind(Some("Unknown"), 403),
+            DeliveryErrorKind::AccessDenied
+        );
+        assert_eq!(service_error_kind(None, 429), DeliveryErrorKind::Throttling);
+        assert_eq!(service_error_kind(None, 500), DeliveryErrorKind::Other);
+    }
+
+    #[test]
+    fn unanswered_calls_are_network_errors() {
+        let timeout = SdkError::<SendMessageError>::timeout_error("timed out");
+        assert_eq!(error_kind(&timeout), DeliveryErrorKind::Network);
+        let unbuilt = SdkError::<SendMessageError>::construction_failure("no region");
+        assert_eq!(error_kind(&unbuilt), DeliveryErrorKind::Other);
+    }
+
+    /// Runs `send_with_retries` against a send failing every time with
+    /// `error`, returning the attempts made and the final error.
+    async fn attempts(
+        delays: &[Duration],
+        error: fn() -> SdkError<SendMessageError>,
+    ) -> (usize, DeliveryError) {
+        let calls = AtomicUsize::new(0);
+        let result = send_with_retries::<(), _, _>("queue", delays, || {
+            calls.fetch_add(1, Ordering::Relaxed);
+            async move { Err(error()) }
+        })
+        .await;
+        (calls.into_inner(), result.unwrap_err())
+    }
+
+    #[tokio::test]
+    async fn retryable_errors_are_retried_as_the_policy_says() {
+        let delays = [Duration::ZERO; 3];
+        let (calls, error) = attempts(&delays, || SdkError::timeout_error("timed out")).await;
+        assert_eq!(calls, 4);
+        assert_eq!(error.kind, DeliveryErrorKind::Network);
+        let (calls, _) = attempts(&[], || SdkError::timeout_error("timed out")).await;
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        let (calls, error) = attempts(&[Duration::ZERO; 3], || {
+            SdkError::construction_failure("no region")
+        })
+        .await;
+        assert_eq!(calls, 1);
+        assert_eq!(error.kind, DeliveryErrorKind::Other);
+    }
+
+    fn attribute(
+        data_type: &str,
+        string_value: Option<&str>,
+        binary_value: Option<&str>,
+    ) -> MessageAttributeValue {
+        MessageAttributeValue {
+            data_type: data_type.to_string(),
+            string_value: string_value.map(str::to_string),
+            binary_value: binary_value.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn string_arrays_travel_as_json_strings() {
+        let value =
+            sqs_message_attribute(&attribute("String.Array", Some(r#"["a","b"]"#), None)).unwrap();
+        assert_eq!(value.data_type(), "String");
+        assert_eq!(value.string_value(), Some(r#"["a","b"]"#));
+        assert!(value.binary_value().is_none());
+    }
+
+    #[test]
+    fn numbers_and_strings_keep_their_data_type() {
+        for data_type in ["String", "String.custom", "Number", "Number.float"] {
+            let value = sqs_message_attribute(&attribute(data_type, Some("1.5"), None)).unwrap();
+            assert_eq!(value.data_type(), data_type);
+            assert_eq!(value.string_value(), Some("1.5"));
+        }
+    }
+
+    #[test]
+    fn binary_values_are_sent_as_bytes() {
+        for data_type in ["Binary", "Binary.gzip"] {
+            let value = sqs_message_attribute(&attribute(data_type, None, Some("AQID"))).unwrap();
+            assert_eq!(value.data_type(), data_type);
+            assert_eq!(value.binary_value().unwrap().as_ref(), [1, 2, 3]);
+            assert!(value.string_value().is_none());
+        }
+        assert!(sqs_message_attribute(&attribute("Binary", None, Some("not base64!"))).is_none());
+    }
+
+    #[test]
+    fn attributes_are_forwarded_only_outside_the_envelope() {
+        let message: Message = serde_json::from_value(serde_json::json!({
+            "id": "m1",
+            "subject": null,
+            "body": "hello",
+            "timestamp": "2026-01-01T00:00:00Z",
+            "message_attributes": {
+                "event": {"data_type": "String", "string_value": "order"},
+                "broken": {"data_type": "Binary", "binary_value": "not base64!"}
+            }
+        }))
+        .unwrap();
+        let mut subscription = Subscription::new(
+            "arn:aws:sns:us-east-1:000000000000:orders",
+            "sqs".to_string(),
+            "http://localhost:4566/000000000000/orders".to_string(),
+        );
+
+        let state = AppState::new(crate::config::Config::default());
+        let attributes = sqs_message_attributes(&state, &message, &subscription).unwrap();
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(attributes["event"].string_value(), Some("order"));
+
+        let enveloping = AppState::new(crate::config::Config {
+            sqs_envelope: true,
+            ..Default::default()
+        });
+        assert!(sqs_message_attributes(&enveloping, &message, &subscription).is_none());
+        subscription.raw_message_delivery = true;
+        assert!(sqs_message_attributes(&enveloping, &message, &subscription).is_some());
+    }
+}
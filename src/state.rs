@@ -1,10 +1,181 @@
+use crate::audit::{AuditLog, AuditLogConfig, AuditRecord, DeliveryRecord};
+#[cfg(feature = "sqs-delivery")]
+use crate::client_cache::ClientCache;
+use crate::clock::Clock;
+use crate::config::{Config, EndpointCheck};
+use crate::delivery::{self, DeliveryResult, SqsQueueUrls};
+use crate::delivery_policy::{self, EffectiveDeliveryPolicy};
+use crate::delivery_status::DeliveryStatusWriter;
+use crate::demux::DemuxRoutes;
+use crate::error::{
+    SUBSCRIPTION_ROLE_ARN_REQUIRED, SnsError, invalid_message_attributes, invalid_parameter,
+};
+use crate::latency::LatencyProfile;
+use crate::memory::ReceivedMessage;
+use crate::platform::{PlatformApplication, PlatformEndpoint, PushDelivery};
+use crate::responses;
+use crate::scheduler::DeliveryScheduler;
+use crate::seed::ReloadStatus;
+use crate::stats::{
+    DeliveryGauges, LastDeliveryError, RetentionStats, SubscriptionHealth, SuspiciousStats,
+    TopicStats,
+};
+use crate::template::MessageTemplate;
+use crate::tracker::FanOutTracker;
+#[cfg(feature = "sqs-delivery")]
 use aws_sdk_sqs::Client;
+use axum::http::{HeaderMap, HeaderName};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use dashmap::DashMap;
-use serde::Deserialize;
-use serde::de::{self, Deserializer, MapAccess, Visitor};
-use std::collections::HashMap;
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fmt;
-use std::sync::Arc;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use url::Url;
+use uuid::Uuid;
+
+/// Number of published messages retained per topic for the admin API.
+pub const MESSAGE_HISTORY_LIMIT: usize = 1000;
+
+/// Published messages buffered for each `/admin/messages/stream` client
+/// before a slow one starts missing them.
+pub const MESSAGE_EVENT_CAPACITY: usize = 256;
+
+/// How long FIFO topics drop publishes repeating a MessageDeduplicationId.
+pub const DEDUPLICATION_WINDOW: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Most entries a single PublishBatch call may carry.
+pub const MAX_BATCH_ENTRIES: usize = 10;
+
+/// The only SNS API version there is.
+pub const API_VERSION: &str = "2010-03-31";
+
+/// Longest alphanumeric sender id SMS carriers accept.
+pub const SMS_SENDER_ID_MAX_LEN: usize = 11;
+
+/// Access policy a topic reports until one is set.
+pub const DEFAULT_TOPIC_POLICY: &str =
+    r#"{"Version":"2012-10-17","Id":"__default_policy_ID","Statement":[]}"#;
+
+/// Largest access policy document SNS accepts, in bytes.
+pub const MAX_POLICY_SIZE: usize = 30 * 1024;
+
+/// Whether XML 1.0 can carry `value`: every character is a tab, a line
+/// break or at least U+0020, and none is U+FFFE or U+FFFF. quick_xml
+/// escapes markup but writes other characters as they are, so a stored
+/// value failing this would break every later response that renders it.
+pub fn is_xml_text(value: &str) -> bool {
+    value.chars().all(|c| {
+        matches!(c, '\t' | '\n' | '\r') || (c >= ' ' && c != '\u{FFFE}' && c != '\u{FFFF}')
+    })
+}
+
+/// Checks a parameter value that is stored and rendered into responses
+/// later, such as a subscription attribute.
+pub fn validate_xml_text(field: &str, value: &str) -> Result<(), String> {
+    if !is_xml_text(value) {
+        return Err(invalid_parameter(field));
+    }
+    Ok(())
+}
+
+/// Checks a tag. Like AWS, keys and values may not hold control
+/// characters, not even line breaks.
+pub fn validate_tag(key: &str, value: &str) -> Result<(), String> {
+    if key.chars().chain(value.chars()).any(char::is_control)
+        || !is_xml_text(key)
+        || !is_xml_text(value)
+    {
+        return Err(invalid_parameter("Tags"));
+    }
+    Ok(())
+}
+
+/// Checks the value of a topic attribute whose shape SNS validates. The
+/// value itself is stored verbatim once it passes.
+pub fn validate_topic_attribute(name: &str, value: &str) -> Result<(), String> {
+    match name {
+        "Policy" if !is_valid_policy(value) => Err(invalid_parameter("Policy")),
+        "DisplayName" if value.chars().any(char::is_control) => {
+            Err(invalid_parameter("DisplayName"))
+        }
+        "FifoThroughputScope" if !matches!(value, "Topic" | "MessageGroup") => {
+            Err(invalid_parameter("FifoThroughputScope"))
+        }
+        _ => validate_xml_text(name, value),
+    }
+}
+
+/// A policy is a JSON object of at most `MAX_POLICY_SIZE` bytes with a
+/// string `Version` and a `Statement` that is a statement object or a list
+/// of them.
+fn is_valid_policy(policy: &str) -> bool {
+    if policy.len() > MAX_POLICY_SIZE {
+        return false;
+    }
+    let Ok(serde_json::Value::Object(document)) = serde_json::from_str(policy) else {
+        return false;
+    };
+    let version = matches!(document.get("Version"), Some(serde_json::Value::String(_)));
+    let statement = match document.get("Statement") {
+        Some(serde_json::Value::Object(_)) => true,
+        Some(serde_json::Value::Array(statements)) => statements.iter().all(|s| s.is_object()),
+        _ => false,
+    };
+    version && statement
+}
+
+/// Topic attributes SNS reports for topics that never set them. Configured
+/// defaults are layered on top, see `topic_defaults`.
+pub const BUILTIN_TOPIC_DEFAULTS: &[(&str, &str)] = &[
+    ("Policy", DEFAULT_TOPIC_POLICY),
+    ("FirehoseSuccessFeedbackSampleRate", "0"),
+    ("SQSSuccessFeedbackSampleRate", "0"),
+    ("HTTPSuccessFeedbackSampleRate", "0"),
+    ("ApplicationSuccessFeedbackSampleRate", "0"),
+    ("LambdaSuccessFeedbackSampleRate", "0"),
+];
+
+/// Builds the topic defaults table from the built-in defaults and the
+/// configured overrides, which must name attributes SetTopicAttributes
+/// accepts.
+pub fn topic_defaults(
+    configured: BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut defaults: BTreeMap<String, String> = BUILTIN_TOPIC_DEFAULTS
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    let mut scratch = Topic::new(String::new(), String::new(), HashMap::new());
+    for (name, value) in configured {
+        if !scratch.set_attribute(&name, value.clone()) {
+            return Err(format!("unknown topic attribute {}", name));
+        }
+        validate_topic_attribute(&name, &value).map_err(|e| format!("{}: {}", name, e))?;
+        defaults.insert(name, value);
+    }
+    Ok(defaults)
+}
+
+pub const SUPPORTED_PROTOCOLS: &[&str] = &[
+    "http",
+    "https",
+    "email",
+    "email-json",
+    "sms",
+    "sqs",
+    "application",
+    "lambda",
+    "firehose",
+    "memory",
+    "demux",
+];
 
 // 1. Core Data Structures
 #[derive(Debug, Clone)]
@@ -38,6 +209,141 @@ pub struct Topic {
     pub fifo_topic: Option<String>,
     pub archive_policy: Option<String>,
     pub fifo_throughput_scope: Option<String>,
+    /// Last SequenceNumber handed out in each message group.
+    pub sequence_numbers: HashMap<String, u64>,
+    /// The latest `RECENT_SEQUENCE_NUMBERS` allocations, oldest first.
+    pub recent_sequence_numbers: VecDeque<SequencedMessage>,
+    /// FIFO publishes inside the deduplication window, keyed by
+    /// `deduplication_key`.
+    pub deduplication: HashMap<String, DeduplicatedPublish>,
+    /// Subscriptions removed from the topic since it was created.
+    pub subscriptions_deleted: u64,
+    /// Creation or the latest publish or subscribe, whichever is later;
+    /// topics with a TTL expire relative to it.
+    pub last_used_at: chrono::DateTime<chrono::Utc>,
+    /// Until then reads and publishes find no such topic, as AWS's eventual
+    /// consistency may have it; see `AppState::consistency_delay`.
+    pub visible_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Sequence number allocations a FIFO topic remembers for debugging.
+pub const RECENT_SEQUENCE_NUMBERS: usize = 50;
+
+/// A SequenceNumber as AWS formats it: 20 zero-padded digits.
+pub fn format_sequence_number(counter: u64) -> String {
+    format!("{:020}", counter)
+}
+
+/// A SequenceNumber handed out to a FIFO publish.
+#[derive(Debug, Clone, Serialize)]
+pub struct SequencedMessage {
+    pub message_group_id: String,
+    pub sequence_number: String,
+    pub message_id: String,
+}
+
+/// The message a MessageDeduplicationId was first published as.
+#[derive(Debug, Clone)]
+pub struct DeduplicatedPublish {
+    pub message_id: String,
+    pub sequence_number: String,
+    pub published_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Message attribute naming the publisher, added with `--tag-publisher`.
+pub const PUBLISHER_ATTRIBUTE: &str = "local-sns-publisher";
+
+/// Who published a message, as far as the request tells: the SigV4 access
+/// key id when the request was signed, and the User-Agent and source IP.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Publisher {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_ip: Option<String>,
+}
+
+impl Publisher {
+    /// Reads the publisher of a request from its headers and peer address.
+    /// The access key id is the first component of the `Credential` in a
+    /// SigV4 `Authorization` header; the signature is not checked.
+    pub fn from_request(headers: &HeaderMap, source_ip: Option<IpAddr>) -> Self {
+        let access_key_id = headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once("Credential="))
+            .and_then(|(_, credential)| credential.split('/').next())
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string);
+        let user_agent = headers
+            .get("user-agent")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Publisher {
+            access_key_id,
+            user_agent,
+            source_ip: source_ip.map(|ip| ip.to_string()),
+        }
+    }
+
+    /// One line naming the publisher: the access key id when known, else
+    /// the User-Agent and source IP.
+    pub fn label(&self) -> String {
+        if let Some(access_key_id) = &self.access_key_id {
+            return access_key_id.clone();
+        }
+        match (&self.user_agent, &self.source_ip) {
+            (Some(user_agent), Some(source_ip)) => format!("{} ({})", user_agent, source_ip),
+            (Some(value), None) | (None, Some(value)) => value.clone(),
+            (None, None) => "unknown".to_string(),
+        }
+    }
+}
+
+/// Subscription attribute holding a JSON object of extra headers for
+/// HTTP/S deliveries, a local-sns extension. Values may reference
+/// environment variables as `${NAME}`.
+pub const HEADERS_ATTRIBUTE: &str = "X-LocalSns-Headers";
+
+/// Prefix of the subscription attributes this server adds to the AWS set.
+pub const EXTENSION_ATTRIBUTE_PREFIX: &str = "X-LocalSns-";
+
+/// Most tags AWS allows on one resource.
+pub const MAX_TAGS_PER_RESOURCE: usize = 50;
+
+/// Subscription attribute holding a `MessageTemplate` that `memory`
+/// deliveries are rendered with, a local-sns extension.
+pub const MESSAGE_TEMPLATE_ATTRIBUTE: &str = "X-LocalSns-MessageTemplate";
+
+/// Subscribe attribute asking for a test notification once the subscription
+/// is confirmed, a local-sns extension. Only read by Subscribe; it is not a
+/// stored attribute.
+pub const SEND_TEST_NOTIFICATION_ATTRIBUTE: &str = "X-LocalSns-SendTestNotification";
+
+/// What Subscribe returns for a subscription awaiting confirmation, unless
+/// asked for its ARN.
+pub const PENDING_CONFIRMATION_ARN: &str = "pending confirmation";
+
+/// What list responses show as the ARN of a subscription awaiting
+/// confirmation.
+pub const PENDING_CONFIRMATION_LISTED: &str = "PendingConfirmation";
+
+/// Message attribute naming the instance in notifications, with
+/// `--tag-instance`.
+pub const INSTANCE_ATTRIBUTE: &str = "local-sns-instance";
+
+/// How long the SubscribeURL of an UnsubscribeConfirmation can restore the
+/// subscription; SNS confirmation tokens are valid for three days.
+pub const UNSUBSCRIBE_TOKEN_TTL: Duration = Duration::from_secs(3 * 24 * 60 * 60);
+
+/// A removed subscription its UnsubscribeConfirmation can restore.
+#[derive(Debug, Clone)]
+pub struct Unsubscribed {
+    pub subscription: Subscription,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Debug, Clone)]
@@ -46,20 +352,1510 @@ pub struct Subscription {
     pub protocol: String,
     pub arn: String,
     pub subscription_arn: String,
+    pub raw_message_delivery: bool,
+    /// Whether the endpoint has yet to confirm the subscription.
+    pub pending_confirmation: bool,
+    /// Token of the SubscribeURL that confirms the subscription. It stays
+    /// valid after confirming, so the link may be visited again.
+    pub confirmation_token: Option<String>,
+    /// When `confirmation_token` was issued. ConfirmSubscription refuses the
+    /// token once `AppState::confirmation_token_ttl` has passed.
+    pub confirmation_issued_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// HTTP/S delivery policy overriding the topic's.
+    pub delivery_policy: Option<String>,
+    /// Tags copied from the topic at creation with `--inherit-tags`.
+    pub tags: HashMap<String, String>,
+    /// Extra headers of HTTP/S deliveries, see `HEADERS_ATTRIBUTE`. Values
+    /// are kept unexpanded.
+    pub headers: BTreeMap<String, String>,
+    /// IAM role SNS assumes to write to a Firehose delivery stream. Only
+    /// stored; no role is assumed.
+    pub subscription_role_arn: Option<String>,
+    /// See `MESSAGE_TEMPLATE_ATTRIBUTE`.
+    pub message_template: Option<MessageTemplate>,
+    /// See `SEND_TEST_NOTIFICATION_ATTRIBUTE`.
+    pub send_test_notification: bool,
+    /// Until then GetSubscriptionAttributes and ListSubscriptionsByTopic do
+    /// not find the subscription; deliveries reach it regardless.
+    pub visible_at: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Debug, Clone)]
+impl Subscription {
+    pub fn new(topic_arn: &str, protocol: String, endpoint: String) -> Self {
+        Subscription {
+            endpoint,
+            protocol,
+            arn: topic_arn.to_string(),
+            subscription_arn: format!("{}:{}", topic_arn, Uuid::new_v4()),
+            raw_message_delivery: false,
+            pending_confirmation: false,
+            confirmation_token: None,
+            confirmation_issued_at: None,
+            delivery_policy: None,
+            tags: HashMap::new(),
+            headers: BTreeMap::new(),
+            subscription_role_arn: None,
+            message_template: None,
+            send_test_notification: false,
+            visible_at: chrono::DateTime::<chrono::Utc>::MIN_UTC,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        chrono::Utc::now() >= self.visible_at
+    }
+
+    /// Points the subscription at another endpoint of its protocol, keeping
+    /// its ARN and attributes. Returns the endpoint it replaced.
+    pub fn set_endpoint(&mut self, endpoint: String) -> Result<String, String> {
+        validate_subscription(&self.protocol, &endpoint)?;
+        Ok(std::mem::replace(&mut self.endpoint, endpoint))
+    }
+
+    /// Stores a settable subscription attribute. Returns `false` for unknown
+    /// names or values of the wrong shape.
+    pub fn set_attribute(&mut self, name: &str, value: &str) -> bool {
+        match name {
+            "RawMessageDelivery" => match value {
+                "true" => self.raw_message_delivery = true,
+                "false" => self.raw_message_delivery = false,
+                _ => return false,
+            },
+            "DeliveryPolicy" => {
+                if delivery_policy::parse_subscription_policy(value).is_err() {
+                    return false;
+                }
+                self.delivery_policy = Some(value.to_string());
+            }
+            HEADERS_ATTRIBUTE => {
+                let Ok(headers) = serde_json::from_str::<BTreeMap<String, String>>(value) else {
+                    return false;
+                };
+                if headers
+                    .keys()
+                    .any(|name| HeaderName::from_bytes(name.as_bytes()).is_err())
+                {
+                    return false;
+                }
+                self.headers = headers;
+            }
+            "SubscriptionRoleArn" => {
+                if !value.starts_with("arn:") {
+                    return false;
+                }
+                self.subscription_role_arn = Some(value.to_string());
+            }
+            MESSAGE_TEMPLATE_ATTRIBUTE if value.is_empty() => self.message_template = None,
+            MESSAGE_TEMPLATE_ATTRIBUTE => match MessageTemplate::parse(value) {
+                Ok(template) => self.message_template = Some(template),
+                Err(_) => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// Settable attributes with non-default values, keyed by their AWS name.
+    pub fn explicit_attributes(&self) -> Vec<(&'static str, String)> {
+        let mut attributes = vec![];
+        if self.raw_message_delivery {
+            attributes.push(("RawMessageDelivery", "true".to_string()));
+        }
+        if let Some(delivery_policy) = &self.delivery_policy {
+            attributes.push(("DeliveryPolicy", delivery_policy.clone()));
+        }
+        if let Some(subscription_role_arn) = &self.subscription_role_arn {
+            attributes.push(("SubscriptionRoleArn", subscription_role_arn.clone()));
+        }
+        if let Some(message_template) = &self.message_template {
+            attributes.push((
+                MESSAGE_TEMPLATE_ATTRIBUTE,
+                message_template.source().to_string(),
+            ));
+        }
+        if !self.headers.is_empty() {
+            attributes.push((
+                HEADERS_ATTRIBUTE,
+                serde_json::to_string(&self.headers).unwrap(),
+            ));
+        }
+        attributes
+    }
+
+    /// The `HEADERS_ATTRIBUTE` value GetSubscriptionAttributes reports:
+    /// header names with masked values, unless secrets are exposed.
+    pub fn reported_headers(&self, expose_secrets: bool) -> Option<String> {
+        if self.headers.is_empty() {
+            return None;
+        }
+        let headers: BTreeMap<&str, &str> = self
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if expose_secrets {
+                    value.as_str()
+                } else {
+                    "***"
+                };
+                (name.as_str(), value)
+            })
+            .collect();
+        Some(serde_json::to_string(&headers).unwrap())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub id: String,
     pub subject: Option<String>,
     pub body: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_group_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_deduplication_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_header: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub message_attributes: BTreeMap<String, MessageAttributeValue>,
+    /// Base URL seen by the publisher; `AppState::base_url` when unset.
+    #[serde(skip)]
+    pub base_url: Option<String>,
+    /// `json` when the body holds a message per protocol.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_structure: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<Publisher>,
+    /// Made up by local-sns rather than published, such as the test
+    /// notification of a new subscription.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub synthetic: bool,
+}
+
+impl Message {
+    /// The body delivered to `key` (a protocol or push platform). With
+    /// MessageStructure=json that is the entry for `key` or else `default`.
+    pub fn body_for(&self, key: &str) -> String {
+        if self.message_structure.as_deref() != Some("json") {
+            return self.body.clone();
+        }
+        let Ok(serde_json::Value::Object(bodies)) = serde_json::from_str(&self.body) else {
+            return self.body.clone();
+        };
+        match bodies.get(key).or_else(|| bodies.get("default")) {
+            Some(serde_json::Value::String(body)) => body.clone(),
+            Some(body) => body.to_string(),
+            None => self.body.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageAttributeValue {
+    pub data_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub string_value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_value: Option<String>,
+}
+
+impl MessageAttributeValue {
+    /// The value as sent, whichever field carries it; Binary values stay
+    /// base64-encoded.
+    pub fn value(&self) -> &str {
+        self.string_value
+            .as_deref()
+            .or(self.binary_value.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn binary_bytes(&self) -> Option<Vec<u8>> {
+        BASE64.decode(self.binary_value.as_deref()?).ok()
+    }
+}
+
+impl Topic {
+    pub fn new(name: String, arn: String, tags: HashMap<String, String>) -> Self {
+        Topic {
+            name,
+            arn,
+            tags,
+            subscriptions: vec![],
+            display_name: None,
+            policy: None,
+            delivery_policy: None,
+            tracing_config: None,
+            firehose_failure_feedback_role_arn: None,
+            firehose_success_feedback_role_arn: None,
+            firehose_success_feedback_sample_rate: None,
+            http_failure_feedback_role_arn: None,
+            sqs_failure_feedback_role_arn: None,
+            sqs_success_feedback_role_arn: None,
+            sqs_success_feedback_sample_rate: None,
+            http_success_feedback_role_arn: None,
+            http_success_feedback_sample_rate: None,
+            application_failure_feedback_role_arn: None,
+            application_success_feedback_role_arn: None,
+            application_success_feedback_sample_rate: None,
+            lambda_failure_feedback_role_arn: None,
+            lambda_success_feedback_role_arn: None,
+            lambda_success_feedback_sample_rate: None,
+            kms_master_key_id: None,
+            signature_version: None,
+            content_based_deduplication: None,
+            fifo_topic: None,
+            archive_policy: None,
+            fifo_throughput_scope: None,
+            sequence_numbers: HashMap::new(),
+            recent_sequence_numbers: VecDeque::new(),
+            deduplication: HashMap::new(),
+            subscriptions_deleted: 0,
+            last_used_at: chrono::Utc::now(),
+            visible_at: chrono::DateTime::<chrono::Utc>::MIN_UTC,
+        }
+    }
+
+    /// Records a publish or subscribe, postponing expiry.
+    pub fn touch(&mut self) {
+        self.last_used_at = chrono::Utc::now();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        chrono::Utc::now() >= self.visible_at
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        self.fifo_topic.as_deref() == Some("true")
+    }
+
+    /// Whether the topic may take a subscription of `protocol`: FIFO topics
+    /// only deliver to queues, which here includes local `memory` buffers.
+    pub fn accepts_protocol(&self, protocol: &str) -> bool {
+        !self.is_fifo() || matches!(protocol, "sqs" | "memory")
+    }
+
+    /// The name recipients see as the sender: DisplayName, or the topic name
+    /// when none is set.
+    pub fn sender_name<'a>(&'a self, defaults: &'a BTreeMap<String, String>) -> &'a str {
+        self.attribute("DisplayName", defaults)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&self.name)
+    }
+
+    /// The SMS sender id AWS derives from the sender name: its first
+    /// `SMS_SENDER_ID_MAX_LEN` alphanumeric characters.
+    pub fn sms_sender_id(&self, defaults: &BTreeMap<String, String>) -> Option<String> {
+        let sender_id: String = self
+            .sender_name(defaults)
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .take(SMS_SENDER_ID_MAX_LEN)
+            .collect();
+        (!sender_id.is_empty()).then_some(sender_id)
+    }
+
+    /// The key a FIFO publish is deduplicated under. With
+    /// `FifoThroughputScope=MessageGroup` (high-throughput mode) a
+    /// MessageDeduplicationId only suppresses repeats within its message
+    /// group; with `Topic`, the default, it does so across the topic. The
+    /// MessageDeduplicationId itself reaches FIFO queues unchanged, so a
+    /// queue with `DeduplicationScope=messageGroup` agrees with the topic.
+    pub fn deduplication_key(
+        &self,
+        defaults: &BTreeMap<String, String>,
+        message_group_id: &str,
+        deduplication_id: &str,
+    ) -> String {
+        match self.attribute("FifoThroughputScope", defaults) {
+            Some("MessageGroup") => format!("{}\0{}", message_group_id, deduplication_id),
+            _ => deduplication_id.to_string(),
+        }
+    }
+
+    /// The earlier publish `key`, from `deduplication_key`, duplicates,
+    /// forgetting publishes that left the deduplication window.
+    pub fn find_duplicate(&mut self, key: &str) -> Option<&DeduplicatedPublish> {
+        let cutoff = chrono::Utc::now() - DEDUPLICATION_WINDOW;
+        self.deduplication
+            .retain(|_, publish| publish.published_at > cutoff);
+        self.deduplication.get(key)
+    }
+
+    /// Confirmed subscriptions, as reported by SubscriptionsConfirmed.
+    pub fn subscriptions_confirmed(&self) -> usize {
+        self.subscriptions
+            .iter()
+            .filter(|sub| !sub.pending_confirmation)
+            .count()
+    }
+
+    /// Subscriptions awaiting confirmation, as reported by
+    /// SubscriptionsPending.
+    pub fn subscriptions_pending(&self) -> usize {
+        self.subscriptions.len() - self.subscriptions_confirmed()
+    }
+
+    /// Allocates the next SequenceNumber of the message group, formatted
+    /// as AWS does, and remembers it among the recent allocations.
+    pub fn next_sequence_number(&mut self, message_group_id: &str, message_id: &str) -> String {
+        let counter = self
+            .sequence_numbers
+            .entry(message_group_id.to_string())
+            .or_default();
+        *counter += 1;
+        let sequence_number = format_sequence_number(*counter);
+        self.recent_sequence_numbers.push_back(SequencedMessage {
+            message_group_id: message_group_id.to_string(),
+            sequence_number: sequence_number.clone(),
+            message_id: message_id.to_string(),
+        });
+        if self.recent_sequence_numbers.len() > RECENT_SEQUENCE_NUMBERS {
+            self.recent_sequence_numbers.pop_front();
+        }
+        sequence_number
+    }
+
+    /// Stores a settable topic attribute. Returns `false` for unknown names.
+    pub fn set_attribute(&mut self, name: &str, value: String) -> bool {
+        let slot = match name {
+            "DisplayName" => &mut self.display_name,
+            "Policy" => &mut self.policy,
+            "DeliveryPolicy" => &mut self.delivery_policy,
+            "TracingConfig" => &mut self.tracing_config,
+            "FirehoseSuccessFeedbackSampleRate" => &mut self.firehose_success_feedback_sample_rate,
+            "FirehoseFailureFeedbackRoleArn" => &mut self.firehose_failure_feedback_role_arn,
+            "FirehoseSuccessFeedbackRoleArn" => &mut self.firehose_success_feedback_role_arn,
+            "HTTPFailureFeedbackRoleArn" => &mut self.http_failure_feedback_role_arn,
+            "SQSSuccessFeedbackSampleRate" => &mut self.sqs_success_feedback_sample_rate,
+            "SQSFailureFeedbackRoleArn" => &mut self.sqs_failure_feedback_role_arn,
+            "SQSSuccessFeedbackRoleArn" => &mut self.sqs_success_feedback_role_arn,
+            "HTTPSuccessFeedbackSampleRate" => &mut self.http_success_feedback_sample_rate,
+            "HTTPSuccessFeedbackRoleArn" => &mut self.http_success_feedback_role_arn,
+            "ApplicationSuccessFeedbackSampleRate" => {
+                &mut self.application_success_feedback_sample_rate
+            }
+            "ApplicationFailureFeedbackRoleArn" => &mut self.application_failure_feedback_role_arn,
+            "ApplicationSuccessFeedbackRoleArn" => &mut self.application_success_feedback_role_arn,
+            "LambdaSuccessFeedbackSampleRate" => &mut self.lambda_success_feedback_sample_rate,
+            "LambdaFailureFeedbackRoleArn" => &mut self.lambda_failure_feedback_role_arn,
+            "LambdaSuccessFeedbackRoleArn" => &mut self.lambda_success_feedback_role_arn,
+            "KmsMasterKeyId" => &mut self.kms_master_key_id,
+            "SignatureVersion" => &mut self.signature_version,
+            "ContentBasedDeduplication" => &mut self.content_based_deduplication,
+            "FifoTopic" => &mut self.fifo_topic,
+            "ArchivePolicy" => &mut self.archive_policy,
+            "FifoThroughputScope" => &mut self.fifo_throughput_scope,
+            _ => return false,
+        };
+        *slot = Some(value);
+        true
+    }
+
+    /// The value of attribute `name`: the topic's own, else the default.
+    /// Defaults are merged in when read rather than stored on the topic, so
+    /// exports only hold what was set and a changed default reaches
+    /// existing topics.
+    pub fn attribute<'a>(
+        &'a self,
+        name: &str,
+        defaults: &'a BTreeMap<String, String>,
+    ) -> Option<&'a str> {
+        self.explicit_attributes()
+            .into_iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+            .or_else(|| defaults.get(name).map(String::as_str))
+    }
+
+    /// Sets every attribute in `defaults` the topic has not set itself, on
+    /// a copy reported by GetTopicAttributes.
+    pub fn apply_defaults(&mut self, defaults: &BTreeMap<String, String>) {
+        let explicit: HashSet<&'static str> = self
+            .explicit_attributes()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        for (name, value) in defaults {
+            if !explicit.contains(name.as_str()) {
+                self.set_attribute(name, value.clone());
+            }
+        }
+    }
+
+    /// Attributes that were explicitly set, keyed by their AWS name.
+    pub fn explicit_attributes(&self) -> Vec<(&'static str, &str)> {
+        let attributes = [
+            ("DisplayName", &self.display_name),
+            ("Policy", &self.policy),
+            ("DeliveryPolicy", &self.delivery_policy),
+            ("TracingConfig", &self.tracing_config),
+            (
+                "FirehoseSuccessFeedbackSampleRate",
+                &self.firehose_success_feedback_sample_rate,
+            ),
+            (
+                "FirehoseFailureFeedbackRoleArn",
+                &self.firehose_failure_feedback_role_arn,
+            ),
+            (
+                "FirehoseSuccessFeedbackRoleArn",
+                &self.firehose_success_feedback_role_arn,
+            ),
+            (
+                "HTTPFailureFeedbackRoleArn",
+                &self.http_failure_feedback_role_arn,
+            ),
+            (
+                "SQSSuccessFeedbackSampleRate",
+                &self.sqs_success_feedback_sample_rate,
+            ),
+            (
+                "SQSFailureFeedbackRoleArn",
+                &self.sqs_failure_feedback_role_arn,
+            ),
+            (
+                "SQSSuccessFeedbackRoleArn",
+                &self.sqs_success_feedback_role_arn,
+            ),
+            (
+                "HTTPSuccessFeedbackSampleRate",
+                &self.http_success_feedback_sample_rate,
+            ),
+            (
+                "HTTPSuccessFeedbackRoleArn",
+                &self.http_success_feedback_role_arn,
+            ),
+            (
+                "ApplicationSuccessFeedbackSampleRate",
+                &self.application_success_feedback_sample_rate,
+            ),
+            (
+                "ApplicationFailureFeedbackRoleArn",
+                &self.application_failure_feedback_role_arn,
+            ),
+            (
+                "ApplicationSuccessFeedbackRoleArn",
+                &self.application_success_feedback_role_arn,
+            ),
+            (
+                "LambdaSuccessFeedbackSampleRate",
+                &self.lambda_success_feedback_sample_rate,
+            ),
+            (
+                "LambdaFailureFeedbackRoleArn",
+                &self.lambda_failure_feedback_role_arn,
+            ),
+            (
+                "LambdaSuccessFeedbackRoleArn",
+                &self.lambda_success_feedback_role_arn,
+            ),
+            ("KmsMasterKeyId", &self.kms_master_key_id),
+            ("SignatureVersion", &self.signature_version),
+            (
+                "ContentBasedDeduplication",
+                &self.content_based_deduplication,
+            ),
+            ("FifoTopic", &self.fifo_topic),
+            ("ArchivePolicy", &self.archive_policy),
+            ("FifoThroughputScope", &self.fifo_throughput_scope),
+        ];
+        attributes
+            .into_iter()
+            .filter_map(|(key, value)| value.as_deref().map(|value| (key, value)))
+            .collect()
+    }
+}
+
+/// Region and account of every ARN this server hands out.
+pub const REGION: &str = "us-east-1";
+pub const ACCOUNT_ID: &str = "000000000000";
+
+pub fn topic_arn(name: &str) -> String {
+    format!("arn:aws:sns:{}:{}:{}", REGION, ACCOUNT_ID, name)
+}
+
+/// Whether an SNS ARN is in this server's region and account. Values not
+/// shaped like an ARN are left for the name lookup to reject.
+pub fn is_local_arn(arn: &str) -> bool {
+    let parts: Vec<&str> = arn.splitn(6, ':').collect();
+    match parts.as_slice() {
+        ["arn", _, "sns", region, account, _] => *region == REGION && *account == ACCOUNT_ID,
+        _ => true,
+    }
+}
+
+/// The topic name a topic ARN refers to, which is also its key in
+/// `AppState::topics`. Names are case-sensitive, so `Orders` and `orders`
+/// are distinct topics; nothing here or in lookups folds case.
+pub fn topic_name_from_arn(topic_arn: &str) -> &str {
+    topic_arn.rsplit(':').next().unwrap_or_default()
+}
+
+/// A subscription ARN, the ARN of its topic followed by `:<uuid>`:
+/// `arn:<partition>:sns:<region>:<account>:<topic>:<uuid>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionArn<'a> {
+    pub topic_arn: &'a str,
+    pub topic_name: &'a str,
+    pub id: Uuid,
+}
+
+impl<'a> SubscriptionArn<'a> {
+    /// Checks every segment: a partition, `sns`, a region, a 12-digit
+    /// account, a valid topic name and a hyphenated UUID. Whether the
+    /// region and account are this server's is left to the ARN scope check.
+    pub fn parse(arn: &'a str) -> Result<Self, String> {
+        let invalid = || invalid_parameter("SubscriptionArn");
+        let (topic_arn, id) = arn.rsplit_once(':').ok_or_else(invalid)?;
+        let parts: Vec<&str> = topic_arn.split(':').collect();
+        let ["arn", partition, "sns", region, account, topic_name] = parts.as_slice() else {
+            return Err(invalid());
+        };
+        let is_word = |value: &str| {
+            !value.is_empty()
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        };
+        if !is_word(partition)
+            || !is_word(region)
+            || account.len() != 12
+            || !account.chars().all(|c| c.is_ascii_digit())
+            || validate_topic_name(topic_name).is_err()
+            || id.len() != 36
+        {
+            return Err(invalid());
+        }
+        let id = Uuid::try_parse(id).map_err(|_| invalid())?;
+        Ok(SubscriptionArn {
+            topic_arn,
+            topic_name,
+            id,
+        })
+    }
+}
+
+/// Topic names are 1-256 alphanumerics, hyphens or underscores, with an
+/// optional `.fifo` suffix.
+pub fn validate_topic_name(name: &str) -> Result<(), String> {
+    let base = name.strip_suffix(".fifo").unwrap_or(name);
+    if base.is_empty()
+        || name.len() > 256
+        || !base
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(invalid_parameter("Topic Name"));
+    }
+    Ok(())
+}
+
+pub fn validate_subscription(protocol: &str, endpoint: &str) -> Result<(), String> {
+    if !SUPPORTED_PROTOCOLS.contains(&protocol) {
+        return Err(invalid_parameter("Protocol"));
+    }
+    if endpoint.is_empty() {
+        return Err(invalid_parameter("Endpoint"));
+    }
+    if protocol == "demux" {
+        DemuxRoutes::parse(endpoint)?;
+    }
+    Ok(())
+}
+
+/// Largest message AWS accepts, counting the body and all message attributes.
+pub const MAX_MESSAGE_SIZE: usize = 262_144;
+
+/// Most message attributes one message may carry when it reaches SQS.
+pub const MAX_MESSAGE_ATTRIBUTES: usize = 10;
+
+const MAX_MESSAGE_ATTRIBUTE_NAME_LEN: usize = 256;
+
+const RESERVED_MESSAGE_ATTRIBUTE_PREFIXES: &[&str] = &["aws.", "amazon."];
+
+/// Reserved message attribute a publisher without an `X-Amzn-Trace-Id`
+/// header can pass its X-Ray trace header in. It is taken off the message
+/// rather than delivered.
+pub const TRACE_HEADER_MESSAGE_ATTRIBUTE: &str = "AWS.SNS.TraceHeader";
+
+/// Reserved-prefix names SNS itself documents (SMS and mobile push
+/// settings), and the trace header attribute.
+const ALLOWED_RESERVED_MESSAGE_ATTRIBUTES: &[&str] = &[
+    "AWS.SNS.MOBILE.",
+    "AWS.SNS.SMS.",
+    "AWS.MM.SMS.",
+    TRACE_HEADER_MESSAGE_ATTRIBUTE,
+];
+
+/// Validates message attributes as AWS does and keys them by name. Returns
+/// the attributes together with the bytes they count toward
+/// `MAX_MESSAGE_SIZE`: name, data type and value of each.
+pub fn validate_message_attributes(
+    entries: Vec<MessageAttributeEntry>,
+    limit: Option<usize>,
+) -> Result<(BTreeMap<String, MessageAttributeValue>, usize), String> {
+    if let Some(limit) = limit
+        && entries.len() > limit
+    {
+        return Err(invalid_message_attributes(&format!(
+            "Number of message attributes [{}] exceeds the allowed maximum [{}].",
+            entries.len(),
+            limit
+        )));
+    }
+
+    let mut attributes = BTreeMap::new();
+    let mut size = 0;
+    for entry in entries {
+        validate_message_attribute_name(&entry.name)?;
+        let name = entry.name;
+
+        let base_type = entry.data_type.split('.').next().unwrap_or_default();
+        if entry.data_type.is_empty() {
+            return Err(invalid_message_attributes(&format!(
+                "The message attribute '{}' must contain non-empty message attribute type.",
+                name
+            )));
+        }
+        let (value, field) = match base_type {
+            "String" | "Number" => (entry.string_value.as_deref(), "String"),
+            "Binary" => (entry.binary_value.as_deref(), "Binary"),
+            _ => {
+                return Err(invalid_message_attributes(&format!(
+                    "The message attribute '{}' has an invalid message attribute type, the set of supported type prefixes is Binary, Number, and String.",
+                    name
+                )));
+            }
+        };
+        let value = match value {
+            Some(value) if !value.is_empty() => value,
+            _ if entry.string_value.is_some() || entry.binary_value.is_some() => {
+                return Err(invalid_message_attributes(&format!(
+                    "The message attribute '{}' with type '{}' must use field '{}'.",
+                    name, base_type, field
+                )));
+            }
+            _ => {
+                return Err(invalid_message_attributes(&format!(
+                    "The message attribute '{}' must contain non-empty message attribute value for message attribute type '{}'.",
+                    name, base_type
+                )));
+            }
+        };
+        if base_type != "Binary" && !is_xml_text(value) {
+            return Err(invalid_message_attributes(&format!(
+                "The message attribute '{}' contains invalid characters.",
+                name
+            )));
+        }
+        if base_type == "Number" && value.parse::<f64>().is_err() {
+            return Err(invalid_message_attributes(&format!(
+                "Could not cast message attribute '{}' value to number.",
+                name
+            )));
+        }
+
+        if entry.data_type == "String.Array"
+            && !matches!(serde_json::from_str(value), Ok(serde_json::Value::Array(_)))
+        {
+            return Err(invalid_message_attributes(&format!(
+                "The message attribute '{}' with type 'String.Array' must be a JSON array.",
+                name
+            )));
+        }
+
+        // Binary values arrive base64-encoded; AWS counts the decoded bytes.
+        let value_size = if base_type == "Binary" {
+            match BASE64.decode(value) {
+                Ok(bytes) => bytes.len(),
+                Err(_) => {
+                    return Err(invalid_message_attributes(&format!(
+                        "The message attribute '{}' has an invalid binary value, it must be base64-encoded.",
+                        name
+                    )));
+                }
+            }
+        } else {
+            value.len()
+        };
+
+        size += name.len() + entry.data_type.len() + value_size;
+        let attribute = MessageAttributeValue {
+            data_type: entry.data_type,
+            string_value: entry.string_value,
+            binary_value: entry.binary_value,
+        };
+        if attributes.insert(name.clone(), attribute).is_some() {
+            return Err(invalid_message_attributes(&format!(
+                "Duplicate message attribute name '{}'.",
+                name
+            )));
+        }
+    }
+    Ok((attributes, size))
+}
+
+fn validate_message_attribute_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err(invalid_message_attributes(
+            "Message attribute name can not be null or empty.",
+        ));
+    }
+    if name.len() > MAX_MESSAGE_ATTRIBUTE_NAME_LEN {
+        return Err(invalid_message_attributes(&format!(
+            "Message attribute name can be up to {} characters long.",
+            MAX_MESSAGE_ATTRIBUTE_NAME_LEN
+        )));
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        return Err(invalid_message_attributes(&format!(
+            "Invalid non-alphanumeric character '#x{:X}' was found in the message attribute name. Can only include alphanumeric characters, hyphens, underscores, or dots.",
+            c as u32
+        )));
+    }
+    if name.starts_with('.') || name.ends_with('.') || name.contains("..") {
+        return Err(invalid_message_attributes(&format!(
+            "Message attribute name '{}' can not start or end with a period, or contain two consecutive periods.",
+            name
+        )));
+    }
+    let lowercase = name.to_ascii_lowercase();
+    if RESERVED_MESSAGE_ATTRIBUTE_PREFIXES
+        .iter()
+        .any(|prefix| lowercase.starts_with(prefix))
+        && !ALLOWED_RESERVED_MESSAGE_ATTRIBUTES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    {
+        return Err(invalid_message_attributes(&format!(
+            "Message attribute name '{}' uses a reserved prefix. You can't use the prefixes 'AWS.' or 'Amazon.'.",
+            name
+        )));
+    }
+    Ok(())
 }
 
 // 2. In-Memory Storage
+/// An email or SMS that would have been sent, kept for inspection.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxEntry {
+    pub protocol: String,
+    pub endpoint: String,
+    pub subscription_arn: String,
+    pub message_id: String,
+    /// The From display name for email, the sender id for SMS.
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+    pub body: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// A message added to a topic's history, as streamed to admin clients.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEvent {
+    pub topic: String,
+    pub message: Message,
+}
+
 pub struct AppState {
     pub topics: DashMap<String, Topic>,
-    pub sqs_clients: DashMap<String, Arc<Client>>,
+    #[cfg(feature = "sqs-delivery")]
+    pub sqs_clients: ClientCache<Client>,
+    #[cfg(feature = "http-delivery")]
+    pub http_client: crate::http_delivery::HttpClient,
+    /// Resolves the queue URLs and service endpoints of SQS subscriptions.
+    pub sqs_queue_urls: SqsQueueUrls,
+    #[cfg(feature = "payload-offload")]
+    pub s3_clients: DashMap<String, Arc<aws_sdk_s3::Client>>,
+    /// Recently published messages, keyed by topic name.
+    pub messages: DashMap<String, VecDeque<Message>>,
+    /// Every message recorded in the history; sending fails harmlessly
+    /// while nobody subscribes.
+    pub message_events: broadcast::Sender<HistoryEvent>,
+    pub seed_status: RwLock<Option<ReloadStatus>>,
+    pub stats: DashMap<String, Arc<TopicStats>>,
+    pub suspicious: SuspiciousStats,
+    /// Delivery health keyed by subscription ARN.
+    pub subscription_health: DashMap<String, SubscriptionHealth>,
+    /// Removed HTTP/S subscriptions keyed by the token of their
+    /// UnsubscribeConfirmation, which can restore them until they expire.
+    pub unsubscribed: DashMap<String, Unsubscribed>,
+    /// Injected response latency keyed by action name.
+    pub latency_profiles: DashMap<String, LatencyProfile>,
+    /// Platform applications keyed by ARN.
+    pub platform_applications: DashMap<String, PlatformApplication>,
+    /// Platform endpoints keyed by ARN.
+    pub platform_endpoints: DashMap<String, PlatformEndpoint>,
+    /// Push notifications delivered to each platform endpoint, oldest first.
+    pub push_outbox: DashMap<String, VecDeque<PushDelivery>>,
+    /// Deliveries to `memory` subscriptions keyed by buffer name, oldest
+    /// first.
+    pub memory_queues: DashMap<String, VecDeque<ReceivedMessage>>,
+    /// Delivered and failed counts of each route of `demux` subscriptions,
+    /// keyed by subscription ARN, then route.
+    pub demux_routes: DashMap<String, BTreeMap<String, (u64, u64)>>,
+    /// Email and SMS deliveries, oldest first.
+    pub outbox: RwLock<VecDeque<OutboxEntry>>,
+    /// Consecutive failed deliveries after which a subscription is disabled;
+    /// 0 never disables.
+    pub endpoint_failure_threshold: u32,
+    /// Attributes applied to new topics and reported for unset ones.
+    pub topic_defaults: BTreeMap<String, String>,
+    /// Externally reachable address of this server, used in generated URLs.
+    pub base_url: String,
+    /// Derive generated URLs from `X-Forwarded-*` headers when present.
+    pub trust_proxy_headers: bool,
+    /// Deliver every publish inline, as with `x-localsns-sync-delivery`.
+    pub sync_delivery: bool,
+    /// Fail synchronous publishes with InternalFailure when a delivery fails.
+    pub strict_sync: bool,
+    /// Fail publishes that would reach no subscription with UnroutedMessage.
+    pub fail_unrouted: bool,
+    /// Copy topic tags onto subscriptions created on it.
+    pub inherit_tags: bool,
+    pub confirm_email_subscriptions: bool,
+    pub confirmation_token_ttl: Duration,
+    /// Send every new subscription a test notification.
+    pub send_test_notification: bool,
+    /// Writer of the per-topic files in `--delivery-status-dir`; unset when
+    /// there is none or its writer failed to start.
+    pub delivery_status: Option<DeliveryStatusWriter>,
+    /// Writer of `--audit-log`; unset when it is off or failed to open.
+    pub audit: Option<AuditLog>,
+    /// See `Config::payload_offload_endpoint`; needs the `payload-offload`
+    /// feature.
+    pub payload_offload_endpoint: Option<String>,
+    pub payload_offload_bucket: String,
+    pub payload_offload_threshold: usize,
+    /// How long history messages and delivery records are kept, unless a
+    /// topic's `x-localsns-retention` tag says otherwise. Kept until evicted
+    /// by the history limit when unset.
+    pub message_retention: Option<Duration>,
+    /// Idle time after which topics are deleted, unless a topic's
+    /// `x-localsns-ttl` tag says otherwise. Topics live on when unset.
+    pub default_topic_ttl: Option<Duration>,
+    /// How long new topics and subscriptions stay invisible, as AWS's
+    /// eventual consistency may leave them for a moment.
+    pub consistency_delay: Duration,
+    pub retention: RetentionStats,
+    /// Log one in this many successful deliveries; 0 and 1 log all of them.
+    pub log_sample: u32,
+    deliveries_logged: AtomicU64,
+    /// Renders the timestamps of deliveries, shifted by `--clock-offset`.
+    pub clock: Clock,
+    /// Content-Type of raw HTTP/S deliveries.
+    pub raw_content_type: String,
+    /// Report `X-LocalSns-Headers` values unmasked.
+    pub expose_secrets: bool,
+    /// Report runtime state such as `X-LocalSns-LastDeliveryError` in
+    /// GetSubscriptionAttributes.
+    pub extended_attributes: bool,
+    /// Carry runtime subscription state through export and import.
+    pub persist_runtime_state: bool,
+    pub instance_name: String,
+    message_id_prefix: String,
+    /// Add `INSTANCE_ATTRIBUTE` to notification envelopes.
+    pub tag_instance: bool,
+    /// Add `PUBLISHER_ATTRIBUTE` to notification envelopes.
+    pub tag_publisher: bool,
+    /// Envelope SQS deliveries without RawMessageDelivery.
+    pub sqs_envelope: bool,
+    /// Simulated quotas of CreateTopic and Subscribe.
+    pub max_topics: Option<usize>,
+    pub max_subscriptions_per_topic: Option<usize>,
+    pub scheduler: DeliveryScheduler,
+    /// The configuration the server started with, redacted; `/admin/config`
+    /// serves it with the delivery tuning in effect.
+    pub startup_config: Config,
+    pub delivery_gauges: DeliveryGauges,
+    /// Background fan-outs accepted but not finished beyond which `/health`
+    /// reports 503.
+    pub max_delivery_backlog: Option<u64>,
+    /// Outstanding deliveries of each published message.
+    pub fan_outs: FanOutTracker,
+    /// Probe HTTP/S endpoints at Subscribe time.
+    pub validate_endpoints_on_subscribe: Option<EndpointCheck>,
+    /// Serve only the AWS surface; see `Config::strict_aws`.
+    pub strict_aws: bool,
+    /// Resolve ARNs of any region or account by name.
+    pub ignore_arn_scope: bool,
+    /// Serve the browser UI under `/ui`.
+    pub ui: bool,
+    /// Bearer token required on the admin API.
+    pub admin_token: Option<String>,
+}
+
+impl AppState {
+    /// Builds an empty server state from a validated `Config`.
+    pub fn new(config: Config) -> Self {
+        let topic_defaults = topic_defaults(config.defaults.clone()).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring configured topic defaults: {}", e);
+            topic_defaults(BTreeMap::new()).unwrap_or_default()
+        });
+        let instance_name = config.instance_name();
+        let sqs_queue_urls =
+            SqsQueueUrls::new(&config.sqs_endpoint, &config.sqs_queue_url_template).unwrap_or_else(
+                |e| {
+                    tracing::warn!("Ignoring configured SQS queue URL template: {}", e);
+                    SqsQueueUrls::new(&config.sqs_endpoint, "elasticmq").unwrap()
+                },
+            );
+        if let Some(namespace) = &config.xml_namespace {
+            responses::set_xml_namespace(namespace.clone());
+        }
+        let startup_config = config.redacted();
+        AppState {
+            topics: DashMap::new(),
+            #[cfg(feature = "sqs-delivery")]
+            sqs_clients: ClientCache::new("SQS", config.max_sqs_clients),
+            #[cfg(feature = "http-delivery")]
+            http_client: crate::http_delivery::client(),
+            sqs_queue_urls,
+            #[cfg(feature = "payload-offload")]
+            s3_clients: DashMap::new(),
+            messages: DashMap::new(),
+            message_events: broadcast::channel(MESSAGE_EVENT_CAPACITY).0,
+            seed_status: RwLock::new(None),
+            stats: DashMap::new(),
+            suspicious: SuspiciousStats::default(),
+            subscription_health: DashMap::new(),
+            unsubscribed: DashMap::new(),
+            latency_profiles: config.latency_profiles.clone().into_iter().collect(),
+            platform_applications: DashMap::new(),
+            platform_endpoints: DashMap::new(),
+            push_outbox: DashMap::new(),
+            memory_queues: DashMap::new(),
+            demux_routes: DashMap::new(),
+            outbox: RwLock::new(VecDeque::new()),
+            endpoint_failure_threshold: config.endpoint_failure_threshold,
+            topic_defaults,
+            base_url: config.base_url(),
+            trust_proxy_headers: config.trust_proxy_headers,
+            sync_delivery: config.sync_delivery,
+            strict_sync: config.strict_sync,
+            fail_unrouted: config.fail_unrouted,
+            inherit_tags: config.inherit_tags,
+            confirm_email_subscriptions: config.confirm_email_subscriptions,
+            confirmation_token_ttl: Duration::from_secs(config.confirmation_token_ttl_secs),
+            send_test_notification: config.send_test_notification,
+            delivery_status: config.delivery_status_dir.and_then(|dir| {
+                DeliveryStatusWriter::start(dir)
+                    .inspect_err(|e| {
+                        tracing::error!("Failed to start the delivery status writer: {}", e)
+                    })
+                    .ok()
+            }),
+            audit: config.audit_log.clone().and_then(|path| {
+                AuditLog::start(AuditLogConfig {
+                    path: path.clone(),
+                    max_bytes: config.audit_log_max_bytes,
+                    keep: config.audit_log_keep,
+                })
+                .inspect_err(|e| {
+                    tracing::error!("Failed to open audit log {}: {}", path.display(), e)
+                })
+                .ok()
+            }),
+            payload_offload_endpoint: config.payload_offload_endpoint,
+            payload_offload_bucket: config.payload_offload_bucket,
+            payload_offload_threshold: config.payload_offload_threshold,
+            message_retention: config.message_retention_secs.map(Duration::from_secs),
+            default_topic_ttl: config.default_topic_ttl_secs.map(Duration::from_secs),
+            consistency_delay: Duration::from_millis(config.consistency_delay_ms),
+            retention: RetentionStats::default(),
+            log_sample: config.log_sample,
+            deliveries_logged: AtomicU64::new(0),
+            clock: Clock::with_offset_secs(config.clock_offset_secs).unwrap_or_default(),
+            raw_content_type: config.raw_content_type,
+            expose_secrets: config.expose_secrets,
+            extended_attributes: config.extended_attributes,
+            persist_runtime_state: config.persist_runtime_state,
+            instance_name,
+            message_id_prefix: config.message_id_prefix.unwrap_or_default(),
+            tag_instance: config.tag_instance,
+            tag_publisher: config.tag_publisher,
+            sqs_envelope: config.sqs_envelope,
+            max_topics: config.max_topics,
+            max_subscriptions_per_topic: config.max_subscriptions_per_topic,
+            scheduler: DeliveryScheduler::new(startup_config.delivery_config()),
+            startup_config,
+            delivery_gauges: DeliveryGauges::default(),
+            max_delivery_backlog: config.max_delivery_backlog,
+            fan_outs: FanOutTracker::default(),
+            validate_endpoints_on_subscribe: config.validate_endpoints_on_subscribe,
+            strict_aws: config.strict_aws,
+            ignore_arn_scope: config.ignore_arn_scope,
+            ui: !config.no_ui,
+            admin_token: config.admin_token.clone(),
+        }
+    }
+
+    /// A fresh ID for a published message, with the configured prefix.
+    pub fn new_message_id(&self) -> String {
+        format!("{}{}", self.message_id_prefix, Uuid::new_v4())
+    }
+
+    /// Whether a successful delivery should be logged under the configured
+    /// sampling. Check it before building the log line so unsampled
+    /// deliveries skip the formatting too.
+    pub fn sample_delivery_log(&self) -> bool {
+        self.log_sample <= 1
+            || self
+                .deliveries_logged
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(u64::from(self.log_sample))
+    }
+
+    /// Counters of a topic, created on first use. Only the publish paths
+    /// call it, under the topic's guard, so a deleted topic never gets
+    /// counters back.
+    pub fn topic_stats(&self, topic_name: &str) -> Arc<TopicStats> {
+        self.stats
+            .entry(topic_name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Counters of a topic without creating them: zeroed ones not kept in
+    /// the map when the topic has none, as for a topic deleted while its
+    /// deliveries complete.
+    pub fn existing_topic_stats(&self, topic_name: &str) -> Arc<TopicStats> {
+        self.stats
+            .get(topic_name)
+            .map(|stats| stats.clone())
+            .unwrap_or_default()
+    }
+
+    /// Drops all topics, history and counters, and the latency profiles.
+    pub fn reset(&self) {
+        self.topics.clear();
+        self.messages.clear();
+        self.stats.clear();
+        self.subscription_health.clear();
+        self.unsubscribed.clear();
+        self.suspicious.reset();
+        self.outbox.write().unwrap().clear();
+        self.platform_applications.clear();
+        self.platform_endpoints.clear();
+        self.push_outbox.clear();
+        self.memory_queues.clear();
+        self.demux_routes.clear();
+        self.latency_profiles.clear();
+        self.scheduler.clear();
+        self.fan_outs.clear();
+    }
+
+    /// Removes a topic together with its subscriptions, history and
+    /// counters, and what its subscriptions left behind.
+    pub fn remove_topic(&self, topic_name: &str) -> Option<Topic> {
+        self.remove_topic_if(topic_name, |_| true)
+    }
+
+    /// Removes a topic as `remove_topic` does, provided `condition` holds
+    /// for it; it is checked under the topic's lock, so a racing publish
+    /// cannot slip in between.
+    pub fn remove_topic_if(
+        &self,
+        topic_name: &str,
+        condition: impl FnOnce(&Topic) -> bool,
+    ) -> Option<Topic> {
+        let (_, topic) = self
+            .topics
+            .remove_if(topic_name, |_, topic| condition(topic))?;
+        for subscription in &topic.subscriptions {
+            self.forget_subscription(subscription);
+        }
+        self.messages.remove(topic_name);
+        self.stats.remove(topic_name);
+        self.scheduler.remove_topic(topic_name);
+        Some(topic)
+    }
+
+    /// Removes a subscription and what it left behind.
+    pub fn remove_subscription(&self, subscription_arn: &str) -> Option<Subscription> {
+        let topic_arn = subscription_arn
+            .rsplit_once(':')
+            .map(|(topic_arn, _)| topic_arn)
+            .unwrap_or_default();
+        let topic_name = topic_name_from_arn(topic_arn);
+        let mut topic = self.topics.get_mut(topic_name)?;
+        let index = topic
+            .subscriptions
+            .iter()
+            .position(|s| s.subscription_arn == subscription_arn)?;
+        let subscription = topic.subscriptions.remove(index);
+        topic.subscriptions_deleted += 1;
+        drop(topic);
+        self.forget_subscription(&subscription);
+        Some(subscription)
+    }
+
+    /// Drops the health record and demux route counts of a removed
+    /// subscription, and its deliveries from its memory buffer, which
+    /// other subscriptions may share.
+    fn forget_subscription(&self, subscription: &Subscription) {
+        let subscription_arn = &subscription.subscription_arn;
+        self.subscription_health.remove(subscription_arn);
+        self.demux_routes.remove(subscription_arn);
+        if subscription.protocol == "memory" {
+            self.memory_queues
+                .remove_if_mut(&subscription.endpoint, |_, queue| {
+                    queue.retain(|received| &received.subscription_arn != subscription_arn);
+                    queue.is_empty()
+                });
+        }
+    }
+
+    /// Fails when creating the topic `name` would exceed `--max-topics`.
+    /// Counts every shard, so it must be called without holding a topic:
+    /// racing creates may overshoot the quota slightly, which a simulation
+    /// allows.
+    pub fn check_topic_quota(&self, name: &str) -> Result<(), SnsError> {
+        if let Some(max_topics) = self.max_topics
+            && !self.topics.contains_key(name)
+            && self.topics.len() >= max_topics
+        {
+            return Err(SnsError::topic_limit_exceeded());
+        }
+        Ok(())
+    }
+
+    /// Fails when `topic` cannot take another subscription under
+    /// `--max-subscriptions-per-topic`.
+    pub fn check_subscription_quota(&self, topic: &Topic) -> Result<(), SnsError> {
+        if self
+            .max_subscriptions_per_topic
+            .is_some_and(|max| topic.subscriptions.len() >= max)
+        {
+            return Err(SnsError::subscription_limit_exceeded());
+        }
+        Ok(())
+    }
+
+    /// Checks what a new subscription, attributes set, needs beyond a valid
+    /// protocol and endpoint: an `application` endpoint must exist, and
+    /// Firehose writes need a role on AWS.
+    pub fn check_new_subscription(&self, subscription: &Subscription) -> Result<(), SnsError> {
+        if subscription.protocol == "application"
+            && !self.platform_endpoints.contains_key(&subscription.endpoint)
+        {
+            return Err(SnsError::invalid_parameter("Endpoint"));
+        }
+        if subscription.protocol == "firehose" && subscription.subscription_role_arn.is_none() {
+            return Err(SnsError::invalid_parameter_message(
+                SUBSCRIPTION_ROLE_ARN_REQUIRED,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Keeps `subscription`, just removed, restorable through `token` for
+    /// `UNSUBSCRIBE_TOKEN_TTL`, forgetting the tokens that expired.
+    pub fn remember_unsubscribed(&self, token: &str, subscription: Subscription) {
+        let now = chrono::Utc::now();
+        self.unsubscribed
+            .retain(|_, unsubscribed| unsubscribed.expires_at > now);
+        self.unsubscribed.insert(
+            token.to_string(),
+            Unsubscribed {
+                subscription,
+                expires_at: now + UNSUBSCRIBE_TOKEN_TTL,
+            },
+        );
+    }
+
+    /// Restores the subscription of the topic removed with `token`, under
+    /// its old ARN, as visiting the SubscribeURL of its
+    /// UnsubscribeConfirmation does. When the endpoint was subscribed again
+    /// meanwhile, that subscription is returned instead. Fails for unknown
+    /// or expired tokens, deleted topics and full topics.
+    pub fn restore_unsubscribed(
+        &self,
+        topic_name: &str,
+        token: &str,
+    ) -> Result<Subscription, SnsError> {
+        let (_, unsubscribed) = self
+            .unsubscribed
+            .remove_if(token, |_, unsubscribed| {
+                topic_name_from_arn(&unsubscribed.subscription.arn) == topic_name
+            })
+            .filter(|(_, unsubscribed)| unsubscribed.expires_at > chrono::Utc::now())
+            .ok_or_else(|| SnsError::invalid_parameter("Token"))?;
+        let mut topic = self
+            .topics
+            .get_mut(topic_name)
+            .ok_or_else(SnsError::not_found_topic)?;
+        let subscription = unsubscribed.subscription;
+        if let Some(existing) = topic
+            .subscriptions
+            .iter()
+            .find(|s| s.protocol == subscription.protocol && s.endpoint == subscription.endpoint)
+        {
+            return Ok(existing.clone());
+        }
+        self.check_subscription_quota(&topic)?;
+        topic.subscriptions.push(subscription.clone());
+        topic.touch();
+        tracing::info!(
+            "Restored subscription {} through its UnsubscribeConfirmation",
+            subscription.subscription_arn
+        );
+        Ok(subscription)
+    }
+
+    /// The base URL clients reached this request through. Forwarded headers
+    /// are only consulted with `trust_proxy_headers`, so they cannot be used
+    /// to spoof generated links otherwise.
+    pub fn request_base_url(&self, headers: &HeaderMap) -> String {
+        if !self.trust_proxy_headers {
+            return self.base_url.clone();
+        }
+        let forwarded = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .map(str::trim)
+                .filter(|value| {
+                    !value.is_empty()
+                        && value.chars().all(|c| {
+                            c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']')
+                        })
+                })
+        };
+        let proto = forwarded("x-forwarded-proto").filter(|p| matches!(*p, "http" | "https"));
+        let host = forwarded("x-forwarded-host");
+        if proto.is_none() && host.is_none() {
+            return self.base_url.clone();
+        }
+
+        let fallback = Url::parse(&self.base_url).ok();
+        let proto = proto
+            .map(str::to_string)
+            .or_else(|| fallback.as_ref().map(|url| url.scheme().to_string()))
+            .unwrap_or_else(|| "http".to_string());
+        let Some(mut host) = host.or_else(|| forwarded("host")).map(str::to_string) else {
+            return self.base_url.clone();
+        };
+        let default_port = if proto == "https" { "443" } else { "80" };
+        if let Some(port) = forwarded("x-forwarded-port")
+            && port.chars().all(|c| c.is_ascii_digit())
+            && port != default_port
+            && !host.ends_with(']')
+            && !host.contains(':')
+        {
+            host = format!("{}:{}", host, port);
+        }
+        format!("{}://{}", proto, host)
+    }
+
+    /// The delivery policy HTTP/S deliveries to `subscription` follow.
+    pub fn effective_delivery_policy(
+        &self,
+        subscription: &Subscription,
+    ) -> EffectiveDeliveryPolicy {
+        let topic_name = topic_name_from_arn(&subscription.arn);
+        let topic_policy = self
+            .topics
+            .get(topic_name)
+            .and_then(|topic| topic.delivery_policy.clone())
+            .or_else(|| self.topic_defaults.get("DeliveryPolicy").cloned());
+        delivery_policy::effective_policy(
+            topic_policy.as_deref(),
+            subscription.delivery_policy.as_deref(),
+        )
+    }
+
+    /// Confirms a subscription, returning it and whether it was pending
+    /// until now. Confirming one that is not pending changes nothing.
+    pub fn confirm_subscription(&self, subscription_arn: &str) -> Option<(Subscription, bool)> {
+        let topic_arn = subscription_arn
+            .rsplit_once(':')
+            .map(|(topic_arn, _)| topic_arn)
+            .unwrap_or_default();
+        let mut topic = self.topics.get_mut(topic_name_from_arn(topic_arn))?;
+        let subscription = topic
+            .subscriptions
+            .iter_mut()
+            .find(|s| s.subscription_arn == subscription_arn)?;
+        let was_pending = std::mem::replace(&mut subscription.pending_confirmation, false);
+        Some((subscription.clone(), was_pending))
+    }
+
+    /// Whether ConfirmSubscription still accepts the confirmation token of
+    /// `subscription`.
+    pub fn confirmation_token_valid(&self, subscription: &Subscription) -> bool {
+        let ttl = chrono::Duration::from_std(self.confirmation_token_ttl)
+            .unwrap_or(chrono::Duration::MAX);
+        subscription
+            .confirmation_issued_at
+            .is_some_and(|issued_at| {
+                issued_at
+                    .checked_add_signed(ttl)
+                    .is_none_or(|expires_at| expires_at > chrono::Utc::now())
+            })
+    }
+
+    /// The token to send the subscription `subscription_arn`, awaiting
+    /// confirmation, in its confirmation message: its current one, or a new
+    /// one when that expired. None when no such subscription is pending.
+    pub fn renew_confirmation_token(
+        &self,
+        subscription_arn: &str,
+    ) -> Option<(Subscription, String)> {
+        let topic_arn = subscription_arn
+            .rsplit_once(':')
+            .map(|(topic_arn, _)| topic_arn)
+            .unwrap_or_default();
+        let mut topic = self.topics.get_mut(topic_name_from_arn(topic_arn))?;
+        let subscription = topic
+            .subscriptions
+            .iter_mut()
+            .find(|s| s.subscription_arn == subscription_arn && s.pending_confirmation)?;
+        let token = match &subscription.confirmation_token {
+            Some(token) if self.confirmation_token_valid(subscription) => token.clone(),
+            _ => {
+                let token = delivery::new_confirmation_token();
+                subscription.confirmation_token = Some(token.clone());
+                subscription.confirmation_issued_at = Some(chrono::Utc::now());
+                token
+            }
+        };
+        Some((subscription.clone(), token))
+    }
+
+    /// When a topic or subscription created now becomes visible.
+    pub fn visible_at(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now() + chrono::Duration::from_std(self.consistency_delay).unwrap_or_default()
+    }
+
+    pub fn find_subscription(&self, subscription_arn: &str) -> Option<Subscription> {
+        self.topics.iter().find_map(|topic| {
+            topic
+                .subscriptions
+                .iter()
+                .find(|s| s.subscription_arn == subscription_arn)
+                .cloned()
+        })
+    }
+
+    pub fn subscription_health(&self, subscription_arn: &str) -> SubscriptionHealth {
+        self.subscription_health
+            .get(subscription_arn)
+            .map(|health| health.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn is_subscription_disabled(&self, subscription_arn: &str) -> bool {
+        self.subscription_health
+            .get(subscription_arn)
+            .is_some_and(|health| health.disabled)
+    }
+
+    /// Adds a delivery attempt to the audit log, if one is kept.
+    pub fn audit_delivery(
+        &self,
+        subscription: &Subscription,
+        message: &Message,
+        result: &DeliveryResult,
+    ) {
+        if let Some(audit) = &self.audit {
+            audit.record(AuditRecord::Delivery(DeliveryRecord::new(
+                subscription,
+                message,
+                result,
+            )));
+        }
+    }
+
+    /// Updates the subscription's health after a delivery attempt. Any
+    /// success re-enables a disabled subscription. Results for subscriptions
+    /// removed meanwhile are dropped; checking under the topic guard keeps a
+    /// racing removal from leaving an orphaned health record behind.
+    pub fn record_delivery_result(&self, subscription_arn: &str, result: &DeliveryResult) {
+        let topic_arn = subscription_arn
+            .rsplit_once(':')
+            .map(|(topic_arn, _)| topic_arn)
+            .unwrap_or_default();
+        let Some(topic) = self.topics.get(topic_name_from_arn(topic_arn)) else {
+            return;
+        };
+        if !topic
+            .subscriptions
+            .iter()
+            .any(|s| s.subscription_arn == subscription_arn)
+        {
+            return;
+        }
+        let mut health = self
+            .subscription_health
+            .entry(subscription_arn.to_string())
+            .or_default();
+        match result {
+            Ok(_) => {
+                if health.disabled {
+                    tracing::info!("Re-enabled subscription {}", subscription_arn);
+                }
+                *health = SubscriptionHealth::default();
+            }
+            Err(error) => {
+                health.consecutive_failures += 1;
+                health.last_error = Some(LastDeliveryError {
+                    timestamp: chrono::Utc::now(),
+                    error: error.message.clone(),
+                    kind: error.kind,
+                    attempts: health.consecutive_failures,
+                });
+                if !health.disabled
+                    && self.endpoint_failure_threshold > 0
+                    && health.consecutive_failures >= self.endpoint_failure_threshold
+                {
+                    health.disabled = true;
+                    tracing::warn!(
+                        "Disabled subscription {} after {} consecutive delivery failures",
+                        subscription_arn,
+                        health.consecutive_failures
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn record_message(&self, topic_name: &str, message: Message) {
+        let mut history = self.messages.entry(topic_name.to_string()).or_default();
+        if history.len() == MESSAGE_HISTORY_LIMIT {
+            history.pop_front();
+        }
+        history.push_back(message.clone());
+        drop(history);
+        let _ = self.message_events.send(HistoryEvent {
+            topic: topic_name.to_string(),
+            message,
+        });
+    }
+
+    pub fn record_outbox(&self, entry: OutboxEntry) {
+        let mut outbox = self.outbox.write().unwrap();
+        if outbox.len() == MESSAGE_HISTORY_LIMIT {
+            outbox.pop_front();
+        }
+        outbox.push_back(entry);
+    }
+
+    /// Looks a message up in the bounded history, returning its topic name.
+    pub fn find_message(&self, message_id: &str) -> Option<(String, Message)> {
+        self.messages.iter().find_map(|history| {
+            history
+                .iter()
+                .find(|m| m.id == message_id)
+                .map(|m| (history.key().clone(), m.clone()))
+        })
+    }
 }
 
 pub type SharedState = Arc<AppState>;
@@ -94,158 +1890,943 @@ pub struct SnsRequest {
     pub subject: Option<String>,
     pub attribute_name: Option<String>,
     pub attribute_value: Option<String>,
+    pub message_structure: Option<String>,
+    pub platform: Option<String>,
+    pub platform_application_arn: Option<String>,
+    pub token: Option<String>,
+    pub return_subscription_arn: Option<String>,
+    pub custom_user_data: Option<String>,
+    pub message_group_id: Option<String>,
+    pub message_deduplication_id: Option<String>,
+    pub version: Option<String>,
+    pub next_token: Option<String>,
+    /// Non-standard page size of the list actions; see `pagination`.
+    pub max_items: Option<String>,
+    // Query protocol boilerplate sent by SDKs. Declared so these keys are
+    // consumed here and never reach the flattened visitors below.
+    #[serde(rename = "AWSAccessKeyId")]
+    _aws_access_key_id: Option<IgnoredAny>,
+    #[serde(rename = "SignatureMethod")]
+    _signature_method: Option<IgnoredAny>,
+    #[serde(rename = "SignatureVersion")]
+    _signature_version: Option<IgnoredAny>,
+    #[serde(rename = "Signature")]
+    _signature: Option<IgnoredAny>,
+    #[serde(rename = "Timestamp")]
+    _timestamp: Option<IgnoredAny>,
+    #[serde(rename = "Expires")]
+    _expires: Option<IgnoredAny>,
+    #[serde(rename = "SecurityToken")]
+    _security_token: Option<IgnoredAny>,
     #[serde(flatten, deserialize_with = "deserialize_attributes")]
     pub attributes_entry: Option<Vec<AttributeEntry>>,
     #[serde(flatten, deserialize_with = "deserialize_tags")]
     pub tags_entry: Option<Vec<TagEntry>>,
     #[serde(flatten, deserialize_with = "deserialize_tag_keys")]
     pub tag_keys_entry: Option<Vec<String>>,
+    #[serde(flatten, deserialize_with = "deserialize_message_attributes")]
+    pub message_attributes: Option<Vec<MessageAttributeEntry>>,
+    #[serde(flatten, deserialize_with = "deserialize_publish_batch_entries")]
+    pub publish_batch_request_entries: Option<Vec<PublishBatchEntry>>,
+    #[serde(skip)]
+    pub context: RequestContext,
 }
 
-fn deserialize_attributes<'de, D>(deserializer: D) -> Result<Option<Vec<AttributeEntry>>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    struct AttributesVisitor;
+/// Per-request data taken from the HTTP layer rather than the form body.
+#[derive(Debug, Default, Clone)]
+pub struct RequestContext {
+    /// RequestId returned to the client for this call.
+    pub request_id: String,
+    /// Base of URLs generated for this call, such as UnsubscribeURL.
+    pub base_url: String,
+    /// Deliver inline and report failures on the response.
+    pub sync_delivery: bool,
+    /// X-Ray trace header (`X-Amzn-Trace-Id`) sent by the publisher.
+    pub trace_header: Option<String>,
+    pub publisher: Publisher,
+}
 
-    impl<'de> Visitor<'de> for AttributesVisitor {
-        type Value = Option<Vec<AttributeEntry>>;
+#[derive(Debug, Default)]
+pub struct PublishBatchEntry {
+    pub id: String,
+    pub message: Option<String>,
+    pub subject: Option<String>,
+    pub message_group_id: Option<String>,
+    pub message_deduplication_id: Option<String>,
+    pub message_structure: Option<String>,
+    pub message_attributes: Vec<MessageAttributeEntry>,
+}
 
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a map of attributes")
-        }
+/// Treats an empty `value` as if the parameter had not been sent.
+fn empty_as_absent(value: &mut Option<String>) {
+    if value.as_deref() == Some("") {
+        *value = None;
+    }
+}
 
-        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-        where
-            A: MapAccess<'de>,
-        {
-            let mut attributes: Vec<AttributeEntry> = Vec::new();
-            while let Some(key) = map.next_key::<String>()? {
-                if key.starts_with("Attributes.entry.") {
-                    let parts: Vec<&str> = key.split('.').collect();
-                    if parts.len() == 4 {
-                        let index = parts[2].parse::<usize>().unwrap_or(0);
-                        if index > 0 {
-                            while attributes.len() < index {
-                                attributes.push(AttributeEntry::default());
-                            }
-                            let field = parts[3];
-                            let value: String = map.next_value()?;
-                            if field == "key" {
-                                attributes[index - 1].key = value;
-                            } else if field == "value" {
-                                attributes[index - 1].value = value;
-                            }
-                        }
-                    }
-                } else {
-                    let _: serde::de::IgnoredAny = map.next_value()?;
-                }
-            }
-            if attributes.is_empty() {
-                Ok(None)
-            } else {
-                Ok(Some(attributes))
+impl SnsRequest {
+    /// Settles what an empty parameter (`Subject=`) means, once, right after
+    /// the form is parsed. SDKs send these for unset optional fields, and
+    /// AWS reads them differently per parameter:
+    ///
+    /// - NextToken, MaxItems, MessageGroupId, MessageDeduplicationId,
+    ///   MessageStructure, ReturnSubscriptionArn, CustomUserData and Version
+    ///   count as absent, so an empty MessageDeduplicationId falls back to
+    ///   content-based deduplication rather than deduplicating on "".
+    /// - Subject stays empty and is rejected by Publish; AWS does not treat
+    ///   it as missing.
+    /// - AttributeValue stays empty: it clears the attribute.
+    /// - Names, ARNs, Protocol, Endpoint, Message and Token stay empty and
+    ///   fail the handlers' own validation like any other invalid value.
+    ///
+    /// Protocol is also trimmed and lowercased, so ` SQS` subscribes a
+    /// queue like `sqs`. PublishBatch entries follow the same rules as
+    /// Publish.
+    pub fn normalize(&mut self) {
+        if let Some(protocol) = &mut self.protocol {
+            *protocol = protocol.trim().to_ascii_lowercase();
+        }
+        for value in [
+            &mut self.next_token,
+            &mut self.max_items,
+            &mut self.message_group_id,
+            &mut self.message_deduplication_id,
+            &mut self.message_structure,
+            &mut self.return_subscription_arn,
+            &mut self.custom_user_data,
+            &mut self.version,
+        ] {
+            empty_as_absent(value);
+        }
+        for entry in self.publish_batch_request_entries.iter_mut().flatten() {
+            for value in [
+                &mut entry.message_group_id,
+                &mut entry.message_deduplication_id,
+                &mut entry.message_structure,
+            ] {
+                empty_as_absent(value);
             }
         }
     }
+}
 
-    deserializer.deserialize_map(AttributesVisitor)
+/// One `MessageAttributes.entry.N` parameter as sent by the publisher.
+#[derive(Debug, Default, Clone)]
+pub struct MessageAttributeEntry {
+    pub name: String,
+    pub data_type: String,
+    pub string_value: Option<String>,
+    pub binary_value: Option<String>,
 }
 
-fn deserialize_tags<'de, D>(deserializer: D) -> Result<Option<Vec<TagEntry>>, D::Error>
+/// Highest index accepted in an indexed parameter list such as
+/// `Tags.member.N.Key`.
+pub const MAX_INDEXED_ENTRIES: usize = 200;
+
+/// The field values of one indexed entry, keyed by their name in `fields`.
+type IndexedFields = HashMap<&'static str, String>;
+
+#[derive(Default)]
+struct IndexedEntry {
+    fields: IndexedFields,
+    /// `(key, value)` pairs below each nested field, with keys relative to
+    /// the entry (e.g. `MessageAttributes.entry.1.Name`).
+    nested: HashMap<&'static str, Vec<(String, String)>>,
+}
+
+struct IndexedVisitor<F> {
+    prefix: &'static str,
+    fields: &'static [&'static str],
+    nested: &'static [&'static str],
+    build: F,
+}
+
+impl<T, F> IndexedVisitor<F>
 where
-    D: Deserializer<'de>,
+    F: Fn(IndexedEntry) -> Result<T, String>,
 {
-    struct TagsVisitor;
+    /// Parses `<prefix>.N[.field]` keys into a list ordered by N. Indices must
+    /// start at 1, be contiguous and not exceed `MAX_INDEXED_ENTRIES`; field
+    /// names are matched case-insensitively against `fields` (an empty field
+    /// name means the value sits directly under the index). Keys under one of
+    /// the `nested` fields are passed to `build` unparsed, for lists within
+    /// list entries.
+    fn parse(&self, pairs: Vec<(String, String)>) -> Result<Option<Vec<T>>, String> {
+        let mut entries: BTreeMap<usize, IndexedEntry> = BTreeMap::new();
+        for (key, value) in pairs {
+            let Some(rest) = key
+                .strip_prefix(self.prefix)
+                .and_then(|rest| rest.strip_prefix('.'))
+            else {
+                continue;
+            };
+            let (index, field) = rest.split_once('.').unwrap_or((rest, ""));
+            let index = match index.parse::<usize>() {
+                Ok(index) if (1..=MAX_INDEXED_ENTRIES).contains(&index) => index,
+                _ => {
+                    return Err(format!(
+                        "{}: index must be between 1 and {}",
+                        key, MAX_INDEXED_ENTRIES
+                    ));
+                }
+            };
+            let entry = entries.entry(index).or_default();
+            if let Some(name) = self
+                .fields
+                .iter()
+                .find(|name| name.eq_ignore_ascii_case(field))
+            {
+                entry.fields.insert(name, value);
+            } else if let Some(name) = self.nested.iter().find(|name| {
+                field.len() > name.len()
+                    && field.is_char_boundary(name.len())
+                    && field[..name.len()].eq_ignore_ascii_case(name)
+                    && field[name.len()..].starts_with('.')
+            }) {
+                let field = format!("{}{}", name, &field[name.len()..]);
+                entry.nested.entry(name).or_default().push((field, value));
+            } else {
+                return Err(format!("{}: unknown field", key));
+            }
+        }
 
-    impl<'de> Visitor<'de> for TagsVisitor {
-        type Value = Option<Vec<TagEntry>>;
+        if let Some((&last, _)) = entries.last_key_value()
+            && last != entries.len()
+        {
+            return Err(format!(
+                "{} indices must be contiguous starting at 1",
+                self.prefix
+            ));
+        }
 
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a map of tags")
+        if entries.is_empty() {
+            Ok(None)
+        } else {
+            entries
+                .into_values()
+                .map(&self.build)
+                .collect::<Result<_, _>>()
+                .map(Some)
         }
+    }
+}
 
-        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-        where
-            A: MapAccess<'de>,
-        {
-            let mut tags: Vec<TagEntry> = Vec::new();
-            while let Some(key) = map.next_key::<String>()? {
-                if key.starts_with("Tags.member.") {
-                    let parts: Vec<&str> = key.split('.').collect();
-                    if parts.len() == 4 {
-                        let index = parts[2].parse::<usize>().unwrap_or(0);
-                        if index > 0 {
-                            while tags.len() < index {
-                                tags.push(TagEntry::default());
-                            }
-                            let field = parts[3];
-                            let value: String = map.next_value()?;
-                            if field == "Key" {
-                                tags[index - 1].key = value;
-                            } else if field == "Value" {
-                                tags[index - 1].value = value;
-                            }
-                        }
-                    }
-                } else {
-                    let _: serde::de::IgnoredAny = map.next_value()?;
-                }
-            }
-            if tags.is_empty() {
-                Ok(None)
+impl<'de, T, F> Visitor<'de> for IndexedVisitor<F>
+where
+    F: Fn(IndexedEntry) -> Result<T, String>,
+{
+    type Value = Option<Vec<T>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a list of {} parameters", self.prefix)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut pairs = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key
+                .strip_prefix(self.prefix)
+                .is_some_and(|rest| rest.starts_with('.'))
+            {
+                pairs.push((key, map.next_value()?));
             } else {
-                Ok(Some(tags))
+                let _: IgnoredAny = map.next_value()?;
             }
         }
+        self.parse(pairs).map_err(de::Error::custom)
     }
+}
+
+fn deserialize_attributes<'de, D>(deserializer: D) -> Result<Option<Vec<AttributeEntry>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(IndexedVisitor {
+        prefix: "Attributes.entry",
+        fields: &["key", "value"],
+        nested: &[],
+        build: |mut entry: IndexedEntry| {
+            Ok(AttributeEntry {
+                key: entry.fields.remove("key").unwrap_or_default(),
+                value: entry.fields.remove("value").unwrap_or_default(),
+            })
+        },
+    })
+}
 
-    deserializer.deserialize_map(TagsVisitor)
+fn deserialize_tags<'de, D>(deserializer: D) -> Result<Option<Vec<TagEntry>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(IndexedVisitor {
+        prefix: "Tags.member",
+        fields: &["Key", "Value"],
+        nested: &[],
+        build: |mut entry: IndexedEntry| {
+            Ok(TagEntry {
+                key: entry.fields.remove("Key").unwrap_or_default(),
+                value: entry.fields.remove("Value").unwrap_or_default(),
+            })
+        },
+    })
 }
 
 fn deserialize_tag_keys<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    struct TagKeysVisitor;
+    deserializer.deserialize_map(IndexedVisitor {
+        prefix: "TagKeys.member",
+        fields: &[""],
+        nested: &[],
+        build: |mut entry: IndexedEntry| Ok(entry.fields.remove("").unwrap_or_default()),
+    })
+}
+
+fn deserialize_publish_batch_entries<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<PublishBatchEntry>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(IndexedVisitor {
+        prefix: "PublishBatchRequestEntries.member",
+        fields: &[
+            "Id",
+            "Message",
+            "Subject",
+            "MessageGroupId",
+            "MessageDeduplicationId",
+            "MessageStructure",
+        ],
+        nested: &["MessageAttributes"],
+        build: |mut entry: IndexedEntry| {
+            let message_attributes = match entry.nested.remove("MessageAttributes") {
+                Some(pairs) => message_attributes_visitor().parse(pairs)?,
+                None => None,
+            };
+            Ok(PublishBatchEntry {
+                id: entry.fields.remove("Id").unwrap_or_default(),
+                message: entry.fields.remove("Message"),
+                subject: entry.fields.remove("Subject"),
+                message_group_id: entry.fields.remove("MessageGroupId"),
+                message_deduplication_id: entry.fields.remove("MessageDeduplicationId"),
+                message_structure: entry.fields.remove("MessageStructure"),
+                message_attributes: message_attributes.unwrap_or_default(),
+            })
+        },
+    })
+}
+
+fn message_attributes_visitor()
+-> IndexedVisitor<impl Fn(IndexedEntry) -> Result<MessageAttributeEntry, String>> {
+    IndexedVisitor {
+        prefix: "MessageAttributes.entry",
+        fields: &[
+            "Name",
+            "Value.DataType",
+            "Value.StringValue",
+            "Value.BinaryValue",
+        ],
+        nested: &[],
+        build: |mut entry: IndexedEntry| {
+            Ok(MessageAttributeEntry {
+                name: entry.fields.remove("Name").unwrap_or_default(),
+                data_type: entry.fields.remove("Value.DataType").unwrap_or_default(),
+                string_value: entry.fields.remove("Value.StringValue"),
+                binary_value: entry.fields.remove("Value.BinaryValue"),
+            })
+        },
+    }
+}
+
+fn deserialize_message_attributes<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<MessageAttributeEntry>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(message_attributes_visitor())
+}
 
-    impl<'de> Visitor<'de> for TagKeysVisitor {
-        type Value = Option<Vec<String>>;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a list of tag keys")
+    fn tags(pairs: &[(&str, &str)]) -> Result<Option<Vec<(String, String)>>, String> {
+        IndexedVisitor {
+            prefix: "Tags.member",
+            fields: &["Key", "Value"],
+            nested: &[],
+            build: |mut entry: IndexedEntry| {
+                Ok((
+                    entry.fields.remove("Key").unwrap_or_default(),
+                    entry.fields.remove("Value").unwrap_or_default(),
+                ))
+            },
         }
+        .parse(
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
 
-        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-        where
-            A: MapAccess<'de>,
-        {
-            let mut keys: Vec<String> = Vec::new();
-            while let Some(key) = map.next_key::<String>()? {
-                if key.starts_with("TagKeys.member.") {
-                    let parts: Vec<&str> = key.split('.').collect();
-                    if parts.len() == 3 {
-                        let index = parts[2].parse::<usize>().unwrap_or(0);
-                        if index > 0 {
-                            while keys.len() < index {
-                                keys.push(String::new());
-                            }
-                            let value: String = map.next_value()?;
-                            keys[index - 1] = value;
+    #[test]
+    fn indexed_entries_are_ordered_by_index() {
+        let parsed = tags(&[
+            ("Tags.member.2.Key", "team"),
+            ("Tags.member.1.Key", "env"),
+            ("Tags.member.2.Value", "a"),
+            ("Tags.member.1.Value", "dev"),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            parsed,
+            [
+                ("env".to_string(), "dev".to_string()),
+                ("team".to_string(), "a".to_string())
+            ]
+        );
+        assert_eq!(tags(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn indices_beyond_the_limit_are_rejected_without_allocating() {
+        let error = tags(&[("Tags.member.4294967295.Key", "team")]).unwrap_err();
+        assert!(
+            error.contains("index must be between 1 and 200"),
+            "{}",
+            error
+        );
+        let error = tags(&[("Tags.member.99999999999999999999999.Key", "team")]).unwrap_err();
+        assert!(error.contains("index must be between"), "{}", error);
+        let pairs: Vec<(String, String)> = (1..=MAX_INDEXED_ENTRIES)
+            .map(|i| (format!("Tags.member.{}.Key", i), i.to_string()))
+            .collect();
+        let pairs: Vec<(&str, &str)> = pairs
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(tags(&pairs).unwrap().unwrap().len(), MAX_INDEXED_ENTRIES);
+    }
+
+    #[test]
+    fn zero_negative_and_gapped_indices_are_rejected() {
+        for key in [
+            "Tags.member.0.Key",
+            "Tags.member.-1.Key",
+            "Tags.member.one.Key",
+        ] {
+            assert!(tags(&[(key, "team")]).is_err(), "{}", key);
+        }
+        let error =
+            tags(&[("Tags.member.1.Key", "env"), ("Tags.member.3.Key", "team")]).unwrap_err();
+        assert!(error.contains("contiguous"), "{}", error);
+    }
+
+    #[test]
+    fn field_names_match_in_any_case() {
+        let parsed = tags(&[("Tags.member.1.key", "env"), ("Tags.member.1.VALUE", "dev")])
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed, [("env".to_string(), "dev".to_string())]);
+        let error = tags(&[("Tags.member.1.Colour", "blue")]).unwrap_err();
+        assert!(error.contains("unknown field"), "{}", error);
+    }
+
+    fn attribute(name: &str, data_type: &str, value: &str) -> MessageAttributeEntry {
+        let (string_value, binary_value) = if data_type.starts_with("Binary") {
+            (None, Some(value.to_string()))
+        } else {
+            (Some(value.to_string()), None)
+        };
+        MessageAttributeEntry {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            string_value,
+            binary_value,
+        }
+    }
+
+    fn rejected(entries: Vec<MessageAttributeEntry>) -> String {
+        validate_message_attributes(entries, None).unwrap_err()
+    }
+
+    #[test]
+    fn message_attribute_names_follow_the_aws_rules() {
+        for name in ["", "with space", ".leading", "trailing.", "two..dots"] {
+            let error = rejected(vec![attribute(name, "String", "x")]);
+            assert!(
+                error.starts_with("Invalid parameter: MessageAttributes Reason:"),
+                "{}",
+                error
+            );
+        }
+        let error = rejected(vec![attribute(&"a".repeat(257), "String", "x")]);
+        assert!(error.contains("up to 256 characters"), "{}", error);
+        assert!(
+            validate_message_attributes(vec![attribute(&"a".repeat(256), "String", "x")], None)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn reserved_prefixes_are_rejected_but_for_documented_names() {
+        for name in ["AWS.custom", "aws.custom", "Amazon.custom", "AMAZON.custom"] {
+            let error = rejected(vec![attribute(name, "String", "x")]);
+            assert!(error.contains("reserved prefix"), "{}: {}", name, error);
+        }
+        for name in [
+            "AWS.SNS.SMS.SenderID",
+            "AWS.SNS.MOBILE.APNS.TTL",
+            "AWS.MM.SMS.OriginationNumber",
+        ] {
+            assert!(
+                validate_message_attributes(vec![attribute(name, "String", "1")], None).is_ok(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn the_attribute_limit_applies_only_when_given() {
+        let entries = || {
+            (0..=MAX_MESSAGE_ATTRIBUTES)
+                .map(|i| attribute(&format!("a{}", i), "String", "x"))
+                .collect::<Vec<_>>()
+        };
+        let error =
+            validate_message_attributes(entries(), Some(MAX_MESSAGE_ATTRIBUTES)).unwrap_err();
+        assert!(
+            error.contains("Number of message attributes [11] exceeds the allowed maximum [10]"),
+            "{}",
+            error
+        );
+        assert!(validate_message_attributes(entries(), None).is_ok());
+    }
+
+    #[test]
+    fn message_attribute_values_must_match_their_type() {
+        let cases = [
+            (attribute("a", "", "x"), "non-empty message attribute type"),
+            (
+                attribute("a", "Text", "x"),
+                "invalid message attribute type",
+            ),
+            (
+                MessageAttributeEntry {
+                    string_value: None,
+                    ..attribute("a", "String", "")
+                },
+                "non-empty message attribute value",
+            ),
+            (attribute("a", "Number", "many"), "Could not cast"),
+            (attribute("a", "String.Array", "x"), "must be a JSON array"),
+            (
+                attribute("a", "Binary", "not base64!"),
+                "must be base64-encoded",
+            ),
+            (
+                MessageAttributeEntry {
+                    binary_value: Some("AQI=".to_string()),
+                    ..attribute("a", "String", "")
+                },
+                "must use field 'String'",
+            ),
+        ];
+        for (entry, reason) in cases {
+            let error = rejected(vec![entry]);
+            assert!(error.contains(reason), "{}: {}", reason, error);
+        }
+        let error = rejected(vec![
+            attribute("a", "String", "x"),
+            attribute("a", "String", "y"),
+        ]);
+        assert!(
+            error.contains("Duplicate message attribute name 'a'"),
+            "{}",
+            error
+        );
+        assert!(
+            validate_message_attributes(
+                vec![
+                    attribute("count", "Number.int", "-1.5e3"),
+                    attribute("tags", "String.Array", r#"["a", 1]"#),
+                ],
+                None
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn message_attribute_sizes_count_name_type_and_value() {
+        let (attributes, size) = validate_message_attributes(
+            vec![
+                attribute("event", "String", "order"),
+                // Three bytes once decoded.
+                attribute("blob", "Binary", "AQID"),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(
+            size,
+            "event".len() + "String".len() + "order".len() + "blob".len() + "Binary".len() + 3
+        );
+    }
+
+    const POLICY: &str = r#"{"Version":"2012-10-17","Statement":[{"Effect":"Allow","Principal":"*","Action":"SNS:Publish","Resource":"*"}]}"#;
+
+    #[test]
+    fn policies_need_a_version_and_statements() {
+        assert!(validate_topic_attribute("Policy", POLICY).is_ok());
+        assert!(
+            validate_topic_attribute(
+                "Policy",
+                r#"{"Version":"2012-10-17","Statement":{"Effect":"Deny"}}"#
+            )
+            .is_ok()
+        );
+        for policy in [
+            r#"{"Version":"2012-10-17","Statement":[{"Effect":"Allow"},]}"#,
+            r#"{"Version":"2012-10-17"}"#,
+            r#"{"Statement":[]}"#,
+            r#"{"Version":"2012-10-17","Statement":["Allow"]}"#,
+            r#"{"Version":2012,"Statement":[]}"#,
+            r#"[]"#,
+            "",
+        ] {
+            assert_eq!(
+                validate_topic_attribute("Policy", policy),
+                Err(invalid_parameter("Policy")),
+                "{}",
+                policy
+            );
+        }
+    }
+
+    #[test]
+    fn policies_are_limited_in_size() {
+        let padded = |len: usize| {
+            let padding = "x".repeat(len - POLICY.len() - r#","Id":"""#.len());
+            format!(r#"{},"Id":"{}"}}"#, &POLICY[..POLICY.len() - 1], padding)
+        };
+        assert_eq!(padded(MAX_POLICY_SIZE).len(), MAX_POLICY_SIZE);
+        assert!(validate_topic_attribute("Policy", &padded(MAX_POLICY_SIZE)).is_ok());
+        assert!(validate_topic_attribute("Policy", &padded(MAX_POLICY_SIZE + 1)).is_err());
+    }
+
+    #[test]
+    fn xml_text_excludes_what_xml_cannot_carry() {
+        for value in [
+            "",
+            "plain",
+            "tab\tline\nreturn\r",
+            "<&>\"'",
+            "é€😀",
+            "\u{7F}\u{85}",
+        ] {
+            assert!(is_xml_text(value), "{:?}", value);
+        }
+        for value in ["\0", "a\u{1}b", "\u{1F}", "\u{FFFE}", "\u{FFFF}"] {
+            assert!(!is_xml_text(value), "{:?}", value);
+            assert!(validate_xml_text("KmsMasterKeyId", value).is_err());
+        }
+    }
+
+    #[test]
+    fn display_names_and_tags_take_no_control_characters() {
+        assert!(validate_topic_attribute("DisplayName", "Orders <eu> & us").is_ok());
+        assert!(validate_topic_attribute("KmsMasterKeyId", "line\nbreak").is_ok());
+        assert!(validate_tag("team", "orders & invoices").is_ok());
+        for value in ["\0", "line\nbreak", "tab\t", "\u{7F}", "\u{85}", "\u{FFFF}"] {
+            assert!(
+                validate_topic_attribute("DisplayName", value).is_err(),
+                "{:?}",
+                value
+            );
+            assert!(validate_tag(value, "a").is_err(), "{:?}", value);
+            assert!(validate_tag("team", value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn topic_names_follow_the_aws_rules() {
+        let longest = "a".repeat(256);
+        let longest_fifo = format!("{}.fifo", "a".repeat(251));
+        for name in [
+            "orders",
+            "Order_Events-2",
+            "orders.fifo",
+            &longest,
+            &longest_fifo,
+        ] {
+            assert!(validate_topic_name(name).is_ok(), "{:?}", name);
+        }
+        let too_long = "a".repeat(257);
+        for name in [
+            "",
+            ".fifo",
+            "bad name",
+            "orders.v2",
+            "orders:eu",
+            "ördérs",
+            &too_long,
+        ] {
+            assert_eq!(
+                validate_topic_name(name),
+                Err("Invalid parameter: Topic Name".to_string()),
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    const SUBSCRIPTION_ARN: &str =
+        "arn:aws:sns:us-east-1:000000000000:orders:2f1c6a4e-0d7b-4c1e-9a55-3b8e2f6d9c10";
+
+    #[test]
+    fn subscription_arns_split_into_topic_and_id() {
+        let parsed = SubscriptionArn::parse(SUBSCRIPTION_ARN).unwrap();
+        assert_eq!(
+            parsed.topic_arn,
+            "arn:aws:sns:us-east-1:000000000000:orders"
+        );
+        assert_eq!(parsed.topic_name, "orders");
+        assert_eq!(
+            parsed.id.to_string(),
+            "2f1c6a4e-0d7b-4c1e-9a55-3b8e2f6d9c10"
+        );
+        let fifo = "arn:aws-cn:sns:cn-north-1:123456789012:orders.fifo:2f1c6a4e-0d7b-4c1e-9a55-3b8e2f6d9c10";
+        assert_eq!(
+            SubscriptionArn::parse(fifo).unwrap().topic_name,
+            "orders.fifo"
+        );
+    }
+
+    #[test]
+    fn malformed_subscription_arns_are_invalid_parameters() {
+        let id = "2f1c6a4e-0d7b-4c1e-9a55-3b8e2f6d9c10";
+        for arn in [
+            String::new(),
+            "garbage".to_string(),
+            "arn:aws:sns:us-east-1:000000000000:orders".to_string(),
+            format!("arn:aws:sqs:us-east-1:000000000000:orders:{}", id),
+            format!("arn::sns:us-east-1:000000000000:orders:{}", id),
+            format!("arn:AWS:sns:us-east-1:000000000000:orders:{}", id),
+            format!("arn:aws:sns::000000000000:orders:{}", id),
+            format!("arn:aws:sns:us-east-1:00000000000:orders:{}", id),
+            format!("arn:aws:sns:us-east-1:00000000000a:orders:{}", id),
+            format!("arn:aws:sns:us-east-1:000000000000:ord ers:{}", id),
+            format!("arn:aws:sns:us-east-1:000000000000::{}", id),
+            format!("arn:aws:sns:us-east-1:000000000000:a:orders:{}", id),
+            format!("x{}", &SUBSCRIPTION_ARN[1..]),
+            format!("{}0", SUBSCRIPTION_ARN),
+            "arn:aws:sns:us-east-1:000000000000:orders:2f1c6a4e0d7b4c1e9a553b8e2f6d9c10"
+                .to_string(),
+            "arn:aws:sns:us-east-1:000000000000:orders:{2f1c6a4e-0d7b-4c1e-9a55-3b8e2f6d9c1}"
+                .to_string(),
+            "arn:aws:sns:us-east-1:000000000000:orders:zf1c6a4e-0d7b-4c1e-9a55-3b8e2f6d9c10"
+                .to_string(),
+        ] {
+            assert_eq!(
+                SubscriptionArn::parse(&arn),
+                Err("Invalid parameter: SubscriptionArn".to_string()),
+                "{:?}",
+                arn
+            );
+        }
+    }
+
+    #[test]
+    fn random_colon_laden_strings_never_panic() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let alphabet: Vec<char> = ":::-.aAs0ü😀 ".chars().collect();
+        let mut rng = StdRng::seed_from_u64(1198);
+        for _ in 0..10_000 {
+            // Mutate a valid ARN or build one from scratch.
+            let arn: String = if rng.gen_bool(0.5) {
+                let mut arn: Vec<char> = SUBSCRIPTION_ARN.chars().collect();
+                for _ in 0..rng.gen_range(1..4) {
+                    let at = rng.gen_range(0..arn.len());
+                    match rng.gen_range(0..3) {
+                        0 => arn[at] = alphabet[rng.gen_range(0..alphabet.len())],
+                        1 => arn.insert(at, alphabet[rng.gen_range(0..alphabet.len())]),
+                        _ => {
+                            arn.remove(at);
                         }
                     }
-                } else {
-                    let _: serde::de::IgnoredAny = map.next_value()?;
                 }
-            }
-            if keys.is_empty() {
-                Ok(None)
+                arn.into_iter().collect()
             } else {
-                Ok(Some(keys))
+                let len = rng.gen_range(0..80);
+                (0..len)
+                    .map(|_| alphabet[rng.gen_range(0..alphabet.len())])
+                    .collect()
+            };
+            // Whatever parses is the topic ARN and the UUID it was made of;
+            // UUIDs parse in either case.
+            if let Ok(parsed) = SubscriptionArn::parse(&arn) {
+                let (topic_arn, id) = arn.rsplit_once(':').unwrap();
+                assert_eq!(parsed.topic_arn, topic_arn);
+                assert!(id.eq_ignore_ascii_case(&parsed.id.to_string()), "{}", arn);
             }
         }
     }
 
-    deserializer.deserialize_map(TagKeysVisitor)
+    #[test]
+    fn sms_sender_ids_derive_from_the_display_name_or_topic_name() {
+        // Topic name, DisplayName, expected sender id.
+        let cases = [
+            ("orders", Some("Acme"), Some("Acme")),
+            // Truncated to 11 alphanumeric characters.
+            ("orders", Some("AcmeCorporation"), Some("AcmeCorpora")),
+            ("orders", Some("12345678901"), Some("12345678901")),
+            // Other characters are dropped before truncating.
+            ("orders", Some("Acme Corp."), Some("AcmeCorp")),
+            (
+                "orders",
+                Some("A-c_m e!C.o,r;p:o/r@a#t$ion"),
+                Some("AcmeCorpora"),
+            ),
+            ("orders", Some("Café Zoë"), Some("CafZo")),
+            // An empty DisplayName falls back to the topic name.
+            ("orders", Some(""), Some("orders")),
+            ("orders", None, Some("orders")),
+            ("order-events-prod", None, Some("orderevents")),
+            ("ship.fifo", Some(""), Some("shipfifo")),
+            // Nothing alphanumeric is left.
+            ("orders", Some("--- !!!"), None),
+        ];
+        let defaults = BTreeMap::new();
+        for (name, display_name, expected) in cases {
+            let mut topic = Topic::new(name.to_string(), topic_arn(name), HashMap::new());
+            if let Some(display_name) = display_name {
+                assert!(topic.set_attribute("DisplayName", display_name.to_string()));
+            }
+            assert_eq!(
+                topic.sms_sender_id(&defaults).as_deref(),
+                expected,
+                "{:?} / {:?}",
+                name,
+                display_name
+            );
+        }
+
+        // A DisplayName default applies to topics without their own.
+        let defaults = BTreeMap::from([("DisplayName".to_string(), "Default Co".to_string())]);
+        let topic = Topic::new("orders".to_string(), topic_arn("orders"), HashMap::new());
+        assert_eq!(topic.sms_sender_id(&defaults).as_deref(), Some("DefaultCo"));
+    }
+
+    /// The SnsRequest a form with `pairs` parses to, normalized.
+    fn normalized(pairs: &[(&str, &str)]) -> SnsRequest {
+        let form: serde_json::Map<String, serde_json::Value> = pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), serde_json::Value::from(*value)))
+            .collect();
+        let mut request: SnsRequest = serde_json::from_value(form.into()).unwrap();
+        request.normalize();
+        request
+    }
+
+    #[test]
+    fn protocols_are_trimmed_and_lowercased() {
+        for (sent, expected) in [
+            ("sqs", "sqs"),
+            ("SQS", "sqs"),
+            ("  Https\t", "https"),
+            ("email-JSON", "email-json"),
+            // Still empty, for Subscribe to reject.
+            (" ", ""),
+            ("", ""),
+        ] {
+            let request = normalized(&[("Action", "Subscribe"), ("Protocol", sent)]);
+            assert_eq!(request.protocol.as_deref(), Some(expected), "{:?}", sent);
+        }
+        let request = normalized(&[("Action", "Subscribe")]);
+        assert_eq!(request.protocol, None);
+    }
+
+    /// Reads one parameter of a parsed request.
+    type Field = fn(&SnsRequest) -> &Option<String>;
+
+    #[test]
+    fn empty_optional_parameters_count_as_absent() {
+        let fields: [(&str, Field); 8] = [
+            ("NextToken", |r| &r.next_token),
+            ("MaxItems", |r| &r.max_items),
+            ("MessageGroupId", |r| &r.message_group_id),
+            ("MessageDeduplicationId", |r| &r.message_deduplication_id),
+            ("MessageStructure", |r| &r.message_structure),
+            ("ReturnSubscriptionArn", |r| &r.return_subscription_arn),
+            ("CustomUserData", |r| &r.custom_user_data),
+            ("Version", |r| &r.version),
+        ];
+        for (name, field) in fields {
+            let request = normalized(&[("Action", "Publish"), (name, "")]);
+            assert_eq!(field(&request), &None, "{}", name);
+            // Values other than "" are kept as sent.
+            let request = normalized(&[("Action", "Publish"), (name, " x ")]);
+            assert_eq!(field(&request).as_deref(), Some(" x "), "{}", name);
+        }
+    }
+
+    #[test]
+    fn other_empty_parameters_stay_empty() {
+        let fields: [(&str, Field); 9] = [
+            ("Subject", |r| &r.subject),
+            ("AttributeValue", |r| &r.attribute_value),
+            ("Name", |r| &r.name),
+            ("TopicArn", |r| &r.topic_arn),
+            ("SubscriptionArn", |r| &r.subscription_arn),
+            ("Endpoint", |r| &r.endpoint),
+            ("Message", |r| &r.message),
+            ("Token", |r| &r.token),
+            ("AttributeName", |r| &r.attribute_name),
+        ];
+        for (name, field) in fields {
+            let request = normalized(&[("Action", "Publish"), (name, "")]);
+            assert_eq!(field(&request).as_deref(), Some(""), "{}", name);
+        }
+    }
+
+    #[test]
+    fn empty_batch_entry_parameters_count_as_absent() {
+        let request = normalized(&[
+            ("Action", "PublishBatch"),
+            ("PublishBatchRequestEntries.member.1.Id", "1"),
+            ("PublishBatchRequestEntries.member.1.Message", "hello"),
+            ("PublishBatchRequestEntries.member.1.Subject", ""),
+            ("PublishBatchRequestEntries.member.1.MessageGroupId", ""),
+            (
+                "PublishBatchRequestEntries.member.1.MessageDeduplicationId",
+                "",
+            ),
+            ("PublishBatchRequestEntries.member.1.MessageStructure", ""),
+            ("PublishBatchRequestEntries.member.2.Id", "2"),
+            ("PublishBatchRequestEntries.member.2.Message", "hello"),
+            (
+                "PublishBatchRequestEntries.member.2.MessageGroupId",
+                "orders",
+            ),
+        ]);
+        let entries = request.publish_batch_request_entries.unwrap();
+        assert_eq!(entries[0].subject.as_deref(), Some(""));
+        assert_eq!(entries[0].message_group_id, None);
+        assert_eq!(entries[0].message_deduplication_id, None);
+        assert_eq!(entries[0].message_structure, None);
+        assert_eq!(entries[1].message_group_id.as_deref(), Some("orders"));
+    }
 }
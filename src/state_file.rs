@@ -0,0 +1,236 @@
+use crate::config::Config;
+use crate::seed;
+use crate::snapshot::{
+    self, FixtureStatus, ImportError, ImportMode, StateDocument, SubscriptionDocument,
+    TopicDocument,
+};
+use crate::state::AppState;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Options of the `state` subcommand, which works on a state document (the
+/// `--seed` file, or an `/admin/export`) while the server is stopped.
+#[derive(Debug, Clone, clap::Args)]
+pub struct StateArgs {
+    /// JSON or YAML state document [default: the --seed file]
+    #[arg(long, value_name = "PATH", global = true)]
+    pub file: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: StateCommand,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum StateCommand {
+    /// Print the topics and subscriptions of the document
+    Show,
+    /// Check the document as the server would when loading it as a seed
+    Validate,
+    /// Add a topic, or update the attributes and tags of an existing one
+    AddTopic {
+        /// Topic name; FIFO topics end in .fifo
+        #[arg(long)]
+        name: String,
+        /// Topic attribute, repeatable
+        #[arg(long = "attribute", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        attributes: Vec<(String, String)>,
+        /// Topic tag, repeatable
+        #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        tags: Vec<(String, String)>,
+    },
+    /// Subscribe an endpoint to a topic of the document
+    AddSubscription {
+        /// Name of the topic
+        #[arg(long)]
+        topic: String,
+        #[arg(long)]
+        protocol: String,
+        #[arg(long)]
+        endpoint: String,
+        /// Subscription attribute, repeatable
+        #[arg(long = "attribute", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        attributes: Vec<(String, String)>,
+    },
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got {}", value))
+}
+
+/// Runs a `state` subcommand. Every command loads the document through the
+/// seed loader into a scratch server state, so edits pass the same checks
+/// as at runtime and the file is rewritten in the form `/admin/export`
+/// produces.
+pub fn run(config: &Config, args: StateArgs) -> Result<(), String> {
+    let path = args
+        .file
+        .or_else(|| config.seed.clone())
+        .ok_or("no state document; pass --file or configure --seed")?;
+    let document = seed::read_document(&path)?;
+    let include_messages = document.topics.iter().any(|topic| topic.messages.is_some());
+    let state = scratch_state(config);
+    let errors = snapshot::import(&state, document, ImportMode::Replace).errors;
+
+    match args.command {
+        StateCommand::Show => {
+            print_errors(&errors);
+            show(&snapshot::export(&state, false));
+            Ok(())
+        }
+        StateCommand::Validate => {
+            print_errors(&errors);
+            if errors.is_empty() {
+                println!("{}: valid", path.display());
+                Ok(())
+            } else {
+                Err(format!("{}: {} errors", path.display(), errors.len()))
+            }
+        }
+        StateCommand::AddTopic {
+            name,
+            attributes,
+            tags,
+        } => {
+            refuse_invalid(&path, &errors)?;
+            let topic = TopicDocument {
+                name,
+                attributes: attributes.into_iter().collect(),
+                tags: tags.into_iter().collect(),
+                subscriptions: Vec::new(),
+                messages: None,
+            };
+            apply(&state, &path, topic, include_messages)
+        }
+        StateCommand::AddSubscription {
+            topic,
+            protocol,
+            endpoint,
+            attributes,
+        } => {
+            refuse_invalid(&path, &errors)?;
+            if !state.topics.contains_key(&topic) {
+                return Err(format!("topic {} is not in {}", topic, path.display()));
+            }
+            let topic = TopicDocument {
+                name: topic,
+                attributes: BTreeMap::new(),
+                tags: BTreeMap::new(),
+                subscriptions: vec![SubscriptionDocument {
+                    protocol,
+                    endpoint,
+                    subscription_arn: None,
+                    attributes: attributes.into_iter().collect(),
+                    last_delivery_error: None,
+                }],
+                messages: None,
+            };
+            apply(&state, &path, topic, include_messages)
+        }
+    }
+}
+
+/// Server state to load the document into. Runtime state is kept so it
+/// survives a rewrite; nothing is written anywhere else.
+fn scratch_state(config: &Config) -> AppState {
+    let mut config = config.clone();
+    config.persist_runtime_state = true;
+    config.audit_log = None;
+    config.delivery_status_dir = None;
+    AppState::new(config)
+}
+
+fn print_errors(errors: &[ImportError]) {
+    for error in errors {
+        eprintln!("error: {}: {}", error.item, error.error);
+    }
+}
+
+/// Edits are refused on a document the server would not load cleanly, as
+/// rewriting it would drop the invalid parts.
+fn refuse_invalid(path: &Path, errors: &[ImportError]) -> Result<(), String> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    print_errors(errors);
+    Err(format!(
+        "{} has {} errors; fix them first",
+        path.display(),
+        errors.len()
+    ))
+}
+
+/// Applies `topic` as a fixture, all or nothing, and rewrites the document.
+fn apply(
+    state: &AppState,
+    path: &Path,
+    topic: TopicDocument,
+    include_messages: bool,
+) -> Result<(), String> {
+    let name = topic.name.clone();
+    let Some(result) = snapshot::create_fixtures(state, vec![topic]).pop() else {
+        return Ok(());
+    };
+    let status = match result.status {
+        FixtureStatus::Created => "created",
+        FixtureStatus::Updated => "updated",
+        FixtureStatus::Unchanged => "unchanged",
+        FixtureStatus::Failed => {
+            print_errors(&result.errors);
+            return Err(format!("topic {} was not changed", name));
+        }
+    };
+    write_document(path, &snapshot::export(state, include_messages))?;
+    println!("{}: topic {} {}", path.display(), name, status);
+    for subscription_arn in &result.subscription_arns {
+        println!("  {}", subscription_arn);
+    }
+    Ok(())
+}
+
+/// Writes the document in the format its extension names, replacing the
+/// file only once the new contents are complete.
+fn write_document(path: &Path, document: &StateDocument) -> Result<(), String> {
+    let contents = if seed::is_yaml(path) {
+        serde_yaml::to_string(document).map_err(|e| e.to_string())?
+    } else {
+        serde_json::to_string_pretty(document).map_err(|e| e.to_string())? + "\n"
+    };
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    std::fs::write(&temporary, contents)
+        .and_then(|()| std::fs::rename(&temporary, path))
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+fn show(document: &StateDocument) {
+    if document.topics.is_empty() {
+        println!("no topics");
+    }
+    for topic in &document.topics {
+        println!("{}", topic.name);
+        for (key, value) in &topic.attributes {
+            println!("  attribute {} = {}", key, value);
+        }
+        for (key, value) in &topic.tags {
+            println!("  tag {} = {}", key, value);
+        }
+        for subscription in &topic.subscriptions {
+            println!(
+                "  subscription {} {}{}",
+                subscription.protocol,
+                subscription.endpoint,
+                subscription
+                    .subscription_arn
+                    .as_deref()
+                    .map(|arn| format!(" ({})", arn))
+                    .unwrap_or_default()
+            );
+            for (key, value) in &subscription.attributes {
+                println!("    {} = {}", key, value);
+            }
+        }
+    }
+}
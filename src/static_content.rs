@@ -0,0 +1,57 @@
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+
+/// A fully buffered response body that clients may revalidate: it is served
+/// with an exact Content-Length and a strong ETag, and an `If-None-Match`
+/// naming that ETag gets 304 Not Modified. HEAD is answered by the router,
+/// which keeps these headers and drops the body.
+pub struct StaticContent {
+    content_type: &'static str,
+    body: Vec<u8>,
+    etag: String,
+}
+
+impl StaticContent {
+    pub fn new(content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        let body = body.into();
+        let etag = format!("\"{}\"", hex::encode(Sha256::digest(&body)));
+        StaticContent {
+            content_type,
+            body,
+            etag,
+        }
+    }
+
+    fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == self.etag)
+    }
+
+    /// Renders the content for a request carrying `headers`.
+    pub fn respond(self, headers: &HeaderMap) -> Response {
+        let etag = HeaderValue::from_str(&self.etag).unwrap();
+        if self.is_fresh(headers) {
+            return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+        }
+        let length = HeaderValue::from(self.body.len());
+        (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(self.content_type),
+                ),
+                (header::CONTENT_LENGTH, length),
+                (header::ETAG, etag),
+            ],
+            Body::from(self.body),
+        )
+            .into_response()
+    }
+}
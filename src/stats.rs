@@ -0,0 +1,379 @@
+use crate::delivery::{DeliveryErrorKind, DeliveryResult};
+use crate::state::SUPPORTED_PROTOCOLS;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+/// Delivery counters for one topic. Kept beside the topic map so that
+/// rewriting a `Topic` never resets them.
+#[derive(Debug, Default)]
+pub struct TopicStats {
+    pub published: AtomicU64,
+    pub delivered_ok: AtomicU64,
+    pub delivered_failed: AtomicU64,
+    pub filtered_out: AtomicU64,
+    /// Failed deliveries by `DeliveryErrorKind`, in `DeliveryErrorKind::ALL`
+    /// order.
+    failures_by_kind: [AtomicU64; DeliveryErrorKind::ALL.len()],
+    /// Time of the latest publish in Unix milliseconds; 0 before the first.
+    last_published_ms: AtomicI64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TopicStatsSnapshot {
+    pub published: u64,
+    pub delivered_ok: u64,
+    pub delivered_failed: u64,
+    pub filtered_out: u64,
+    /// `delivered_failed` by error kind; kinds that never failed are left
+    /// out.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub failures_by_kind: BTreeMap<DeliveryErrorKind, u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_published: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl TopicStats {
+    pub fn record_publish(&self, timestamp: chrono::DateTime<chrono::Utc>) {
+        self.published.fetch_add(1, Ordering::Relaxed);
+        self.last_published_ms
+            .fetch_max(timestamp.timestamp_millis(), Ordering::Relaxed);
+    }
+
+    pub fn record_delivery(&self, result: &DeliveryResult) {
+        match result {
+            Ok(_) => self.delivered_ok.fetch_add(1, Ordering::Relaxed),
+            Err(error) => {
+                self.failures_by_kind[error.kind as usize].fetch_add(1, Ordering::Relaxed);
+                self.delivered_failed.fetch_add(1, Ordering::Relaxed)
+            }
+        };
+    }
+
+    pub fn snapshot(&self) -> TopicStatsSnapshot {
+        TopicStatsSnapshot {
+            published: self.published.load(Ordering::Relaxed),
+            delivered_ok: self.delivered_ok.load(Ordering::Relaxed),
+            delivered_failed: self.delivered_failed.load(Ordering::Relaxed),
+            filtered_out: self.filtered_out.load(Ordering::Relaxed),
+            failures_by_kind: DeliveryErrorKind::ALL
+                .into_iter()
+                .zip(&self.failures_by_kind)
+                .map(|(kind, count)| (kind, count.load(Ordering::Relaxed)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+            last_published: match self.last_published_ms.load(Ordering::Relaxed) {
+                0 => None,
+                ms => chrono::DateTime::from_timestamp_millis(ms),
+            },
+        }
+    }
+}
+
+impl TopicStatsSnapshot {
+    pub fn add(&mut self, other: &TopicStatsSnapshot) {
+        self.published += other.published;
+        self.delivered_ok += other.delivered_ok;
+        self.delivered_failed += other.delivered_failed;
+        self.filtered_out += other.filtered_out;
+        for (kind, count) in &other.failures_by_kind {
+            *self.failures_by_kind.entry(*kind).or_default() += count;
+        }
+        self.last_published = self.last_published.max(other.last_published);
+    }
+}
+
+/// Server-wide counters for calls that usually point at a fixture bug. Test
+/// harnesses can assert they stay at zero.
+#[derive(Debug, Default)]
+pub struct SuspiciousStats {
+    /// CreateTopic calls for a topic that already existed.
+    pub duplicate_topics: AtomicU64,
+    /// Subscribe calls matching an existing protocol and endpoint.
+    pub duplicate_subscriptions: AtomicU64,
+    /// Publishes whose topic has two subscriptions with the same protocol
+    /// and endpoint.
+    pub duplicate_fanouts: AtomicU64,
+    /// Deliveries skipped because another subscription on the topic reaches
+    /// the same SQS queue, e.g. once by URL and once by ARN.
+    pub collapsed_deliveries: AtomicU64,
+    /// Publishes that reached no subscription.
+    pub publishes_unrouted: AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SuspiciousStatsSnapshot {
+    pub duplicate_topics: u64,
+    pub duplicate_subscriptions: u64,
+    pub duplicate_fanouts: u64,
+    pub collapsed_deliveries: u64,
+    pub publishes_unrouted: u64,
+}
+
+impl SuspiciousStats {
+    pub fn snapshot(&self) -> SuspiciousStatsSnapshot {
+        SuspiciousStatsSnapshot {
+            duplicate_topics: self.duplicate_topics.load(Ordering::Relaxed),
+            duplicate_subscriptions: self.duplicate_subscriptions.load(Ordering::Relaxed),
+            duplicate_fanouts: self.duplicate_fanouts.load(Ordering::Relaxed),
+            collapsed_deliveries: self.collapsed_deliveries.load(Ordering::Relaxed),
+            publishes_unrouted: self.publishes_unrouted.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.duplicate_topics.store(0, Ordering::Relaxed);
+        self.duplicate_subscriptions.store(0, Ordering::Relaxed);
+        self.duplicate_fanouts.store(0, Ordering::Relaxed);
+        self.collapsed_deliveries.store(0, Ordering::Relaxed);
+        self.publishes_unrouted.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Delivery health of one subscription. Subscriptions whose endpoint keeps
+/// failing are disabled and skipped by publish until a delivery succeeds.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SubscriptionHealth {
+    pub consecutive_failures: u32,
+    pub disabled: bool,
+    /// The most recent failure, cleared by the next successful delivery.
+    pub last_error: Option<LastDeliveryError>,
+}
+
+/// The latest failed delivery of a subscription.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastDeliveryError {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub error: String,
+    #[serde(default)]
+    pub kind: DeliveryErrorKind,
+    /// Failed attempts since the last successful delivery.
+    pub attempts: u32,
+}
+
+impl SubscriptionHealth {
+    pub fn effective_delivery_state(&self) -> &'static str {
+        if self.disabled { "disabled" } else { "enabled" }
+    }
+}
+
+/// Work done by the retention sweeper, served in `/admin/stats`.
+#[derive(Debug, Default)]
+pub struct RetentionStats {
+    pub last_run: RwLock<Option<chrono::DateTime<chrono::Utc>>>,
+    pub messages_evicted: AtomicU64,
+    pub deliveries_evicted: AtomicU64,
+    pub topics_expired: AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RetentionStatsSnapshot {
+    pub last_run: Option<chrono::DateTime<chrono::Utc>>,
+    pub messages_evicted: u64,
+    pub deliveries_evicted: u64,
+    pub topics_expired: u64,
+}
+
+impl RetentionStats {
+    pub fn snapshot(&self) -> RetentionStatsSnapshot {
+        RetentionStatsSnapshot {
+            last_run: *self.last_run.read().unwrap(),
+            messages_evicted: self.messages_evicted.load(Ordering::Relaxed),
+            deliveries_evicted: self.deliveries_evicted.load(Ordering::Relaxed),
+            topics_expired: self.topics_expired.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Window fan-out completions are counted over for the throughput average.
+const THROUGHPUT_WINDOW_MS: i64 = 1000;
+/// A window this many times longer spanned an idle pipeline, not a slow
+/// one, and is dropped rather than averaged in.
+const THROUGHPUT_IDLE_WINDOWS: i64 = 5;
+/// Weight of the latest window in the throughput average.
+const THROUGHPUT_EWMA_ALPHA: f64 = 0.3;
+/// Most a publish is told to wait.
+pub const MAX_SUGGESTED_DELAY_MS: u64 = 10_000;
+
+/// Gauges of the delivery pipeline, moved by guards the pipeline holds
+/// while fan-outs queue, run and deliver, so `/health` reads them without
+/// locking or counting anything.
+#[derive(Debug, Default)]
+pub struct DeliveryGauges {
+    queued: AtomicU64,
+    /// When the queue last became non-empty, in Unix milliseconds; 0 while
+    /// it is empty.
+    backlog_since_ms: AtomicI64,
+    active_workers: AtomicU64,
+    /// Deliveries in progress, indexed like `SUPPORTED_PROTOCOLS`.
+    in_flight: [AtomicU64; SUPPORTED_PROTOCOLS.len()],
+    /// Fan-outs finished in the current throughput window.
+    finished_in_window: AtomicU64,
+    /// Start of the current throughput window, in Unix milliseconds; 0
+    /// before the first fan-out finishes.
+    window_start_ms: AtomicI64,
+    /// Exponentially weighted average of fan-outs finished per second, as
+    /// `f64` bits; 0 until the first window closes.
+    throughput_bits: AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DeliveryGaugesSnapshot {
+    /// Background fan-outs waiting for a delivery worker.
+    pub queued: u64,
+    /// Milliseconds since the queue was last empty, an upper bound on the
+    /// age of the oldest queued fan-out.
+    pub oldest_queued_ms: u64,
+    /// Background fan-outs running.
+    pub active_workers: u64,
+    /// Deliveries in progress by protocol; idle protocols are left out.
+    pub in_flight: BTreeMap<&'static str, u64>,
+    /// Recent background fan-outs finished per second.
+    pub throughput_per_sec: f64,
+}
+
+impl DeliveryGaugesSnapshot {
+    /// Fan-outs accepted but not finished.
+    pub fn backlog(&self) -> u64 {
+        self.queued + self.active_workers
+    }
+}
+
+/// Holds a gauge up until dropped.
+#[derive(Debug)]
+pub struct GaugeGuard<'a> {
+    gauge: &'a AtomicU64,
+    /// Reset when the gauge drops to zero.
+    since: Option<&'a AtomicI64>,
+}
+
+impl Drop for GaugeGuard<'_> {
+    fn drop(&mut self) {
+        if self.gauge.fetch_sub(1, Ordering::Relaxed) == 1
+            && let Some(since) = self.since
+        {
+            since.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl DeliveryGauges {
+    /// Counts a fan-out waiting for a worker until the guard drops.
+    pub fn queue(&self) -> GaugeGuard<'_> {
+        if self.queued.fetch_add(1, Ordering::Relaxed) == 0 {
+            let now = chrono::Utc::now().timestamp_millis();
+            let _ = self.backlog_since_ms.compare_exchange(
+                0,
+                now,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        }
+        GaugeGuard {
+            gauge: &self.queued,
+            since: Some(&self.backlog_since_ms),
+        }
+    }
+
+    /// Counts a running fan-out until the guard drops.
+    pub fn start_worker(&self) -> GaugeGuard<'_> {
+        self.active_workers.fetch_add(1, Ordering::Relaxed);
+        GaugeGuard {
+            gauge: &self.active_workers,
+            since: None,
+        }
+    }
+
+    /// Counts a delivery to `protocol` until the guard drops; unknown
+    /// protocols are not counted.
+    pub fn deliver(&self, protocol: &str) -> Option<GaugeGuard<'_>> {
+        let index = SUPPORTED_PROTOCOLS.iter().position(|p| *p == protocol)?;
+        self.in_flight[index].fetch_add(1, Ordering::Relaxed);
+        Some(GaugeGuard {
+            gauge: &self.in_flight[index],
+            since: None,
+        })
+    }
+
+    /// Counts a finished background fan-out towards the throughput
+    /// average. Whoever closes a window folds it into the average, so
+    /// concurrent callers never fold one twice.
+    pub fn finish_fan_out(&self) {
+        self.finished_in_window.fetch_add(1, Ordering::Relaxed);
+        let now = chrono::Utc::now().timestamp_millis();
+        let start = self.window_start_ms.load(Ordering::Relaxed);
+        if start == 0 {
+            let _ =
+                self.window_start_ms
+                    .compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed);
+            return;
+        }
+        let elapsed = now - start;
+        if elapsed < THROUGHPUT_WINDOW_MS
+            || self
+                .window_start_ms
+                .compare_exchange(start, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            return;
+        }
+        let finished = self.finished_in_window.swap(0, Ordering::Relaxed);
+        if elapsed > THROUGHPUT_WINDOW_MS * THROUGHPUT_IDLE_WINDOWS {
+            return;
+        }
+        let rate = finished as f64 * 1000.0 / elapsed as f64;
+        let average = f64::from_bits(self.throughput_bits.load(Ordering::Relaxed));
+        let average = if average == 0.0 {
+            rate
+        } else {
+            THROUGHPUT_EWMA_ALPHA * rate + (1.0 - THROUGHPUT_EWMA_ALPHA) * average
+        };
+        self.throughput_bits
+            .store(average.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Fan-outs accepted but not finished.
+    pub fn backlog(&self) -> u64 {
+        self.queued.load(Ordering::Relaxed) + self.active_workers.load(Ordering::Relaxed)
+    }
+
+    fn throughput_per_sec(&self) -> f64 {
+        f64::from_bits(self.throughput_bits.load(Ordering::Relaxed))
+    }
+
+    /// How long a publisher should wait before publishing again: the time
+    /// the workers need to work off `backlog` at their recent throughput,
+    /// up to `MAX_SUGGESTED_DELAY_MS`. Until a throughput is known each
+    /// fan-out is taken to need a millisecond.
+    pub fn suggested_delay_ms(&self, backlog: u64) -> u64 {
+        let throughput = self.throughput_per_sec();
+        let delay_ms = if throughput > 0.0 {
+            backlog as f64 * 1000.0 / throughput
+        } else {
+            backlog as f64
+        };
+        (delay_ms.ceil() as u64).min(MAX_SUGGESTED_DELAY_MS)
+    }
+
+    pub fn snapshot(&self) -> DeliveryGaugesSnapshot {
+        let since = self.backlog_since_ms.load(Ordering::Relaxed);
+        let oldest_queued_ms = if since == 0 {
+            0
+        } else {
+            (chrono::Utc::now().timestamp_millis() - since).max(0) as u64
+        };
+        DeliveryGaugesSnapshot {
+            queued: self.queued.load(Ordering::Relaxed),
+            oldest_queued_ms,
+            active_workers: self.active_workers.load(Ordering::Relaxed),
+            in_flight: SUPPORTED_PROTOCOLS
+                .iter()
+                .zip(&self.in_flight)
+                .map(|(protocol, count)| (*protocol, count.load(Ordering::Relaxed)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+            throughput_per_sec: self.throughput_per_sec(),
+        }
+    }
+}
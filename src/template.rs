@@ -0,0 +1,255 @@
+use serde_json::Value;
+
+/// A message template of the `X-LocalSns-MessageTemplate` subscription
+/// attribute: literal text with `{{...}}` placeholders. A placeholder is a
+/// field (`subject`, `message`, `message_id`, `topic_arn`, `timestamp`) or
+/// `json <field> "<path>"`, which parses the field as JSON and extracts the
+/// value at a dotted path such as `detail.items.0.status`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageTemplate {
+    source: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(Field),
+    Json { field: Field, path: Vec<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Subject,
+    Message,
+    MessageId,
+    TopicArn,
+    Timestamp,
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Field, String> {
+        match name {
+            "subject" => Ok(Field::Subject),
+            "message" => Ok(Field::Message),
+            "message_id" => Ok(Field::MessageId),
+            "topic_arn" => Ok(Field::TopicArn),
+            "timestamp" => Ok(Field::Timestamp),
+            _ => Err(format!("unknown field {}", name)),
+        }
+    }
+}
+
+/// The values placeholders are filled from.
+#[derive(Debug, Clone, Copy)]
+pub struct TemplateContext<'a> {
+    pub subject: Option<&'a str>,
+    pub message: &'a str,
+    pub message_id: &'a str,
+    pub topic_arn: &'a str,
+    pub timestamp: &'a str,
+}
+
+impl TemplateContext<'_> {
+    fn get(&self, field: Field) -> &str {
+        match field {
+            Field::Subject => self.subject.unwrap_or_default(),
+            Field::Message => self.message,
+            Field::MessageId => self.message_id,
+            Field::TopicArn => self.topic_arn,
+            Field::Timestamp => self.timestamp,
+        }
+    }
+}
+
+fn parse_placeholder(expression: &str) -> Result<Part, String> {
+    let expression = expression.trim();
+    let Some(rest) = expression.strip_prefix("json ") else {
+        return Field::parse(expression).map(Part::Field);
+    };
+    let (field, path) = rest
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| format!("json needs a field and a path in {{{{{}}}}}", expression))?;
+    let path = path
+        .trim()
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+        .ok_or_else(|| format!("path must be quoted in {{{{{}}}}}", expression))?;
+    if path.is_empty() || path.split('.').any(str::is_empty) {
+        return Err(format!("invalid path \"{}\"", path));
+    }
+    Ok(Part::Json {
+        field: Field::parse(field)?,
+        path: path.split('.').map(str::to_string).collect(),
+    })
+}
+
+impl MessageTemplate {
+    pub fn parse(source: &str) -> Result<MessageTemplate, String> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let end = after.find("}}").ok_or_else(|| "unclosed {{".to_string())?;
+            parts.push(parse_placeholder(&after[..end])?);
+            rest = &after[end + 2..];
+        }
+        if rest.contains("}}") {
+            return Err("unopened }}".to_string());
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(MessageTemplate {
+            source: source.to_string(),
+            parts,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Renders the template. Fails when a `json` placeholder's field is not
+    /// JSON or its path does not exist.
+    pub fn render(&self, context: &TemplateContext) -> Result<String, String> {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Field(field) => rendered.push_str(context.get(*field)),
+                Part::Json { field, path } => {
+                    let document: Value = serde_json::from_str(context.get(*field))
+                        .map_err(|e| format!("field is not JSON: {}", e))?;
+                    let value = path
+                        .iter()
+                        .try_fold(&document, |value, key| match value {
+                            Value::Array(items) => {
+                                key.parse::<usize>().ok().and_then(|i| items.get(i))
+                            }
+                            _ => value.get(key),
+                        })
+                        .ok_or_else(|| format!("no value at \"{}\"", path.join(".")))?;
+                    match value {
+                        Value::String(text) => rendered.push_str(text),
+                        value => rendered.push_str(&value.to_string()),
+                    }
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, subject: Option<&str>, message: &str) -> Result<String, String> {
+        MessageTemplate::parse(template)
+            .unwrap()
+            .render(&TemplateContext {
+                subject,
+                message,
+                message_id: "m1",
+                topic_arn: "arn:aws:sns:us-east-1:000000000000:orders",
+                timestamp: "2026-01-01T00:00:00.000Z",
+            })
+    }
+
+    #[test]
+    fn fields_and_text_are_rendered_in_order() {
+        assert_eq!(
+            render(
+                "{{subject}} — {{message}} ({{message_id}})",
+                Some("Paid"),
+                "hi"
+            )
+            .unwrap(),
+            "Paid — hi (m1)"
+        );
+        assert_eq!(
+            render("{{ topic_arn }} at {{timestamp}}", None, "").unwrap(),
+            "arn:aws:sns:us-east-1:000000000000:orders at 2026-01-01T00:00:00.000Z"
+        );
+        assert_eq!(render("[{{subject}}]", None, "").unwrap(), "[]");
+        assert_eq!(
+            render("no placeholders", None, "").unwrap(),
+            "no placeholders"
+        );
+    }
+
+    #[test]
+    fn json_paths_reach_into_nested_objects_and_arrays() {
+        let message = r#"{"detail":{"status":"shipped","items":[{"sku":"a"},{"sku":"b","qty":2}],"paid":true}}"#;
+        assert_eq!(
+            render(r#"{{json message "detail.status"}}"#, None, message).unwrap(),
+            "shipped"
+        );
+        assert_eq!(
+            render(r#"{{json message "detail.items.1.sku"}}"#, None, message).unwrap(),
+            "b"
+        );
+        // Values that are not strings are rendered as JSON.
+        assert_eq!(
+            render(r#"{{json message "detail.items.1.qty"}}"#, None, message).unwrap(),
+            "2"
+        );
+        assert_eq!(
+            render(r#"{{json message "detail.paid"}}"#, None, message).unwrap(),
+            "true"
+        );
+        assert_eq!(
+            render(r#"{{json message "detail.items.0"}}"#, None, message).unwrap(),
+            r#"{"sku":"a"}"#
+        );
+        assert_eq!(
+            render(r#"{{json subject "id"}}"#, Some(r#"{"id":7}"#), "").unwrap(),
+            "7"
+        );
+    }
+
+    #[test]
+    fn missing_keys_and_non_json_fields_fail_to_render() {
+        let message = r#"{"detail":{"items":[{"sku":"a"}]}}"#;
+        for path in [
+            "detail.status",
+            "detail.items.1",
+            "detail.items.sku",
+            "other",
+        ] {
+            let template = format!(r#"{{{{json message "{}"}}}}"#, path);
+            assert_eq!(
+                render(&template, None, message),
+                Err(format!("no value at \"{}\"", path))
+            );
+        }
+        let error = render(r#"{{json message "status"}}"#, None, "not json").unwrap_err();
+        assert!(error.starts_with("field is not JSON"), "{}", error);
+        // A missing subject is empty, which is not JSON either.
+        assert!(render(r#"{{json subject "id"}}"#, None, "{}").is_err());
+    }
+
+    #[test]
+    fn invalid_templates_do_not_parse() {
+        for template in [
+            "{{subject",
+            "subject}}",
+            "{{body}}",
+            "{{json message}}",
+            "{{json message detail.status}}",
+            r#"{{json body "status"}}"#,
+            r#"{{json message ""}}"#,
+            r#"{{json message "detail..status"}}"#,
+            r#"{{json message ".status"}}"#,
+        ] {
+            assert!(MessageTemplate::parse(template).is_err(), "{}", template);
+        }
+        let source = r#"{{subject}}: {{json message "a.b"}}"#;
+        assert_eq!(MessageTemplate::parse(source).unwrap().source(), source);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::config::Config;
+use crate::memory::{self, ReceivedMessage};
+use crate::state::{AppState, SharedState};
+use crate::tracker::{DEFAULT_WAIT_TIMEOUT, FanOutStatus};
+use axum::Router;
+use axum::body::Body;
+use axum::http::{HeaderValue, Request, header};
+use axum::response::Response;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tower::ServiceExt;
+
+/// An in-process server on an ephemeral port, for integration tests of
+/// code that talks to SNS. Subscribe `memory` endpoints to read deliveries
+/// back with `received` instead of running an SQS emulator.
+pub struct TestServer {
+    addr: SocketAddr,
+    state: SharedState,
+    app: Router,
+    server: JoinHandle<()>,
+}
+
+impl TestServer {
+    /// Starts a server with the default configuration.
+    pub async fn start() -> std::io::Result<TestServer> {
+        Self::with_config(Config::default()).await
+    }
+
+    /// Starts a server with `config`, listening on 127.0.0.1 at a free port
+    /// regardless of `config.addr`.
+    pub async fn with_config(mut config: Config) -> std::io::Result<TestServer> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        config.addr = addr.to_string();
+        let state = Arc::new(AppState::new(config));
+        let app = crate::app(state.clone());
+        let serving = app.clone();
+        let server = tokio::spawn(async move {
+            if let Err(e) = axum::serve(
+                listener,
+                serving.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            {
+                tracing::error!("Test server failed: {}", e);
+            }
+        });
+        Ok(TestServer {
+            addr,
+            state,
+            app,
+            server,
+        })
+    }
+
+    /// Base URL to point an SNS client's endpoint at.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn state(&self) -> &SharedState {
+        &self.state
+    }
+
+    /// Messages delivered to the `memory` buffer `name`, oldest first.
+    pub fn received(&self, name: &str) -> Vec<ReceivedMessage> {
+        memory::received(&self.state, name)
+    }
+
+    /// Waits for every delivery of a published message to finish, so a test
+    /// can read the targets without sleeping. Fails when the message is
+    /// unknown or still has deliveries pending after five seconds.
+    pub async fn delivered(&self, message_id: &str) -> Result<FanOutStatus, String> {
+        self.delivered_within(message_id, DEFAULT_WAIT_TIMEOUT)
+            .await
+    }
+
+    pub async fn delivered_within(
+        &self,
+        message_id: &str,
+        timeout: std::time::Duration,
+    ) -> Result<FanOutStatus, String> {
+        match self.state.fan_outs.wait(message_id, timeout).await {
+            Some(status) if status.complete => Ok(status),
+            Some(status) => Err(format!(
+                "{} of {} deliveries of message {} still pending after {:?}",
+                status.pending, status.deliveries, message_id, timeout
+            )),
+            None => Err(format!("message {} was not published", message_id)),
+        }
+    }
+
+    /// Sends `request` to the app, with the `--admin-token` bearer token
+    /// when one is configured and the request has no `Authorization` of its
+    /// own, so tests reach `/admin` routes either way.
+    pub async fn request(&self, mut request: Request<Body>) -> Response {
+        if let Some(token) = &self.state.admin_token
+            && !request.headers().contains_key(header::AUTHORIZATION)
+            && let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token))
+        {
+            request.headers_mut().insert(header::AUTHORIZATION, value);
+        }
+        match self.app.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(infallible) => match infallible {},
+        }
+    }
+
+    /// Empties the `memory` buffer `name`.
+    pub fn clear(&self, name: &str) {
+        memory::clear(&self.state, name);
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
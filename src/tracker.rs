@@ -0,0 +1,134 @@
+use crate::delivery::DeliveryResult;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How long waiting for a fan-out lasts unless told otherwise.
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Messages whose fan-out is remembered; the oldest are forgotten first.
+const TRACKED_MESSAGE_LIMIT: usize = 10_000;
+
+/// Progress of the deliveries one publish enqueued.
+#[derive(Debug, Clone, Serialize)]
+pub struct FanOutStatus {
+    pub message_id: String,
+    pub topic_arn: String,
+    /// Delivery jobs enqueued, one per subscription.
+    pub deliveries: usize,
+    pub pending: usize,
+    pub delivered: usize,
+    pub failed: usize,
+    /// Jobs dropped without a delivery attempt: disabled subscriptions,
+    /// queues already reached through another subscription, and messages
+    /// a demux subscription filtered out.
+    pub skipped: usize,
+    pub complete: bool,
+    pub published_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// How one delivery job of a fan-out ended. Failed means retries, if any,
+/// were exhausted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobOutcome {
+    Delivered,
+    Failed,
+    Skipped,
+}
+
+impl From<&DeliveryResult> for JobOutcome {
+    fn from(result: &DeliveryResult) -> Self {
+        match result {
+            Ok(_) => JobOutcome::Delivered,
+            Err(_) => JobOutcome::Failed,
+        }
+    }
+}
+
+/// Counts the outstanding delivery jobs of each published message, so
+/// tests can wait for a fan-out to finish instead of polling its targets.
+#[derive(Debug, Default)]
+pub struct FanOutTracker {
+    messages: DashMap<String, watch::Sender<FanOutStatus>>,
+    order: Mutex<VecDeque<String>>,
+}
+
+impl FanOutTracker {
+    /// Records that `deliveries` jobs were enqueued for the message.
+    pub fn start(&self, message_id: &str, topic_arn: &str, deliveries: usize) {
+        let now = Utc::now();
+        let status = FanOutStatus {
+            message_id: message_id.to_string(),
+            topic_arn: topic_arn.to_string(),
+            deliveries,
+            pending: deliveries,
+            delivered: 0,
+            failed: 0,
+            skipped: 0,
+            complete: deliveries == 0,
+            published_at: now,
+            completed_at: (deliveries == 0).then_some(now),
+        };
+        self.messages
+            .insert(message_id.to_string(), watch::Sender::new(status));
+        let mut order = self.order.lock().unwrap();
+        order.push_back(message_id.to_string());
+        while order.len() > TRACKED_MESSAGE_LIMIT {
+            if let Some(oldest) = order.pop_front() {
+                self.messages.remove(&oldest);
+            }
+        }
+    }
+
+    /// Counts one finished job of the message; untracked messages, such as
+    /// redeliveries, are ignored.
+    pub fn finish(&self, message_id: &str, outcome: JobOutcome) {
+        let Some(sender) = self.messages.get(message_id) else {
+            return;
+        };
+        sender.send_if_modified(|status| {
+            if status.pending == 0 {
+                return false;
+            }
+            status.pending -= 1;
+            match outcome {
+                JobOutcome::Delivered => status.delivered += 1,
+                JobOutcome::Failed => status.failed += 1,
+                JobOutcome::Skipped => status.skipped += 1,
+            }
+            if status.pending == 0 {
+                status.complete = true;
+                status.completed_at = Some(Utc::now());
+            }
+            true
+        });
+    }
+
+    pub fn status(&self, message_id: &str) -> Option<FanOutStatus> {
+        self.messages
+            .get(message_id)
+            .map(|sender| sender.borrow().clone())
+    }
+
+    /// Waits up to `timeout` for the message's fan-out to complete and
+    /// returns its status then, complete or not. `None` when untracked.
+    pub async fn wait(&self, message_id: &str, timeout: Duration) -> Option<FanOutStatus> {
+        let mut receiver = self.messages.get(message_id)?.subscribe();
+        // An error means the message was forgotten meanwhile; the last
+        // status seen is still the best answer.
+        let _ = tokio::time::timeout(timeout, receiver.wait_for(|status| status.complete)).await;
+        let status = receiver.borrow().clone();
+        Some(status)
+    }
+
+    pub fn clear(&self) {
+        self.messages.clear();
+        self.order.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,54 @@
+use crate::static_content::StaticContent;
+use axum::Router;
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::get;
+use rust_embed::RustEmbed;
+
+/// The browser UI: plain HTML, CSS and JS compiled into the binary, so
+/// there is no frontend build. It reads the admin API and publishes through
+/// the Query API like any other client.
+#[derive(RustEmbed)]
+#[folder = "ui/"]
+struct Assets;
+
+/// Serves the UI under `/ui/`.
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route("/ui", get(redirect))
+        .route("/ui/", get(index))
+        .route("/ui/*path", get(asset))
+}
+
+async fn index(headers: HeaderMap) -> Response {
+    serve("index.html", &headers)
+}
+
+async fn asset(Path(path): Path<String>, headers: HeaderMap) -> Response {
+    serve(&path, &headers)
+}
+
+/// `/ui` without the slash, so relative asset links resolve under `/ui/`.
+async fn redirect() -> Redirect {
+    Redirect::permanent("/ui/")
+}
+
+fn serve(path: &str, headers: &HeaderMap) -> Response {
+    match Assets::get(path) {
+        Some(file) => {
+            StaticContent::new(content_type(path), file.data.into_owned()).respond(headers)
+        }
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
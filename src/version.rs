@@ -0,0 +1,38 @@
+use crate::handlers::supported_actions;
+use crate::state::SharedState;
+use axum::Json;
+use axum::extract::State;
+use serde::Serialize;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_SHA: &str = env!("LOCAL_SNS_GIT_SHA");
+const FEATURES: &str = env!("LOCAL_SNS_FEATURES");
+
+/// What this binary is and what it supports, for automation that gates on
+/// server capabilities.
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// `--instance-name`, or the hostname.
+    pub instance: String,
+    /// Whether `--strict-aws` disabled the local extensions.
+    pub strict_aws: bool,
+    pub git_sha: &'static str,
+    pub features: Vec<&'static str>,
+    pub actions: Vec<&'static str>,
+}
+
+pub fn build_info(instance: &str, strict_aws: bool) -> BuildInfo {
+    BuildInfo {
+        version: VERSION,
+        instance: instance.to_string(),
+        strict_aws,
+        git_sha: GIT_SHA,
+        features: FEATURES.split(',').filter(|f| !f.is_empty()).collect(),
+        actions: supported_actions(),
+    }
+}
+
+pub async fn version(State(state): State<SharedState>) -> Json<BuildInfo> {
+    Json(build_info(&state.instance_name, state.strict_aws))
+}
@@ -0,0 +1,90 @@
+use quick_xml::Writer;
+use quick_xml::events::BytesText;
+use std::io::{self, Write};
+
+/// Writes each pair as `<entry><key>..</key><value>..</value></entry>`, the
+/// shape of the attribute maps of Get*Attributes responses.
+pub fn write_attribute_entries<W: Write, K: AsRef<str>, V: AsRef<str>>(
+    writer: &mut Writer<W>,
+    entries: &[(K, V)],
+) -> io::Result<()> {
+    for (key, value) in entries {
+        writer
+            .create_element("entry")
+            .write_inner_content(|writer| {
+                writer
+                    .create_element("key")
+                    .write_text_content(BytesText::new(key.as_ref()))?;
+                writer
+                    .create_element("value")
+                    .write_text_content(BytesText::new(value.as_ref()))?;
+                Ok(())
+            })?;
+    }
+    Ok(())
+}
+
+/// Writes a `<member>` per item of a list, its content written by `write`.
+pub fn write_members<W: Write, T>(
+    writer: &mut Writer<W>,
+    items: impl IntoIterator<Item = T>,
+    mut write: impl FnMut(&mut Writer<W>, T) -> io::Result<()>,
+) -> io::Result<()> {
+    for item in items {
+        writer
+            .create_element("member")
+            .write_inner_content(|writer| write(writer, item))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn written(write: impl FnOnce(&mut Writer<Cursor<Vec<u8>>>) -> io::Result<()>) -> String {
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        write(&mut writer).unwrap();
+        String::from_utf8(writer.into_inner().into_inner()).unwrap()
+    }
+
+    #[test]
+    fn attribute_entries_keep_their_order_and_escape_text() {
+        let xml = written(|writer| {
+            write_attribute_entries(writer, &[("DisplayName", "A & <B>"), ("FifoTopic", "true")])
+        });
+        assert_eq!(
+            xml,
+            "<entry><key>DisplayName</key><value>A &amp; &lt;B&gt;</value></entry>\
+             <entry><key>FifoTopic</key><value>true</value></entry>"
+        );
+        let owned = vec![("Policy".to_string(), String::new())];
+        assert_eq!(
+            written(|writer| write_attribute_entries(writer, &owned)),
+            "<entry><key>Policy</key><value></value></entry>"
+        );
+        let none: [(&str, &str); 0] = [];
+        assert_eq!(written(|writer| write_attribute_entries(writer, &none)), "");
+    }
+
+    #[test]
+    fn members_wrap_what_the_closure_writes() {
+        let xml = written(|writer| {
+            write_members(writer, ["a", "b"], |writer, item| {
+                writer
+                    .create_element("TopicArn")
+                    .write_text_content(BytesText::new(item))?;
+                Ok(())
+            })
+        });
+        assert_eq!(
+            xml,
+            "<member><TopicArn>a</TopicArn></member><member><TopicArn>b</TopicArn></member>"
+        );
+        assert_eq!(
+            written(|writer| write_members(writer, Vec::<&str>::new(), |_, _| Ok(()))),
+            ""
+        );
+    }
+}
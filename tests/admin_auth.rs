@@ -0,0 +1,59 @@
+#![cfg(feature = "admin-api")]
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use local_sns_rs::config::Config;
+use local_sns_rs::testing::TestServer;
+use tower::ServiceExt;
+
+async fn server() -> TestServer {
+    let config = Config {
+        admin_token: Some("secret".to_string()),
+        ..Config::default()
+    };
+    TestServer::with_config(config).await.unwrap()
+}
+
+/// Sends a GET straight to the app, as `TestServer::request` would add the
+/// token.
+async fn get(server: &TestServer, uri: &str, authorization: Option<&str>) -> StatusCode {
+    let mut request = Request::get(uri);
+    if let Some(authorization) = authorization {
+        request = request.header("Authorization", authorization);
+    }
+    local_sns_rs::app(server.state().clone())
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn admin_requests_need_the_token() {
+    let server = server().await;
+    assert_eq!(
+        get(&server, "/admin/stats", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        get(&server, "/admin/stats", Some("Bearer wrong")).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        get(&server, "/admin/stats", Some("Bearer secret")).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn only_the_message_stream_takes_the_token_in_the_query() {
+    let server = server().await;
+    assert_eq!(
+        get(&server, "/admin/stats?access_token=secret", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        get(&server, "/admin/messages/stream?access_token=secret", None).await,
+        StatusCode::OK
+    );
+}
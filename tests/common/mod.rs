@@ -0,0 +1,205 @@
+#![allow(dead_code)]
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use local_sns_rs::testing::TestServer;
+
+/// Sends one form-encoded Query API call, returning the status and body.
+pub async fn call(server: &TestServer, params: &[(&str, &str)]) -> (StatusCode, String) {
+    call_with_headers(server, &[], params).await
+}
+
+/// Sends a Query API call as `call` does, with extra request headers.
+pub async fn call_with_headers(
+    server: &TestServer,
+    headers: &[(&str, &str)],
+    params: &[(&str, &str)],
+) -> (StatusCode, String) {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    let mut request =
+        Request::post("/").header("Content-Type", "application/x-www-form-urlencoded");
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    read(
+        server
+            .request(request.body(Body::from(body)).unwrap())
+            .await,
+    )
+    .await
+}
+
+/// Sends an admin API request with an optional JSON body.
+pub async fn admin(
+    server: &TestServer,
+    method: &str,
+    path: &str,
+    body: Option<serde_json::Value>,
+) -> (StatusCode, String) {
+    let request = Request::builder().method(method).uri(path);
+    let request = match body {
+        Some(body) => request
+            .header("Content-Type", "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    };
+    read(server.request(request.unwrap()).await).await
+}
+
+async fn read(response: axum::response::Response) -> (StatusCode, String) {
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+/// The text of the first `<name>` element of an XML response.
+pub fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}>", name);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&format!("</{}>", name))?;
+    Some(&xml[start..start + end])
+}
+
+/// The value of `key` in a GetTopicAttributes or GetSubscriptionAttributes
+/// response.
+pub fn attribute<'a>(xml: &'a str, key: &str) -> Option<&'a str> {
+    let entry = format!("<key>{}</key>", key);
+    let start = xml.find(&entry)? + entry.len();
+    element(&xml[start..], "value")
+}
+
+pub async fn create_topic(server: &TestServer, name: &str) -> String {
+    let (status, body) = call(server, &[("Action", "CreateTopic"), ("Name", name)]).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    element(&body, "TopicArn").unwrap().to_string()
+}
+
+pub async fn set_topic_attribute(server: &TestServer, topic_arn: &str, name: &str, value: &str) {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "SetTopicAttributes"),
+            ("TopicArn", topic_arn),
+            ("AttributeName", name),
+            ("AttributeValue", value),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+pub async fn subscribe(
+    server: &TestServer,
+    topic_arn: &str,
+    protocol: &str,
+    endpoint: &str,
+) -> String {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "Subscribe"),
+            ("TopicArn", topic_arn),
+            ("Protocol", protocol),
+            ("Endpoint", endpoint),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    element(&body, "SubscriptionArn").unwrap().to_string()
+}
+
+/// Publishes `message` and returns its MessageId.
+pub async fn publish(server: &TestServer, topic_arn: &str, message: &str) -> String {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", topic_arn),
+            ("Message", message),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    element(&body, "MessageId").unwrap().to_string()
+}
+
+/// A request an `Endpoint` received.
+#[derive(Debug, Clone)]
+pub struct Received {
+    pub headers: axum::http::HeaderMap,
+    pub body: String,
+}
+
+/// A local HTTP endpoint to subscribe, recording every POST and answering
+/// each with the next of its scripted statuses, then 200.
+pub struct Endpoint {
+    pub url: String,
+    received: std::sync::Arc<std::sync::Mutex<Vec<Received>>>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl Endpoint {
+    pub async fn start(statuses: &[u16]) -> Endpoint {
+        Self::start_slow(statuses, std::time::Duration::ZERO).await
+    }
+
+    /// Starts an endpoint taking `delay` to answer each request.
+    pub async fn start_slow(statuses: &[u16], delay: std::time::Duration) -> Endpoint {
+        use std::sync::{Arc, Mutex};
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(statuses.to_vec()));
+        let recorded = received.clone();
+        let app =
+            axum::Router::new().fallback(move |headers: axum::http::HeaderMap, body: String| {
+                let recorded = recorded.clone();
+                let statuses = statuses.clone();
+                async move {
+                    tokio::time::sleep(delay).await;
+                    recorded.lock().unwrap().push(Received { headers, body });
+                    let mut statuses = statuses.lock().unwrap();
+                    let status = if statuses.is_empty() {
+                        200
+                    } else {
+                        statuses.remove(0)
+                    };
+                    StatusCode::from_u16(status).unwrap()
+                }
+            });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        Endpoint {
+            url,
+            received,
+            server,
+        }
+    }
+
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Waits until the endpoint has received `count` requests.
+    pub async fn wait_for(&self, count: usize) -> Vec<Received> {
+        for _ in 0..250 {
+            let received = self.received();
+            if received.len() >= count {
+                return received;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("{} never received {} requests", self.url, count);
+    }
+}
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{call, create_topic, element, subscribe};
+use local_sns_rs::testing::TestServer;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn concurrent_create_topic_and_subscribe_keep_every_subscription() {
+    let server = Arc::new(TestServer::start().await.unwrap());
+    let mut tasks = JoinSet::new();
+    for i in 0..100 {
+        let server = server.clone();
+        tasks.spawn(async move {
+            let topic_arn = create_topic(&server, "shared").await;
+            subscribe(&server, &topic_arn, "memory", &format!("inbox-{}", i)).await;
+            topic_arn
+        });
+    }
+    let topic_arns: Vec<String> = tasks.join_all().await;
+    assert!(topic_arns.iter().all(|arn| *arn == topic_arns[0]));
+
+    let state = server.state();
+    assert_eq!(state.topics.len(), 1);
+    let topic = state.topics.get("shared").unwrap();
+    assert_eq!(topic.subscriptions.len(), 100);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn create_topic_racing_delete_topic_answers_every_call() {
+    let server = Arc::new(TestServer::start().await.unwrap());
+    let topic_arn = create_topic(&server, "shared").await;
+    let mut tasks = JoinSet::new();
+    for i in 0..100 {
+        let server = server.clone();
+        let topic_arn = topic_arn.clone();
+        tasks.spawn(async move {
+            if i % 2 == 0 {
+                call(
+                    &server,
+                    &[("Action", "DeleteTopic"), ("TopicArn", &topic_arn)],
+                )
+                .await
+            } else {
+                create_topic(&server, "shared").await;
+                call(
+                    &server,
+                    &[
+                        ("Action", "Subscribe"),
+                        ("TopicArn", &topic_arn),
+                        ("Protocol", "memory"),
+                        ("Endpoint", &format!("inbox-{}", i)),
+                    ],
+                )
+                .await
+            }
+        });
+    }
+    let results = tokio::time::timeout(Duration::from_secs(10), tasks.join_all())
+        .await
+        .expect("create and delete calls deadlocked");
+    for (status, body) in results {
+        // A Subscribe may find the topic deleted after its CreateTopic.
+        assert!(
+            status == StatusCode::OK
+                || status == StatusCode::NOT_FOUND && element(&body, "Code") == Some("NotFound"),
+            "{}",
+            body
+        );
+    }
+
+    // Whatever survived is consistent and the topic is usable again.
+    create_topic(&server, "shared").await;
+    subscribe(&server, &topic_arn, "memory", "after").await;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "ListSubscriptionsByTopic"),
+            ("TopicArn", &topic_arn),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let listed = body.matches("<SubscriptionArn>").count();
+    assert_eq!(
+        listed,
+        server
+            .state()
+            .topics
+            .get("shared")
+            .unwrap()
+            .subscriptions
+            .len()
+    );
+    assert!(listed >= 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn deleting_a_busy_topic_leaves_no_orphans() {
+    let server = Arc::new(TestServer::start().await.unwrap());
+    let topic_arn = create_topic(&server, "shared").await;
+    let mut tasks = JoinSet::new();
+    for i in 0..300 {
+        let server = server.clone();
+        let topic_arn = topic_arn.clone();
+        tasks.spawn(async move {
+            let endpoint = format!("inbox-{}", i % 10);
+            let params: Vec<(&str, &str)> = match i % 3 {
+                0 if i % 30 == 0 => vec![("Action", "DeleteTopic"), ("TopicArn", &topic_arn)],
+                0 => vec![("Action", "CreateTopic"), ("Name", "shared")],
+                1 => vec![
+                    ("Action", "Subscribe"),
+                    ("TopicArn", &topic_arn),
+                    ("Protocol", "memory"),
+                    ("Endpoint", &endpoint),
+                ],
+                _ => vec![
+                    ("Action", "Publish"),
+                    ("TopicArn", &topic_arn),
+                    ("Message", "order"),
+                ],
+            };
+            let (status, body) = call(&server, &params).await;
+            // Calls after a removal find no topic; nothing else may fail.
+            assert!(
+                status == StatusCode::OK
+                    || status == StatusCode::NOT_FOUND
+                        && element(&body, "Code") == Some("NotFound"),
+                "{:?}: {}",
+                params,
+                body
+            );
+            element(&body, "MessageId").map(str::to_string)
+        });
+    }
+    let message_ids = tokio::time::timeout(Duration::from_secs(20), tasks.join_all())
+        .await
+        .expect("calls deadlocked");
+    for message_id in message_ids.into_iter().flatten() {
+        server.delivered(&message_id).await.unwrap();
+    }
+
+    let state = server.state();
+    let live: Vec<String> = state
+        .topics
+        .iter()
+        .flat_map(|topic| {
+            topic
+                .subscriptions
+                .iter()
+                .map(|s| s.subscription_arn.clone())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    for health in state.subscription_health.iter() {
+        assert!(
+            live.contains(health.key()),
+            "orphaned health {}",
+            health.key()
+        );
+    }
+    for routes in state.demux_routes.iter() {
+        assert!(
+            live.contains(routes.key()),
+            "orphaned routes {}",
+            routes.key()
+        );
+    }
+    if !state.topics.contains_key("shared") {
+        assert!(!state.stats.contains_key("shared"));
+    }
+    for topic in state.topics.iter() {
+        assert!(topic.subscriptions.iter().all(|s| s.arn == topic.arn));
+    }
+}
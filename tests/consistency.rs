@@ -0,0 +1,157 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{call, element};
+use local_sns_rs::config::Config;
+use local_sns_rs::latency::LatencyProfile;
+use local_sns_rs::testing::TestServer;
+use std::time::{Duration, Instant};
+
+const DELAY: Duration = Duration::from_millis(300);
+
+async fn server(consistency_delay: Duration) -> TestServer {
+    TestServer::with_config(Config {
+        consistency_delay_ms: consistency_delay.as_millis() as u64,
+        ..Config::default()
+    })
+    .await
+    .unwrap()
+}
+
+/// Creates a topic, which CreateTopic answers at once even while it stays
+/// invisible.
+async fn create_topic(server: &TestServer, name: &str) -> String {
+    let (status, body) = call(server, &[("Action", "CreateTopic"), ("Name", name)]).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    element(&body, "TopicArn").unwrap().to_string()
+}
+
+async fn publish(server: &TestServer, topic_arn: &str) -> (StatusCode, String) {
+    call(
+        server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", topic_arn),
+            ("Message", "order"),
+        ],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn new_topics_are_not_found_until_the_delay_passes() {
+    let server = server(DELAY).await;
+    let created = Instant::now();
+    let topic_arn = create_topic(&server, "orders").await;
+
+    let (status, body) = publish(&server, &topic_arn).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("NotFound"));
+    let (status, _) = call(
+        &server,
+        &[("Action", "GetTopicAttributes"), ("TopicArn", &topic_arn)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = call(&server, &[("Action", "ListTopics")]).await;
+    assert!(!body.contains(&topic_arn), "{}", body);
+    assert!(created.elapsed() < DELAY, "the checks took too long");
+
+    tokio::time::sleep(DELAY).await;
+    let (status, body) = publish(&server, &topic_arn).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (_, body) = call(&server, &[("Action", "ListTopics")]).await;
+    assert!(body.contains(&topic_arn), "{}", body);
+}
+
+#[tokio::test]
+async fn new_subscriptions_are_not_found_until_the_delay_passes() {
+    let server = server(DELAY).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    tokio::time::sleep(DELAY).await;
+
+    let subscription_arn = common::subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "GetSubscriptionAttributes"),
+            ("SubscriptionArn", &subscription_arn),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{}", body);
+    let (_, body) = call(
+        &server,
+        &[
+            ("Action", "ListSubscriptionsByTopic"),
+            ("TopicArn", &topic_arn),
+        ],
+    )
+    .await;
+    assert!(!body.contains(&subscription_arn), "{}", body);
+
+    tokio::time::sleep(DELAY).await;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "GetSubscriptionAttributes"),
+            ("SubscriptionArn", &subscription_arn),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn without_a_delay_new_topics_are_visible_at_once() {
+    let server = server(Duration::ZERO).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let (status, body) = publish(&server, &topic_arn).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let subscription_arn = common::subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "GetSubscriptionAttributes"),
+            ("SubscriptionArn", &subscription_arn),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn confirm_subscription_can_be_slowed_on_its_own() {
+    let server = TestServer::with_config(Config {
+        latency_profiles: [(
+            "ConfirmSubscription".to_string(),
+            LatencyProfile {
+                delay_ms: DELAY.as_millis() as u64,
+                ..LatencyProfile::default()
+            },
+        )]
+        .into(),
+        ..Config::default()
+    })
+    .await
+    .unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+
+    let started = Instant::now();
+    publish(&server, &topic_arn).await;
+    assert!(started.elapsed() < DELAY);
+
+    let started = Instant::now();
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "ConfirmSubscription"),
+            ("TopicArn", &topic_arn),
+            ("Token", "unknown"),
+        ],
+    )
+    .await;
+    assert!(started.elapsed() >= DELAY, "{:?}", started.elapsed());
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(element(&body, "Message"), Some("Invalid parameter: Token"));
+}
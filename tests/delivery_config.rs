@@ -0,0 +1,86 @@
+#![cfg(feature = "admin-api")]
+
+mod common;
+
+use common::admin;
+use local_sns_rs::testing::TestServer;
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn delivery_tuning_changes_at_runtime() {
+    let server = TestServer::start().await.unwrap();
+    let patch = json!({
+        "delivery_workers": 4,
+        "retry_time_scale": 0.5,
+        "http_timeout_ms": 2000,
+    });
+    let (status, body) = admin(&server, "PATCH", "/admin/config/delivery", Some(patch)).await;
+    assert!(status.is_success(), "{}", body);
+    let delivery: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(delivery["delivery_workers"], 4);
+    assert_eq!(delivery["retry_time_scale"], 0.5);
+    assert_eq!(delivery["http_timeout_ms"], 2000);
+
+    let (_, body) = admin(&server, "GET", "/admin/config", None).await;
+    let config: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(config["retry_time_scale"], 0.5);
+    assert_eq!(config["http_timeout_ms"], 2000);
+}
+
+#[tokio::test]
+async fn out_of_bounds_tuning_is_refused_whole() {
+    let server = TestServer::start().await.unwrap();
+    for patch in [
+        json!({ "retry_time_scale": -1.0 }),
+        json!({ "http_timeout_ms": 0 }),
+        json!({ "delivery_workers": 2, "http_timeout_ms": 10_000_000 }),
+    ] {
+        let (status, body) = admin(&server, "PATCH", "/admin/config/delivery", Some(patch)).await;
+        assert_eq!(status.as_u16(), 400, "{}", body);
+    }
+    let delivery = server.state().scheduler.config();
+    assert_eq!(delivery.delivery_workers, None);
+    assert_eq!(delivery.retry_time_scale, 1.0);
+}
+
+#[cfg(feature = "http-delivery")]
+#[tokio::test]
+async fn more_workers_drain_a_backlog_faster() {
+    use common::{Endpoint, create_topic, publish, subscribe};
+    use local_sns_rs::config::Config;
+    use std::time::{Duration, Instant};
+
+    const MESSAGES: usize = 30;
+    const DELAY: Duration = Duration::from_millis(100);
+    let config = Config {
+        delivery_workers: Some(1),
+        topic_worker_share: 1.0,
+        ..Config::default()
+    };
+    let server = TestServer::with_config(config).await.unwrap();
+    let endpoint = Endpoint::start_slow(&[], DELAY).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+    for n in 0..MESSAGES {
+        publish(&server, &topic_arn, &n.to_string()).await;
+    }
+
+    // One worker delivers one message per DELAY.
+    tokio::time::sleep(DELAY * 5).await;
+    let backlog = MESSAGES - endpoint.received().len();
+    assert!(backlog >= MESSAGES - 10, "{} left", backlog);
+
+    let started = Instant::now();
+    let patch = json!({ "delivery_workers": 10 });
+    let (status, body) = admin(&server, "PATCH", "/admin/config/delivery", Some(patch)).await;
+    assert!(status.is_success(), "{}", body);
+    endpoint.wait_for(MESSAGES).await;
+    let drained = started.elapsed();
+    // One worker would need DELAY per message left.
+    assert!(
+        drained < DELAY * backlog as u32 / 2,
+        "{} messages drained in {:?}",
+        backlog,
+        drained
+    );
+}
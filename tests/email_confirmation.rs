@@ -0,0 +1,155 @@
+#![cfg(feature = "admin-api")]
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{admin, attribute, call, create_topic, element};
+use local_sns_rs::config::Config;
+use local_sns_rs::testing::TestServer;
+
+async fn server(confirmation_token_ttl_secs: u64) -> TestServer {
+    TestServer::with_config(Config {
+        confirm_email_subscriptions: true,
+        confirmation_token_ttl_secs,
+        ..Config::default()
+    })
+    .await
+    .unwrap()
+}
+
+/// Subscribes an email-json endpoint, returning the ARN of the pending
+/// subscription.
+async fn subscribe(server: &TestServer, topic_arn: &str) -> String {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "Subscribe"),
+            ("TopicArn", topic_arn),
+            ("Protocol", "email-json"),
+            ("Endpoint", "someone@example.com"),
+            ("ReturnSubscriptionArn", "true"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    element(&body, "SubscriptionArn").unwrap().to_string()
+}
+
+/// The tokens of the confirmation emails in the outbox, oldest first.
+async fn tokens(server: &TestServer) -> Vec<String> {
+    let (status, body) = admin(server, "GET", "/admin/outbox", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let outbox: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    outbox
+        .iter()
+        .map(|email| {
+            let body: serde_json::Value =
+                serde_json::from_str(email["body"].as_str().unwrap()).unwrap();
+            body["Token"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+async fn confirm(server: &TestServer, topic_arn: &str, token: &str) -> (StatusCode, String) {
+    call(
+        server,
+        &[
+            ("Action", "ConfirmSubscription"),
+            ("TopicArn", topic_arn),
+            ("Token", token),
+        ],
+    )
+    .await
+}
+
+async fn pending_confirmation(server: &TestServer, subscription_arn: &str) -> String {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "GetSubscriptionAttributes"),
+            ("SubscriptionArn", subscription_arn),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    attribute(&body, "PendingConfirmation").unwrap().to_string()
+}
+
+/// Makes the confirmation token of the only subscription of `orders`
+/// older than the default lifetime.
+fn expire_token(server: &TestServer) {
+    let mut topic = server.state().topics.get_mut("orders").unwrap();
+    topic.subscriptions[0].confirmation_issued_at =
+        Some(chrono::Utc::now() - chrono::Duration::days(4));
+}
+
+#[tokio::test]
+async fn expired_confirmation_tokens_are_refused() {
+    let server = server(1).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn).await;
+    let token = tokens(&server).await.remove(0);
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let (status, body) = confirm(&server, &topic_arn, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(element(&body, "Message"), Some("Invalid parameter: Token"));
+    assert_eq!(
+        pending_confirmation(&server, &subscription_arn).await,
+        "true"
+    );
+}
+
+#[tokio::test]
+async fn resent_confirmations_can_be_confirmed() {
+    let server = server(Config::default().confirmation_token_ttl_secs).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn).await;
+    let resend = format!(
+        "/admin/subscriptions/{}/resend-confirmation",
+        subscription_arn
+    );
+
+    // A valid token is sent again.
+    let (status, body) = admin(&server, "POST", &resend, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let sent = tokens(&server).await;
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0], sent[1]);
+
+    // An expired one is replaced.
+    expire_token(&server);
+    let (status, body) = admin(&server, "POST", &resend, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let sent = tokens(&server).await;
+    assert_eq!(sent.len(), 3);
+    assert_ne!(sent[2], sent[0]);
+
+    let (status, _) = confirm(&server, &topic_arn, &sent[0]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = confirm(&server, &topic_arn, &sent[2]).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        element(&body, "SubscriptionArn"),
+        Some(subscription_arn.as_str())
+    );
+    assert_eq!(
+        pending_confirmation(&server, &subscription_arn).await,
+        "false"
+    );
+
+    // Confirmed subscriptions have nothing to resend.
+    let (status, _) = admin(&server, "POST", &resend, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = admin(
+        &server,
+        "POST",
+        &format!(
+            "/admin/subscriptions/{}:missing/resend-confirmation",
+            topic_arn
+        ),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,253 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{call, create_topic, element};
+use local_sns_rs::testing::TestServer;
+
+const MISSING_TOPIC_ARN: &str = "arn:aws:sns:us-east-1:000000000000:missing";
+
+/// A bad request, with the status, code and message AWS answers it with.
+type Case<'a> = (&'a [(&'a str, &'a str)], StatusCode, &'a str, &'a str);
+
+#[tokio::test]
+async fn bad_requests_get_the_aws_error_responses() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let unknown_subscription_arn = format!("{}:0000", topic_arn);
+    let cases: &[Case] = &[
+        (
+            &[("Action", "Publish"), ("Message", "order")],
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter: TopicArn Reason: no value for required parameter",
+        ),
+        (
+            &[("Action", "Publish"), ("TopicArn", &topic_arn)],
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter: Message Reason: no value for required parameter",
+        ),
+        (
+            &[
+                ("Action", "Publish"),
+                ("TopicArn", MISSING_TOPIC_ARN),
+                ("Message", "order"),
+            ],
+            StatusCode::NOT_FOUND,
+            "NotFound",
+            "Topic does not exist",
+        ),
+        (
+            &[
+                ("Action", "GetTopicAttributes"),
+                ("TopicArn", MISSING_TOPIC_ARN),
+            ],
+            StatusCode::NOT_FOUND,
+            "NotFound",
+            "Topic does not exist",
+        ),
+        (
+            &[("Action", "DeleteTopic")],
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter: TopicArn Reason: no value for required parameter",
+        ),
+        (
+            &[("Action", "CreateTopic")],
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter: Name Reason: no value for required parameter",
+        ),
+        (
+            &[("Action", "CreateTopic"), ("Name", "bad name")],
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter: Topic Name",
+        ),
+        (
+            &[
+                ("Action", "Subscribe"),
+                ("TopicArn", &topic_arn),
+                ("Endpoint", "inbox"),
+            ],
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter: Protocol Reason: no value for required parameter",
+        ),
+        (
+            &[
+                ("Action", "Subscribe"),
+                ("TopicArn", &topic_arn),
+                ("Protocol", "carrier-pigeon"),
+                ("Endpoint", "inbox"),
+            ],
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter: Protocol",
+        ),
+        (
+            &[
+                ("Action", "Unsubscribe"),
+                ("SubscriptionArn", &unknown_subscription_arn),
+            ],
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter: SubscriptionArn",
+        ),
+        (
+            &[
+                ("Action", "SetTopicAttributes"),
+                ("TopicArn", &topic_arn),
+                ("AttributeName", "Bogus"),
+                ("AttributeValue", "1"),
+            ],
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter: AttributeName",
+        ),
+        (
+            &[("Action", "Frobnicate")],
+            StatusCode::BAD_REQUEST,
+            "InvalidAction",
+            "The action Frobnicate is not valid for this endpoint",
+        ),
+        (
+            &[("Action", "ListTopics"), ("Version", "2009-01-01")],
+            StatusCode::BAD_REQUEST,
+            "NoSuchVersion",
+            "The requested version (2009-01-01) of service AmazonSNS does not exist",
+        ),
+        (
+            &[("Action", "PublishBatch"), ("TopicArn", &topic_arn)],
+            StatusCode::BAD_REQUEST,
+            "EmptyBatchRequest",
+            "The batch request doesn&apos;t contain any entries",
+        ),
+    ];
+    for (params, status, code, message) in cases {
+        let (actual_status, body) = call(&server, params).await;
+        assert_eq!(actual_status, *status, "{:?}: {}", params, body);
+        assert_eq!(
+            element(&body, "Code"),
+            Some(*code),
+            "{:?}: {}",
+            params,
+            body
+        );
+        assert_eq!(
+            element(&body, "Message"),
+            Some(*message),
+            "{:?}: {}",
+            params,
+            body
+        );
+        assert_eq!(element(&body, "Type"), Some("Sender"), "{:?}", params);
+    }
+}
+
+#[tokio::test]
+async fn huge_list_indices_are_malformed_input() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "TagResource"),
+            ("ResourceArn", &topic_arn),
+            ("Tags.member.4294967295.Key", "team"),
+            ("Tags.member.4294967295.Value", "a"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("MalformedInput"), "{}", body);
+}
+
+/// Sends `method` to `path` with `params` as its form, returning the status
+/// and body.
+async fn request_path(
+    server: &TestServer,
+    method: &str,
+    path: &str,
+    params: &[(&str, &str)],
+) -> (StatusCode, String) {
+    let form = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    let request = Request::builder()
+        .method(method)
+        .uri(path)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(Body::from(form))
+        .unwrap();
+    let response = server.request(request).await;
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+#[tokio::test]
+async fn unknown_paths_get_an_aws_error() {
+    let server = TestServer::start().await.unwrap();
+    for (method, path) in [("POST", "/foo"), ("GET", "/foo"), ("POST", "/foo/bar/")] {
+        let (status, body) = request_path(&server, method, path, &[]).await;
+        assert_eq!(
+            status,
+            StatusCode::NOT_FOUND,
+            "{} {}: {}",
+            method,
+            path,
+            body
+        );
+        assert_eq!(element(&body, "Code"), Some("InvalidAction"), "{}", body);
+        assert_eq!(
+            element(&body, "Message"),
+            Some(format!("The path {} is not valid for this endpoint", path).as_str())
+        );
+        assert_eq!(element(&body, "Type"), Some("Sender"), "{}", body);
+    }
+}
+
+#[tokio::test]
+async fn query_calls_on_suffixed_paths_are_dispatched() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+
+    // The action in the path.
+    let (status, body) = request_path(
+        &server,
+        "POST",
+        "/000000000000/Publish",
+        &[("TopicArn", &topic_arn), ("Message", "hello")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body.contains("<PublishResponse"), "{}", body);
+    let message_id = element(&body, "MessageId").unwrap();
+    assert!(server.state().find_message(message_id).is_some());
+
+    // A prefix before a form naming its action.
+    let (status, body) = request_path(
+        &server,
+        "POST",
+        "/doc/2010-03-31/",
+        &[("Action", "CreateTopic"), ("Name", "shipments")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(element(&body, "TopicArn").unwrap().ends_with(":shipments"));
+
+    // The form's Action wins over the path.
+    let (status, body) = request_path(
+        &server,
+        "POST",
+        "/000000000000/Publish",
+        &[("Action", "ListTopics")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body.contains("<ListTopicsResponse"), "{}", body);
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "http-delivery")]
+
+mod common;
+
+use common::{Endpoint, create_topic, publish, subscribe};
+use local_sns_rs::config::Config;
+use local_sns_rs::testing::TestServer;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn a_flooded_topic_does_not_hold_up_the_others() {
+    const FLOOD: usize = 40;
+    const SPORADIC: usize = 20;
+    const DELAY: Duration = Duration::from_secs(1);
+    let config = Config {
+        delivery_workers: Some(4),
+        topic_worker_share: 0.5,
+        ..Config::default()
+    };
+    let server = TestServer::with_config(config).await.unwrap();
+    let endpoint = Endpoint::start_slow(&[], DELAY).await;
+    let flooded = create_topic(&server, "flooded").await;
+    subscribe(&server, &flooded, "http", &endpoint.url).await;
+    let sporadic = create_topic(&server, "sporadic").await;
+    subscribe(&server, &sporadic, "memory", "sporadic").await;
+
+    // The flood holds its two workers for FLOOD / 2 * DELAY (20s). Were it
+    // to hold all four, each sporadic message would wait up to DELAY for
+    // one of them.
+    for n in 0..FLOOD {
+        publish(&server, &flooded, &n.to_string()).await;
+    }
+    for n in 0..SPORADIC {
+        let message_id = publish(&server, &sporadic, &n.to_string()).await;
+        server.delivered(&message_id).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+
+    let received = server.received("sporadic");
+    assert_eq!(received.len(), SPORADIC);
+    let mut lags: Vec<_> = received
+        .iter()
+        .map(|message| message.received_at - message.timestamp)
+        .collect();
+    lags.sort();
+    let p99 = lags[(lags.len() * 99).div_ceil(100) - 1];
+    assert!(
+        p99 < chrono::Duration::milliseconds(500),
+        "p99 lag {:?} of {:?}",
+        p99,
+        lags
+    );
+    assert!(endpoint.received().len() < FLOOD / 2, "the flood drained");
+}
+
+#[tokio::test]
+async fn the_suggested_delay_grows_with_the_backlog() {
+    const PUBLISHES: usize = 6;
+    let config = Config {
+        delivery_workers: Some(1),
+        ..Config::default()
+    };
+    let server = TestServer::with_config(config).await.unwrap();
+    let endpoint = Endpoint::start_slow(&[], Duration::from_secs(2)).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+
+    // The one worker is stuck on the first fan-out, so each publish queues
+    // another behind it.
+    let mut headers = vec![];
+    for n in 0..PUBLISHES {
+        let response = server
+            .request(
+                axum::http::Request::post("/")
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(axum::body::Body::from(format!(
+                        "Action=Publish&TopicArn={}&Message={}",
+                        topic_arn, n
+                    )))
+                    .unwrap(),
+            )
+            .await;
+        assert!(response.status().is_success());
+        let header =
+            |name: &str| -> u64 { response.headers()[name].to_str().unwrap().parse().unwrap() };
+        headers.push((
+            header("x-localsns-backlog"),
+            header("x-localsns-suggested-delay-ms"),
+        ));
+        // Lets the fan-out task register in the backlog.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    for pair in headers.windows(2) {
+        assert!(pair[0].0 <= pair[1].0, "backlog {:?}", headers);
+        assert!(pair[0].1 <= pair[1].1, "suggested delay {:?}", headers);
+    }
+    let (first, last) = (headers[0], headers[PUBLISHES - 1]);
+    assert!(last.0 >= first.0 + PUBLISHES as u64 - 2, "{:?}", headers);
+    assert!(last.1 > first.1, "{:?}", headers);
+    // Nothing was delivered meanwhile.
+    assert!(endpoint.received().len() <= 1);
+}
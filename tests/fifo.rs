@@ -0,0 +1,374 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{call, element, subscribe};
+use local_sns_rs::state::DEDUPLICATION_WINDOW;
+use local_sns_rs::testing::TestServer;
+
+async fn create_fifo_topic(server: &TestServer, name: &str) -> String {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "CreateTopic"),
+            ("Name", name),
+            ("Attributes.entry.1.key", "FifoTopic"),
+            ("Attributes.entry.1.value", "true"),
+            ("Attributes.entry.2.key", "ContentBasedDeduplication"),
+            ("Attributes.entry.2.value", "true"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    element(&body, "TopicArn").unwrap().to_string()
+}
+
+/// Publishes to a FIFO topic, returning the MessageId and SequenceNumber.
+async fn publish(
+    server: &TestServer,
+    topic_arn: &str,
+    message: &str,
+    deduplication_id: Option<&str>,
+) -> (String, String) {
+    let mut params = vec![
+        ("Action", "Publish"),
+        ("TopicArn", topic_arn),
+        ("Message", message),
+        ("MessageGroupId", "orders"),
+    ];
+    if let Some(deduplication_id) = deduplication_id {
+        params.push(("MessageDeduplicationId", deduplication_id));
+    }
+    let (status, body) = call(server, &params).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    (
+        element(&body, "MessageId").unwrap().to_string(),
+        element(&body, "SequenceNumber").unwrap().to_string(),
+    )
+}
+
+#[tokio::test]
+async fn duplicates_by_id_return_the_original_publish() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_fifo_topic(&server, "orders.fifo").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+
+    let original = publish(&server, &topic_arn, "order 1", Some("order-1")).await;
+    let duplicate = publish(&server, &topic_arn, "order 1 again", Some("order-1")).await;
+    assert_eq!(duplicate, original);
+    let other = publish(&server, &topic_arn, "order 2", Some("order-2")).await;
+    assert_ne!(other.0, original.0);
+    assert!(other.1 > original.1);
+
+    server.delivered(&other.0).await.unwrap();
+    let received = server.received("inbox");
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0].message_id, original.0);
+    assert_eq!(received[1].message_id, other.0);
+}
+
+#[tokio::test]
+async fn duplicates_by_content_return_the_original_publish() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_fifo_topic(&server, "orders.fifo").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+
+    let original = publish(&server, &topic_arn, "order 1", None).await;
+    assert_eq!(
+        publish(&server, &topic_arn, "order 1", None).await,
+        original
+    );
+    server.delivered(&original.0).await.unwrap();
+    assert_eq!(server.received("inbox").len(), 1);
+
+    // PublishBatch entries are deduplicated against earlier publishes too.
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "PublishBatch"),
+            ("TopicArn", &topic_arn),
+            ("PublishBatchRequestEntries.member.1.Id", "1"),
+            ("PublishBatchRequestEntries.member.1.Message", "order 1"),
+            (
+                "PublishBatchRequestEntries.member.1.MessageGroupId",
+                "orders",
+            ),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(element(&body, "MessageId"), Some(original.0.as_str()));
+    assert_eq!(element(&body, "SequenceNumber"), Some(original.1.as_str()));
+    assert_eq!(server.received("inbox").len(), 1);
+}
+
+#[tokio::test]
+async fn publishes_outside_the_window_are_new_messages() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_fifo_topic(&server, "orders.fifo").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+
+    let original = publish(&server, &topic_arn, "order 1", Some("order-1")).await;
+    for publish in server
+        .state()
+        .topics
+        .get_mut("orders.fifo")
+        .unwrap()
+        .deduplication
+        .values_mut()
+    {
+        publish.published_at -= DEDUPLICATION_WINDOW + chrono::Duration::seconds(1);
+    }
+    let republished = publish(&server, &topic_arn, "order 1", Some("order-1")).await;
+    assert_ne!(republished.0, original.0);
+    assert!(republished.1 > original.1);
+    server.delivered(&republished.0).await.unwrap();
+    assert_eq!(server.received("inbox").len(), 2);
+}
+
+#[tokio::test]
+async fn fifo_topics_only_take_queue_subscriptions() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_fifo_topic(&server, "orders.fifo").await;
+    for (protocol, endpoint) in [
+        ("http", "http://example.com/events"),
+        ("https", "https://example.com/events"),
+        ("email", "orders@example.com"),
+        ("email-json", "orders@example.com"),
+        ("sms", "+15555550100"),
+        (
+            "lambda",
+            "arn:aws:lambda:us-east-1:000000000000:function:orders",
+        ),
+    ] {
+        let (status, body) = call(
+            &server,
+            &[
+                ("Action", "Subscribe"),
+                ("TopicArn", &topic_arn),
+                ("Protocol", protocol),
+                ("Endpoint", endpoint),
+            ],
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", protocol, body);
+        assert_eq!(element(&body, "Code"), Some("InvalidParameter"));
+        assert_eq!(
+            element(&body, "Message"),
+            Some("Invalid parameter: Only SQS subscriptions are supported for FIFO topics"),
+            "{}",
+            protocol
+        );
+    }
+
+    subscribe(
+        &server,
+        &topic_arn,
+        "sqs",
+        "arn:aws:sqs:us-east-1:000000000000:orders.fifo",
+    )
+    .await;
+    subscribe(
+        &server,
+        &topic_arn,
+        "sqs",
+        "arn:aws:sqs:us-east-1:000000000000:orders",
+    )
+    .await;
+    subscribe(&server, &topic_arn, "memory", "orders").await;
+    let topic = server.state().topics.get("orders.fifo").unwrap();
+    assert_eq!(topic.subscriptions.len(), 3);
+}
+
+/// Publishes to `group` of a FIFO topic, returning the MessageId.
+async fn publish_to_group(
+    server: &TestServer,
+    topic_arn: &str,
+    group: &str,
+    deduplication_id: &str,
+) -> String {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", topic_arn),
+            ("Message", "order"),
+            ("MessageGroupId", group),
+            ("MessageDeduplicationId", deduplication_id),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    element(&body, "MessageId").unwrap().to_string()
+}
+
+#[tokio::test]
+async fn message_group_scope_deduplicates_within_each_group() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_fifo_topic(&server, "orders.fifo").await;
+    common::set_topic_attribute(&server, &topic_arn, "FifoThroughputScope", "MessageGroup").await;
+
+    let first = publish_to_group(&server, &topic_arn, "eu", "order-1").await;
+    let other_group = publish_to_group(&server, &topic_arn, "us", "order-1").await;
+    assert_ne!(other_group, first);
+    let same_group = publish_to_group(&server, &topic_arn, "eu", "order-1").await;
+    assert_eq!(same_group, first);
+}
+
+#[tokio::test]
+async fn topic_scope_deduplicates_across_groups() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_fifo_topic(&server, "orders.fifo").await;
+    common::set_topic_attribute(&server, &topic_arn, "FifoThroughputScope", "Topic").await;
+
+    let first = publish_to_group(&server, &topic_arn, "eu", "order-1").await;
+    assert_eq!(
+        publish_to_group(&server, &topic_arn, "us", "order-1").await,
+        first
+    );
+
+    // Without the attribute the scope is the topic too.
+    let topic_arn = create_fifo_topic(&server, "invoices.fifo").await;
+    let first = publish_to_group(&server, &topic_arn, "eu", "invoice-1").await;
+    assert_eq!(
+        publish_to_group(&server, &topic_arn, "us", "invoice-1").await,
+        first
+    );
+}
+
+#[tokio::test]
+async fn fifo_throughput_scope_takes_only_aws_values() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_fifo_topic(&server, "orders.fifo").await;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "SetTopicAttributes"),
+            ("TopicArn", &topic_arn),
+            ("AttributeName", "FifoThroughputScope"),
+            ("AttributeValue", "Queue"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("InvalidParameter"));
+}
+
+#[tokio::test]
+async fn malformed_fifo_ids_are_invalid_parameters() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_fifo_topic(&server, "orders.fifo").await;
+    let too_long = "a".repeat(129);
+    for (field, id) in [
+        ("MessageGroupId", ""),
+        ("MessageGroupId", "eu west"),
+        ("MessageGroupId", too_long.as_str()),
+        ("MessageDeduplicationId", "order\n1"),
+        ("MessageDeduplicationId", "ordér"),
+        ("MessageDeduplicationId", too_long.as_str()),
+    ] {
+        let mut params = vec![
+            ("Action", "Publish"),
+            ("TopicArn", topic_arn.as_str()),
+            ("Message", "order"),
+            ("MessageGroupId", "eu"),
+            ("MessageDeduplicationId", "order-1"),
+        ];
+        params.retain(|(key, _)| *key != field);
+        params.push((field, id));
+        let (status, body) = call(&server, &params).await;
+        assert_eq!(
+            status,
+            StatusCode::BAD_REQUEST,
+            "{} {:?}: {}",
+            field,
+            id,
+            body
+        );
+        assert_eq!(element(&body, "Code"), Some("InvalidParameter"));
+        assert!(
+            element(&body, "Message").unwrap().contains(field),
+            "{}",
+            body
+        );
+    }
+    let longest = "a".repeat(128);
+    publish_to_group(&server, &topic_arn, &longest, &longest).await;
+}
+
+#[tokio::test]
+async fn malformed_fifo_ids_fail_only_their_batch_entry() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_fifo_topic(&server, "orders.fifo").await;
+    let key = |index: usize, field: &str| {
+        format!("PublishBatchRequestEntries.member.{}.{}", index, field)
+    };
+    let entries = [
+        ("good", "eu", "order-1"),
+        ("bad-group", "eu west", "order-2"),
+        ("bad-dedup", "eu", "order 3"),
+    ];
+    let keys: Vec<[String; 4]> = (1..=entries.len())
+        .map(|i| {
+            [
+                key(i, "Id"),
+                key(i, "Message"),
+                key(i, "MessageGroupId"),
+                key(i, "MessageDeduplicationId"),
+            ]
+        })
+        .collect();
+    let mut params = vec![("Action", "PublishBatch"), ("TopicArn", topic_arn.as_str())];
+    for (keys, (id, group, deduplication_id)) in keys.iter().zip(entries) {
+        params.extend([
+            (keys[0].as_str(), id),
+            (keys[1].as_str(), "order"),
+            (keys[2].as_str(), group),
+            (keys[3].as_str(), deduplication_id),
+        ]);
+    }
+    let (status, body) = call(&server, &params).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let successful = element(&body, "Successful").unwrap();
+    assert_eq!(element(successful, "Id"), Some("good"));
+    let failed = element(&body, "Failed").unwrap();
+    let failures: Vec<&str> = failed.split("<member>").skip(1).collect();
+    assert_eq!(failures.len(), 2, "{}", body);
+    for (failure, (id, field)) in failures.iter().zip([
+        ("bad-group", "MessageGroupId"),
+        ("bad-dedup", "MessageDeduplicationId"),
+    ]) {
+        assert_eq!(element(failure, "Id"), Some(id));
+        assert_eq!(element(failure, "Code"), Some("InvalidParameter"));
+        assert!(
+            element(failure, "Message").unwrap().contains(field),
+            "{}",
+            failure
+        );
+    }
+}
+
+#[tokio::test]
+async fn standard_topics_refuse_fifo_ids() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = common::create_topic(&server, "orders").await;
+    for field in ["MessageGroupId", "MessageDeduplicationId"] {
+        let (status, body) = call(
+            &server,
+            &[
+                ("Action", "Publish"),
+                ("TopicArn", &topic_arn),
+                ("Message", "order"),
+                (field, "eu"),
+            ],
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(
+            element(&body, "Message").unwrap(),
+            format!(
+                "Invalid parameter: The request includes {} parameter that is not valid for this topic type",
+                field
+            )
+        );
+    }
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{call, element};
+use local_sns_rs::testing::TestServer;
+
+/// `xml` with its RequestId and the subscription ARN, which differ on
+/// every run, replaced by placeholders.
+fn normalized(xml: &str, subscription_arn: &str) -> String {
+    let request_id = element(xml, "RequestId").unwrap();
+    xml.replace(request_id, "REQUEST_ID")
+        .replace(subscription_arn, "SUBSCRIPTION_ARN")
+}
+
+/// The Get*Attributes responses of a FIFO topic with an SQS subscription
+/// match those recorded before the attribute maps moved to the `xml`
+/// helpers.
+#[tokio::test]
+async fn attribute_responses_match_the_golden_files() {
+    let server = TestServer::start().await.unwrap();
+    let (_, body) = call(
+        &server,
+        &[
+            ("Action", "CreateTopic"),
+            ("Name", "orders.fifo"),
+            ("Attributes.entry.1.key", "FifoTopic"),
+            ("Attributes.entry.1.value", "true"),
+            ("Attributes.entry.2.key", "ContentBasedDeduplication"),
+            ("Attributes.entry.2.value", "true"),
+            ("Attributes.entry.3.key", "DisplayName"),
+            ("Attributes.entry.3.value", "Orders & <Co>"),
+            ("Attributes.entry.4.key", "KmsMasterKeyId"),
+            ("Attributes.entry.4.value", "alias/aws/sns"),
+            ("Attributes.entry.5.key", "FifoThroughputScope"),
+            ("Attributes.entry.5.value", "MessageGroup"),
+        ],
+    )
+    .await;
+    let topic_arn = element(&body, "TopicArn").unwrap().to_string();
+    let (_, body) = call(
+        &server,
+        &[
+            ("Action", "Subscribe"),
+            ("TopicArn", &topic_arn),
+            ("Protocol", "sqs"),
+            ("Endpoint", "arn:aws:sqs:us-east-1:000000000000:orders.fifo"),
+            ("Attributes.entry.1.key", "RawMessageDelivery"),
+            ("Attributes.entry.1.value", "true"),
+        ],
+    )
+    .await;
+    let subscription_arn = element(&body, "SubscriptionArn").unwrap().to_string();
+
+    let (_, body) = call(
+        &server,
+        &[("Action", "GetTopicAttributes"), ("TopicArn", &topic_arn)],
+    )
+    .await;
+    assert_eq!(
+        normalized(&body, &subscription_arn),
+        include_str!("golden/get_topic_attributes.xml").trim_end()
+    );
+    let (_, body) = call(
+        &server,
+        &[
+            ("Action", "GetSubscriptionAttributes"),
+            ("SubscriptionArn", &subscription_arn),
+        ],
+    )
+    .await;
+    assert_eq!(
+        normalized(&body, &subscription_arn),
+        include_str!("golden/get_subscription_attributes.xml").trim_end()
+    );
+}
@@ -0,0 +1,572 @@
+#![cfg(feature = "http-delivery")]
+
+mod common;
+
+use common::{
+    Endpoint, attribute, call, call_with_headers, create_topic, element, publish,
+    set_topic_attribute, subscribe,
+};
+use local_sns_rs::config::Config;
+use local_sns_rs::testing::TestServer;
+
+/// A server retrying failed deliveries without pausing.
+async fn server() -> TestServer {
+    let config = Config {
+        retry_time_scale: 0.0,
+        ..Config::default()
+    };
+    TestServer::with_config(config).await.unwrap()
+}
+
+#[tokio::test]
+async fn notifications_are_posted_to_http_endpoints() {
+    let server = TestServer::start().await.unwrap();
+    let endpoint = Endpoint::start(&[]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+
+    let message_id = publish(&server, &topic_arn, "hello").await;
+    let status = server.delivered(&message_id).await.unwrap();
+    assert_eq!(status.failed, 0);
+
+    let received = endpoint.received();
+    assert_eq!(received.len(), 1);
+    let headers = &received[0].headers;
+    assert_eq!(headers["x-amz-sns-message-type"], "Notification");
+    assert_eq!(headers["x-amz-sns-message-id"], message_id.as_str());
+    assert_eq!(
+        headers["x-amz-sns-subscription-arn"],
+        subscription_arn.as_str()
+    );
+    let envelope: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+    assert_eq!(envelope["Message"], "hello");
+    assert_eq!(envelope["TopicArn"], topic_arn.as_str());
+
+    let stats = server.state().existing_topic_stats("orders").snapshot();
+    assert_eq!(stats.delivered_ok, 1);
+}
+
+#[tokio::test]
+async fn failed_posts_reach_stats_and_health() {
+    let server = server().await;
+    let endpoint = Endpoint::start(&[500; 10]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+
+    let message_id = publish(&server, &topic_arn, "hello").await;
+    let status = server.delivered(&message_id).await.unwrap();
+    assert_eq!(status.failed, 1);
+
+    let stats = server.state().existing_topic_stats("orders").snapshot();
+    assert_eq!(stats.delivered_failed, 1);
+    let health = server
+        .state()
+        .subscription_health
+        .get(&subscription_arn)
+        .unwrap()
+        .clone();
+    assert!(health.consecutive_failures >= 1);
+    let error = health.last_error.unwrap();
+    assert!(error.error.contains("500"), "{}", error.error);
+}
+
+#[tokio::test]
+async fn unreachable_endpoints_fail_as_network_errors() {
+    let server = server().await;
+    let endpoint = Endpoint::start(&[]).await;
+    let url = endpoint.url.clone();
+    drop(endpoint);
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "http", &url).await;
+
+    let message_id = publish(&server, &topic_arn, "hello").await;
+    let status = server.delivered(&message_id).await.unwrap();
+    assert_eq!(status.failed, 1);
+    let stats = server.state().existing_topic_stats("orders").snapshot();
+    assert_eq!(
+        stats
+            .failures_by_kind
+            .get(&local_sns_rs::delivery::DeliveryErrorKind::Network),
+        Some(&1)
+    );
+}
+
+const TOPIC_POLICY: &str = r#"{"http":{"defaultHealthyRetryPolicy":{"numRetries":4,"minDelayTarget":1,"maxDelayTarget":1}}}"#;
+const OVERRIDING_TOPIC_POLICY: &str = r#"{"http":{"defaultHealthyRetryPolicy":{"numRetries":4,"minDelayTarget":1,"maxDelayTarget":1},"disableSubscriptionOverrides":true}}"#;
+const SUBSCRIPTION_POLICY: &str =
+    r#"{"healthyRetryPolicy":{"numRetries":1,"minDelayTarget":1,"maxDelayTarget":1}}"#;
+
+/// Publishes once to an endpoint that always fails, under the topic and
+/// subscription policies given, and returns the attempts it received.
+async fn attempts_under(topic_policy: &str, subscription_policy: Option<&str>) -> usize {
+    let server = server().await;
+    let endpoint = Endpoint::start(&[500; 20]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    set_topic_attribute(&server, &topic_arn, "DeliveryPolicy", topic_policy).await;
+    let subscription_arn = subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+    if let Some(subscription_policy) = subscription_policy {
+        let (status, body) = call(
+            &server,
+            &[
+                ("Action", "SetSubscriptionAttributes"),
+                ("SubscriptionArn", &subscription_arn),
+                ("AttributeName", "DeliveryPolicy"),
+                ("AttributeValue", subscription_policy),
+            ],
+        )
+        .await;
+        assert!(status.is_success(), "{}", body);
+    }
+
+    let message_id = publish(&server, &topic_arn, "hello").await;
+    server.delivered(&message_id).await.unwrap();
+    endpoint.received().len()
+}
+
+#[tokio::test]
+async fn retries_follow_the_topic_policy() {
+    assert_eq!(attempts_under(TOPIC_POLICY, None).await, 5);
+}
+
+#[tokio::test]
+async fn the_subscription_policy_overrides_the_topic_policy() {
+    assert_eq!(
+        attempts_under(TOPIC_POLICY, Some(SUBSCRIPTION_POLICY)).await,
+        2
+    );
+}
+
+#[tokio::test]
+async fn topics_can_disable_subscription_overrides() {
+    assert_eq!(
+        attempts_under(OVERRIDING_TOPIC_POLICY, Some(SUBSCRIPTION_POLICY)).await,
+        5
+    );
+}
+
+#[tokio::test]
+async fn deliveries_succeed_once_a_retry_does() {
+    let server = server().await;
+    let endpoint = Endpoint::start(&[503, 503]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+
+    let message_id = publish(&server, &topic_arn, "hello").await;
+    let status = server.delivered(&message_id).await.unwrap();
+    assert_eq!(status.delivered, 1);
+    assert_eq!(endpoint.received().len(), 3);
+}
+
+/// Waits for the delivery status file of `topic` to hold `count` records.
+async fn status_records(
+    dir: &std::path::Path,
+    topic: &str,
+    count: usize,
+) -> Vec<serde_json::Value> {
+    let path = dir.join(format!("{}.log", topic));
+    for _ in 0..100 {
+        let records: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        if records.len() >= count {
+            return records;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("{} never held {} records", path.display(), count);
+}
+
+#[tokio::test]
+async fn delivery_status_logs_count_the_attempts() {
+    let dir = std::env::temp_dir().join(format!("delivery-status-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = Config {
+        retry_time_scale: 0.0,
+        delivery_status_dir: Some(dir.clone()),
+        ..Config::default()
+    };
+    let server = TestServer::with_config(config).await.unwrap();
+    let endpoint = Endpoint::start(&[500, 500]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let role = "arn:aws:iam::000000000000:role/feedback";
+    set_topic_attribute(&server, &topic_arn, "HTTPSuccessFeedbackRoleArn", role).await;
+    set_topic_attribute(&server, &topic_arn, "HTTPSuccessFeedbackSampleRate", "100").await;
+    subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+
+    let message_id = publish(&server, &topic_arn, "hello").await;
+    server.delivered(&message_id).await.unwrap();
+    let records = status_records(&dir, "orders", 1).await;
+    assert_eq!(records[0]["status"], "SUCCESS");
+    assert_eq!(records[0]["delivery"]["attempts"], 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn unsubscribe_confirmations_restore_the_subscription() {
+    let config = Config {
+        trust_proxy_headers: true,
+        ..Config::default()
+    };
+    let server = TestServer::with_config(config).await.unwrap();
+    let endpoint = Endpoint::start(&[]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+
+    let (status, body) = call_with_headers(
+        &server,
+        &[
+            ("X-Forwarded-Proto", "https"),
+            ("X-Forwarded-Host", "sns.example.test"),
+        ],
+        &[
+            ("Action", "Unsubscribe"),
+            ("SubscriptionArn", &subscription_arn),
+        ],
+    )
+    .await;
+    assert!(status.is_success(), "{}", body);
+    let received = endpoint.wait_for(1).await;
+    assert_eq!(
+        received[0].headers["x-amz-sns-message-type"],
+        "UnsubscribeConfirmation"
+    );
+    let confirmation: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+    let token = confirmation["Token"].as_str().unwrap();
+    assert!(
+        confirmation["SubscribeURL"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://sns.example.test/?Action=ConfirmSubscription"),
+        "{}",
+        confirmation["SubscribeURL"]
+    );
+
+    let confirm = [
+        ("Action", "ConfirmSubscription"),
+        ("TopicArn", topic_arn.as_str()),
+        ("Token", token),
+    ];
+    let (status, body) = call(&server, &confirm).await;
+    assert!(status.is_success(), "{}", body);
+    assert_eq!(
+        element(&body, "SubscriptionArn"),
+        Some(subscription_arn.as_str())
+    );
+    let message_id = publish(&server, &topic_arn, "hello").await;
+    assert_eq!(server.delivered(&message_id).await.unwrap().delivered, 1);
+
+    // A token restores the subscription once.
+    let (status, _) = call(&server, &confirm).await;
+    assert_eq!(status.as_u16(), 400);
+}
+
+#[tokio::test]
+async fn forwarded_headers_are_ignored_unless_trusted() {
+    let server = TestServer::start().await.unwrap();
+    let base_url = server.state().base_url.clone();
+    let endpoint = Endpoint::start(&[]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+    let forwarded = [
+        ("X-Forwarded-Proto", "https"),
+        ("X-Forwarded-Host", "sns.example.test"),
+        ("X-Forwarded-Port", "8443"),
+    ];
+
+    let (status, body) = call_with_headers(
+        &server,
+        &forwarded,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", &topic_arn),
+            ("Message", "hello"),
+        ],
+    )
+    .await;
+    assert!(status.is_success(), "{}", body);
+    server
+        .delivered(element(&body, "MessageId").unwrap())
+        .await
+        .unwrap();
+    let (status, body) = call_with_headers(
+        &server,
+        &forwarded,
+        &[
+            ("Action", "Unsubscribe"),
+            ("SubscriptionArn", &subscription_arn),
+        ],
+    )
+    .await;
+    assert!(status.is_success(), "{}", body);
+
+    let received = endpoint.wait_for(2).await;
+    let notification: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+    let confirmation: serde_json::Value = serde_json::from_str(&received[1].body).unwrap();
+    for url in [
+        &notification["UnsubscribeURL"],
+        &confirmation["SubscribeURL"],
+    ] {
+        let url = url.as_str().unwrap();
+        assert!(
+            url.starts_with(&format!("{}/?Action=", base_url)),
+            "{}",
+            url
+        );
+        assert!(!url.contains("sns.example.test"), "{}", url);
+    }
+}
+
+#[tokio::test]
+async fn unsubscribe_tokens_expire() {
+    let server = TestServer::start().await.unwrap();
+    let endpoint = Endpoint::start(&[]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+    call(
+        &server,
+        &[
+            ("Action", "Unsubscribe"),
+            ("SubscriptionArn", &subscription_arn),
+        ],
+    )
+    .await;
+    let received = endpoint.wait_for(1).await;
+    let confirmation: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+    let token = confirmation["Token"].as_str().unwrap();
+    server
+        .state()
+        .unsubscribed
+        .get_mut(token)
+        .unwrap()
+        .expires_at = chrono::Utc::now();
+
+    let (status, _) = call(
+        &server,
+        &[
+            ("Action", "ConfirmSubscription"),
+            ("TopicArn", &topic_arn),
+            ("Token", token),
+        ],
+    )
+    .await;
+    assert_eq!(status.as_u16(), 400);
+}
+
+/// How long a synchronous publish to three endpoints, each taking 500ms to
+/// answer, takes with `max_concurrent_deliveries`.
+async fn publish_to_three_slow_endpoints(max_concurrent_deliveries: usize) -> std::time::Duration {
+    let config = Config {
+        max_concurrent_deliveries,
+        ..Config::default()
+    };
+    let server = TestServer::with_config(config).await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let mut endpoints = vec![];
+    for _ in 0..3 {
+        let endpoint = Endpoint::start_slow(&[], std::time::Duration::from_millis(500)).await;
+        subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+        endpoints.push(endpoint);
+    }
+
+    let started = std::time::Instant::now();
+    let (status, body) = call_with_headers(
+        &server,
+        &[("X-LocalSns-Sync-Delivery", "true")],
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", &topic_arn),
+            ("Message", "hello"),
+        ],
+    )
+    .await;
+    let elapsed = started.elapsed();
+    assert!(status.is_success(), "{}", body);
+    for endpoint in &endpoints {
+        assert_eq!(endpoint.received().len(), 1);
+    }
+    elapsed
+}
+
+#[tokio::test]
+async fn one_publish_delivers_to_its_subscriptions_concurrently() {
+    let elapsed = publish_to_three_slow_endpoints(16).await;
+    assert!(
+        elapsed < std::time::Duration::from_millis(1000),
+        "{:?}",
+        elapsed
+    );
+
+    // One at a time, the three deliveries follow each other.
+    let elapsed = publish_to_three_slow_endpoints(1).await;
+    assert!(
+        elapsed >= std::time::Duration::from_millis(1500),
+        "{:?}",
+        elapsed
+    );
+}
+
+const TRACE_HEADER: &str = "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1";
+
+#[tokio::test]
+async fn trace_headers_are_forwarded_to_http_endpoints() {
+    let server = TestServer::start().await.unwrap();
+    let endpoint = Endpoint::start(&[]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+
+    let (status, body) = call_with_headers(
+        &server,
+        &[("X-Amzn-Trace-Id", TRACE_HEADER)],
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", &topic_arn),
+            ("Message", "traced"),
+        ],
+    )
+    .await;
+    assert_eq!(status, 200, "{}", body);
+    let message_id = element(&body, "MessageId").unwrap();
+    server.delivered(message_id).await.unwrap();
+
+    // Untraced publishes carry no trace.
+    let message_id = publish(&server, &topic_arn, "untraced").await;
+    server.delivered(&message_id).await.unwrap();
+
+    let received = endpoint.received();
+    assert_eq!(received[0].headers["x-amzn-trace-id"], TRACE_HEADER);
+    assert!(!received[1].headers.contains_key("x-amzn-trace-id"));
+}
+
+#[tokio::test]
+async fn the_trace_header_attribute_is_a_trace_source() {
+    let server = TestServer::start().await.unwrap();
+    let endpoint = Endpoint::start(&[]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "http", &endpoint.url).await;
+
+    let publish_traced = |headers: &'static [(&'static str, &'static str)]| {
+        let server = &server;
+        let topic_arn = topic_arn.clone();
+        async move {
+            let (status, body) = call_with_headers(
+                server,
+                headers,
+                &[
+                    ("Action", "Publish"),
+                    ("TopicArn", &topic_arn),
+                    ("Message", "traced"),
+                    ("MessageAttributes.entry.1.Name", "AWS.SNS.TraceHeader"),
+                    ("MessageAttributes.entry.1.Value.DataType", "String"),
+                    ("MessageAttributes.entry.1.Value.StringValue", TRACE_HEADER),
+                ],
+            )
+            .await;
+            assert_eq!(status, 200, "{}", body);
+            let message_id = element(&body, "MessageId").unwrap().to_string();
+            server.delivered(&message_id).await.unwrap();
+        }
+    };
+    publish_traced(&[]).await;
+    // The header wins over the attribute.
+    publish_traced(&[(
+        "X-Amzn-Trace-Id",
+        "Root=1-00000000-000000000000000000000000",
+    )])
+    .await;
+
+    let received = endpoint.received();
+    assert_eq!(received[0].headers["x-amzn-trace-id"], TRACE_HEADER);
+    assert_eq!(
+        received[1].headers["x-amzn-trace-id"],
+        "Root=1-00000000-000000000000000000000000"
+    );
+    // The attribute is taken off the message rather than delivered.
+    assert!(
+        !received[0].body.contains("AWS.SNS.TraceHeader"),
+        "{}",
+        received[0].body
+    );
+}
+
+const CUSTOM_HEADERS: &str = r#"{"Authorization":"Bearer s3cret","X-Tenant":"acme"}"#;
+
+/// Subscribes `endpoint` with `CUSTOM_HEADERS` as its `X-LocalSns-Headers`.
+async fn subscribe_with_headers(server: &TestServer, topic_arn: &str, endpoint: &str) -> String {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "Subscribe"),
+            ("TopicArn", topic_arn),
+            ("Protocol", "http"),
+            ("Endpoint", endpoint),
+            ("Attributes.entry.1.key", "X-LocalSns-Headers"),
+            ("Attributes.entry.1.value", CUSTOM_HEADERS),
+        ],
+    )
+    .await;
+    assert_eq!(status, 200, "{}", body);
+    element(&body, "SubscriptionArn").unwrap().to_string()
+}
+
+/// The `X-LocalSns-Headers` GetSubscriptionAttributes reports.
+async fn reported_headers(server: &TestServer, subscription_arn: &str) -> serde_json::Value {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "GetSubscriptionAttributes"),
+            ("SubscriptionArn", subscription_arn),
+        ],
+    )
+    .await;
+    assert_eq!(status, 200, "{}", body);
+    let headers = attribute(&body, "X-LocalSns-Headers").unwrap();
+    serde_json::from_str(&quick_xml::escape::unescape(headers).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn custom_headers_reach_the_endpoint() {
+    let server = TestServer::start().await.unwrap();
+    let endpoint = Endpoint::start(&[]).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe_with_headers(&server, &topic_arn, &endpoint.url).await;
+
+    let message_id = publish(&server, &topic_arn, "hello").await;
+    server.delivered(&message_id).await.unwrap();
+
+    let received = endpoint.received();
+    assert_eq!(received[0].headers["authorization"], "Bearer s3cret");
+    assert_eq!(received[0].headers["x-tenant"], "acme");
+    assert_eq!(
+        received[0].headers["x-amz-sns-message-type"],
+        "Notification"
+    );
+}
+
+#[tokio::test]
+async fn custom_header_values_are_masked_unless_secrets_are_exposed() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn =
+        subscribe_with_headers(&server, &topic_arn, "http://localhost:1/events").await;
+    assert_eq!(
+        reported_headers(&server, &subscription_arn).await,
+        serde_json::json!({"Authorization": "***", "X-Tenant": "***"})
+    );
+
+    let server = TestServer::with_config(Config {
+        expose_secrets: true,
+        ..Config::default()
+    })
+    .await
+    .unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn =
+        subscribe_with_headers(&server, &topic_arn, "http://localhost:1/events").await;
+    assert_eq!(
+        reported_headers(&server, &subscription_arn).await,
+        serde_json::from_str::<serde_json::Value>(CUSTOM_HEADERS).unwrap()
+    );
+}
@@ -0,0 +1,94 @@
+use local_sns_rs::config::Config;
+use local_sns_rs::snapshot::{self, ImportMode, StateDocument};
+use local_sns_rs::state::AppState;
+use serde_json::json;
+
+fn document(value: serde_json::Value) -> StateDocument {
+    serde_json::from_value(value).unwrap()
+}
+
+fn import(state: &AppState, value: serde_json::Value) -> Vec<String> {
+    snapshot::import(state, document(value), ImportMode::Merge)
+        .errors
+        .into_iter()
+        .map(|error| format!("{}: {}", error.item, error.error))
+        .collect()
+}
+
+#[test]
+fn imports_keep_to_the_topic_quota() {
+    let state = AppState::new(Config {
+        max_topics: Some(1),
+        ..Config::default()
+    });
+    let errors = import(
+        &state,
+        json!({ "topics": [{ "name": "orders" }, { "name": "invoices" }] }),
+    );
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].starts_with("topic invoices:"), "{:?}", errors);
+    assert!(state.topics.contains_key("orders"));
+    assert!(!state.topics.contains_key("invoices"));
+
+    // Updating a topic that already exists is not a new topic.
+    let errors = import(
+        &state,
+        json!({ "topics": [{ "name": "orders", "tags": { "team": "a" } }] }),
+    );
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn imports_keep_to_the_subscription_quota() {
+    let state = AppState::new(Config {
+        max_subscriptions_per_topic: Some(1),
+        ..Config::default()
+    });
+    let errors = import(
+        &state,
+        json!({ "topics": [{
+            "name": "orders",
+            "subscriptions": [
+                { "protocol": "memory", "endpoint": "first" },
+                { "protocol": "memory", "endpoint": "second" }
+            ]
+        }] }),
+    );
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(errors[0].contains("memory:second"), "{:?}", errors);
+    assert_eq!(state.topics.get("orders").unwrap().subscriptions.len(), 1);
+}
+
+#[test]
+fn imported_subscriptions_are_checked_as_subscribe_checks_them() {
+    let state = AppState::new(Config::default());
+    let errors = import(
+        &state,
+        json!({ "topics": [{
+            "name": "orders",
+            "subscriptions": [
+                {
+                    "protocol": "application",
+                    "endpoint": "arn:aws:sns:us-east-1:000000000000:endpoint/GCM/app/missing"
+                },
+                {
+                    "protocol": "firehose",
+                    "endpoint": "arn:aws:firehose:us-east-1:000000000000:deliverystream/orders"
+                },
+                {
+                    "protocol": "firehose",
+                    "endpoint": "arn:aws:firehose:us-east-1:000000000000:deliverystream/audit",
+                    "attributes": {
+                        "SubscriptionRoleArn": "arn:aws:iam::000000000000:role/firehose"
+                    }
+                }
+            ]
+        }] }),
+    );
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[0].contains("application:"), "{:?}", errors);
+    assert!(errors[1].contains("deliverystream/orders"), "{:?}", errors);
+    let topic = state.topics.get("orders").unwrap();
+    assert_eq!(topic.subscriptions.len(), 1);
+    assert!(topic.subscriptions[0].endpoint.ends_with("/audit"));
+}
@@ -0,0 +1,154 @@
+#![cfg(feature = "localstack-tests")]
+
+mod common;
+
+use std::sync::Arc;
+
+use aws_sdk_sqs::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_sqs::types::{MessageSystemAttributeName, QueueAttributeName};
+use axum::http::StatusCode;
+use common::{call, element, subscribe};
+use local_sns_rs::testing::TestServer;
+
+fn localstack_endpoint() -> String {
+    std::env::var("LOCALSTACK_ENDPOINT").unwrap_or_else(|_| "http://localhost:4566".to_string())
+}
+
+/// A client of LocalStack's SQS, also used by `server` to deliver there.
+fn localstack_client(server: &TestServer) -> Arc<aws_sdk_sqs::Client> {
+    let endpoint = localstack_endpoint();
+    let config = aws_sdk_sqs::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .endpoint_url(&endpoint)
+        .build();
+    let client = Arc::new(aws_sdk_sqs::Client::from_conf(config));
+    server.state().sqs_clients.insert(endpoint, client.clone());
+    client
+}
+
+/// Creates a FIFO queue with a fresh name and returns its URL, in the
+/// account form `service_endpoint` maps back to LocalStack.
+async fn create_fifo_queue(client: &aws_sdk_sqs::Client) -> String {
+    let name = format!("local-sns-{}.fifo", uuid::Uuid::new_v4().simple());
+    client
+        .create_queue()
+        .queue_name(&name)
+        .attributes(QueueAttributeName::FifoQueue, "true")
+        .send()
+        .await
+        .expect("LocalStack is not reachable at LOCALSTACK_ENDPOINT");
+    format!("{}/000000000000/{}", localstack_endpoint(), name)
+}
+
+async fn create_fifo_topic(server: &TestServer) -> String {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "CreateTopic"),
+            ("Name", "orders.fifo"),
+            ("Attributes.entry.1.key", "FifoTopic"),
+            ("Attributes.entry.1.value", "true"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    element(&body, "TopicArn").unwrap().to_string()
+}
+
+/// Receives the messages on `queue_url` with their system attributes,
+/// waiting for `count`.
+async fn receive(
+    client: &aws_sdk_sqs::Client,
+    queue_url: &str,
+    count: usize,
+) -> Vec<aws_sdk_sqs::types::Message> {
+    let mut messages = Vec::new();
+    for _ in 0..10 {
+        let output = client
+            .receive_message()
+            .queue_url(queue_url)
+            .message_system_attribute_names(MessageSystemAttributeName::All)
+            .max_number_of_messages(10)
+            .wait_time_seconds(1)
+            .send()
+            .await
+            .unwrap();
+        messages.extend(output.messages.unwrap_or_default());
+        if messages.len() >= count {
+            return messages;
+        }
+    }
+    panic!("{} never received {} messages", queue_url, count);
+}
+
+fn system_attribute(
+    message: &aws_sdk_sqs::types::Message,
+    name: MessageSystemAttributeName,
+) -> Option<&str> {
+    message.attributes()?.get(&name).map(String::as_str)
+}
+
+#[tokio::test]
+#[ignore = "needs LocalStack; run with --features localstack-tests -- --ignored"]
+async fn fifo_ids_reach_a_localstack_fifo_queue() {
+    let server = TestServer::start().await.unwrap();
+    let client = localstack_client(&server);
+    let queue_url = create_fifo_queue(&client).await;
+    let topic_arn = create_fifo_topic(&server).await;
+    subscribe(&server, &topic_arn, "sqs", &queue_url).await;
+
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", &topic_arn),
+            ("Message", "created"),
+            ("MessageGroupId", "order-1"),
+            ("MessageDeduplicationId", "created-1"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "PublishBatch"),
+            ("TopicArn", &topic_arn),
+            ("PublishBatchRequestEntries.member.1.Id", "1"),
+            ("PublishBatchRequestEntries.member.1.Message", "shipped"),
+            (
+                "PublishBatchRequestEntries.member.1.MessageGroupId",
+                "order-2",
+            ),
+            (
+                "PublishBatchRequestEntries.member.1.MessageDeduplicationId",
+                "shipped-2",
+            ),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(element(&body, "SequenceNumber").is_some(), "{}", body);
+
+    let messages = receive(&client, &queue_url, 2).await;
+    let mut ids: Vec<_> = messages
+        .iter()
+        .map(|message| {
+            (
+                system_attribute(message, MessageSystemAttributeName::MessageGroupId),
+                system_attribute(message, MessageSystemAttributeName::MessageDeduplicationId),
+            )
+        })
+        .collect();
+    // Messages of different groups may arrive in either order.
+    ids.sort();
+    assert_eq!(
+        ids,
+        vec![
+            (Some("order-1"), Some("created-1")),
+            (Some("order-2"), Some("shipped-2")),
+        ]
+    );
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{call, create_topic, publish, subscribe};
+use local_sns_rs::state::MESSAGE_HISTORY_LIMIT;
+use local_sns_rs::testing::TestServer;
+
+#[tokio::test]
+async fn memory_buffers_receive_the_envelope_or_the_raw_message() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let enveloped = subscribe(&server, &topic_arn, "memory", "enveloped").await;
+    let raw = subscribe(&server, &topic_arn, "memory", "raw").await;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "SetSubscriptionAttributes"),
+            ("SubscriptionArn", &raw),
+            ("AttributeName", "RawMessageDelivery"),
+            ("AttributeValue", "true"),
+        ],
+    )
+    .await;
+    assert!(status.is_success(), "{}", body);
+
+    let message_id = publish(&server, &topic_arn, "order 1").await;
+    server.delivered(&message_id).await.unwrap();
+
+    let received = server.received("enveloped");
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].message_id, message_id);
+    assert_eq!(received[0].subscription_arn, enveloped);
+    assert!(!received[0].raw);
+    let envelope: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+    assert_eq!(envelope["Type"], "Notification");
+    assert_eq!(envelope["Message"], "order 1");
+    assert_eq!(envelope["TopicArn"], topic_arn.as_str());
+
+    let received = server.received("raw");
+    assert_eq!(received.len(), 1);
+    assert!(received[0].raw);
+    assert_eq!(received[0].body, "order 1");
+}
+
+#[tokio::test]
+async fn memory_buffers_keep_the_newest_messages() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let mut last = String::new();
+    for i in 0..=MESSAGE_HISTORY_LIMIT {
+        last = publish(&server, &topic_arn, &format!("order {}", i)).await;
+    }
+    server.delivered(&last).await.unwrap();
+
+    let received = server.received("inbox");
+    assert_eq!(received.len(), MESSAGE_HISTORY_LIMIT);
+    let first: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+    assert_eq!(first["Message"], "order 1");
+    assert_eq!(received.last().unwrap().message_id, last);
+}
+
+#[tokio::test]
+async fn memory_buffers_can_be_cleared() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+    subscribe(&server, &topic_arn, "memory", "audit").await;
+    let message_id = publish(&server, &topic_arn, "order").await;
+    server.delivered(&message_id).await.unwrap();
+
+    server.clear("inbox");
+    assert!(server.received("inbox").is_empty());
+    assert_eq!(server.received("audit").len(), 1);
+    assert!(server.received("unknown").is_empty());
+}
+
+#[cfg(feature = "admin-api")]
+#[tokio::test]
+async fn memory_buffers_are_readable_over_the_admin_api() {
+    use common::admin;
+    use serde_json::Value;
+
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let message_id = publish(&server, &topic_arn, "order").await;
+    server.delivered(&message_id).await.unwrap();
+
+    let (status, body) = admin(&server, "GET", "/admin/queues/inbox/messages", None).await;
+    assert!(status.is_success(), "{}", body);
+    let messages: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(messages.as_array().unwrap().len(), 1);
+    assert_eq!(messages[0]["message_id"], message_id.as_str());
+
+    let (status, body) = admin(&server, "DELETE", "/admin/queues/inbox/messages", None).await;
+    assert!(status.is_success(), "{}", body);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["cleared"], 1);
+    let (_, body) = admin(&server, "GET", "/admin/queues/inbox/messages", None).await;
+    assert_eq!(body, "[]");
+}
+
+#[tokio::test]
+async fn envelope_timestamps_follow_the_clock_offset() {
+    let server = TestServer::with_config(local_sns_rs::config::Config {
+        clock_offset_secs: -3600,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let message_id = publish(&server, &topic_arn, "order").await;
+    server.delivered(&message_id).await.unwrap();
+
+    let received = &server.received("inbox")[0];
+    let envelope: serde_json::Value = serde_json::from_str(&received.body).unwrap();
+    let timestamp = envelope["Timestamp"].as_str().unwrap();
+    assert_eq!(
+        timestamp,
+        (received.timestamp - chrono::Duration::hours(1))
+            .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+            .to_string()
+    );
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{call, element, subscribe};
+use local_sns_rs::testing::TestServer;
+
+const BOUNDARY: &str = "local-sns-boundary";
+
+/// Sends a Query API call as a `multipart/form-data` body.
+async fn call_multipart(server: &TestServer, params: &[(&str, &str)]) -> (StatusCode, String) {
+    let mut body = String::new();
+    for (name, value) in params {
+        body.push_str(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            BOUNDARY, name, value
+        ));
+    }
+    body.push_str(&format!("--{}--\r\n", BOUNDARY));
+    send(server, Body::from(body)).await
+}
+
+async fn send(server: &TestServer, body: Body) -> (StatusCode, String) {
+    let request = Request::post("/")
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(body)
+        .unwrap();
+    let response = server.request(request).await;
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Drops the RequestId, which differs between any two responses.
+fn without_request_id(body: &str) -> String {
+    match element(body, "RequestId") {
+        Some(request_id) => body.replace(request_id, ""),
+        None => body.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn create_topic_over_multipart_matches_the_form() {
+    let server = TestServer::start().await.unwrap();
+    let params = [
+        ("Action", "CreateTopic"),
+        ("Name", "orders"),
+        ("Tags.member.1.Key", "team"),
+        ("Tags.member.1.Value", "a"),
+        ("Tags.member.2.Key", "env"),
+        ("Tags.member.2.Value", "dev"),
+        ("Attributes.entry.1.key", "DisplayName"),
+        ("Attributes.entry.1.value", "Orders & more"),
+    ];
+    let (status, multipart_body) = call_multipart(&server, &params).await;
+    assert_eq!(status, StatusCode::OK, "{}", multipart_body);
+    let (_, form_body) = call(&server, &params).await;
+    assert_eq!(
+        without_request_id(&multipart_body),
+        without_request_id(&form_body)
+    );
+
+    let topic = server.state().topics.get("orders").unwrap().clone();
+    assert_eq!(topic.tags.len(), 2);
+    assert_eq!(topic.tags.get("env").map(String::as_str), Some("dev"));
+    assert_eq!(topic.display_name.as_deref(), Some("Orders & more"));
+}
+
+#[tokio::test]
+async fn publish_over_multipart_delivers_the_same_message() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = common::create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let message = "{\"order\": 1}\r\nsecond line & é";
+    let (status, body) = call_multipart(
+        &server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", &topic_arn),
+            ("Subject", "Order"),
+            ("Message", message),
+            ("MessageAttributes.entry.1.Name", "event_type"),
+            ("MessageAttributes.entry.1.Value.DataType", "String"),
+            ("MessageAttributes.entry.1.Value.StringValue", "created"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let message_id = element(&body, "MessageId").unwrap();
+    server.delivered(message_id).await.unwrap();
+
+    let envelope: serde_json::Value =
+        serde_json::from_str(&server.received("inbox")[0].body).unwrap();
+    assert_eq!(envelope["Message"], message);
+    assert_eq!(envelope["Subject"], "Order");
+    assert_eq!(
+        envelope["MessageAttributes"]["event_type"]["Value"],
+        "created"
+    );
+}
+
+#[tokio::test]
+async fn errors_over_multipart_match_the_form() {
+    let server = TestServer::start().await.unwrap();
+    let params = [("Action", "Publish"), ("Message", "order")];
+    let (multipart_status, multipart_body) = call_multipart(&server, &params).await;
+    let (form_status, form_body) = call(&server, &params).await;
+    assert_eq!(multipart_status, form_status);
+    assert_eq!(
+        without_request_id(&multipart_body),
+        without_request_id(&form_body)
+    );
+}
+
+#[tokio::test]
+async fn malformed_multipart_bodies_are_rejected() {
+    let server = TestServer::start().await.unwrap();
+    let (status, body) = send(&server, Body::from("Action=ListTopics")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert!(
+        body.contains("multipart body has no boundary delimiter"),
+        "{}",
+        body
+    );
+}
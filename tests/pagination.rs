@@ -0,0 +1,102 @@
+mod common;
+
+use common::{call, create_topic, element, subscribe};
+use local_sns_rs::testing::TestServer;
+
+/// Pages through a list action `max_items` at a time, returning the
+/// `element` values of every page and the number of pages.
+async fn walk(
+    server: &TestServer,
+    params: &[(&str, &str)],
+    max_items: &str,
+    element_name: &str,
+) -> (Vec<String>, usize) {
+    let mut values = vec![];
+    let mut pages = 0;
+    let mut next_token: Option<String> = None;
+    loop {
+        let mut params = params.to_vec();
+        params.push(("MaxItems", max_items));
+        if let Some(next_token) = &next_token {
+            params.push(("NextToken", next_token));
+        }
+        let (status, body) = call(server, &params).await;
+        assert!(status.is_success(), "{}", body);
+        pages += 1;
+        let open = format!("<{}>", element_name);
+        for member in body.split(&open).skip(1) {
+            values.push(member[..member.find("</").unwrap()].to_string());
+        }
+        next_token = element(&body, "NextToken").map(str::to_string);
+        if next_token.is_none() {
+            return (values, pages);
+        }
+    }
+}
+
+#[tokio::test]
+async fn topics_can_be_listed_one_at_a_time() {
+    let server = TestServer::start().await.unwrap();
+    let mut topic_arns = vec![];
+    for name in ["a", "b", "c", "d", "e"] {
+        topic_arns.push(create_topic(&server, name).await);
+    }
+    let (listed, pages) = walk(&server, &[("Action", "ListTopics")], "1", "TopicArn").await;
+    assert_eq!(listed, topic_arns);
+    assert_eq!(pages, 5);
+
+    let (listed, pages) = walk(&server, &[("Action", "ListTopics")], "2", "TopicArn").await;
+    assert_eq!(listed, topic_arns);
+    assert_eq!(pages, 3);
+}
+
+#[tokio::test]
+async fn subscriptions_by_topic_can_be_listed_one_at_a_time() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let mut subscription_arns = vec![];
+    for name in ["a", "b", "c", "d", "e"] {
+        subscription_arns.push(subscribe(&server, &topic_arn, "memory", name).await);
+    }
+    let params = [
+        ("Action", "ListSubscriptionsByTopic"),
+        ("TopicArn", topic_arn.as_str()),
+    ];
+    let (mut listed, pages) = walk(&server, &params, "1", "SubscriptionArn").await;
+    listed.sort();
+    subscription_arns.sort();
+    assert_eq!(listed, subscription_arns);
+    assert_eq!(pages, 5);
+}
+
+#[tokio::test]
+async fn max_items_out_of_range_is_invalid() {
+    let server = TestServer::start().await.unwrap();
+    create_topic(&server, "orders").await;
+    for max_items in ["0", "101", "many"] {
+        let (status, body) = call(
+            &server,
+            &[("Action", "ListTopics"), ("MaxItems", max_items)],
+        )
+        .await;
+        assert_eq!(status, 400, "{}", max_items);
+        assert_eq!(element(&body, "Code"), Some("InvalidParameter"));
+    }
+}
+
+#[tokio::test]
+async fn strict_aws_mode_ignores_max_items() {
+    let server = TestServer::with_config(local_sns_rs::config::Config {
+        strict_aws: true,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    for name in ["a", "b"] {
+        create_topic(&server, name).await;
+    }
+    let (status, body) = call(&server, &[("Action", "ListTopics"), ("MaxItems", "1")]).await;
+    assert!(status.is_success(), "{}", body);
+    assert_eq!(body.matches("<TopicArn>").count(), 2);
+    assert_eq!(element(&body, "NextToken"), None);
+}
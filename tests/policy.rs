@@ -0,0 +1,69 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{attribute, call, create_topic, element};
+use local_sns_rs::testing::TestServer;
+
+/// Spaced as a person writes it; GetTopicAttributes must not reformat it.
+const POLICY: &str = r#"{
+  "Version": "2012-10-17",
+  "Statement": [ { "Effect": "Allow", "Principal": "*", "Action": "SNS:Publish", "Resource": "*" } ]
+}"#;
+
+#[tokio::test]
+async fn valid_policies_are_kept_verbatim() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "SetTopicAttributes"),
+            ("TopicArn", &topic_arn),
+            ("AttributeName", "Policy"),
+            ("AttributeValue", POLICY),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (_, body) = call(
+        &server,
+        &[("Action", "GetTopicAttributes"), ("TopicArn", &topic_arn)],
+    )
+    .await;
+    let policy = attribute(&body, "Policy").unwrap().replace("&quot;", "\"");
+    assert_eq!(policy, POLICY);
+}
+
+#[tokio::test]
+async fn malformed_policies_are_rejected_wherever_they_are_set() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let malformed = r#"{"Version":"2012-10-17","Statement":[{"Effect":"Allow"},]}"#;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "SetTopicAttributes"),
+            ("TopicArn", &topic_arn),
+            ("AttributeName", "Policy"),
+            ("AttributeValue", malformed),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("InvalidParameter"));
+    assert_eq!(element(&body, "Message"), Some("Invalid parameter: Policy"));
+
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "CreateTopic"),
+            ("Name", "invoices"),
+            ("Attributes.entry.1.key", "Policy"),
+            ("Attributes.entry.1.value", r#"{"Version":"2012-10-17"}"#),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(element(&body, "Message"), Some("Invalid parameter: Policy"));
+    assert!(!server.state().topics.contains_key("invoices"));
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{call, create_topic, element, subscribe};
+use local_sns_rs::state::MAX_MESSAGE_SIZE;
+use local_sns_rs::testing::TestServer;
+
+#[tokio::test]
+async fn publish_and_publish_batch_check_message_attributes_alike() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", &topic_arn),
+            ("Message", "order"),
+            ("MessageAttributes.entry.1.Name", "AWS.custom"),
+            ("MessageAttributes.entry.1.Value.DataType", "String"),
+            ("MessageAttributes.entry.1.Value.StringValue", "x"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    let publish_error = element(&body, "Message").unwrap().to_string();
+    assert!(publish_error.contains("reserved prefix"), "{}", body);
+
+    let prefix = "PublishBatchRequestEntries.member.1";
+    let key = |suffix: &str| format!("{}.{}", prefix, suffix);
+    let (id, message, name, data_type, value) = (
+        key("Id"),
+        key("Message"),
+        key("MessageAttributes.entry.1.Name"),
+        key("MessageAttributes.entry.1.Value.DataType"),
+        key("MessageAttributes.entry.1.Value.StringValue"),
+    );
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "PublishBatch"),
+            ("TopicArn", &topic_arn),
+            (&id, "1"),
+            (&message, "order"),
+            (&name, "AWS.custom"),
+            (&data_type, "String"),
+            (&value, "x"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("InvalidParameter"), "{}", body);
+    assert_eq!(
+        element(&body, "Message"),
+        Some(publish_error.as_str()),
+        "{}",
+        body
+    );
+}
+
+/// Publishes `message` with one 16-byte attribute.
+async fn publish_with_attribute(
+    server: &TestServer,
+    topic_arn: &str,
+    message: &str,
+) -> (StatusCode, String) {
+    call(
+        server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", topic_arn),
+            ("Message", message),
+            ("MessageAttributes.entry.1.Name", "event"),
+            ("MessageAttributes.entry.1.Value.DataType", "String"),
+            ("MessageAttributes.entry.1.Value.StringValue", "order"),
+        ],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn message_attributes_count_toward_the_size_limit() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let attributes_size = "event".len() + "String".len() + "order".len();
+
+    let largest = "x".repeat(MAX_MESSAGE_SIZE - attributes_size);
+    let (status, body) = publish_with_attribute(&server, &topic_arn, &largest).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = publish_with_attribute(&server, &topic_arn, &(largest + "x")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(
+        element(&body, "Message"),
+        Some("Invalid parameter: Message too long")
+    );
+}
+
+/// The `(Id, MessageId or Message)` pairs under one list of a PublishBatch
+/// result.
+fn batch_entries(body: &str, list: &str, field: &str) -> Vec<(String, String)> {
+    let Some(list) = element(body, list) else {
+        return Vec::new();
+    };
+    list.split("<member>")
+        .skip(1)
+        .map(|member| {
+            (
+                element(member, "Id").unwrap().to_string(),
+                element(member, field).unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn batch_failures_are_reported_per_entry_while_the_rest_deliver() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let too_long = "x".repeat(MAX_MESSAGE_SIZE + 1);
+    let key = |index: usize, field: &str| {
+        format!("PublishBatchRequestEntries.member.{}.{}", index, field)
+    };
+    let keys: Vec<String> = (1..=5)
+        .flat_map(|i| [key(i, "Id"), key(i, "Message")])
+        .chain([key(2, "MessageStructure")])
+        .collect();
+    let mut params = vec![("Action", "PublishBatch"), ("TopicArn", topic_arn.as_str())];
+    let entries = [
+        ("a", "one"),
+        ("b", "not json"),
+        ("c", "three"),
+        ("d", too_long.as_str()),
+        ("e", "five"),
+    ];
+    for (i, (id, message)) in entries.iter().enumerate() {
+        params.push((&keys[2 * i], id));
+        params.push((&keys[2 * i + 1], message));
+    }
+    params.push((&keys[10], "json"));
+    let (status, body) = call(&server, &params).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let successful = batch_entries(&body, "Successful", "MessageId");
+    let ids: Vec<&str> = successful.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, ["a", "c", "e"]);
+    let failed = batch_entries(&body, "Failed", "Message");
+    assert_eq!(
+        failed,
+        [
+            (
+                "b".to_string(),
+                "Invalid parameter: Message Structure - JSON message body failed to parse"
+                    .to_string()
+            ),
+            (
+                "d".to_string(),
+                "Invalid parameter: Message too long".to_string()
+            ),
+        ]
+    );
+    let failures = element(&body, "Failed").unwrap();
+    assert_eq!(failures.matches("<Code>InvalidParameter</Code>").count(), 2);
+    assert_eq!(
+        failures.matches("<SenderFault>true</SenderFault>").count(),
+        2
+    );
+
+    // The single Publish reports the same error.
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", &topic_arn),
+            ("Message", "not json"),
+            ("MessageStructure", "json"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(element(&body, "Message"), Some(failed[0].1.as_str()));
+
+    for (_, message_id) in &successful {
+        server.delivered(message_id).await.unwrap();
+    }
+    let received: Vec<String> = server
+        .received("inbox")
+        .into_iter()
+        .map(|message| message.message_id)
+        .collect();
+    let published: Vec<String> = successful.into_iter().map(|(_, id)| id).collect();
+    assert_eq!(received, published);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{call, element, subscribe};
+use local_sns_rs::testing::TestServer;
+
+/// Sends a Query API call as a GET, parameters in the query string.
+async fn get(server: &TestServer, params: &[(&str, &str)]) -> (StatusCode, String) {
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    let response = server
+        .request(
+            Request::get(format!("/?{}", query))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Drops the RequestId, which differs between any two responses.
+fn without_request_id(body: &str) -> String {
+    match element(body, "RequestId") {
+        Some(request_id) => body.replace(request_id, ""),
+        None => body.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn create_topic_and_publish_work_over_get() {
+    let server = TestServer::start().await.unwrap();
+    let params = [
+        ("Action", "CreateTopic"),
+        ("Name", "orders"),
+        ("Tags.member.1.Key", "team"),
+        ("Tags.member.1.Value", "a"),
+        ("Attributes.entry.1.key", "DisplayName"),
+        ("Attributes.entry.1.value", "Orders & more"),
+    ];
+    let (status, get_body) = get(&server, &params).await;
+    assert_eq!(status, StatusCode::OK, "{}", get_body);
+    let topic_arn = element(&get_body, "TopicArn").unwrap().to_string();
+    let (_, post_body) = call(&server, &params).await;
+    assert_eq!(
+        without_request_id(&get_body),
+        without_request_id(&post_body)
+    );
+
+    let topic = server.state().topics.get("orders").unwrap().clone();
+    assert_eq!(topic.tags.get("team").map(String::as_str), Some("a"));
+    assert_eq!(topic.display_name.as_deref(), Some("Orders & more"));
+
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let (status, body) = get(
+        &server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", &topic_arn),
+            ("Message", "order 1 + 1 = 2"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let message_id = element(&body, "MessageId").unwrap();
+    server.delivered(message_id).await.unwrap();
+    let envelope: serde_json::Value =
+        serde_json::from_str(&server.received("inbox")[0].body).unwrap();
+    assert_eq!(envelope["Message"], "order 1 + 1 = 2");
+}
+
+#[tokio::test]
+async fn errors_over_get_match_those_over_post() {
+    let server = TestServer::start().await.unwrap();
+    for params in [
+        &[("Action", "Publish"), ("Message", "order")][..],
+        &[("Action", "Frobnicate")][..],
+        &[("Action", "TagResource"), ("Tags.member.0.Key", "team")][..],
+    ] {
+        let (get_status, get_body) = get(&server, params).await;
+        let (post_status, post_body) = call(&server, params).await;
+        assert_eq!(get_status, post_status, "{:?}", params);
+        assert_eq!(
+            without_request_id(&get_body),
+            without_request_id(&post_body),
+            "{:?}",
+            params
+        );
+    }
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{call, create_topic, publish, subscribe};
+use local_sns_rs::latency::LatencyProfile;
+use local_sns_rs::testing::TestServer;
+
+async fn unsubscribe(server: &TestServer, subscription_arn: &str) {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "Unsubscribe"),
+            ("SubscriptionArn", subscription_arn),
+        ],
+    )
+    .await;
+    assert!(status.is_success(), "{}", body);
+}
+
+#[tokio::test]
+async fn unsubscribing_drops_the_deliveries_of_the_subscription() {
+    let server = TestServer::start().await.unwrap();
+    let orders = create_topic(&server, "orders").await;
+    let invoices = create_topic(&server, "invoices").await;
+    let removed = subscribe(&server, &orders, "memory", "inbox").await;
+    subscribe(&server, &invoices, "memory", "inbox").await;
+    let first = publish(&server, &orders, "order").await;
+    let second = publish(&server, &invoices, "invoice").await;
+    server.delivered(&first).await.unwrap();
+    server.delivered(&second).await.unwrap();
+    assert_eq!(server.received("inbox").len(), 2);
+
+    unsubscribe(&server, &removed).await;
+    let received = server.received("inbox");
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].message_id, second);
+}
+
+#[tokio::test]
+async fn deleting_a_topic_drops_what_its_subscriptions_left_behind() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let message_id = publish(&server, &topic_arn, "order").await;
+    server.delivered(&message_id).await.unwrap();
+    let state = server.state();
+    state
+        .demux_routes
+        .entry(subscription_arn.clone())
+        .or_default()
+        .insert("default".to_string(), (1, 0));
+
+    call(
+        &server,
+        &[("Action", "DeleteTopic"), ("TopicArn", &topic_arn)],
+    )
+    .await;
+    assert!(server.received("inbox").is_empty());
+    assert!(!state.demux_routes.contains_key(&subscription_arn));
+    assert!(!state.subscription_health.contains_key(&subscription_arn));
+}
+
+#[tokio::test]
+async fn reset_drops_latency_profiles_and_demux_routes() {
+    let server = TestServer::start().await.unwrap();
+    let state = server.state();
+    state
+        .latency_profiles
+        .insert("Publish".to_string(), LatencyProfile::default());
+    state
+        .demux_routes
+        .entry("arn".to_string())
+        .or_default()
+        .insert("default".to_string(), (1, 0));
+
+    state.reset();
+    assert!(state.latency_profiles.is_empty());
+    assert!(state.demux_routes.is_empty());
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "sqs-delivery")]
+
+mod common;
+
+use std::sync::Arc;
+
+use aws_sdk_sqs::config::{BehaviorVersion, Credentials, Region};
+use common::{Endpoint, call_with_headers, create_topic, element, publish, subscribe};
+use local_sns_rs::config::Config;
+use local_sns_rs::testing::TestServer;
+
+const TRACE_HEADER: &str = "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1";
+
+/// Points the server's SQS client for `endpoint` at it, with static
+/// credentials, so the SendMessage calls it receives can be inspected.
+fn stub_sqs(server: &TestServer, endpoint: &Endpoint) -> String {
+    let service = endpoint.url.trim_end_matches("/events").to_string();
+    let config = aws_sdk_sqs::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .endpoint_url(&service)
+        .build();
+    server.state().sqs_clients.insert(
+        service.clone(),
+        Arc::new(aws_sdk_sqs::Client::from_conf(config)),
+    );
+    format!("{}/000000000000/orders", service)
+}
+
+#[tokio::test]
+async fn trace_headers_reach_sqs_as_the_aws_trace_header() {
+    let server = TestServer::start().await.unwrap();
+    let endpoint = Endpoint::start(&[]).await;
+    let queue_url = stub_sqs(&server, &endpoint);
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "sqs", &queue_url).await;
+
+    let (status, body) = call_with_headers(
+        &server,
+        &[("X-Amzn-Trace-Id", TRACE_HEADER)],
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", &topic_arn),
+            ("Message", "traced"),
+        ],
+    )
+    .await;
+    assert_eq!(status, 200, "{}", body);
+    assert!(element(&body, "MessageId").is_some());
+
+    let received = endpoint.wait_for(1).await;
+    let send: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+    assert_eq!(send["QueueUrl"], queue_url);
+    assert_eq!(
+        send["MessageSystemAttributes"]["AWSTraceHeader"],
+        serde_json::json!({"DataType": "String", "StringValue": TRACE_HEADER})
+    );
+}
+
+#[tokio::test]
+async fn the_sqs_client_cache_stays_bounded() {
+    const ENDPOINTS: u16 = 1000;
+    const CAPACITY: usize = 8;
+    let server = TestServer::with_config(Config {
+        max_sqs_clients: CAPACITY,
+        retry_time_scale: 0.0,
+        delivery_workers: Some(64),
+        max_concurrent_deliveries: 256,
+        ..Config::default()
+    })
+    .await
+    .unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    // One queue per port, as a test minting random ports would make;
+    // nothing listens, so each delivery fails fast.
+    for port in 20_000..20_000 + ENDPOINTS {
+        let queue_url = format!("http://127.0.0.1:{}/000000000000/orders", port);
+        subscribe(&server, &topic_arn, "sqs", &queue_url).await;
+    }
+
+    let message_id = publish(&server, &topic_arn, "hello").await;
+    let cache = &server.state().sqs_clients;
+    let mut largest = 0;
+    let started = std::time::Instant::now();
+    while !server
+        .state()
+        .fan_outs
+        .status(&message_id)
+        .is_some_and(|status| status.complete)
+    {
+        assert!(started.elapsed() < std::time::Duration::from_secs(120));
+        largest = largest.max(cache.snapshot().size);
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+
+    let stats = cache.snapshot();
+    assert!(largest <= CAPACITY, "{}", largest);
+    assert_eq!(stats.size, CAPACITY);
+    assert_eq!(stats.misses, u64::from(ENDPOINTS));
+    assert_eq!(stats.evictions, u64::from(ENDPOINTS) - CAPACITY as u64);
+}
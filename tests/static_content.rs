@@ -0,0 +1,80 @@
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode, header};
+use axum::response::Response;
+use local_sns_rs::testing::TestServer;
+
+async fn request(server: &TestServer, method: Method, path: &str, etag: Option<&str>) -> Response {
+    let mut request = Request::builder().method(method).uri(path);
+    if let Some(etag) = etag {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    server.request(request.body(Body::empty()).unwrap()).await
+}
+
+async fn body(response: Response) -> Vec<u8> {
+    axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+/// Checks the 200, HEAD and 304 answers of a revalidatable path.
+async fn check_revalidation(server: &TestServer, path: &str) {
+    let response = request(server, Method::GET, path, None).await;
+    assert_eq!(response.status(), StatusCode::OK, "{}", path);
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let length: usize = response.headers()[header::CONTENT_LENGTH]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(etag.starts_with('"') && etag.ends_with('"'), "{}", etag);
+    let content = body(response).await;
+    assert_eq!(content.len(), length, "{}", path);
+
+    let head = request(server, Method::HEAD, path, None).await;
+    assert_eq!(head.status(), StatusCode::OK, "{}", path);
+    assert_eq!(head.headers()[header::ETAG], etag.as_str());
+    assert_eq!(
+        head.headers()[header::CONTENT_LENGTH],
+        length.to_string().as_str()
+    );
+    assert!(body(head).await.is_empty());
+
+    for if_none_match in [
+        etag.as_str(),
+        &format!("W/{}", etag),
+        &format!("\"other\", {}", etag),
+        "*",
+    ] {
+        let response = request(server, Method::GET, path, Some(if_none_match)).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::NOT_MODIFIED,
+            "{}",
+            if_none_match
+        );
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        assert!(body(response).await.is_empty());
+    }
+    let response = request(server, Method::GET, path, Some("\"other\"")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(response).await, content);
+}
+
+#[tokio::test]
+async fn health_can_be_revalidated_and_probed_with_head() {
+    let server = TestServer::start().await.unwrap();
+    check_revalidation(&server, "/health").await;
+}
+
+#[cfg(feature = "ui")]
+#[tokio::test]
+async fn ui_assets_can_be_revalidated_and_probed_with_head() {
+    let server = TestServer::start().await.unwrap();
+    check_revalidation(&server, "/ui/").await;
+    check_revalidation(&server, "/ui/app.js").await;
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "admin-api")]
+
+mod common;
+
+use common::{admin, call, create_topic, publish, set_topic_attribute, subscribe};
+use local_sns_rs::testing::TestServer;
+use serde_json::Value;
+
+async fn topic_stats(server: &TestServer, name: &str) -> Value {
+    let (status, body) = admin(
+        server,
+        "GET",
+        &format!("/admin/topics/{}/stats", name),
+        None,
+    )
+    .await;
+    assert!(status.is_success(), "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
+
+#[tokio::test]
+async fn topic_stats_count_publishes_and_deliveries() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+    subscribe(&server, &topic_arn, "memory", "audit").await;
+    for i in 0..3 {
+        let message_id = publish(&server, &topic_arn, &format!("order {}", i)).await;
+        server.delivered(&message_id).await.unwrap();
+    }
+
+    let stats = topic_stats(&server, "orders").await;
+    assert_eq!(stats["published"], 3);
+    assert_eq!(stats["delivered_ok"], 6);
+    assert_eq!(stats["delivered_failed"], 0);
+
+    // Attribute changes keep the counters; a reset drops them.
+    set_topic_attribute(&server, &topic_arn, "DisplayName", "Orders").await;
+    assert_eq!(topic_stats(&server, "orders").await["published"], 3);
+    let (_, body) = admin(&server, "GET", "/admin/stats", None).await;
+    let totals: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(totals["totals"]["published"], 3, "{}", body);
+
+    let (status, body) = admin(&server, "POST", "/admin/reset", None).await;
+    assert!(status.is_success(), "{}", body);
+    create_topic(&server, "orders").await;
+    assert_eq!(topic_stats(&server, "orders").await["published"], 0);
+}
+
+#[cfg(feature = "sqs-delivery")]
+#[tokio::test]
+async fn demux_subscriptions_count_what_they_filter_out() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let endpoint = r#"{"attribute":"event_type","routes":{"order":"http://localhost:4566/000000000000/orders"}}"#;
+    subscribe(&server, &topic_arn, "demux", endpoint).await;
+    let message_id = publish(&server, &topic_arn, "no event type").await;
+    let status = server.delivered(&message_id).await.unwrap();
+    assert_eq!(status.skipped, 1);
+    assert_eq!(topic_stats(&server, "orders").await["filtered_out"], 1);
+
+    // A deleted topic takes its counters along.
+    call(
+        &server,
+        &[("Action", "DeleteTopic"), ("TopicArn", &topic_arn)],
+    )
+    .await;
+    assert!(!server.state().stats.contains_key("orders"));
+}
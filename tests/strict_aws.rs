@@ -0,0 +1,178 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{attribute, call, create_topic, element, subscribe};
+use local_sns_rs::config::Config;
+use local_sns_rs::state::{MAX_TAGS_PER_RESOURCE, MESSAGE_TEMPLATE_ATTRIBUTE};
+use local_sns_rs::testing::TestServer;
+
+const QUEUE_ARN: &str = "arn:aws:sqs:us-east-1:000000000000:orders";
+
+async fn start(strict_aws: bool) -> TestServer {
+    TestServer::with_config(Config {
+        strict_aws,
+        extended_attributes: true,
+        ..Config::default()
+    })
+    .await
+    .unwrap()
+}
+
+async fn get(server: &TestServer, path: &str) -> (StatusCode, String) {
+    let response = server
+        .request(Request::get(path).body(Body::empty()).unwrap())
+        .await;
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+#[tokio::test]
+async fn version_reports_strict_mode() {
+    for strict_aws in [false, true] {
+        let server = start(strict_aws).await;
+        let (status, body) = get(&server, "/version").await;
+        assert_eq!(status, StatusCode::OK);
+        let version: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(version["strict_aws"], strict_aws);
+    }
+}
+
+#[cfg(feature = "admin-api")]
+#[tokio::test]
+async fn strict_mode_has_no_admin_api() {
+    let (status, _) = get(&start(false).await, "/admin/config").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = get(&start(true).await, "/admin/config").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn strict_mode_rejects_extension_attributes_and_protocols() {
+    let server = start(true).await;
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "sqs", QUEUE_ARN).await;
+
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "SetSubscriptionAttributes"),
+            ("SubscriptionArn", &subscription_arn),
+            ("AttributeName", MESSAGE_TEMPLATE_ATTRIBUTE),
+            ("AttributeValue", "{{message}}"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("InvalidParameter"));
+
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "Subscribe"),
+            ("TopicArn", &topic_arn),
+            ("Protocol", "sqs"),
+            ("Endpoint", "arn:aws:sqs:us-east-1:000000000000:audit"),
+            ("Attributes.entry.1.key", MESSAGE_TEMPLATE_ATTRIBUTE),
+            ("Attributes.entry.1.value", "{{message}}"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("InvalidParameter"));
+
+    for protocol in ["memory", "demux"] {
+        let (status, body) = call(
+            &server,
+            &[
+                ("Action", "Subscribe"),
+                ("TopicArn", &topic_arn),
+                ("Protocol", protocol),
+                ("Endpoint", "inbox"),
+            ],
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", protocol, body);
+    }
+}
+
+#[tokio::test]
+async fn strict_mode_hides_extension_topic_attributes() {
+    for strict_aws in [false, true] {
+        let server = start(strict_aws).await;
+        let topic_arn = create_topic(&server, "orders").await;
+        let (_, body) = call(
+            &server,
+            &[("Action", "GetTopicAttributes"), ("TopicArn", &topic_arn)],
+        )
+        .await;
+        assert_eq!(
+            attribute(&body, "X-LocalSns-MessagesPublished").is_some(),
+            !strict_aws,
+            "{}",
+            body
+        );
+    }
+}
+
+#[tokio::test]
+async fn the_effective_delivery_state_is_an_extended_attribute() {
+    for (strict_aws, extended_attributes, reported) in [
+        (false, false, None),
+        (false, true, Some("enabled")),
+        (true, true, None),
+    ] {
+        let server = TestServer::with_config(Config {
+            strict_aws,
+            extended_attributes,
+            ..Config::default()
+        })
+        .await
+        .unwrap();
+        let topic_arn = create_topic(&server, "orders").await;
+        let subscription_arn = subscribe(&server, &topic_arn, "sqs", QUEUE_ARN).await;
+        let (status, body) = call(
+            &server,
+            &[
+                ("Action", "GetSubscriptionAttributes"),
+                ("SubscriptionArn", &subscription_arn),
+            ],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(
+            attribute(&body, "X-LocalSns-EffectiveDeliveryState"),
+            reported,
+            "{}",
+            body
+        );
+    }
+}
+
+#[tokio::test]
+async fn strict_mode_enforces_the_tag_limit() {
+    let keys: Vec<String> = (0..=MAX_TAGS_PER_RESOURCE)
+        .map(|n| format!("tag{}", n))
+        .collect();
+    let mut params = vec![
+        ("Action".to_string(), "CreateTopic".to_string()),
+        ("Name".to_string(), "orders".to_string()),
+    ];
+    for (n, key) in keys.iter().enumerate() {
+        params.push((format!("Tags.member.{}.Key", n + 1), key.clone()));
+        params.push((format!("Tags.member.{}.Value", n + 1), "a".to_string()));
+    }
+    let params: Vec<(&str, &str)> = params
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+
+    let (status, body) = call(&start(false).await, &params).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = call(&start(true).await, &params).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("TagLimitExceeded"));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{call, create_topic, element, subscribe};
+use local_sns_rs::state::SubscriptionArn;
+use local_sns_rs::testing::TestServer;
+
+/// Calls `action` on a subscription ARN; SetSubscriptionAttributes gets a
+/// valid attribute so only the ARN can be at fault.
+async fn call_with_arn(
+    server: &TestServer,
+    action: &str,
+    subscription_arn: &str,
+) -> (StatusCode, String) {
+    let mut params = vec![("Action", action), ("SubscriptionArn", subscription_arn)];
+    if action == "SetSubscriptionAttributes" {
+        params.extend([
+            ("AttributeName", "RawMessageDelivery"),
+            ("AttributeValue", "true"),
+        ]);
+    }
+    call(server, &params).await
+}
+
+const ACTIONS: [&str; 3] = [
+    "Unsubscribe",
+    "GetSubscriptionAttributes",
+    "SetSubscriptionAttributes",
+];
+
+#[tokio::test]
+async fn subscription_arns_extend_the_topic_arn_with_a_uuid() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let first = subscribe(&server, &topic_arn, "memory", "first").await;
+    let second = subscribe(&server, &topic_arn, "memory", "second").await;
+    let parsed = SubscriptionArn::parse(&first).unwrap();
+    assert_eq!(parsed.topic_arn, topic_arn);
+    assert_eq!(parsed.topic_name, "orders");
+    assert_ne!(SubscriptionArn::parse(&second).unwrap().id, parsed.id);
+}
+
+#[tokio::test]
+async fn malformed_subscription_arns_are_invalid_parameters() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "memory", "inbox").await;
+    for arn in [
+        "garbage".to_string(),
+        topic_arn.clone(),
+        format!("{}:not-a-uuid", topic_arn),
+        format!("{}x", subscription_arn),
+        subscription_arn.replacen(":sns:", ":sqs:", 1),
+        format!("{}:{}", topic_arn, "::::::"),
+    ] {
+        for action in ACTIONS {
+            let (status, body) = call_with_arn(&server, action, &arn).await;
+            assert_eq!(
+                status,
+                StatusCode::BAD_REQUEST,
+                "{} {}: {}",
+                action,
+                arn,
+                body
+            );
+            assert_eq!(element(&body, "Code"), Some("InvalidParameter"));
+            assert_eq!(
+                element(&body, "Message"),
+                Some("Invalid parameter: SubscriptionArn")
+            );
+        }
+    }
+    // Nothing was unsubscribed along the way.
+    let (status, _) = call_with_arn(&server, "GetSubscriptionAttributes", &subscription_arn).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn well_formed_unknown_subscription_arns_are_not_found() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let unknown = format!("{}:{}", topic_arn, uuid::Uuid::new_v4());
+    let (status, body) = call_with_arn(&server, "GetSubscriptionAttributes", &unknown).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("NotFound"));
+    let (status, body) = call_with_arn(&server, "SetSubscriptionAttributes", &unknown).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{}", body);
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::{attribute, call, create_topic, subscribe};
+use local_sns_rs::state::MESSAGE_TEMPLATE_ATTRIBUTE;
+use local_sns_rs::testing::TestServer;
+
+async fn set_template(
+    server: &TestServer,
+    subscription_arn: &str,
+    template: &str,
+) -> (axum::http::StatusCode, String) {
+    call(
+        server,
+        &[
+            ("Action", "SetSubscriptionAttributes"),
+            ("SubscriptionArn", subscription_arn),
+            ("AttributeName", MESSAGE_TEMPLATE_ATTRIBUTE),
+            ("AttributeValue", template),
+        ],
+    )
+    .await
+}
+
+async fn publish_with_subject(
+    server: &TestServer,
+    topic_arn: &str,
+    subject: &str,
+    message: &str,
+) -> String {
+    let (status, body) = call(
+        server,
+        &[
+            ("Action", "Publish"),
+            ("TopicArn", topic_arn),
+            ("Subject", subject),
+            ("Message", message),
+        ],
+    )
+    .await;
+    assert!(status.is_success(), "{}", body);
+    common::element(&body, "MessageId").unwrap().to_string()
+}
+
+#[tokio::test]
+async fn templated_subscriptions_receive_the_rendered_message() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let templated = subscribe(&server, &topic_arn, "memory", "templated").await;
+    subscribe(&server, &topic_arn, "memory", "plain").await;
+    let template = r#"{{subject}} — {{json message "detail.status"}}"#;
+    let (status, body) = set_template(&server, &templated, template).await;
+    assert!(status.is_success(), "{}", body);
+
+    let (_, body) = call(
+        &server,
+        &[
+            ("Action", "GetSubscriptionAttributes"),
+            ("SubscriptionArn", &templated),
+        ],
+    )
+    .await;
+    assert_eq!(
+        attribute(&body, MESSAGE_TEMPLATE_ATTRIBUTE),
+        Some(r#"{{subject}} — {{json message &quot;detail.status&quot;}}"#)
+    );
+
+    let message = r#"{"detail":{"status":"shipped"}}"#;
+    let message_id = publish_with_subject(&server, &topic_arn, "Order 1", message).await;
+    server.delivered(&message_id).await.unwrap();
+    assert_eq!(server.received("templated")[0].body, "Order 1 — shipped");
+    let envelope: serde_json::Value =
+        serde_json::from_str(&server.received("plain")[0].body).unwrap();
+    assert_eq!(envelope["Message"], message);
+}
+
+#[tokio::test]
+async fn messages_the_template_cannot_render_are_delivered_unrendered() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let templated = subscribe(&server, &topic_arn, "memory", "templated").await;
+    let (status, body) =
+        set_template(&server, &templated, r#"{{json message "detail.status"}}"#).await;
+    assert!(status.is_success(), "{}", body);
+
+    let message = r#"{"detail":{}}"#;
+    let message_id = publish_with_subject(&server, &topic_arn, "Order 1", message).await;
+    server.delivered(&message_id).await.unwrap();
+    let envelope: serde_json::Value =
+        serde_json::from_str(&server.received("templated")[0].body).unwrap();
+    assert_eq!(envelope["Type"], "Notification");
+    assert_eq!(envelope["Message"], message);
+}
+
+#[tokio::test]
+async fn invalid_templates_are_rejected_and_empty_ones_clear_it() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let templated = subscribe(&server, &topic_arn, "memory", "templated").await;
+    for template in ["{{subject", "{{body}}", r#"{{json message "a..b"}}"#] {
+        let (status, body) = set_template(&server, &templated, template).await;
+        assert_eq!(status, 400, "{}", template);
+        assert!(body.contains("<Code>InvalidParameter</Code>"), "{}", body);
+    }
+
+    let (status, _) = set_template(&server, &templated, "{{message}}!").await;
+    assert!(status.is_success());
+    let (status, _) = set_template(&server, &templated, "").await;
+    assert!(status.is_success());
+    let (_, body) = call(
+        &server,
+        &[
+            ("Action", "GetSubscriptionAttributes"),
+            ("SubscriptionArn", &templated),
+        ],
+    )
+    .await;
+    assert_eq!(attribute(&body, MESSAGE_TEMPLATE_ATTRIBUTE), None);
+}
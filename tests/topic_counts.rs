@@ -0,0 +1,99 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{attribute, call, create_topic, element, subscribe};
+use local_sns_rs::config::Config;
+use local_sns_rs::testing::TestServer;
+
+/// SubscriptionsConfirmed, SubscriptionsPending and SubscriptionsDeleted.
+async fn counts(server: &TestServer, topic_arn: &str) -> (String, String, String) {
+    let (status, body) = call(
+        server,
+        &[("Action", "GetTopicAttributes"), ("TopicArn", topic_arn)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let count = |name| attribute(&body, name).unwrap().to_string();
+    (
+        count("SubscriptionsConfirmed"),
+        count("SubscriptionsPending"),
+        count("SubscriptionsDeleted"),
+    )
+}
+
+fn expected(confirmed: &str, pending: &str, deleted: &str) -> (String, String, String) {
+    (confirmed.into(), pending.into(), deleted.into())
+}
+
+#[tokio::test]
+async fn counts_follow_the_subscription_lifecycle() {
+    let server = TestServer::with_config(Config {
+        confirm_email_subscriptions: true,
+        ..Config::default()
+    })
+    .await
+    .unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    assert_eq!(counts(&server, &topic_arn).await, expected("0", "0", "0"));
+
+    subscribe(&server, &topic_arn, "memory", "inbox").await;
+    let email = subscribe(&server, &topic_arn, "email", "ops@example.com").await;
+    assert_eq!(email, "pending confirmation");
+    assert_eq!(counts(&server, &topic_arn).await, expected("1", "1", "0"));
+
+    let token = server.state().topics.get("orders").unwrap().subscriptions[1]
+        .confirmation_token
+        .clone()
+        .unwrap();
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "ConfirmSubscription"),
+            ("TopicArn", &topic_arn),
+            ("Token", &token),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(counts(&server, &topic_arn).await, expected("2", "0", "0"));
+
+    let subscription_arn = element(&body, "SubscriptionArn").unwrap().to_string();
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "Unsubscribe"),
+            ("SubscriptionArn", &subscription_arn),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(counts(&server, &topic_arn).await, expected("1", "0", "1"));
+}
+
+#[tokio::test]
+async fn recreated_topics_start_counting_afresh() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "memory", "inbox").await;
+    call(
+        &server,
+        &[
+            ("Action", "Unsubscribe"),
+            ("SubscriptionArn", &subscription_arn),
+        ],
+    )
+    .await;
+    assert_eq!(counts(&server, &topic_arn).await, expected("0", "0", "1"));
+
+    // CreateTopic of an existing topic keeps it, counts included.
+    create_topic(&server, "orders").await;
+    assert_eq!(counts(&server, &topic_arn).await, expected("0", "0", "1"));
+
+    call(
+        &server,
+        &[("Action", "DeleteTopic"), ("TopicArn", &topic_arn)],
+    )
+    .await;
+    create_topic(&server, "orders").await;
+    assert_eq!(counts(&server, &topic_arn).await, expected("0", "0", "0"));
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::{attribute, call, create_topic};
+use local_sns_rs::config::Config;
+use local_sns_rs::testing::TestServer;
+
+fn config_with_defaults() -> Config {
+    let mut config = Config::default();
+    config
+        .defaults
+        .insert("DisplayName".to_string(), "defaulted".to_string());
+    config
+}
+
+#[tokio::test]
+async fn defaults_are_reported_but_not_stored() {
+    let server = TestServer::with_config(config_with_defaults())
+        .await
+        .unwrap();
+    let arn = create_topic(&server, "orders").await;
+
+    let (_, body) = call(
+        &server,
+        &[("Action", "GetTopicAttributes"), ("TopicArn", &arn)],
+    )
+    .await;
+    assert_eq!(attribute(&body, "DisplayName"), Some("defaulted"));
+    assert_eq!(attribute(&body, "SQSSuccessFeedbackSampleRate"), Some("0"));
+
+    let topic = server.state().topics.get("orders").unwrap();
+    assert!(topic.display_name.is_none());
+    assert!(topic.policy.is_none());
+}
+
+#[tokio::test]
+async fn explicit_attributes_win_over_defaults() {
+    let server = TestServer::with_config(config_with_defaults())
+        .await
+        .unwrap();
+    let (_, body) = call(
+        &server,
+        &[
+            ("Action", "CreateTopic"),
+            ("Name", "payments"),
+            ("Attributes.entry.1.key", "DisplayName"),
+            ("Attributes.entry.1.value", "explicit"),
+        ],
+    )
+    .await;
+    let arn = common::element(&body, "TopicArn").unwrap().to_string();
+
+    let (_, body) = call(
+        &server,
+        &[("Action", "GetTopicAttributes"), ("TopicArn", &arn)],
+    )
+    .await;
+    assert_eq!(attribute(&body, "DisplayName"), Some("explicit"));
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{attribute, call, create_topic, element, publish, subscribe};
+use local_sns_rs::testing::TestServer;
+
+#[tokio::test]
+async fn names_differing_in_case_are_distinct_topics() {
+    let server = TestServer::start().await.unwrap();
+    let upper = create_topic(&server, "Orders").await;
+    let lower = create_topic(&server, "orders").await;
+    assert_ne!(upper, lower);
+    assert!(upper.ends_with(":Orders"));
+    subscribe(&server, &upper, "memory", "upper").await;
+    subscribe(&server, &lower, "memory", "lower").await;
+
+    let message_id = publish(&server, &upper, "to Orders").await;
+    server.delivered(&message_id).await.unwrap();
+    let message_id = publish(&server, &lower, "to orders").await;
+    server.delivered(&message_id).await.unwrap();
+    assert_eq!(server.received("upper").len(), 1);
+    assert_eq!(server.received("lower").len(), 1);
+    assert!(server.received("upper")[0].body.contains("to Orders"));
+
+    // Tags and attributes are looked up by the exact name too.
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "TagResource"),
+            ("ResourceArn", &upper),
+            ("Tags.member.1.Key", "team"),
+            ("Tags.member.1.Value", "a"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (_, body) = call(
+        &server,
+        &[("Action", "ListTagsForResource"), ("ResourceArn", &lower)],
+    )
+    .await;
+    assert!(!body.contains("<Key>team</Key>"), "{}", body);
+
+    // A third casing exists in neither.
+    let missing = upper.replace(":Orders", ":ORDERS");
+    let (status, body) = call(
+        &server,
+        &[("Action", "GetTopicAttributes"), ("TopicArn", &missing)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{}", body);
+}
+
+#[tokio::test]
+async fn list_topics_returns_every_casing_in_order() {
+    let server = TestServer::start().await.unwrap();
+    for name in ["orders", "Orders", "ORDERS", "oRders"] {
+        create_topic(&server, name).await;
+    }
+    let list = || async {
+        let (status, body) = call(&server, &[("Action", "ListTopics")]).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body.split("<TopicArn>")
+            .skip(1)
+            .map(|rest| rest[..rest.find("</TopicArn>").unwrap()].to_string())
+            .collect::<Vec<_>>()
+    };
+    let first = list().await;
+    let names: Vec<&str> = first
+        .iter()
+        .map(|arn| arn.rsplit(':').next().unwrap())
+        .collect();
+    assert_eq!(names, ["ORDERS", "Orders", "oRders", "orders"]);
+    assert_eq!(list().await, first);
+}
+
+#[tokio::test]
+async fn invalid_names_create_no_topic() {
+    let server = TestServer::start().await.unwrap();
+    for name in ["bad name", "orders.v2", ".fifo"] {
+        let (status, body) = call(&server, &[("Action", "CreateTopic"), ("Name", name)]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(
+            element(&body, "Message"),
+            Some("Invalid parameter: Topic Name")
+        );
+    }
+    assert!(server.state().topics.is_empty());
+}
+
+#[tokio::test]
+async fn create_topic_applies_its_attributes() {
+    let server = TestServer::start().await.unwrap();
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "CreateTopic"),
+            ("Name", "orders"),
+            ("Attributes.entry.1.key", "DisplayName"),
+            ("Attributes.entry.1.value", "Orders"),
+            ("Attributes.entry.2.key", "KmsMasterKeyId"),
+            ("Attributes.entry.2.value", "alias/aws/sns"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let arn = element(&body, "TopicArn").unwrap().to_string();
+
+    let (_, body) = call(
+        &server,
+        &[("Action", "GetTopicAttributes"), ("TopicArn", &arn)],
+    )
+    .await;
+    assert_eq!(attribute(&body, "DisplayName"), Some("Orders"));
+    assert_eq!(attribute(&body, "KmsMasterKeyId"), Some("alias/aws/sns"));
+}
+
+#[tokio::test]
+async fn unsupported_attributes_create_no_topic() {
+    let server = TestServer::start().await.unwrap();
+    let (status, body) = call(
+        &server,
+        &[
+            ("Action", "CreateTopic"),
+            ("Name", "orders"),
+            ("Attributes.entry.1.key", "DisplayName"),
+            ("Attributes.entry.1.value", "Orders"),
+            ("Attributes.entry.2.key", "Colour"),
+            ("Attributes.entry.2.value", "blue"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(element(&body, "Code"), Some("InvalidParameter"));
+    assert!(server.state().topics.is_empty());
+}
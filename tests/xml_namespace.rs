@@ -0,0 +1,93 @@
+mod common;
+
+use common::{call, create_topic, subscribe};
+use local_sns_rs::responses;
+use local_sns_rs::supported_actions;
+use local_sns_rs::testing::TestServer;
+use quick_xml::events::Event;
+
+/// The name and `xmlns` of the root element of `xml`.
+fn root(xml: &str) -> (String, Option<String>) {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        match reader.read_event().unwrap() {
+            Event::Start(start) => {
+                let name = String::from_utf8(start.name().as_ref().to_vec()).unwrap();
+                let xmlns = start
+                    .try_get_attribute("xmlns")
+                    .unwrap()
+                    .map(|xmlns| xmlns.unescape_value().unwrap().into_owned());
+                return (name, xmlns);
+            }
+            Event::Eof => panic!("no root element: {}", xml),
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn every_response_carries_the_sns_namespace() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let subscription_arn = subscribe(&server, &topic_arn, "memory", "inbox").await;
+    // Enough for most actions to succeed; the rest fail validation, and
+    // their error responses are checked alike.
+    let params = [
+        ("TopicArn", topic_arn.as_str()),
+        ("SubscriptionArn", &subscription_arn),
+        ("ResourceArn", &topic_arn),
+        ("Name", "orders"),
+        ("Message", "hello"),
+        ("Protocol", "memory"),
+        ("Endpoint", "inbox"),
+        ("AttributeName", "DisplayName"),
+        ("AttributeValue", "Orders"),
+        ("Tags.member.1.Key", "team"),
+        ("Tags.member.1.Value", "checkout"),
+        ("TagKeys.member.1", "team"),
+        ("PublishBatchRequestEntries.member.1.Id", "1"),
+        ("PublishBatchRequestEntries.member.1.Message", "hello"),
+    ];
+
+    let (mut succeeded, mut failed) = (0, 0);
+    // Deleting actions sort before most others; run them last.
+    let mut actions = supported_actions();
+    actions.sort_by_key(|action| action.starts_with("Delete") || *action == "Unsubscribe");
+    for action in actions {
+        let bare = [("Action", action)];
+        let mut full = params.to_vec();
+        full.push(("Action", action));
+        for params in [&bare[..], &full] {
+            let (status, body) = call(&server, params).await;
+            let (name, xmlns) = root(&body);
+            if status.is_success() {
+                assert_eq!(name, format!("{}Response", action), "{}", body);
+                succeeded += 1;
+            } else {
+                assert_eq!(name, "ErrorResponse", "{}: {}", action, body);
+                failed += 1;
+            }
+            assert_eq!(
+                xmlns.as_deref(),
+                Some(responses::xml_namespace()),
+                "{}: {}",
+                action,
+                body
+            );
+        }
+    }
+    assert!(succeeded > 0 && failed > 0, "{} / {}", succeeded, failed);
+
+    // Errors raised before dispatch too.
+    for params in [&[][..], &[("Action", "NoSuchAction")]] {
+        let (status, body) = call(&server, params).await;
+        assert!(status.is_client_error(), "{}", body);
+        assert_eq!(
+            root(&body),
+            (
+                "ErrorResponse".to_string(),
+                Some(responses::xml_namespace().to_string())
+            )
+        );
+    }
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{attribute, call, create_topic, element};
+use local_sns_rs::testing::TestServer;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const ROUNDS: usize = 200;
+
+/// Characters the random strings are made of: ordinary text, XML markup,
+/// the whitespace XML allows, and control and non-characters it does not.
+const ALPHABET: &[char] = &[
+    'a', 'Z', '0', ' ', '-', '<', '>', '&', '"', '\'', ']', '\t', '\n', '\r', '\0', '\u{1}',
+    '\u{1B}', '\u{1F}', '\u{7F}', '\u{85}', '\u{FFFE}', '\u{FFFF}', 'é', '€', '\u{200B}', '😀',
+];
+
+fn random_string(rng: &mut StdRng) -> String {
+    let len = rng.gen_range(1..=12);
+    (0..len)
+        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())])
+        .collect()
+}
+
+/// Characters XML 1.0 documents can hold.
+fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r') || (c >= ' ' && c != '\u{FFFE}' && c != '\u{FFFF}')
+}
+
+/// Text fields such as DisplayName and tags: XML characters, but no
+/// control characters, not even line breaks.
+fn is_plain_text(value: &str) -> bool {
+    value.chars().all(|c| is_xml_char(c) && !c.is_control())
+}
+
+fn assert_well_formed(xml: &str) {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        match reader.read_event() {
+            Ok(quick_xml::events::Event::Eof) => return,
+            Ok(_) => {}
+            Err(e) => panic!("{}: {}", e, xml),
+        }
+    }
+}
+
+fn unescape(text: &str) -> String {
+    quick_xml::escape::unescape(text).unwrap().into_owned()
+}
+
+fn assert_invalid_parameter(status: StatusCode, body: &str, value: &str) {
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{:?}: {}", value, body);
+    assert_eq!(element(body, "Code"), Some("InvalidParameter"), "{}", body);
+}
+
+async fn set_topic_attribute(
+    server: &TestServer,
+    topic_arn: &str,
+    name: &str,
+    value: &str,
+) -> (StatusCode, String) {
+    call(
+        server,
+        &[
+            ("Action", "SetTopicAttributes"),
+            ("TopicArn", topic_arn),
+            ("AttributeName", name),
+            ("AttributeValue", value),
+        ],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn topic_attributes_round_trip_or_are_rejected() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let mut rng = StdRng::seed_from_u64(1182);
+    for _ in 0..ROUNDS {
+        let value = random_string(&mut rng);
+        for (name, valid) in [
+            ("DisplayName", is_plain_text(&value)),
+            ("KmsMasterKeyId", value.chars().all(is_xml_char)),
+        ] {
+            let (status, body) = set_topic_attribute(&server, &topic_arn, name, &value).await;
+            if !valid {
+                assert_invalid_parameter(status, &body, &value);
+                continue;
+            }
+            assert_eq!(status, StatusCode::OK, "{:?}: {}", value, body);
+            let (_, body) = call(
+                &server,
+                &[("Action", "GetTopicAttributes"), ("TopicArn", &topic_arn)],
+            )
+            .await;
+            assert_well_formed(&body);
+            assert_eq!(unescape(attribute(&body, name).unwrap()), value);
+        }
+    }
+    let (_, body) = call(&server, &[("Action", "ListTopics")]).await;
+    assert_well_formed(&body);
+}
+
+#[tokio::test]
+async fn tags_round_trip_or_are_rejected() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let mut rng = StdRng::seed_from_u64(1182);
+    for _ in 0..ROUNDS {
+        let value = random_string(&mut rng);
+        let (status, body) = call(
+            &server,
+            &[
+                ("Action", "TagResource"),
+                ("ResourceArn", &topic_arn),
+                ("Tags.member.1.Key", "note"),
+                ("Tags.member.1.Value", &value),
+            ],
+        )
+        .await;
+        if !is_plain_text(&value) {
+            assert_invalid_parameter(status, &body, &value);
+            continue;
+        }
+        assert_eq!(status, StatusCode::OK, "{:?}: {}", value, body);
+        let (_, body) = call(
+            &server,
+            &[
+                ("Action", "ListTagsForResource"),
+                ("ResourceArn", &topic_arn),
+            ],
+        )
+        .await;
+        assert_well_formed(&body);
+        assert_eq!(unescape(element(&body, "Value").unwrap()), value);
+    }
+}
+
+#[tokio::test]
+async fn subjects_with_control_characters_are_rejected() {
+    let server = TestServer::start().await.unwrap();
+    let topic_arn = create_topic(&server, "orders").await;
+    let mut rng = StdRng::seed_from_u64(1182);
+    for _ in 0..ROUNDS {
+        let subject = random_string(&mut rng);
+        let (status, body) = call(
+            &server,
+            &[
+                ("Action", "Publish"),
+                ("TopicArn", &topic_arn),
+                ("Subject", &subject),
+                ("Message", "order"),
+            ],
+        )
+        .await;
+        assert_well_formed(&body);
+        if is_plain_text(&subject) {
+            assert_eq!(status, StatusCode::OK, "{:?}: {}", subject, body);
+        } else {
+            assert_invalid_parameter(status, &body, &subject);
+        }
+    }
+}
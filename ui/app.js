@@ -0,0 +1,177 @@
+// Browser UI for local-sns. Everything goes through the admin API and the
+// SNS Query API; paths are relative so the UI also works behind a prefix.
+"use strict";
+
+const admin = "../admin";
+let selected = null;
+
+function el(tag, props, ...children) {
+  const node = document.createElement(tag);
+  Object.assign(node, props);
+  node.append(...children);
+  return node;
+}
+
+// With --admin-token, the admin API wants the token; it is asked for once
+// and kept for the browser session.
+const tokenKey = "local-sns-admin-token";
+
+async function adminFetch(path, options = {}) {
+  const send = () => {
+    const token = sessionStorage.getItem(tokenKey);
+    const headers = token
+      ? { ...options.headers, Authorization: `Bearer ${token}` }
+      : options.headers;
+    return fetch(path, { ...options, headers });
+  };
+  let response = await send();
+  if (response.status === 401) {
+    const token = prompt("Admin token");
+    if (token) {
+      sessionStorage.setItem(tokenKey, token.trim());
+      response = await send();
+    }
+  }
+  return response;
+}
+
+async function getJson(path) {
+  const response = await adminFetch(path);
+  if (!response.ok) {
+    throw new Error(`${path}: ${response.status}`);
+  }
+  return response.json();
+}
+
+function renderMessage(message, fresh) {
+  const meta = [message.timestamp, message.id];
+  if (message.message_group_id) {
+    meta.push(`group ${message.message_group_id}`);
+  }
+  if (message.sequence_number) {
+    meta.push(`seq ${message.sequence_number}`);
+  }
+  if (message.publisher) {
+    const publisher = message.publisher;
+    meta.push(`from ${publisher.access_key_id
+      ?? [publisher.user_agent, publisher.source_ip].filter(Boolean).join(" @ ")}`);
+  }
+  const item = el("li", { className: fresh ? "new" : "" },
+    el("div", { className: "meta", textContent: meta.join(" · ") }));
+  if (message.subject) {
+    item.append(el("strong", { textContent: message.subject }));
+  }
+  item.append(el("pre", { textContent: message.body }));
+  const attributes = Object.entries(message.message_attributes || {});
+  if (attributes.length) {
+    item.append(el("div", {
+      className: "meta",
+      textContent: attributes.map(([name, value]) =>
+        `${name}=${value.string_value ?? value.binary_value}`).join(", "),
+    }));
+  }
+  return item;
+}
+
+async function loadTopics() {
+  const topics = await getJson(`${admin}/topics`);
+  const list = document.getElementById("topics");
+  list.replaceChildren(...topics.map((topic) => {
+    const item = el("li", {
+      textContent: `${topic.name} (${topic.subscriptions})`,
+      className: topic.name === selected ? "selected" : "",
+    });
+    item.onclick = () => selectTopic(topic.name);
+    return item;
+  }));
+  if (selected && !topics.some((topic) => topic.name === selected)) {
+    selected = null;
+    document.getElementById("topic").hidden = true;
+  }
+}
+
+async function selectTopic(name) {
+  selected = name;
+  const topicPath = `${admin}/topics/${encodeURIComponent(name)}`;
+  const [topic, messages] = await Promise.all([
+    getJson(topicPath),
+    getJson(`${topicPath}/messages`),
+  ]);
+  document.getElementById("topic").hidden = false;
+  document.getElementById("topic-name").textContent = topic.name;
+  document.getElementById("topic-arn").textContent = topic.topic_arn;
+  document.getElementById("publish-result").textContent = "";
+  document.getElementById("subscriptions").replaceChildren(
+    ...topic.subscriptions.map((subscription) => el("tr", {},
+      el("td", { textContent: subscription.protocol }),
+      el("td", { textContent: subscription.endpoint }),
+      el("td", { textContent: subscription.effective_delivery_state }),
+      el("td", { textContent: subscription.consecutive_failures }))));
+  const attributes = Object.entries(topic.attributes)
+    .concat(Object.entries(topic.tags).map(([key, value]) => [`tag ${key}`, value]));
+  document.getElementById("attributes").replaceChildren(
+    ...attributes.map(([key, value]) => el("tr", {},
+      el("th", { textContent: key }),
+      el("td", {}, el("pre", { textContent: value })))));
+  document.getElementById("messages").replaceChildren(
+    ...messages.map((message) => renderMessage(message, false)));
+  await loadTopics();
+}
+
+// Publishes through the real Publish action, exactly as an SDK would.
+async function publish(event) {
+  event.preventDefault();
+  const result = document.getElementById("publish-result");
+  const form = new FormData(event.target);
+  const params = new URLSearchParams({
+    Action: "Publish",
+    Version: "2010-03-31",
+    TopicArn: document.getElementById("topic-arn").textContent,
+  });
+  for (const [key, value] of form) {
+    if (value) {
+      params.append(key, value);
+    }
+  }
+  const response = await fetch("../", { method: "POST", body: params });
+  const xml = new DOMParser().parseFromString(await response.text(), "application/xml");
+  if (response.ok) {
+    result.className = "";
+    result.textContent = `Published ${xml.querySelector("MessageId")?.textContent}`;
+  } else {
+    result.className = "error";
+    result.textContent = xml.querySelector("Message")?.textContent || response.statusText;
+  }
+}
+
+async function reset() {
+  if (!confirm("Delete all topics, subscriptions and messages?")) {
+    return;
+  }
+  await adminFetch(`${admin}/reset`, { method: "POST" });
+  selected = null;
+  document.getElementById("topic").hidden = true;
+  await loadTopics();
+}
+
+function stream() {
+  const status = document.getElementById("status");
+  // EventSource cannot send headers, so the token goes in the query.
+  const token = sessionStorage.getItem(tokenKey);
+  const query = token ? `?access_token=${encodeURIComponent(token)}` : "";
+  const events = new EventSource(`${admin}/messages/stream${query}`);
+  events.onopen = () => { status.textContent = "live"; };
+  events.onerror = () => { status.textContent = "reconnecting…"; };
+  events.addEventListener("message", (event) => {
+    const { topic, message } = JSON.parse(event.data);
+    if (topic === selected) {
+      document.getElementById("messages").prepend(renderMessage(message, true));
+    }
+  });
+}
+
+document.getElementById("publish").onsubmit = publish;
+document.getElementById("reset").onclick = reset;
+// The first load asks for the token, if needed, before the stream opens.
+loadTopics().finally(stream);
+setInterval(loadTopics, 5000);